@@ -0,0 +1,236 @@
+//! A blocking client sending the program's instructions over RPC
+//!
+//! [`CounterClient`] has the methods of the async
+//! `counter_client::CounterClient`, each returning once the request or the
+//! transaction's confirmation completes, for scripts, CLI tools and tests
+//! that do without an async runtime.
+
+use crate::{
+    Counter, ID, InitializeArgs, RpcError, decrement_ix, find_counter_address, increment_ix,
+    initialize_ix, initialize_pda_ix, reset_ix, rpc::decode_response, set_ix,
+};
+use solana_client::{rpc_client::RpcClient, rpc_response::Response};
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    signature::{Keypair, Signature, Signer},
+    transaction::Transaction,
+};
+
+/// A connection to a cluster, paying for and signing transactions with
+/// `payer`
+///
+/// Counters it creates have the payer as their authority, so it can also
+/// decrement, reset and set them.
+pub struct CounterClient {
+    rpc: RpcClient,
+    payer: Keypair,
+}
+
+impl CounterClient {
+    /// Connect to the cluster at `url`, waiting for `confirmed` commitment
+    pub fn new(url: impl Into<String>, payer: Keypair) -> Self {
+        Self::with_rpc_client(
+            RpcClient::new_with_commitment(url.into(), CommitmentConfig::confirmed()),
+            payer,
+        )
+    }
+
+    /// Send through `rpc`, with whatever endpoint, commitment and timeouts it
+    /// was built with
+    pub fn with_rpc_client(rpc: RpcClient, payer: Keypair) -> Self {
+        Self { rpc, payer }
+    }
+
+    /// The underlying RPC client, for requests this one has no method for
+    pub fn rpc(&self) -> &RpcClient {
+        &self.rpc
+    }
+
+    /// The address paying for and signing every transaction
+    pub fn payer(&self) -> Pubkey {
+        self.payer.pubkey()
+    }
+
+    /// Fetch and decode the counter at `counter`, at the client's commitment
+    pub fn get_counter(&self, counter: &Pubkey) -> Result<Counter, RpcError> {
+        Ok(self.fetch(counter, self.rpc.commitment())?.value)
+    }
+
+    /// Fetch and decode the counter at `counter` as of `commitment`, along
+    /// with the slot it was read at in the response's context
+    ///
+    /// The account must be owned by the program and hold a counter in the
+    /// current layout, checked by its discriminator and version.
+    pub fn fetch(
+        &self,
+        counter: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> Result<Response<Counter>, RpcError> {
+        let response = self.rpc.get_account_with_commitment(counter, commitment)?;
+        decode_response(counter, response)
+    }
+
+    /// Create a counter at a fresh keypair address and return the address
+    pub fn create_counter(&self, args: InitializeArgs) -> Result<Pubkey, RpcError> {
+        let counter = Keypair::new();
+        self.send(
+            &[initialize_ix(&ID, &counter.pubkey(), &self.payer(), args)],
+            &[&counter],
+        )?;
+        Ok(counter.pubkey())
+    }
+
+    /// Create the payer's counter under `seed`, at [`find_counter_address`],
+    /// and return the address
+    pub fn create_counter_pda(&self, seed: &str, args: InitializeArgs) -> Result<Pubkey, RpcError> {
+        self.send(&[initialize_pda_ix(&ID, &self.payer(), seed, args)], &[])?;
+        Ok(find_counter_address(&ID, &self.payer(), seed).0)
+    }
+
+    /// Increment `counter` by `amount`
+    pub fn increment(&self, counter: &Pubkey, amount: u32) -> Result<Signature, RpcError> {
+        self.send(&[increment_ix(&ID, counter, amount)], &[])
+    }
+
+    /// Decrement `counter` by `amount`, as its authority or delegate
+    pub fn decrement(&self, counter: &Pubkey, amount: u32) -> Result<Signature, RpcError> {
+        self.send(&[decrement_ix(&ID, counter, &self.payer(), amount)], &[])
+    }
+
+    /// Set `counter` back to zero, as its authority
+    pub fn reset(&self, counter: &Pubkey) -> Result<Signature, RpcError> {
+        self.send(&[reset_ix(&ID, counter, &self.payer())], &[])
+    }
+
+    /// Set `counter` to `value`, as its authority
+    pub fn set(&self, counter: &Pubkey, value: u64) -> Result<Signature, RpcError> {
+        self.send(&[set_ix(&ID, counter, &self.payer(), value)], &[])
+    }
+
+    /// Sign `instructions` with the payer and `signers`, send them in one
+    /// transaction and wait for it to be confirmed
+    pub fn send(
+        &self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<Signature, RpcError> {
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let mut keypairs = vec![&self.payer];
+        keypairs.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer()),
+            &keypairs,
+            blockhash,
+        );
+        self.rpc
+            .send_and_confirm_transaction(&transaction)
+            .map_err(RpcError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CounterError, rpc::mocks};
+    use solana_client::{rpc_client::RpcClientConfig, rpc_request::RpcRequest};
+    use solana_sdk::hash::Hash;
+    use std::collections::HashMap;
+
+    /// A client whose requests are answered by `mocks`, or else by the mock
+    /// sender's defaults
+    fn client(mocks: HashMap<RpcRequest, serde_json::Value>) -> CounterClient {
+        CounterClient::with_rpc_client(
+            RpcClient::new_mock_with_mocks("succeeds", mocks),
+            Keypair::new(),
+        )
+    }
+
+    #[test]
+    fn send_signs_with_the_payer_and_returns_the_signature() {
+        let blockhash = Hash::new_unique();
+        let client = client(mocks::blockhash(blockhash));
+        let counter = Pubkey::new_unique();
+
+        let expected = Transaction::new_signed_with_payer(
+            &[reset_ix(&ID, &counter, &client.payer())],
+            Some(&client.payer()),
+            &[&client.payer],
+            blockhash,
+        );
+        assert_eq!(client.reset(&counter).unwrap(), expected.signatures[0]);
+    }
+
+    #[test]
+    fn send_surfaces_program_errors() {
+        let client = client(mocks::failed(CounterError::Unauthorized));
+        let error = client.set(&Pubkey::new_unique(), 3).unwrap_err();
+        assert!(matches!(
+            error,
+            RpcError::Program(CounterError::Unauthorized)
+        ));
+    }
+
+    #[test]
+    fn get_counter_decodes_the_account() {
+        let client = client(mocks::counter(9, 42));
+        let counter = client.get_counter(&Pubkey::new_unique()).unwrap();
+        assert_eq!(counter.value(), 42);
+    }
+
+    #[test]
+    fn get_counter_reports_a_missing_account() {
+        // The mock sender has no account by default
+        let client = client(HashMap::new());
+        let address = Pubkey::new_unique();
+        assert!(matches!(
+            client.get_counter(&address),
+            Err(RpcError::AccountNotFound(missing)) if missing == address
+        ));
+    }
+
+    #[test]
+    fn fetch_reads_at_the_commitment_and_returns_the_slot() {
+        let (recorder, requests) = mocks::Recorder::new(mocks::counter(77, 42));
+        let config = RpcClientConfig::with_commitment(CommitmentConfig::processed());
+        let client =
+            CounterClient::with_rpc_client(RpcClient::new_sender(recorder, config), Keypair::new());
+        let address = Pubkey::new_unique();
+
+        let response = client
+            .fetch(&address, CommitmentConfig::finalized())
+            .unwrap();
+        assert_eq!(response.context.slot, 77);
+        assert_eq!(response.value.value(), 42);
+
+        let requests = requests.lock().unwrap();
+        let (_, params) = requests
+            .iter()
+            .find(|(request, _)| *request == RpcRequest::GetAccountInfo)
+            .unwrap();
+        assert_eq!(params[0], address.to_string());
+        assert_eq!(params[1]["commitment"], "finalized");
+    }
+
+    #[test]
+    fn fetch_rejects_accounts_of_other_programs() {
+        let owner = Pubkey::new_unique();
+        let client = client(mocks::account(1, owner, mocks::counter_data(42)));
+        let address = Pubkey::new_unique();
+        assert!(matches!(
+            client.fetch(&address, CommitmentConfig::confirmed()),
+            Err(RpcError::NotACounter(rejected)) if rejected == address
+        ));
+    }
+
+    #[test]
+    fn fetch_rejects_accounts_without_the_counter_discriminator() {
+        let client = client(mocks::account(1, ID, mocks::wrong_discriminator(42)));
+        let address = Pubkey::new_unique();
+        assert!(matches!(
+            client.fetch(&address, CommitmentConfig::confirmed()),
+            Err(RpcError::NotACounter(rejected)) if rejected == address
+        ));
+    }
+}
@@ -0,0 +1,2106 @@
+//! Constructors for the instructions the program's own [`builders`] leave out
+//!
+//! Together with those, re-exported at the crate root, there is one for every
+//! [`CounterInstruction`]. Companion accounts at a fixed address, such as a
+//! counter's hooks or decay, are derived from the counter rather than passed.
+//!
+//! [`builders`]: solana_counter_program::builders
+
+use solana_counter_program::{
+    CallbackAccount, CounterInstruction, CounterOp, DecayUnit, FeeConfig, ID, find_burn_address,
+    find_callback_address, find_config_address, find_cooldown_address, find_decay_address,
+    find_escrow_address, find_escrow_deposit_address, find_fee_vault_address, find_history_address,
+    find_hooks_address, find_operators_address, find_permits_address, find_rewards_address,
+    find_shard_address, find_token_gate_address,
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+/// Catch instructions addressed to another deployment in debug builds
+fn check_program_id(program_id: &Pubkey) {
+    debug_assert_eq!(
+        *program_id, ID,
+        "instruction built for {}, but this program is {}",
+        program_id, ID
+    );
+}
+
+/// An instruction with `data` and `accounts`, addressed to `program_id`
+fn ix(program_id: &Pubkey, data: &CounterInstruction, accounts: Vec<AccountMeta>) -> Instruction {
+    check_program_id(program_id);
+    Instruction::new_with_borsh(*program_id, data, accounts)
+}
+
+/// An instruction changing the settings of `target`, a counter or the config,
+/// signed by its `authority`
+fn authority_ix(
+    program_id: &Pubkey,
+    target: &Pubkey,
+    authority: &Pubkey,
+    data: &CounterInstruction,
+) -> Instruction {
+    ix(
+        program_id,
+        data,
+        vec![
+            AccountMeta::new(*target, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+    )
+}
+
+/// An instruction changing the config on behalf of its `admin`, with `payer`
+/// funding any growth of the account
+fn admin_ix(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    payer: &Pubkey,
+    data: &CounterInstruction,
+) -> Instruction {
+    ix(
+        program_id,
+        data,
+        vec![
+            AccountMeta::new(find_config_address(program_id).0, false),
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// An instruction creating or rewriting `companion`, an account kept
+/// alongside `counter`, with `payer` funding its rent
+fn companion_ix(
+    program_id: &Pubkey,
+    counter: AccountMeta,
+    authority: &Pubkey,
+    companion: Pubkey,
+    payer: &Pubkey,
+    data: &CounterInstruction,
+) -> Instruction {
+    ix(
+        program_id,
+        data,
+        vec![
+            counter,
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(companion, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// An instruction closing `companion`, an account kept alongside `counter`,
+/// and sending its lamports to `destination`
+fn clear_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    companion: Pubkey,
+    destination: &Pubkey,
+    data: &CounterInstruction,
+) -> Instruction {
+    ix(
+        program_id,
+        data,
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(companion, false),
+            AccountMeta::new(*destination, false),
+        ],
+    )
+}
+
+/// Apply `ops` to `counter` in order, all or nothing
+///
+/// `authority` may be left out only if every operation is an increment.
+pub fn batch_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: Option<&Pubkey>,
+    ops: Vec<CounterOp>,
+) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(*counter, false)];
+    if let Some(authority) = authority {
+        accounts.push(AccountMeta::new_readonly(*authority, true));
+    }
+    accounts.push(AccountMeta::new_readonly(
+        find_config_address(program_id).0,
+        false,
+    ));
+    ix(program_id, &CounterInstruction::Batch(ops), accounts)
+}
+
+/// Rewrite the pre-versioning counter `counter` in the current layout
+pub fn migrate_legacy_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::MigrateLegacy,
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Rewrite the outdated counter `counter` in the current layout
+pub fn migrate_v1_to_v2_ix(program_id: &Pubkey, counter: &Pubkey, payer: &Pubkey) -> Instruction {
+    migrate_accounts_ix(
+        program_id,
+        counter,
+        payer,
+        &CounterInstruction::MigrateV1ToV2,
+    )
+}
+
+/// Rewrite `counter`, in any older layout, in the current one
+pub fn migrate_ix(program_id: &Pubkey, counter: &Pubkey, payer: &Pubkey) -> Instruction {
+    migrate_accounts_ix(program_id, counter, payer, &CounterInstruction::Migrate)
+}
+
+/// A migration of `counter`, with `payer` funding any growth
+fn migrate_accounts_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    payer: &Pubkey,
+    data: &CounterInstruction,
+) -> Instruction {
+    ix(
+        program_id,
+        data,
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Create the program config, with `payer` as its admin
+pub fn initialize_config_ix(program_id: &Pubkey, payer: &Pubkey, max_amount: u32) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::InitializeConfig { max_amount },
+        vec![
+            AccountMeta::new(find_config_address(program_id).0, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Replace the config's admin, pause flag and largest allowed amount
+pub fn update_config_ix(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    new_admin: &Pubkey,
+    paused: bool,
+    max_amount: u32,
+) -> Instruction {
+    authority_ix(
+        program_id,
+        &find_config_address(program_id).0,
+        admin,
+        &CounterInstruction::UpdateConfig {
+            admin: *new_admin,
+            paused,
+            max_amount,
+        },
+    )
+}
+
+/// Create a multisig at the new keypair address `multisig`, needing `m` of
+/// `signers`
+pub fn initialize_multisig_ix(
+    program_id: &Pubkey,
+    multisig: &Pubkey,
+    payer: &Pubkey,
+    m: u8,
+    signers: Vec<Pubkey>,
+) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::InitializeMultisig { m, signers },
+        vec![
+            AccountMeta::new(*multisig, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Let `delegate` decrement `counter` by up to `allowance` in total
+pub fn approve_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    delegate: &Pubkey,
+    allowance: u64,
+) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        &CounterInstruction::Approve {
+            delegate: *delegate,
+            allowance,
+        },
+    )
+}
+
+/// Remove the delegate of `counter`
+pub fn revoke_ix(program_id: &Pubkey, counter: &Pubkey, authority: &Pubkey) -> Instruction {
+    authority_ix(program_id, counter, authority, &CounterInstruction::Revoke)
+}
+
+/// Add the count of `source` to `destination` and zero it
+///
+/// Counters with a history or an armed callback need those accounts
+/// appended, the destination's first, followed by the destination's reward
+/// accounts if it has milestone rewards.
+pub fn merge_ix(
+    program_id: &Pubkey,
+    destination: &Pubkey,
+    destination_authority: &Pubkey,
+    source: &Pubkey,
+    source_authority: &Pubkey,
+) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::Merge,
+        vec![
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*destination_authority, true),
+            AccountMeta::new(*source, false),
+            AccountMeta::new_readonly(*source_authority, true),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        ],
+    )
+}
+
+/// Copy the settings of `source` to `new_counter`, a new keypair address, or
+/// with a `seed` the authority's counter PDA under it
+pub fn clone_ix(
+    program_id: &Pubkey,
+    source: &Pubkey,
+    authority: &Pubkey,
+    new_counter: &Pubkey,
+    payer: &Pubkey,
+    seed: Option<String>,
+) -> Instruction {
+    let signs = seed.is_none();
+    ix(
+        program_id,
+        &CounterInstruction::Clone { seed },
+        vec![
+            AccountMeta::new_readonly(*source, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*new_counter, signs),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Create a vote account at the new keypair address `votes`
+pub fn initialize_votes_ix(program_id: &Pubkey, votes: &Pubkey, payer: &Pubkey) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::InitializeVotes,
+        vec![
+            AccountMeta::new(*votes, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Add `amount` up votes to `votes`
+pub fn vote_up_ix(program_id: &Pubkey, votes: &Pubkey, amount: u32) -> Instruction {
+    vote_ix(program_id, votes, &CounterInstruction::VoteUp(amount))
+}
+
+/// Add `amount` down votes to `votes`
+pub fn vote_down_ix(program_id: &Pubkey, votes: &Pubkey, amount: u32) -> Instruction {
+    vote_ix(program_id, votes, &CounterInstruction::VoteDown(amount))
+}
+
+/// A vote cast on `votes` by anyone
+fn vote_ix(program_id: &Pubkey, votes: &Pubkey, data: &CounterInstruction) -> Instruction {
+    ix(
+        program_id,
+        data,
+        vec![
+            AccountMeta::new(*votes, false),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        ],
+    )
+}
+
+/// Create a map of named counters at the new keypair address `map`, owned by
+/// `payer`
+pub fn initialize_map_ix(program_id: &Pubkey, map: &Pubkey, payer: &Pubkey) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::InitializeMap,
+        vec![
+            AccountMeta::new(*map, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Add the entry `name` to `map`, starting at zero
+pub fn add_map_entry_ix(
+    program_id: &Pubkey,
+    map: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    name: [u8; 32],
+) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::AddMapEntry { name },
+        vec![
+            AccountMeta::new(*map, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Remove the entry `name` from `map`, sending the freed rent to
+/// `destination`
+pub fn remove_map_entry_ix(
+    program_id: &Pubkey,
+    map: &Pubkey,
+    authority: &Pubkey,
+    destination: &Pubkey,
+    name: [u8; 32],
+) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::RemoveMapEntry { name },
+        vec![
+            AccountMeta::new(*map, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*destination, false),
+        ],
+    )
+}
+
+/// Increment the entry `name` of `map` by `amount`
+pub fn increment_map_entry_ix(
+    program_id: &Pubkey,
+    map: &Pubkey,
+    name: [u8; 32],
+    amount: u32,
+) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::IncrementMapEntry { name, amount },
+        vec![
+            AccountMeta::new(*map, false),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        ],
+    )
+}
+
+/// Decrement the entry `name` of `map` by `amount`
+pub fn decrement_map_entry_ix(
+    program_id: &Pubkey,
+    map: &Pubkey,
+    authority: &Pubkey,
+    name: [u8; 32],
+    amount: u32,
+) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::DecrementMapEntry { name, amount },
+        vec![
+            AccountMeta::new(*map, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        ],
+    )
+}
+
+/// Grow `counter` to `len` bytes, making room for optional data
+pub fn resize_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    len: u32,
+) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::Resize { len },
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Store `name` and `description` after the counter in `counter`
+pub fn set_metadata_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    name: String,
+    description: String,
+) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::SetMetadata { name, description },
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Create shard `index` of `counter`, at [`find_shard_address`]
+pub fn initialize_shard_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    index: u16,
+) -> Instruction {
+    companion_ix(
+        program_id,
+        AccountMeta::new_readonly(*counter, false),
+        authority,
+        find_shard_address(program_id, counter, index).0,
+        payer,
+        &CounterInstruction::InitializeShard { index },
+    )
+}
+
+/// Increment shard `index` of `counter` by `amount`
+pub fn increment_shard_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    index: u16,
+    amount: u32,
+) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::IncrementShard(amount),
+        vec![
+            AccountMeta::new(find_shard_address(program_id, counter, index).0, false),
+            AccountMeta::new_readonly(*counter, false),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        ],
+    )
+}
+
+/// Drain the shards `indices` of `counter` into it
+///
+/// At most [`MAX_AGGREGATE_SHARDS`](solana_counter_program::MAX_AGGREGATE_SHARDS)
+/// shards fit in one instruction. A counter with a history or an armed
+/// callback needs those accounts appended after the shards.
+pub fn aggregate_ix(program_id: &Pubkey, counter: &Pubkey, indices: &[u16]) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*counter, false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+    ];
+    accounts.extend(
+        indices.iter().map(|&index| {
+            AccountMeta::new(find_shard_address(program_id, counter, index).0, false)
+        }),
+    );
+    ix(program_id, &CounterInstruction::Aggregate, accounts)
+}
+
+/// Start recording the last `capacity` changes of `counter`
+pub fn initialize_history_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    capacity: u16,
+) -> Instruction {
+    companion_ix(
+        program_id,
+        AccountMeta::new(*counter, false),
+        authority,
+        find_history_address(program_id, counter).0,
+        payer,
+        &CounterInstruction::InitializeHistory { capacity },
+    )
+}
+
+/// Restrict which programs may invoke the counter program, for every counter
+pub fn set_caller_guard_ix(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    payer: &Pubkey,
+    enabled: bool,
+    allowed_programs: Vec<Pubkey>,
+    forbidden_programs: Vec<Pubkey>,
+) -> Instruction {
+    admin_ix(
+        program_id,
+        admin,
+        payer,
+        &CounterInstruction::SetCallerGuard {
+            enabled,
+            allowed_programs,
+            forbidden_programs,
+        },
+    )
+}
+
+/// Let a transaction change `counter` with at most one instruction
+pub fn set_exactly_once_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    enabled: bool,
+) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        &CounterInstruction::SetExactlyOnce { enabled },
+    )
+}
+
+/// Let `freeze_authority` pause and unpause `counter`
+pub fn set_freeze_authority_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    freeze_authority: &Pubkey,
+) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        &CounterInstruction::SetFreezeAuthority {
+            freeze_authority: *freeze_authority,
+        },
+    )
+}
+
+/// Stop every instruction but [`emergency_resume_ix`]
+pub fn emergency_halt_ix(program_id: &Pubkey, admin: &Pubkey) -> Instruction {
+    authority_ix(
+        program_id,
+        &find_config_address(program_id).0,
+        admin,
+        &CounterInstruction::EmergencyHalt,
+    )
+}
+
+/// Lift an [`emergency_halt_ix`]
+pub fn emergency_resume_ix(program_id: &Pubkey, admin: &Pubkey) -> Instruction {
+    authority_ix(
+        program_id,
+        &find_config_address(program_id).0,
+        admin,
+        &CounterInstruction::EmergencyResume,
+    )
+}
+
+/// Emit an event whenever `counter` crosses one of `thresholds`
+pub fn set_thresholds_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    thresholds: Vec<i128>,
+) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        &CounterInstruction::SetThresholds { thresholds },
+    )
+}
+
+/// Return a [`CounterView`](solana_counter_program::events::CounterView) of
+/// `counter`, for simulating
+pub fn view_ix(program_id: &Pubkey, counter: &Pubkey) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::View,
+        vec![AccountMeta::new_readonly(*counter, false)],
+    )
+}
+
+/// Invoke `program` with `accounts` once `counter` reaches `target`
+pub fn set_callback_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    target: i128,
+    program: &Pubkey,
+    accounts: Vec<CallbackAccount>,
+) -> Instruction {
+    companion_ix(
+        program_id,
+        AccountMeta::new(*counter, false),
+        authority,
+        find_callback_address(program_id, counter).0,
+        payer,
+        &CounterInstruction::SetCallback {
+            target,
+            program: *program,
+            accounts,
+        },
+    )
+}
+
+/// Remove the callback of `counter`
+pub fn clear_callback_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    clear_ix(
+        program_id,
+        counter,
+        authority,
+        find_callback_address(program_id, counter).0,
+        destination,
+        &CounterInstruction::ClearCallback,
+    )
+}
+
+/// Let `operator` decrement `counter` by up to `allowance` until
+/// `expiry_slot`
+pub fn add_operator_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    operator: &Pubkey,
+    allowance: u64,
+    expiry_slot: u64,
+) -> Instruction {
+    companion_ix(
+        program_id,
+        AccountMeta::new_readonly(*counter, false),
+        authority,
+        find_operators_address(program_id, counter).0,
+        payer,
+        &CounterInstruction::AddOperator {
+            operator: *operator,
+            allowance,
+            expiry_slot,
+        },
+    )
+}
+
+/// Remove `operator` from the operators of `counter`
+pub fn remove_operator_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    operator: &Pubkey,
+) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::RemoveOperator {
+            operator: *operator,
+        },
+        vec![
+            AccountMeta::new_readonly(*counter, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(find_operators_address(program_id, counter).0, false),
+        ],
+    )
+}
+
+/// Invoke `pre` before and `post` after every change to `counter`
+pub fn set_hooks_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    pre: Option<Pubkey>,
+    post: Option<Pubkey>,
+) -> Instruction {
+    companion_ix(
+        program_id,
+        AccountMeta::new(*counter, false),
+        authority,
+        find_hooks_address(program_id, counter).0,
+        payer,
+        &CounterInstruction::SetHooks { pre, post },
+    )
+}
+
+/// Remove the hooks of `counter`
+pub fn clear_hooks_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    clear_ix(
+        program_id,
+        counter,
+        authority,
+        find_hooks_address(program_id, counter).0,
+        destination,
+        &CounterInstruction::ClearHooks,
+    )
+}
+
+/// Charge `fee` in tokens on every change, or stop charging one with `None`
+pub fn set_fee_ix(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    payer: &Pubkey,
+    fee: Option<FeeConfig>,
+) -> Instruction {
+    admin_ix(
+        program_id,
+        admin,
+        payer,
+        &CounterInstruction::SetFee { fee },
+    )
+}
+
+/// Charge `lamports` into the fee vault on every change
+pub fn set_lamport_fee_ix(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    payer: &Pubkey,
+    lamports: u64,
+) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::SetLamportFee { lamports },
+        vec![
+            AccountMeta::new(find_config_address(program_id).0, false),
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(find_fee_vault_address(program_id).0, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Send `amount` lamports of collected fees to `destination`
+pub fn withdraw_fees_ix(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::WithdrawFees { amount },
+        vec![
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(find_fee_vault_address(program_id).0, false),
+            AccountMeta::new(*destination, false),
+        ],
+    )
+}
+
+/// Only let holders of at least `min_balance` of `mint` increment `counter`
+pub fn set_token_gate_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    min_balance: u64,
+) -> Instruction {
+    companion_ix(
+        program_id,
+        AccountMeta::new(*counter, false),
+        authority,
+        find_token_gate_address(program_id, counter).0,
+        payer,
+        &CounterInstruction::SetTokenGate {
+            mint: *mint,
+            min_balance,
+        },
+    )
+}
+
+/// Remove the token gate of `counter`
+pub fn clear_token_gate_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    clear_ix(
+        program_id,
+        counter,
+        authority,
+        find_token_gate_address(program_id, counter).0,
+        destination,
+        &CounterInstruction::ClearTokenGate,
+    )
+}
+
+/// Hand the authority of `counter` to whoever holds the NFT `mint`
+pub fn set_nft_authority_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    mint: &Pubkey,
+) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::SetNftAuthority { mint: *mint },
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new_readonly(*mint, false),
+        ],
+    )
+}
+
+/// Hand `target`, a counter or the config, to `governance`, or with
+/// `treasury` to its native treasury
+pub fn set_governance_authority_ix(
+    program_id: &Pubkey,
+    target: &Pubkey,
+    authority: &Pubkey,
+    governance: &Pubkey,
+    treasury: bool,
+) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::SetGovernanceAuthority {
+            governance: *governance,
+            treasury,
+        },
+        vec![
+            AccountMeta::new(*target, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new_readonly(*governance, false),
+        ],
+    )
+}
+
+/// Make every increment of `counter` burn as many base units of `mint`
+pub fn set_burn_mint_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+) -> Instruction {
+    companion_ix(
+        program_id,
+        AccountMeta::new(*counter, false),
+        authority,
+        find_burn_address(program_id, counter).0,
+        payer,
+        &CounterInstruction::SetBurnMint { mint: *mint },
+    )
+}
+
+/// Mint `amount` of `mint` to whoever takes `counter` past each of
+/// `milestones`
+pub fn set_rewards_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    amount: u64,
+    milestones: Vec<i128>,
+) -> Instruction {
+    companion_ix(
+        program_id,
+        AccountMeta::new(*counter, false),
+        authority,
+        find_rewards_address(program_id, counter).0,
+        payer,
+        &CounterInstruction::SetRewards {
+            mint: *mint,
+            amount,
+            milestones,
+        },
+    )
+}
+
+/// Remove the rewards of `counter`
+pub fn clear_rewards_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    clear_ix(
+        program_id,
+        counter,
+        authority,
+        find_rewards_address(program_id, counter).0,
+        destination,
+        &CounterInstruction::ClearRewards,
+    )
+}
+
+/// Open an escrow paying `beneficiary` if `counter` reaches `target` by
+/// `deadline_slot`
+pub fn initialize_escrow_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    beneficiary: &Pubkey,
+    target: i128,
+    deadline_slot: u64,
+) -> Instruction {
+    companion_ix(
+        program_id,
+        AccountMeta::new(*counter, false),
+        authority,
+        find_escrow_address(program_id, counter).0,
+        payer,
+        &CounterInstruction::InitializeEscrow {
+            beneficiary: *beneficiary,
+            target,
+            deadline_slot,
+        },
+    )
+}
+
+/// Deposit `lamports` from `depositor` into the escrow of `counter`
+pub fn deposit_escrow_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    depositor: &Pubkey,
+    lamports: u64,
+) -> Instruction {
+    let escrow = find_escrow_address(program_id, counter).0;
+    ix(
+        program_id,
+        &CounterInstruction::DepositEscrow { lamports },
+        vec![
+            AccountMeta::new_readonly(*counter, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new(
+                find_escrow_deposit_address(program_id, &escrow, depositor).0,
+                false,
+            ),
+            AccountMeta::new(*depositor, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Pay the escrow of `counter` out to its `beneficiary`
+pub fn settle_ix(program_id: &Pubkey, counter: &Pubkey, beneficiary: &Pubkey) -> Instruction {
+    ix(
+        program_id,
+        &CounterInstruction::Settle,
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new(find_escrow_address(program_id, counter).0, false),
+            AccountMeta::new(*beneficiary, false),
+        ],
+    )
+}
+
+/// Refund `depositor` from the escrow of `counter`, or close their deposit
+/// record once it has settled
+pub fn refund_ix(program_id: &Pubkey, counter: &Pubkey, depositor: &Pubkey) -> Instruction {
+    let escrow = find_escrow_address(program_id, counter).0;
+    let mut instruction = settle_ix(program_id, counter, depositor);
+    instruction.accounts.push(AccountMeta::new(
+        find_escrow_deposit_address(program_id, &escrow, depositor).0,
+        false,
+    ));
+    instruction
+}
+
+/// Require a signed permit from the authority on every increment of
+/// `counter`
+///
+/// Permits are signed over [`permit_message`](crate::permit_message). Using
+/// one voids every unused permit with a lower nonce, so hand out nonces in
+/// the order the permits will be used.
+pub fn require_permits_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+) -> Instruction {
+    companion_ix(
+        program_id,
+        AccountMeta::new(*counter, false),
+        authority,
+        find_permits_address(program_id, counter).0,
+        payer,
+        &CounterInstruction::RequirePermits,
+    )
+}
+
+/// Stop requiring permits on `counter`
+pub fn clear_permits_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    clear_ix(
+        program_id,
+        counter,
+        authority,
+        find_permits_address(program_id, counter).0,
+        destination,
+        &CounterInstruction::ClearPermits,
+    )
+}
+
+/// Make each actor wait `slots` between increments of `counter`, adding at
+/// most `daily_cap` a day
+pub fn set_cooldown_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    slots: u64,
+    daily_cap: u64,
+) -> Instruction {
+    companion_ix(
+        program_id,
+        AccountMeta::new(*counter, false),
+        authority,
+        find_cooldown_address(program_id, counter).0,
+        payer,
+        &CounterInstruction::SetCooldown { slots, daily_cap },
+    )
+}
+
+/// Remove the cooldown of `counter`
+pub fn clear_cooldown_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    clear_ix(
+        program_id,
+        counter,
+        authority,
+        find_cooldown_address(program_id, counter).0,
+        destination,
+        &CounterInstruction::ClearCooldown,
+    )
+}
+
+/// Cap how far changes may move any counter in one epoch, or lift the cap
+/// with zero
+pub fn set_epoch_cap_ix(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    payer: &Pubkey,
+    cap: u64,
+) -> Instruction {
+    admin_ix(
+        program_id,
+        admin,
+        payer,
+        &CounterInstruction::SetEpochCap { cap },
+    )
+}
+
+/// Make the first change to `counter` in each epoch start it over from zero
+pub fn set_epoch_reset_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    enabled: bool,
+) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        &CounterInstruction::SetEpochReset { enabled },
+    )
+}
+
+/// Let `counter` decay towards zero by `rate` per `unit`
+pub fn set_decay_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    rate: u64,
+    unit: DecayUnit,
+) -> Instruction {
+    companion_ix(
+        program_id,
+        AccountMeta::new(*counter, false),
+        authority,
+        find_decay_address(program_id, counter).0,
+        payer,
+        &CounterInstruction::SetDecay { rate, unit },
+    )
+}
+
+/// Stop the decay of `counter`
+pub fn clear_decay_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    clear_ix(
+        program_id,
+        counter,
+        authority,
+        find_decay_address(program_id, counter).0,
+        destination,
+        &CounterInstruction::ClearDecay,
+    )
+}
+
+/// Pay each crank that does work `lamports` from the fee vault, up to
+/// `epoch_budget` in total per epoch
+pub fn set_crank_reward_ix(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    payer: &Pubkey,
+    lamports: u64,
+    epoch_budget: u64,
+) -> Instruction {
+    admin_ix(
+        program_id,
+        admin,
+        payer,
+        &CounterInstruction::SetCrankReward {
+            lamports,
+            epoch_budget,
+        },
+    )
+}
+
+/// Bring `counter` up to date, paying `cranker` any reward
+///
+/// Set `decays` if the counter has a decay. A counter with a callback also
+/// needs its callback group appended to `accounts`.
+pub fn crank_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    cranker: &Pubkey,
+    decays: bool,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*counter, false),
+        AccountMeta::new(find_config_address(program_id).0, false),
+        AccountMeta::new(*cranker, true),
+        AccountMeta::new(find_fee_vault_address(program_id).0, false),
+    ];
+    if decays {
+        accounts.push(AccountMeta::new_readonly(
+            find_decay_address(program_id, counter).0,
+            false,
+        ));
+    }
+    ix(program_id, &CounterInstruction::Crank, accounts)
+}
+
+/// Freeze the value of `counter` until `until_slot`
+pub fn lock_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    until_slot: u64,
+) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        &CounterInstruction::Lock { until_slot },
+    )
+}
+
+/// Make `counter` read-only from the Unix timestamp `expires_at`, or never
+/// with zero
+pub fn set_expiry_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    expires_at: i64,
+) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        &CounterInstruction::SetExpiry { expires_at },
+    )
+}
+
+/// Keep a moving average of `counter` weighting each change by `alpha_bps`
+/// basis points, or stop with zero
+pub fn set_ema_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    alpha_bps: u16,
+) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        &CounterInstruction::SetEma { alpha_bps },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::BorshDeserialize;
+
+    /// The instruction `instruction` carries, decoded as the program decodes it
+    fn decode(instruction: &Instruction) -> CounterInstruction {
+        assert_eq!(instruction.program_id, ID);
+        CounterInstruction::try_from_slice(&instruction.data).unwrap()
+    }
+
+    /// The key and the signer and writable flags of each account of
+    /// `instruction`, in order
+    fn accounts(instruction: &Instruction) -> Vec<(Pubkey, bool, bool)> {
+        instruction
+            .accounts
+            .iter()
+            .map(|meta| (meta.pubkey, meta.is_signer, meta.is_writable))
+            .collect()
+    }
+
+    // The flags the program validates each account against, named like its
+    // account specs; an authority is expected to sign for itself
+    fn readonly(key: Pubkey) -> (Pubkey, bool, bool) {
+        (key, false, false)
+    }
+
+    fn writable(key: Pubkey) -> (Pubkey, bool, bool) {
+        (key, false, true)
+    }
+
+    fn signer(key: Pubkey) -> (Pubkey, bool, bool) {
+        (key, true, false)
+    }
+
+    fn writable_signer(key: Pubkey) -> (Pubkey, bool, bool) {
+        (key, true, true)
+    }
+
+    fn config() -> Pubkey {
+        find_config_address(&ID).0
+    }
+
+    fn system() -> (Pubkey, bool, bool) {
+        readonly(system_program::ID)
+    }
+
+    #[test]
+    fn batch() {
+        let (counter, authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let ix = batch_ix(&ID, &counter, None, vec![CounterOp::Increment(2)]);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::Batch(ops) if matches!(ops[..], [CounterOp::Increment(2)])
+        ));
+        assert_eq!(accounts(&ix), [writable(counter), readonly(config())]);
+
+        let ops = vec![CounterOp::Increment(2), CounterOp::Set(7)];
+        let ix = batch_ix(&ID, &counter, Some(&authority), ops);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::Batch(ops)
+                if matches!(ops[..], [CounterOp::Increment(2), CounterOp::Set(7)])
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [writable(counter), signer(authority), readonly(config())]
+        );
+    }
+
+    #[test]
+    fn migrations() {
+        let (counter, authority, payer) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+
+        let ix = migrate_legacy_ix(&ID, &counter, &authority, &payer);
+        assert!(matches!(decode(&ix), CounterInstruction::MigrateLegacy));
+        assert_eq!(
+            accounts(&ix),
+            [
+                writable(counter),
+                signer(authority),
+                writable_signer(payer),
+                system()
+            ]
+        );
+
+        let ix = migrate_v1_to_v2_ix(&ID, &counter, &payer);
+        assert!(matches!(decode(&ix), CounterInstruction::MigrateV1ToV2));
+        assert_eq!(
+            accounts(&ix),
+            [writable(counter), writable_signer(payer), system()]
+        );
+
+        let ix = migrate_ix(&ID, &counter, &payer);
+        assert!(matches!(decode(&ix), CounterInstruction::Migrate));
+        assert_eq!(
+            accounts(&ix),
+            [writable(counter), writable_signer(payer), system()]
+        );
+    }
+
+    #[test]
+    fn config_instructions() {
+        let (admin, payer, new_admin) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let admin_accounts = [
+            writable(config()),
+            signer(admin),
+            writable_signer(payer),
+            system(),
+        ];
+
+        let ix = initialize_config_ix(&ID, &payer, 50);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::InitializeConfig { max_amount: 50 }
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [writable(config()), writable_signer(payer), system()]
+        );
+
+        let ix = update_config_ix(&ID, &admin, &new_admin, true, 9);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::UpdateConfig { admin, paused: true, max_amount: 9 } if admin == new_admin
+        ));
+        assert_eq!(accounts(&ix), [writable(config()), signer(admin)]);
+
+        let ix = emergency_halt_ix(&ID, &admin);
+        assert!(matches!(decode(&ix), CounterInstruction::EmergencyHalt));
+        assert_eq!(accounts(&ix), [writable(config()), signer(admin)]);
+
+        let ix = emergency_resume_ix(&ID, &admin);
+        assert!(matches!(decode(&ix), CounterInstruction::EmergencyResume));
+        assert_eq!(accounts(&ix), [writable(config()), signer(admin)]);
+
+        let (allowed, forbidden) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = set_caller_guard_ix(&ID, &admin, &payer, true, vec![allowed], vec![forbidden]);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetCallerGuard { enabled: true, allowed_programs, forbidden_programs }
+                if allowed_programs == [allowed] && forbidden_programs == [forbidden]
+        ));
+        assert_eq!(accounts(&ix), admin_accounts);
+
+        let fee = FeeConfig {
+            mint: Pubkey::new_unique(),
+            amount: 3,
+            treasury: Pubkey::new_unique(),
+        };
+        let ix = set_fee_ix(&ID, &admin, &payer, Some(fee));
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetFee { fee: Some(decoded) } if decoded == fee
+        ));
+        assert_eq!(accounts(&ix), admin_accounts);
+
+        let vault = find_fee_vault_address(&ID).0;
+        let ix = set_lamport_fee_ix(&ID, &admin, &payer, 5000);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetLamportFee { lamports: 5000 }
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [
+                writable(config()),
+                signer(admin),
+                writable(vault),
+                writable_signer(payer),
+                system()
+            ]
+        );
+
+        let destination = Pubkey::new_unique();
+        let ix = withdraw_fees_ix(&ID, &admin, &destination, 700);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::WithdrawFees { amount: 700 }
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [
+                readonly(config()),
+                signer(admin),
+                writable(vault),
+                writable(destination)
+            ]
+        );
+
+        let ix = set_epoch_cap_ix(&ID, &admin, &payer, 100);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetEpochCap { cap: 100 }
+        ));
+        assert_eq!(accounts(&ix), admin_accounts);
+
+        let ix = set_crank_reward_ix(&ID, &admin, &payer, 10, 40);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetCrankReward {
+                lamports: 10,
+                epoch_budget: 40
+            }
+        ));
+        assert_eq!(accounts(&ix), admin_accounts);
+    }
+
+    #[test]
+    fn multisigs_and_delegates() {
+        let (multisig, payer, members) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            vec![Pubkey::new_unique(), Pubkey::new_unique()],
+        );
+        let ix = initialize_multisig_ix(&ID, &multisig, &payer, 2, members.clone());
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::InitializeMultisig { m: 2, signers } if signers == members
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [writable_signer(multisig), writable_signer(payer), system()]
+        );
+
+        let (counter, authority, delegate) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let ix = approve_ix(&ID, &counter, &authority, &delegate, 8);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::Approve { delegate: decoded, allowance: 8 } if decoded == delegate
+        ));
+        assert_eq!(accounts(&ix), [writable(counter), signer(authority)]);
+
+        let ix = revoke_ix(&ID, &counter, &authority);
+        assert!(matches!(decode(&ix), CounterInstruction::Revoke));
+        assert_eq!(accounts(&ix), [writable(counter), signer(authority)]);
+    }
+
+    #[test]
+    fn merge_and_clone() {
+        let (destination, destination_authority, source, source_authority, payer) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+
+        let ix = merge_ix(
+            &ID,
+            &destination,
+            &destination_authority,
+            &source,
+            &source_authority,
+        );
+        assert!(matches!(decode(&ix), CounterInstruction::Merge));
+        assert_eq!(
+            accounts(&ix),
+            [
+                writable(destination),
+                signer(destination_authority),
+                writable(source),
+                signer(source_authority),
+                readonly(config())
+            ]
+        );
+
+        // Only a keypair account signs for itself
+        let ix = clone_ix(&ID, &source, &source_authority, &destination, &payer, None);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::Clone { seed: None }
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [
+                readonly(source),
+                signer(source_authority),
+                writable_signer(destination),
+                writable_signer(payer),
+                system()
+            ]
+        );
+
+        let ix = clone_ix(
+            &ID,
+            &source,
+            &source_authority,
+            &destination,
+            &payer,
+            Some("copy".into()),
+        );
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::Clone { seed: Some(seed) } if seed == "copy"
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [
+                readonly(source),
+                signer(source_authority),
+                writable(destination),
+                writable_signer(payer),
+                system()
+            ]
+        );
+    }
+
+    #[test]
+    fn votes_and_maps() {
+        let (account, authority, payer, destination) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let name = [7; 32];
+
+        let ix = initialize_votes_ix(&ID, &account, &payer);
+        assert!(matches!(decode(&ix), CounterInstruction::InitializeVotes));
+        assert_eq!(
+            accounts(&ix),
+            [writable_signer(account), writable_signer(payer), system()]
+        );
+
+        let ix = vote_up_ix(&ID, &account, 3);
+        assert!(matches!(decode(&ix), CounterInstruction::VoteUp(3)));
+        assert_eq!(accounts(&ix), [writable(account), readonly(config())]);
+
+        let ix = vote_down_ix(&ID, &account, 4);
+        assert!(matches!(decode(&ix), CounterInstruction::VoteDown(4)));
+        assert_eq!(accounts(&ix), [writable(account), readonly(config())]);
+
+        let ix = initialize_map_ix(&ID, &account, &payer);
+        assert!(matches!(decode(&ix), CounterInstruction::InitializeMap));
+        assert_eq!(
+            accounts(&ix),
+            [writable_signer(account), writable_signer(payer), system()]
+        );
+
+        let ix = add_map_entry_ix(&ID, &account, &authority, &payer, name);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::AddMapEntry { name: decoded } if decoded == name
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [
+                writable(account),
+                signer(authority),
+                writable_signer(payer),
+                system()
+            ]
+        );
+
+        let ix = remove_map_entry_ix(&ID, &account, &authority, &destination, name);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::RemoveMapEntry { name: decoded } if decoded == name
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [writable(account), signer(authority), writable(destination)]
+        );
+
+        let ix = increment_map_entry_ix(&ID, &account, name, 5);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::IncrementMapEntry { name: decoded, amount: 5 } if decoded == name
+        ));
+        assert_eq!(accounts(&ix), [writable(account), readonly(config())]);
+
+        let ix = decrement_map_entry_ix(&ID, &account, &authority, name, 6);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::DecrementMapEntry { name: decoded, amount: 6 } if decoded == name
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [writable(account), signer(authority), readonly(config())]
+        );
+    }
+
+    #[test]
+    fn resize_and_metadata() {
+        let (counter, authority, payer) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let expected = [
+            writable(counter),
+            signer(authority),
+            writable_signer(payer),
+            system(),
+        ];
+
+        let ix = resize_ix(&ID, &counter, &authority, &payer, 512);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::Resize { len: 512 }
+        ));
+        assert_eq!(accounts(&ix), expected);
+
+        let ix = set_metadata_ix(
+            &ID,
+            &counter,
+            &authority,
+            &payer,
+            "visits".into(),
+            "Page visits".into(),
+        );
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetMetadata { name, description }
+                if name == "visits" && description == "Page visits"
+        ));
+        assert_eq!(accounts(&ix), expected);
+    }
+
+    #[test]
+    fn shards() {
+        let (counter, authority, payer) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let shard = |index| find_shard_address(&ID, &counter, index).0;
+
+        let ix = initialize_shard_ix(&ID, &counter, &authority, &payer, 1);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::InitializeShard { index: 1 }
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [
+                readonly(counter),
+                signer(authority),
+                writable(shard(1)),
+                writable_signer(payer),
+                system()
+            ]
+        );
+
+        let ix = increment_shard_ix(&ID, &counter, 1, 9);
+        assert!(matches!(decode(&ix), CounterInstruction::IncrementShard(9)));
+        assert_eq!(
+            accounts(&ix),
+            [writable(shard(1)), readonly(counter), readonly(config())]
+        );
+
+        let ix = aggregate_ix(&ID, &counter, &[0, 2]);
+        assert!(matches!(decode(&ix), CounterInstruction::Aggregate));
+        assert_eq!(
+            accounts(&ix),
+            [
+                writable(counter),
+                readonly(config()),
+                writable(shard(0)),
+                writable(shard(2))
+            ]
+        );
+    }
+
+    #[test]
+    fn counter_settings() {
+        let (counter, authority, other) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let expected = [writable(counter), signer(authority)];
+
+        let ix = set_exactly_once_ix(&ID, &counter, &authority, true);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetExactlyOnce { enabled: true }
+        ));
+        assert_eq!(accounts(&ix), expected);
+
+        let ix = set_freeze_authority_ix(&ID, &counter, &authority, &other);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetFreezeAuthority { freeze_authority } if freeze_authority == other
+        ));
+        assert_eq!(accounts(&ix), expected);
+
+        let ix = set_thresholds_ix(&ID, &counter, &authority, vec![-5, 10]);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetThresholds { thresholds } if thresholds == [-5, 10]
+        ));
+        assert_eq!(accounts(&ix), expected);
+
+        let ix = set_epoch_reset_ix(&ID, &counter, &authority, true);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetEpochReset { enabled: true }
+        ));
+        assert_eq!(accounts(&ix), expected);
+
+        let ix = lock_ix(&ID, &counter, &authority, 1_000);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::Lock { until_slot: 1_000 }
+        ));
+        assert_eq!(accounts(&ix), expected);
+
+        let ix = set_expiry_ix(&ID, &counter, &authority, 1_700_000_000);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetExpiry {
+                expires_at: 1_700_000_000
+            }
+        ));
+        assert_eq!(accounts(&ix), expected);
+
+        let ix = set_ema_ix(&ID, &counter, &authority, 2_500);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetEma { alpha_bps: 2_500 }
+        ));
+        assert_eq!(accounts(&ix), expected);
+
+        let ix = set_nft_authority_ix(&ID, &counter, &authority, &other);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetNftAuthority { mint } if mint == other
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [writable(counter), signer(authority), readonly(other)]
+        );
+
+        let ix = set_governance_authority_ix(&ID, &counter, &authority, &other, true);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetGovernanceAuthority { governance, treasury: true }
+                if governance == other
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [writable(counter), signer(authority), readonly(other)]
+        );
+
+        let ix = view_ix(&ID, &counter);
+        assert!(matches!(decode(&ix), CounterInstruction::View));
+        assert_eq!(accounts(&ix), [readonly(counter)]);
+    }
+
+    #[test]
+    fn companion_accounts() {
+        let (counter, authority, payer, destination, other) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        // Creating or rewriting a companion, then closing it
+        let set = |companion| {
+            [
+                writable(counter),
+                signer(authority),
+                writable(companion),
+                writable_signer(payer),
+                system(),
+            ]
+        };
+        let clear = |companion| {
+            [
+                writable(counter),
+                signer(authority),
+                writable(companion),
+                writable(destination),
+            ]
+        };
+
+        let ix = initialize_history_ix(&ID, &counter, &authority, &payer, 16);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::InitializeHistory { capacity: 16 }
+        ));
+        assert_eq!(accounts(&ix), set(find_history_address(&ID, &counter).0));
+
+        let callback = find_callback_address(&ID, &counter).0;
+        let registered = vec![CallbackAccount {
+            address: destination,
+            writable: true,
+        }];
+        let ix = set_callback_ix(
+            &ID,
+            &counter,
+            &authority,
+            &payer,
+            -3,
+            &other,
+            registered.clone(),
+        );
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetCallback { target: -3, program, accounts }
+                if program == other && accounts == registered
+        ));
+        assert_eq!(accounts(&ix), set(callback));
+        let ix = clear_callback_ix(&ID, &counter, &authority, &destination);
+        assert!(matches!(decode(&ix), CounterInstruction::ClearCallback));
+        assert_eq!(accounts(&ix), clear(callback));
+
+        let hooks = find_hooks_address(&ID, &counter).0;
+        let ix = set_hooks_ix(&ID, &counter, &authority, &payer, Some(other), None);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetHooks { pre: Some(pre), post: None } if pre == other
+        ));
+        assert_eq!(accounts(&ix), set(hooks));
+        let ix = clear_hooks_ix(&ID, &counter, &authority, &destination);
+        assert!(matches!(decode(&ix), CounterInstruction::ClearHooks));
+        assert_eq!(accounts(&ix), clear(hooks));
+
+        let gate = find_token_gate_address(&ID, &counter).0;
+        let ix = set_token_gate_ix(&ID, &counter, &authority, &payer, &other, 10);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetTokenGate { mint, min_balance: 10 } if mint == other
+        ));
+        assert_eq!(accounts(&ix), set(gate));
+        let ix = clear_token_gate_ix(&ID, &counter, &authority, &destination);
+        assert!(matches!(decode(&ix), CounterInstruction::ClearTokenGate));
+        assert_eq!(accounts(&ix), clear(gate));
+
+        let ix = set_burn_mint_ix(&ID, &counter, &authority, &payer, &other);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetBurnMint { mint } if mint == other
+        ));
+        assert_eq!(accounts(&ix), set(find_burn_address(&ID, &counter).0));
+
+        let rewards = find_rewards_address(&ID, &counter).0;
+        let ix = set_rewards_ix(&ID, &counter, &authority, &payer, &other, 4, vec![100]);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetRewards { mint, amount: 4, milestones }
+                if mint == other && milestones == [100]
+        ));
+        assert_eq!(accounts(&ix), set(rewards));
+        let ix = clear_rewards_ix(&ID, &counter, &authority, &destination);
+        assert!(matches!(decode(&ix), CounterInstruction::ClearRewards));
+        assert_eq!(accounts(&ix), clear(rewards));
+
+        let permits = find_permits_address(&ID, &counter).0;
+        let ix = require_permits_ix(&ID, &counter, &authority, &payer);
+        assert!(matches!(decode(&ix), CounterInstruction::RequirePermits));
+        assert_eq!(accounts(&ix), set(permits));
+        let ix = clear_permits_ix(&ID, &counter, &authority, &destination);
+        assert!(matches!(decode(&ix), CounterInstruction::ClearPermits));
+        assert_eq!(accounts(&ix), clear(permits));
+
+        let cooldown = find_cooldown_address(&ID, &counter).0;
+        let ix = set_cooldown_ix(&ID, &counter, &authority, &payer, 20, 50);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetCooldown {
+                slots: 20,
+                daily_cap: 50
+            }
+        ));
+        assert_eq!(accounts(&ix), set(cooldown));
+        let ix = clear_cooldown_ix(&ID, &counter, &authority, &destination);
+        assert!(matches!(decode(&ix), CounterInstruction::ClearCooldown));
+        assert_eq!(accounts(&ix), clear(cooldown));
+
+        let decay = find_decay_address(&ID, &counter).0;
+        let ix = set_decay_ix(&ID, &counter, &authority, &payer, 2, DecayUnit::Second);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::SetDecay {
+                rate: 2,
+                unit: DecayUnit::Second
+            }
+        ));
+        assert_eq!(accounts(&ix), set(decay));
+        let ix = clear_decay_ix(&ID, &counter, &authority, &destination);
+        assert!(matches!(decode(&ix), CounterInstruction::ClearDecay));
+        assert_eq!(accounts(&ix), clear(decay));
+    }
+
+    #[test]
+    fn operators() {
+        let (counter, authority, payer, operator) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let operators = find_operators_address(&ID, &counter).0;
+
+        let ix = add_operator_ix(&ID, &counter, &authority, &payer, &operator, 30, 900);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::AddOperator { operator: decoded, allowance: 30, expiry_slot: 900 }
+                if decoded == operator
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [
+                readonly(counter),
+                signer(authority),
+                writable(operators),
+                writable_signer(payer),
+                system()
+            ]
+        );
+
+        let ix = remove_operator_ix(&ID, &counter, &authority, &operator);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::RemoveOperator { operator: decoded } if decoded == operator
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [readonly(counter), signer(authority), writable(operators)]
+        );
+    }
+
+    #[test]
+    fn escrows() {
+        let (counter, authority, payer, beneficiary) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let escrow = find_escrow_address(&ID, &counter).0;
+        let deposit = find_escrow_deposit_address(&ID, &escrow, &payer).0;
+
+        let ix = initialize_escrow_ix(&ID, &counter, &authority, &payer, &beneficiary, 100, 5_000);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::InitializeEscrow { beneficiary: decoded, target: 100, deadline_slot: 5_000 }
+                if decoded == beneficiary
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [
+                writable(counter),
+                signer(authority),
+                writable(escrow),
+                writable_signer(payer),
+                system()
+            ]
+        );
+
+        let ix = deposit_escrow_ix(&ID, &counter, &payer, 1_000);
+        assert!(matches!(
+            decode(&ix),
+            CounterInstruction::DepositEscrow { lamports: 1_000 }
+        ));
+        assert_eq!(
+            accounts(&ix),
+            [
+                readonly(counter),
+                writable(escrow),
+                writable(deposit),
+                writable_signer(payer),
+                system()
+            ]
+        );
+
+        let ix = settle_ix(&ID, &counter, &beneficiary);
+        assert!(matches!(decode(&ix), CounterInstruction::Settle));
+        assert_eq!(
+            accounts(&ix),
+            [writable(counter), writable(escrow), writable(beneficiary)]
+        );
+
+        // A refund is a settle that also passes the deposit record
+        let ix = refund_ix(&ID, &counter, &payer);
+        assert!(matches!(decode(&ix), CounterInstruction::Settle));
+        assert_eq!(
+            accounts(&ix),
+            [
+                writable(counter),
+                writable(escrow),
+                writable(payer),
+                writable(deposit)
+            ]
+        );
+    }
+
+    #[test]
+    fn crank() {
+        let (counter, cranker) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let expected = [
+            writable(counter),
+            writable(config()),
+            writable_signer(cranker),
+            writable(find_fee_vault_address(&ID).0),
+        ];
+
+        let ix = crank_ix(&ID, &counter, &cranker, false);
+        assert!(matches!(decode(&ix), CounterInstruction::Crank));
+        assert_eq!(accounts(&ix), expected);
+
+        // The decay is only read
+        let ix = crank_ix(&ID, &counter, &cranker, true);
+        assert!(matches!(decode(&ix), CounterInstruction::Crank));
+        assert_eq!(accounts(&ix)[..4], expected);
+        assert_eq!(
+            accounts(&ix)[4..],
+            [readonly(find_decay_address(&ID, &counter).0)]
+        );
+    }
+}
@@ -0,0 +1,86 @@
+//! Off-chain Rust client for the counter program
+//!
+//! Everything a wallet, service or script needs to talk to the program,
+//! without linking its entrypoint or processor: a builder returning a
+//! ready-to-send [`Instruction`](solana_program::instruction::Instruction)
+//! for every instruction, the account layouts and the addresses they live
+//! at, and decoders for counters, errors and return data. Enable the
+//! `anchor` feature when talking to a deployment built with the program's
+//! own `anchor` feature, so discriminators match. The `rpc` feature adds
+//! `CounterClient`, which sends them to a cluster, and the `blocking` feature
+//! `blocking::CounterClient`, the same without an async runtime.
+//!
+//! The builders follow the program's [`builders`] module, which covers the
+//! everyday instructions and is re-exported here: accounts in the order the
+//! program validates them, authorities passed as signers, and optional
+//! trailing groups (history, multisig signers, fees, hooks) appended to
+//! `accounts` by the caller.
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod instructions;
+#[cfg(feature = "rpc")]
+mod nonblocking;
+#[cfg(any(feature = "rpc", feature = "blocking"))]
+mod rpc;
+
+pub use instructions::*;
+#[cfg(feature = "rpc")]
+pub use nonblocking::CounterClient;
+#[cfg(any(feature = "rpc", feature = "blocking"))]
+pub use rpc::RpcError;
+#[cfg(any(feature = "rpc", feature = "blocking"))]
+pub use solana_client::rpc_response::{Response, RpcResponseContext};
+pub use solana_counter_program::{
+    BURN_SEED, CALLBACK_SEED, CONFIG_SEED, COOLDOWN_SEED, COOLDOWN_STAMP_SEED,
+    COUNTER_AUTHORITY_OFFSET, COUNTER_DISCRIMINATOR, COUNTER_DISCRIMINATOR_OFFSET, COUNTER_LEN,
+    COUNTER_SEED, COUNTER_VERSION, COUNTER_VERSION_OFFSET, CallbackAccount, Counter, CounterFlag,
+    CounterInstruction, CounterKind, CounterOp, DECAY_SEED, DecayUnit, ESCROW_DEPOSIT_SEED,
+    ESCROW_SEED, FEE_VAULT_SEED, FeeConfig, HISTORY_SEED, HOOKS_SEED, ID, InitializeArgs,
+    LockStatus, MAX_AGGREGATE_SHARDS, MAX_BATCH_OPS, MAX_CALLBACK_ACCOUNTS, MAX_MEMO_LEN,
+    MAX_MILESTONES, MAX_OPERATORS, MEMO_PROGRAM_ID, NATIVE_TREASURY_SEED, OPERATORS_SEED, Operator,
+    OverflowMode, PERMIT_MESSAGE_LEN, PERMITS_SEED, RETURN_DATA_LEN, REWARDS_SEED, SHARD_SEED,
+    TOKEN_GATE_SEED,
+    builders::{self, *},
+    create_config_address, create_counter_address, decode_return_data,
+    error::CounterError,
+    events::{self, CounterView, Direction, EVENT_SCHEMA_VERSION, Event, OpId, Operation},
+    find_burn_address, find_callback_address, find_config_address, find_cooldown_address,
+    find_cooldown_stamp_address, find_counter_address, find_decay_address, find_escrow_address,
+    find_escrow_deposit_address, find_fee_vault_address, find_history_address, find_hooks_address,
+    find_native_treasury_address, find_operators_address, find_permits_address,
+    find_rewards_address, find_shard_address, find_token_gate_address, max_instruction_len,
+    permit_message, required_lamports, schema, time_weighted_average,
+};
+#[cfg(any(feature = "rpc", feature = "blocking"))]
+pub use solana_sdk::commitment_config::CommitmentConfig;
+
+use num_traits::FromPrimitive;
+use solana_program::program_error::ProgramError;
+
+/// The [`CounterError`] behind `error`, as a failed transaction reports it
+///
+/// `None` for errors that are not one of the program's custom codes, such
+/// as a missing signature or too few lamports.
+pub fn decode_error(error: &ProgramError) -> Option<CounterError> {
+    match error {
+        ProgramError::Custom(code) => CounterError::from_u32(*code),
+        _ => None,
+    }
+}
+
+/// The counter in `data`, an account's data as fetched over RPC
+///
+/// `None` unless the data holds a counter in the current layout, checked by
+/// its discriminator and version. A counter in an older layout must first be
+/// brought up to date with [`migrate_ix`]. Bytes after the counter, such as
+/// its metadata, are ignored.
+pub fn decode_counter(data: &[u8]) -> Option<Counter> {
+    let bytes = data.get(..COUNTER_LEN)?;
+    if bytes[COUNTER_DISCRIMINATOR_OFFSET..][..COUNTER_DISCRIMINATOR.len()] != COUNTER_DISCRIMINATOR
+        || bytes[COUNTER_VERSION_OFFSET] != COUNTER_VERSION
+    {
+        return None;
+    }
+    Some(bytemuck::pod_read_unaligned(bytes))
+}
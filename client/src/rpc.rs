@@ -0,0 +1,215 @@
+//! Errors shared by the clients sending the program's instructions over RPC
+
+use crate::{Counter, CounterError, ID, decode_counter};
+use num_traits::FromPrimitive;
+use solana_client::{client_error::ClientError, rpc_response::Response};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{account::Account, instruction::InstructionError, transaction::TransactionError};
+
+/// Why a call of either `CounterClient` failed
+#[derive(Debug, thiserror::Error)]
+pub enum RpcError {
+    /// The program rejected the transaction with one of its own errors
+    #[error("counter program error: {0}")]
+    Program(CounterError),
+    /// No account exists at the address
+    #[error("no account at {0}")]
+    AccountNotFound(Pubkey),
+    /// The account is not owned by the program or does not hold a counter in
+    /// the current layout
+    #[error("{0} is not a counter in the current layout")]
+    NotACounter(Pubkey),
+    /// The RPC request failed, or the transaction failed outside the program
+    #[error(transparent)]
+    Client(Box<ClientError>),
+}
+
+/// Surfaces a custom error of the program as [`RpcError::Program`]
+impl From<ClientError> for RpcError {
+    fn from(error: ClientError) -> Self {
+        match error.get_transaction_error() {
+            Some(TransactionError::InstructionError(_, InstructionError::Custom(code))) => {
+                match CounterError::from_u32(code) {
+                    Some(error) => Self::Program(error),
+                    None => Self::Client(Box::new(error)),
+                }
+            }
+            _ => Self::Client(Box::new(error)),
+        }
+    }
+}
+
+/// The counter in the account at `counter`, as fetched in `response`
+pub(crate) fn decode_response(
+    counter: &Pubkey,
+    response: Response<Option<Account>>,
+) -> Result<Response<Counter>, RpcError> {
+    let account = response.value.ok_or(RpcError::AccountNotFound(*counter))?;
+    let value = (account.owner == ID)
+        .then(|| decode_counter(&account.data))
+        .flatten()
+        .ok_or(RpcError::NotACounter(*counter))?;
+    Ok(Response {
+        context: response.context,
+        value,
+    })
+}
+
+/// Canned responses for testing the clients against a mock RPC sender
+#[cfg(test)]
+pub(crate) mod mocks {
+    use crate::{COUNTER_DISCRIMINATOR_OFFSET, Counter, CounterError, ID};
+    use async_trait::async_trait;
+    use bytemuck::bytes_of;
+    use serde_json::{Value, json};
+    use solana_account_decoder::{UiAccount, UiAccountEncoding};
+    use solana_client::{
+        client_error::Result,
+        rpc_request::RpcRequest,
+        rpc_response::{Response, RpcResponseContext},
+        rpc_sender::{RpcSender, RpcTransportStats},
+    };
+    use solana_program::pubkey::Pubkey;
+    use solana_sdk::{
+        account::Account, hash::Hash, instruction::InstructionError, transaction::TransactionError,
+    };
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    /// The requests a [`Recorder`] was sent, with their params
+    pub(crate) type Requests = Arc<Mutex<Vec<(RpcRequest, Value)>>>;
+
+    /// A sender answering each request once from its mocks, or else with
+    /// `null`, and recording what it was sent
+    ///
+    /// The mock sender drops the params, so tests checking what a client
+    /// asked for, such as the commitment of a read, go through this one.
+    pub(crate) struct Recorder {
+        mocks: Mutex<HashMap<RpcRequest, Value>>,
+        requests: Requests,
+    }
+
+    impl Recorder {
+        /// A sender answering from `mocks`, and the requests it records
+        pub(crate) fn new(mocks: HashMap<RpcRequest, Value>) -> (Self, Requests) {
+            let requests = Requests::default();
+            let recorder = Self {
+                mocks: Mutex::new(mocks),
+                requests: requests.clone(),
+            };
+            (recorder, requests)
+        }
+    }
+
+    #[async_trait]
+    impl RpcSender for Recorder {
+        async fn send(&self, request: RpcRequest, params: Value) -> Result<Value> {
+            self.requests.lock().unwrap().push((request, params));
+            Ok(match request {
+                // Recent enough for the client to send commitments as they are
+                RpcRequest::GetVersion => json!({ "solana-core": "1.18.26", "feature-set": null }),
+                _ => self
+                    .mocks
+                    .lock()
+                    .unwrap()
+                    .remove(&request)
+                    .unwrap_or(Value::Null),
+            })
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            "recorder".to_string()
+        }
+    }
+
+    /// The data of a counter account holding `count`
+    pub(crate) fn counter_data(count: u64) -> Vec<u8> {
+        let mut counter =
+            Counter::new(Pubkey::new_unique(), Default::default(), Default::default());
+        counter.count = count;
+        bytes_of(&counter).to_vec()
+    }
+
+    /// Mocks answering `getAccountInfo` with an account owned by `owner`
+    /// holding `data`, as read at `slot`
+    pub(crate) fn account(slot: u64, owner: Pubkey, data: Vec<u8>) -> HashMap<RpcRequest, Value> {
+        let account = Account {
+            lamports: 1_000_000,
+            data,
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        };
+        let value = UiAccount::encode(
+            &Pubkey::new_unique(),
+            &account,
+            UiAccountEncoding::Base64,
+            None,
+            None,
+        );
+        HashMap::from([(
+            RpcRequest::GetAccountInfo,
+            serde_json::to_value(Response {
+                context: RpcResponseContext {
+                    slot,
+                    api_version: None,
+                },
+                value: Some(value),
+            })
+            .unwrap(),
+        )])
+    }
+
+    /// Mocks answering `getAccountInfo` with a counter of the program holding
+    /// `count`, as read at `slot`
+    pub(crate) fn counter(slot: u64, count: u64) -> HashMap<RpcRequest, Value> {
+        account(slot, ID, counter_data(count))
+    }
+
+    /// The data of a counter account holding `count`, with its discriminator
+    /// corrupted
+    pub(crate) fn wrong_discriminator(count: u64) -> Vec<u8> {
+        let mut data = counter_data(count);
+        data[COUNTER_DISCRIMINATOR_OFFSET] ^= 0xff;
+        data
+    }
+
+    /// Mocks answering `getLatestBlockhash` with `hash`
+    pub(crate) fn blockhash(hash: Hash) -> HashMap<RpcRequest, Value> {
+        HashMap::from([(
+            RpcRequest::GetLatestBlockhash,
+            json!({
+                "context": { "slot": 1 },
+                "value": { "blockhash": hash.to_string(), "lastValidBlockHeight": 100 },
+            }),
+        )])
+    }
+
+    /// Mocks reporting that the transaction sent failed with `error`
+    pub(crate) fn failed(error: CounterError) -> HashMap<RpcRequest, Value> {
+        let err = serde_json::to_value(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(error as u32),
+        ))
+        .unwrap();
+        HashMap::from([(
+            RpcRequest::GetSignatureStatuses,
+            json!({
+                "context": { "slot": 1 },
+                "value": [{
+                    "slot": 1,
+                    "confirmations": null,
+                    "status": { "Err": err },
+                    "err": err,
+                    "confirmationStatus": "finalized",
+                }],
+            }),
+        )])
+    }
+}
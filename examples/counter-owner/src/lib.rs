@@ -0,0 +1,184 @@
+//! A sample program that owns counters
+//!
+//! Every counter this program creates has the program's [`find_owner_address`]
+//! PDA as its authority, so only this program can change it in the ways an
+//! authority can. The PDA has no private key; the program signs for it with
+//! `invoke_signed` when it invokes the counter program through
+//! `solana_counter_program::cpi`. A protocol embedding counters works the
+//! same way, checking its own rules before each signed call; this one lets
+//! anyone decrement or reset its counters. Its increments read the new count
+//! back from the counter program's return data.
+//!
+//! The program also works as a hook any counter can register with
+//! `SetHooks`: it logs each change it is invoked for and accepts it.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_counter_program::{
+    cpi::{self, AuthorityAccounts, IncrementAccounts, InitializePdaAccounts, OnCounterChange},
+    decode_return_data, find_hooks_address,
+};
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    program::get_return_data,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+/// Seed of the PDA that is the authority of every counter this program creates
+pub const OWNER_SEED: &[u8] = b"owner";
+
+/// The address of this program's owner PDA and its bump seed
+pub fn find_owner_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[OWNER_SEED], program_id)
+}
+
+/// The instructions of the sample program
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub enum OwnerInstruction {
+    /// Create a counter owned by this program under `seed`
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter, at `find_counter_address` for the owner PDA
+    /// 1. `[writable, signer]` The payer funding the counter's rent
+    /// 2. `[]` The owner PDA
+    /// 3. `[]` The system program
+    /// 4. `[]` The counter program's config
+    /// 5. `[]` The counter program
+    Create { seed: String },
+    /// Decrement one of this program's counters, signing as its authority
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter
+    /// 1. `[]` The owner PDA
+    /// 2. `[]` The counter program's config
+    /// 3. `[]` The counter program
+    Decrement(u32),
+    /// Set one of this program's counters back to zero
+    ///
+    /// Accounts expected as for `Decrement`.
+    Reset,
+    /// Increment any counter and log the new count the counter program
+    /// returns
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter
+    /// 1. `[]` The counter program's config
+    /// 2. `[]` The counter program
+    Increment(u32),
+}
+
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
+
+/// Decode the instruction and invoke the counter program on the owner's behalf
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    if let Some(data) = instruction_data.strip_prefix(&OnCounterChange::DISCRIMINATOR) {
+        return process_hook(accounts, data);
+    }
+    let instruction = OwnerInstruction::try_from_slice(instruction_data)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    let (owner_address, bump) = find_owner_address(program_id);
+    let signer_seeds: &[&[&[u8]]] = &[&[OWNER_SEED, &[bump]]];
+    let accounts_iter = &mut accounts.iter();
+
+    match instruction {
+        OwnerInstruction::Create { seed } => {
+            let counter = next_account_info(accounts_iter)?;
+            let payer = next_account_info(accounts_iter)?;
+            let owner = next_owner(accounts_iter, &owner_address)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let config = next_account_info(accounts_iter)?;
+            let counter_program = next_account_info(accounts_iter)?;
+            let mut accounts =
+                InitializePdaAccounts::new(counter_program, counter, payer, system_program, config);
+            accounts.authority = Some(owner);
+            // The payer's signature carries over from this transaction; the
+            // owner's comes from the seeds
+            cpi::initialize_pda_signed(&accounts, &seed, Default::default(), signer_seeds)?;
+            msg!("Created counter {} owned by {}", counter.key, owner.key);
+        }
+        OwnerInstruction::Decrement(amount) => {
+            let accounts = authority_accounts(accounts_iter, &owner_address)?;
+            cpi::decrement_signed(&accounts, amount, signer_seeds)?;
+        }
+        OwnerInstruction::Reset => {
+            let accounts = authority_accounts(accounts_iter, &owner_address)?;
+            cpi::reset_signed(&accounts, signer_seeds)?;
+        }
+        OwnerInstruction::Increment(amount) => {
+            let counter = next_account_info(accounts_iter)?;
+            let config = next_account_info(accounts_iter)?;
+            let counter_program = next_account_info(accounts_iter)?;
+            cpi::increment(
+                &IncrementAccounts::new(counter_program, counter, config),
+                amount,
+            )?;
+            // Return data set by any other program is not the new count
+            let count = get_return_data()
+                .filter(|(program, _)| *program == solana_counter_program::ID)
+                .and_then(|(_, data)| decode_return_data(&data))
+                .ok_or(ProgramError::InvalidAccountData)?;
+            msg!("Counter {} is now {}", counter.key, count);
+        }
+    }
+    Ok(())
+}
+
+/// Log a change to a counter this program is a hook of, accepting it
+fn process_hook(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let change =
+        OnCounterChange::try_from_slice(data).map_err(|_| ProgramError::InvalidInstructionData)?;
+    let accounts_iter = &mut accounts.iter();
+    let hooks = next_account_info(accounts_iter)?;
+    let counter = next_account_info(accounts_iter)?;
+    // Only the counter program can sign for a counter's hooks account
+    let (expected, _) = find_hooks_address(&solana_counter_program::ID, counter.key);
+    if !hooks.is_signer || *hooks.key != expected || change.counter != *counter.key {
+        msg!("Not invoked by the counter program for {}", counter.key);
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    msg!(
+        "{:?} hook of counter {}: {} -> {:?}",
+        change.phase,
+        counter.key,
+        change.old_value,
+        change.new_value
+    );
+    Ok(())
+}
+
+/// The next account, which must be the owner PDA
+fn next_owner<'a, 'info>(
+    accounts_iter: &mut impl Iterator<Item = &'a AccountInfo<'info>>,
+    owner_address: &Pubkey,
+) -> Result<&'a AccountInfo<'info>, ProgramError> {
+    let owner = next_account_info(accounts_iter)?;
+    if owner.key != owner_address {
+        msg!("{} is not this program's owner PDA", owner.key);
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(owner)
+}
+
+/// The accounts of a change the owner PDA signs for
+fn authority_accounts<'a, 'info>(
+    accounts_iter: &mut impl Iterator<Item = &'a AccountInfo<'info>>,
+    owner_address: &Pubkey,
+) -> Result<AuthorityAccounts<'a, 'info>, ProgramError> {
+    let counter = next_account_info(accounts_iter)?;
+    let owner = next_owner(accounts_iter, owner_address)?;
+    let config = next_account_info(accounts_iter)?;
+    let counter_program = next_account_info(accounts_iter)?;
+    Ok(AuthorityAccounts::new(
+        counter_program,
+        counter,
+        owner,
+        config,
+    ))
+}
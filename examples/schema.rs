@@ -0,0 +1,30 @@
+//! Write the Borsh schemas of the counter account, its metadata, the
+//! instruction data and the events to a directory, `target/schema` unless one is given
+//!
+//! Each file holds a Borsh-encoded `BorshSchemaContainer`.
+
+use solana_counter_program::schema::{
+    counter_schema, event_schema, instruction_schema, metadata_schema,
+};
+use std::{fs, path::PathBuf};
+
+fn main() -> std::io::Result<()> {
+    let dir = PathBuf::from(
+        std::env::args()
+            .nth(1)
+            .unwrap_or_else(|| "target/schema".to_string()),
+    );
+    fs::create_dir_all(&dir)?;
+
+    for (name, schema) in [
+        ("counter", counter_schema()),
+        ("metadata", metadata_schema()),
+        ("instruction", instruction_schema()),
+        ("event", event_schema()),
+    ] {
+        let path = dir.join(format!("{name}.borsh"));
+        fs::write(&path, borsh::to_vec(&schema)?)?;
+        println!("{} ({})", path.display(), schema.declaration());
+    }
+    Ok(())
+}
@@ -0,0 +1,145 @@
+//! The arithmetic behind every change to a counter's value
+//!
+//! Results are computed exactly in `i128`, which holds any `u64` or `i64`
+//! count plus or minus any `u64` amount, or times any `u32` factor, and only
+//! then fitted into the counter's range. Instructions carry `u32` amounts,
+//! but increments and decrements take a `u64` so that totals drained from
+//! shards go through the same path. That leaves a handful of edge cases, each with a fixed
+//! outcome:
+//!
+//! - an amount of zero fails with `ZeroAmount`, for votes and map entries as
+//!   well, rather than changing nothing while still counting as an update
+//! - `u32::MAX` is an ordinary amount; only the result is checked against the
+//!   counter's range
+//! - decrementing by exactly the count leaves zero, which unsigned counters
+//!   may hold
+//! - multiplying by zero sets the count to zero, and multiplying or dividing
+//!   by one leaves it as it is
+//! - dividing by zero fails with `DivisionByZero`, and other divisions round
+//!   towards zero
+//! - a result outside the counter's kind is handled by its overflow mode, see
+//!   [`fit`]
+
+use crate::{error::CounterError, state::OverflowMode};
+
+/// Reject an increment or decrement of zero
+pub(crate) fn check_nonzero(amount: u32) -> Result<(), CounterError> {
+    if amount == 0 {
+        return Err(CounterError::ZeroAmount);
+    }
+    Ok(())
+}
+
+/// The exact result of adding `amount` to `value`
+pub(crate) fn increment(value: i128, amount: u64) -> i128 {
+    value + i128::from(amount)
+}
+
+/// The exact result of subtracting `amount` from `value`
+pub(crate) fn decrement(value: i128, amount: u64) -> i128 {
+    value - i128::from(amount)
+}
+
+/// The exact result of multiplying `value` by `factor`
+pub(crate) fn multiply(value: i128, factor: u32) -> i128 {
+    value * i128::from(factor)
+}
+
+/// The result of dividing `value` by `divisor`, rounded towards zero
+pub(crate) fn divide(value: i128, divisor: u32) -> Result<i128, CounterError> {
+    if divisor == 0 {
+        return Err(CounterError::DivisionByZero);
+    }
+    Ok(value / i128::from(divisor))
+}
+
+/// `result` reduced into `0..modulus`, or unchanged if `modulus` is zero
+///
+/// Negative results wrap around from the top, so `-1` becomes `modulus - 1`.
+pub(crate) fn reduce(result: i128, modulus: u64) -> i128 {
+    if modulus == 0 {
+        result
+    } else {
+        result.rem_euclid(modulus.into())
+    }
+}
+
+/// Fit the exact result of an operation into `min..=max` according to `mode`
+///
+/// Checked counters fail with `Overflow` or `Underflow`, saturating ones stop
+/// at the nearer end of the range and wrapping ones continue from the other.
+pub(crate) fn fit(
+    mode: OverflowMode,
+    result: i128,
+    min: i128,
+    max: i128,
+) -> Result<i128, CounterError> {
+    if (min..=max).contains(&result) {
+        return Ok(result);
+    }
+    match mode {
+        OverflowMode::Checked if result > max => Err(CounterError::Overflow),
+        OverflowMode::Checked => Err(CounterError::Underflow),
+        OverflowMode::Saturating => Ok(result.clamp(min, max)),
+        OverflowMode::Wrapping => Ok(min + (result - min).rem_euclid(max - min + 1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNSIGNED_MAX: i128 = u64::MAX as i128;
+
+    #[test]
+    fn zero_amounts_are_rejected() {
+        assert_eq!(check_nonzero(0), Err(CounterError::ZeroAmount));
+        assert_eq!(check_nonzero(1), Ok(()));
+        assert_eq!(check_nonzero(u32::MAX), Ok(()));
+    }
+
+    #[test]
+    fn u32_max_is_an_ordinary_amount() {
+        let amount = u64::from(u32::MAX);
+        assert_eq!(increment(0, amount), i128::from(u32::MAX));
+        assert_eq!(decrement(amount.into(), amount), 0);
+
+        // Only the result is checked against the range
+        let near_top = UNSIGNED_MAX - 1;
+        let result = increment(near_top, amount);
+        assert_eq!(
+            fit(OverflowMode::Checked, result, 0, UNSIGNED_MAX),
+            Err(CounterError::Overflow)
+        );
+        assert_eq!(
+            fit(OverflowMode::Saturating, result, 0, UNSIGNED_MAX),
+            Ok(UNSIGNED_MAX)
+        );
+        assert_eq!(
+            fit(OverflowMode::Wrapping, result, 0, UNSIGNED_MAX),
+            Ok(i128::from(u32::MAX) - 2)
+        );
+    }
+
+    #[test]
+    fn decrementing_by_the_count_leaves_zero() {
+        let result = decrement(5, 5);
+        assert_eq!(result, 0);
+        assert_eq!(fit(OverflowMode::Checked, result, 0, UNSIGNED_MAX), Ok(0));
+
+        // One more is an underflow on an unsigned counter
+        assert_eq!(
+            fit(OverflowMode::Checked, decrement(5, 6), 0, UNSIGNED_MAX),
+            Err(CounterError::Underflow)
+        );
+    }
+
+    #[test]
+    fn multiplying_and_dividing_by_zero_and_one() {
+        assert_eq!(multiply(9, 0), 0);
+        assert_eq!(multiply(9, 1), 9);
+        assert_eq!(divide(9, 1), Ok(9));
+        assert_eq!(divide(9, 0), Err(CounterError::DivisionByZero));
+        assert_eq!(divide(-7, 2), Ok(-3));
+    }
+}
@@ -0,0 +1,463 @@
+//! Constructors for the program's most common instructions
+//!
+//! Each returns a ready-to-send [`Instruction`] with the accounts in the
+//! order the program validates them and the data encoded as it decodes it,
+//! so integrators never lay out either by hand. Builds with debug assertions
+//! reject a `program_id` other than [`crate::ID`].
+//!
+//! The off-chain `counter-client` crate re-exports these alongside builders
+//! for every other instruction.
+//!
+//! Authorities are passed as signers. An authority that is a multisig
+//! instead needs its approving signers appended to `accounts`, and the
+//! optional history and Instructions sysvar accounts are appended the same way.
+
+use crate::{
+    events::OpId,
+    instruction::{
+        CounterInstruction, INCREMENT_BY_ONE_TAG, InitializeArgs, MEMO_PROGRAM_ID, WITH_MEMO_TAG,
+        WITH_OP_ID_TAG,
+    },
+    state::{find_config_address, find_counter_address},
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+/// Catch instructions addressed to another deployment in debug builds
+fn check_program_id(program_id: &Pubkey) {
+    debug_assert_eq!(
+        *program_id,
+        crate::ID,
+        "instruction built for {}, but this program is {}",
+        program_id,
+        crate::ID
+    );
+}
+
+/// An instruction changing `counter` on behalf of anyone
+fn counter_ix(program_id: &Pubkey, counter: &Pubkey, data: &CounterInstruction) -> Instruction {
+    check_program_id(program_id);
+    Instruction::new_with_borsh(
+        *program_id,
+        data,
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        ],
+    )
+}
+
+/// An instruction changing `counter` on behalf of `authority`, which also
+/// reads the config when `with_config` is set
+fn authority_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    with_config: bool,
+    data: &CounterInstruction,
+) -> Instruction {
+    check_program_id(program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*counter, false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+    if with_config {
+        accounts.push(AccountMeta::new_readonly(
+            find_config_address(program_id).0,
+            false,
+        ));
+    }
+    Instruction::new_with_borsh(*program_id, data, accounts)
+}
+
+/// Increment `counter` by `amount`
+pub fn increment_ix(program_id: &Pubkey, counter: &Pubkey, amount: u32) -> Instruction {
+    counter_ix(program_id, counter, &CounterInstruction::Increment(amount))
+}
+
+/// Increment `counter` by one, with the single-byte encoding
+pub fn increment_by_one_ix(program_id: &Pubkey, counter: &Pubkey) -> Instruction {
+    let mut instruction = counter_ix(program_id, counter, &CounterInstruction::IncrementByOne);
+    instruction.data = vec![INCREMENT_BY_ONE_TAG];
+    instruction
+}
+
+/// Decrement `counter` by `amount`, signed by its `authority` or delegate
+pub fn decrement_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    amount: u32,
+) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        true,
+        &CounterInstruction::Decrement(amount),
+    )
+}
+
+/// Create a counter at the new keypair address `counter`, owned by `payer`
+pub fn initialize_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    payer: &Pubkey,
+    args: InitializeArgs,
+) -> Instruction {
+    check_program_id(program_id);
+    Instruction::new_with_borsh(
+        *program_id,
+        &CounterInstruction::Initialize(args),
+        vec![
+            AccountMeta::new(*counter, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Create the counter `payer` owns under `seed`, at [`find_counter_address`]
+pub fn initialize_pda_ix(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    seed: &str,
+    args: InitializeArgs,
+) -> Instruction {
+    check_program_id(program_id);
+    let (counter, _) = find_counter_address(program_id, payer, seed);
+    Instruction::new_with_borsh(
+        *program_id,
+        &CounterInstruction::InitializePda {
+            seed: seed.to_string(),
+            args,
+        },
+        vec![
+            AccountMeta::new(counter, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Set `counter` back to zero
+pub fn reset_ix(program_id: &Pubkey, counter: &Pubkey, authority: &Pubkey) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        true,
+        &CounterInstruction::Reset,
+    )
+}
+
+/// Set `counter` to `value`
+pub fn set_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    value: u64,
+) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        true,
+        &CounterInstruction::Set(value),
+    )
+}
+
+/// Multiply `counter` by `factor`
+pub fn multiply_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    factor: u32,
+) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        true,
+        &CounterInstruction::Multiply(factor),
+    )
+}
+
+/// Divide `counter` by `divisor`
+pub fn divide_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    divisor: u32,
+) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        true,
+        &CounterInstruction::Divide(divisor),
+    )
+}
+
+/// Set `counter` to `new` if it currently holds `expected`
+pub fn compare_and_swap_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    expected: u64,
+    new: u64,
+) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        true,
+        &CounterInstruction::CompareAndSwap { expected, new },
+    )
+}
+
+/// Propose `new_authority` as the authority of `counter`
+pub fn transfer_authority_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    new_authority: &Pubkey,
+) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        false,
+        &CounterInstruction::TransferAuthority {
+            new_authority: *new_authority,
+        },
+    )
+}
+
+/// Accept a pending transfer of `counter` to `pending_authority`
+pub fn accept_authority_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    pending_authority: &Pubkey,
+) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        pending_authority,
+        false,
+        &CounterInstruction::AcceptAuthority,
+    )
+}
+
+/// Close `counter`, sending its lamports to `destination`
+pub fn close_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    let mut instruction = authority_ix(
+        program_id,
+        counter,
+        authority,
+        false,
+        &CounterInstruction::Close,
+    );
+    instruction
+        .accounts
+        .push(AccountMeta::new(*destination, false));
+    instruction
+}
+
+/// Stop every change to the count of `counter` until [`unpause_ix`]
+pub fn pause_ix(program_id: &Pubkey, counter: &Pubkey, authority: &Pubkey) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        false,
+        &CounterInstruction::Pause,
+    )
+}
+
+/// Allow changes to the count of `counter` again after [`pause_ix`]
+pub fn unpause_ix(program_id: &Pubkey, counter: &Pubkey, authority: &Pubkey) -> Instruction {
+    authority_ix(
+        program_id,
+        counter,
+        authority,
+        false,
+        &CounterInstruction::Unpause,
+    )
+}
+
+/// Wrap `instruction`, one of the above that changes a count, so `memo` is
+/// forwarded to the SPL Memo program alongside it
+///
+/// The memo must fit in [`MAX_MEMO_LEN`](crate::MAX_MEMO_LEN) bytes. Append
+/// history or multisig accounts before wrapping, and an Instructions sysvar
+/// after.
+pub fn with_memo_ix(mut instruction: Instruction, memo: &str) -> Instruction {
+    check_program_id(&instruction.program_id);
+    // The Borsh encoding of the variant: its tag, the memo as a string, then
+    // the wrapped instruction's data as it is
+    let mut data = vec![WITH_MEMO_TAG];
+    data.extend_from_slice(&(memo.len() as u32).to_le_bytes());
+    data.extend_from_slice(memo.as_bytes());
+    data.append(&mut instruction.data);
+    instruction.data = data;
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(MEMO_PROGRAM_ID, false));
+    instruction
+}
+
+/// Wrap `instruction`, one of the above that changes a count, so `op_id` is
+/// echoed in the event its change emits
+///
+/// The accounts are left as they are. To add a memo as well, pass the result
+/// to [`with_memo_ix`].
+pub fn with_op_id_ix(mut instruction: Instruction, op_id: OpId) -> Instruction {
+    check_program_id(&instruction.program_id);
+    // The Borsh encoding of the variant: its tag, the id's 16 bytes, then the
+    // wrapped instruction's data as it is
+    let mut data = vec![WITH_OP_ID_TAG];
+    data.extend_from_slice(&op_id);
+    data.append(&mut instruction.data);
+    instruction.data = data;
+    instruction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ID, state::OverflowMode};
+    use borsh::BorshDeserialize;
+
+    /// The instruction `instruction` carries, decoded as the program reads
+    /// its data
+    fn decode(instruction: &Instruction) -> CounterInstruction {
+        assert_eq!(instruction.program_id, ID);
+        CounterInstruction::try_from_slice(&instruction.data).unwrap()
+    }
+
+    #[test]
+    fn count_changes_decode_to_their_variants() {
+        let (counter, authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert!(matches!(
+            decode(&increment_ix(&ID, &counter, 7)),
+            CounterInstruction::Increment(7)
+        ));
+        assert!(matches!(
+            decode(&increment_by_one_ix(&ID, &counter)),
+            CounterInstruction::IncrementByOne
+        ));
+        assert!(matches!(
+            decode(&decrement_ix(&ID, &counter, &authority, 3)),
+            CounterInstruction::Decrement(3)
+        ));
+        assert!(matches!(
+            decode(&reset_ix(&ID, &counter, &authority)),
+            CounterInstruction::Reset
+        ));
+        assert!(matches!(
+            decode(&set_ix(&ID, &counter, &authority, 42)),
+            CounterInstruction::Set(42)
+        ));
+        assert!(matches!(
+            decode(&multiply_ix(&ID, &counter, &authority, 5)),
+            CounterInstruction::Multiply(5)
+        ));
+        assert!(matches!(
+            decode(&divide_ix(&ID, &counter, &authority, 2)),
+            CounterInstruction::Divide(2)
+        ));
+        assert!(matches!(
+            decode(&compare_and_swap_ix(&ID, &counter, &authority, 4, 9)),
+            CounterInstruction::CompareAndSwap {
+                expected: 4,
+                new: 9
+            }
+        ));
+    }
+
+    #[test]
+    fn initializations_decode_with_their_args() {
+        let payer = Pubkey::new_unique();
+        let args = InitializeArgs {
+            overflow_mode: OverflowMode::Wrapping,
+            step: 3,
+            modulus: 10,
+            ..Default::default()
+        };
+        assert!(matches!(
+            decode(&initialize_ix(&ID, &Pubkey::new_unique(), &payer, args)),
+            CounterInstruction::Initialize(InitializeArgs {
+                overflow_mode: OverflowMode::Wrapping,
+                step: 3,
+                modulus: 10,
+                ..
+            })
+        ));
+        assert!(matches!(
+            decode(&initialize_pda_ix(&ID, &payer, "votes", args)),
+            CounterInstruction::InitializePda {
+                seed,
+                args: InitializeArgs { step: 3, .. },
+            } if seed == "votes"
+        ));
+    }
+
+    #[test]
+    fn authority_changes_decode_to_their_variants() {
+        let (counter, authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let new_authority = Pubkey::new_unique();
+        assert!(matches!(
+            decode(&transfer_authority_ix(&ID, &counter, &authority, &new_authority)),
+            CounterInstruction::TransferAuthority { new_authority: proposed }
+                if proposed == new_authority
+        ));
+        assert!(matches!(
+            decode(&accept_authority_ix(&ID, &counter, &new_authority)),
+            CounterInstruction::AcceptAuthority
+        ));
+        assert!(matches!(
+            decode(&close_ix(&ID, &counter, &authority, &Pubkey::new_unique())),
+            CounterInstruction::Close
+        ));
+        assert!(matches!(
+            decode(&pause_ix(&ID, &counter, &authority)),
+            CounterInstruction::Pause
+        ));
+        assert!(matches!(
+            decode(&unpause_ix(&ID, &counter, &authority)),
+            CounterInstruction::Unpause
+        ));
+    }
+
+    #[test]
+    fn wrappers_decode_around_the_wrapped_instruction() {
+        let counter = Pubkey::new_unique();
+        let op_id = [7; 16];
+        let instruction = with_memo_ix(
+            with_op_id_ix(increment_ix(&ID, &counter, 2), op_id),
+            "tip jar",
+        );
+        let CounterInstruction::WithMemo { memo, instruction } = decode(&instruction) else {
+            panic!("expected WithMemo");
+        };
+        assert_eq!(memo, "tip jar");
+        let CounterInstruction::WithOpId {
+            op_id: echoed,
+            instruction,
+        } = *instruction
+        else {
+            panic!("expected WithOpId");
+        };
+        assert_eq!(echoed, op_id);
+        assert!(matches!(*instruction, CounterInstruction::Increment(2)));
+    }
+}
@@ -0,0 +1,556 @@
+//! Helpers for other on-chain programs invoking this one
+//!
+//! Each function builds the instruction from the account infos it is given,
+//! in the order the program validates them, and invokes it. The `_signed`
+//! variants sign with `signer_seeds` through `invoke_signed`, for callers
+//! whose program-derived address is the authority or actor:
+//!
+//! ```no_run
+//! # use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult};
+//! use solana_counter_program::cpi::{self, IncrementAccounts};
+//!
+//! fn bump<'info>(
+//!     program: &AccountInfo<'info>,
+//!     counter: &AccountInfo<'info>,
+//!     config: &AccountInfo<'info>,
+//! ) -> ProgramResult {
+//!     cpi::increment(&IncrementAccounts::new(program, counter, config), 1)
+//! }
+//! ```
+//!
+//! Depend on this crate with the `no-entrypoint` feature to use them. A change
+//! sets the new count as return data, which the caller reads with
+//! `get_return_data` and [`decode_return_data`](crate::decode_return_data).
+//! Multisig authorities are not covered; build those instructions with
+//! [`crate::builders`] and append the signers.
+//!
+//! The other direction, this program invoking a counter's callback once its
+//! count reaches a target, uses the interface in [`OnTargetReached`], and
+//! invoking its hooks around each change the one in [`OnCounterChange`].
+
+use crate::instruction::{CounterInstruction, InitializeArgs};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+    pubkey::Pubkey,
+};
+
+/// The instruction a counter's callback program is invoked with when a change
+/// reaches the target `SetCallback` registered
+///
+/// The data is [`OnTargetReached::DISCRIMINATOR`] followed by the Borsh
+/// encoding of this struct, which is how Anchor encodes an instruction named
+/// `on_target_reached` taking these fields as arguments. The accounts are:
+///
+/// 0. `[signer]` The counter's callback account, at
+///    [`find_callback_address`](crate::find_callback_address); its signature
+///    proves the call came from this program
+/// 1. `[]` The counter account
+/// 2. .. The accounts registered with the callback, in order
+///
+/// The callback fires once, after the change is written, and a callback that
+/// fails fails the change with it.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Clone, Debug, PartialEq, Eq)]
+pub struct OnTargetReached {
+    pub counter: Pubkey,
+    pub target: i128,
+    pub old_value: i128,
+    pub new_value: i128,
+    pub slot: u64,
+}
+
+impl OnTargetReached {
+    /// Anchor's `sha256("global:on_target_reached")[..8]`
+    pub const DISCRIMINATOR: [u8; 8] = [97, 169, 99, 251, 78, 12, 200, 246];
+}
+
+/// Which side of a change a hook is invoked on
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[borsh(use_discriminant = true)]
+#[repr(u8)]
+pub enum HookPhase {
+    /// Before the change is applied; failing rejects it
+    Pre = 0,
+    /// After the change is written; failing reverts it
+    Post = 1,
+}
+
+/// The instruction a counter's hook programs are invoked with around each
+/// change, as registered by `SetHooks`
+///
+/// The data is [`OnCounterChange::DISCRIMINATOR`] followed by the Borsh
+/// encoding of this struct, as Anchor encodes an instruction named
+/// `on_counter_change`. The accounts are:
+///
+/// 0. `[signer]` The counter's hooks account, at
+///    [`find_hooks_address`](crate::find_hooks_address); its signature proves
+///    the call came from this program
+/// 1. `[]` The counter account
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Clone)]
+pub struct OnCounterChange {
+    pub counter: Pubkey,
+    pub phase: HookPhase,
+    /// The change, without any `WithMemo` or `WithOpId` around it
+    pub instruction: CounterInstruction,
+    pub old_value: i128,
+    /// The value the change wrote, for [`HookPhase::Post`] only
+    pub new_value: Option<i128>,
+}
+
+impl OnCounterChange {
+    /// Anchor's `sha256("global:on_counter_change")[..8]`
+    pub const DISCRIMINATOR: [u8; 8] = [232, 192, 14, 13, 17, 173, 211, 103];
+}
+
+/// Accounts for creating a counter at its program-derived address
+pub struct InitializePdaAccounts<'a, 'info> {
+    /// The counter program itself
+    pub counter_program: &'a AccountInfo<'info>,
+    /// The new counter, at [`crate::find_counter_address`] for its authority
+    pub counter: &'a AccountInfo<'info>,
+    /// The signer funding the account's rent
+    pub payer: &'a AccountInfo<'info>,
+    /// The system program
+    pub system_program: &'a AccountInfo<'info>,
+    /// The counter's authority, if not the payer; usually a PDA of the caller
+    pub authority: Option<&'a AccountInfo<'info>>,
+    /// The program's config account, see [`crate::find_config_address`],
+    /// which the caller guard reads for every instruction arriving through a
+    /// CPI
+    pub config: &'a AccountInfo<'info>,
+}
+
+impl<'a, 'info> InitializePdaAccounts<'a, 'info> {
+    /// The accounts of a counter the payer owns
+    pub fn new(
+        counter_program: &'a AccountInfo<'info>,
+        counter: &'a AccountInfo<'info>,
+        payer: &'a AccountInfo<'info>,
+        system_program: &'a AccountInfo<'info>,
+        config: &'a AccountInfo<'info>,
+    ) -> Self {
+        Self {
+            counter_program,
+            counter,
+            payer,
+            system_program,
+            authority: None,
+            config,
+        }
+    }
+}
+
+/// Accounts for a permissionless increment
+pub struct IncrementAccounts<'a, 'info> {
+    /// The counter program itself
+    pub counter_program: &'a AccountInfo<'info>,
+    /// The counter to change, writable
+    pub counter: &'a AccountInfo<'info>,
+    /// The program's config account, see [`crate::find_config_address`]
+    pub config: &'a AccountInfo<'info>,
+    /// The counter's history account, required once it has one
+    pub history: Option<&'a AccountInfo<'info>>,
+    /// A signer recorded as the increment's actor
+    pub actor: Option<&'a AccountInfo<'info>>,
+}
+
+impl<'a, 'info> IncrementAccounts<'a, 'info> {
+    /// The accounts of an increment with neither a history nor an actor
+    pub fn new(
+        counter_program: &'a AccountInfo<'info>,
+        counter: &'a AccountInfo<'info>,
+        config: &'a AccountInfo<'info>,
+    ) -> Self {
+        Self {
+            counter_program,
+            counter,
+            config,
+            history: None,
+            actor: None,
+        }
+    }
+
+    fn invoke(&self, data: &CounterInstruction, signer_seeds: &[&[&[u8]]]) -> ProgramResult {
+        let mut metas = vec![
+            AccountMeta::new(*self.counter.key, false),
+            AccountMeta::new_readonly(*self.config.key, false),
+        ];
+        let mut infos = vec![
+            self.counter.clone(),
+            self.config.clone(),
+            self.counter_program.clone(),
+        ];
+        if let Some(history) = self.history {
+            metas.push(AccountMeta::new(*history.key, false));
+            infos.push(history.clone());
+        }
+        if let Some(actor) = self.actor {
+            metas.push(AccountMeta::new_readonly(*actor.key, true));
+            infos.push(actor.clone());
+        }
+        invoke_signed(
+            &Instruction::new_with_borsh(*self.counter_program.key, data, metas),
+            &infos,
+            signer_seeds,
+        )
+    }
+}
+
+/// Accounts for a change the counter's authority, or its delegate, signs
+pub struct AuthorityAccounts<'a, 'info> {
+    /// The counter program itself
+    pub counter_program: &'a AccountInfo<'info>,
+    /// The counter to change, writable
+    pub counter: &'a AccountInfo<'info>,
+    /// The counter's authority, or a delegate for decrements
+    pub authority: &'a AccountInfo<'info>,
+    /// The program's config account, see [`crate::find_config_address`]
+    pub config: &'a AccountInfo<'info>,
+    /// The counter's history account, required once it has one
+    pub history: Option<&'a AccountInfo<'info>>,
+}
+
+impl<'a, 'info> AuthorityAccounts<'a, 'info> {
+    /// The accounts of a change to a counter without a history
+    pub fn new(
+        counter_program: &'a AccountInfo<'info>,
+        counter: &'a AccountInfo<'info>,
+        authority: &'a AccountInfo<'info>,
+        config: &'a AccountInfo<'info>,
+    ) -> Self {
+        Self {
+            counter_program,
+            counter,
+            authority,
+            config,
+            history: None,
+        }
+    }
+
+    fn invoke(&self, data: &CounterInstruction, signer_seeds: &[&[&[u8]]]) -> ProgramResult {
+        let mut metas = vec![
+            AccountMeta::new(*self.counter.key, false),
+            AccountMeta::new_readonly(*self.authority.key, true),
+            AccountMeta::new_readonly(*self.config.key, false),
+        ];
+        let mut infos = vec![
+            self.counter.clone(),
+            self.authority.clone(),
+            self.config.clone(),
+            self.counter_program.clone(),
+        ];
+        if let Some(history) = self.history {
+            metas.push(AccountMeta::new(*history.key, false));
+            infos.push(history.clone());
+        }
+        invoke_signed(
+            &Instruction::new_with_borsh(*self.counter_program.key, data, metas),
+            &infos,
+            signer_seeds,
+        )
+    }
+}
+
+/// Create a counter under `seed`, owned by the authority if one is given
+pub fn initialize_pda(
+    accounts: &InitializePdaAccounts,
+    seed: &str,
+    args: InitializeArgs,
+) -> ProgramResult {
+    initialize_pda_signed(accounts, seed, args, &[])
+}
+
+/// [`initialize_pda`], with the payer or authority signing through `signer_seeds`
+pub fn initialize_pda_signed(
+    accounts: &InitializePdaAccounts,
+    seed: &str,
+    args: InitializeArgs,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let mut metas = vec![
+        AccountMeta::new(*accounts.counter.key, false),
+        AccountMeta::new(*accounts.payer.key, true),
+        AccountMeta::new_readonly(*accounts.system_program.key, false),
+    ];
+    let mut infos = vec![
+        accounts.counter.clone(),
+        accounts.payer.clone(),
+        accounts.system_program.clone(),
+        accounts.counter_program.clone(),
+    ];
+    if let Some(authority) = accounts.authority {
+        metas.push(AccountMeta::new_readonly(*authority.key, true));
+        infos.push(authority.clone());
+    }
+    // Only there for the caller guard
+    metas.push(AccountMeta::new_readonly(*accounts.config.key, false));
+    infos.push(accounts.config.clone());
+    let data = CounterInstruction::InitializePda {
+        seed: seed.to_string(),
+        args,
+    };
+    invoke_signed(
+        &Instruction::new_with_borsh(*accounts.counter_program.key, &data, metas),
+        &infos,
+        signer_seeds,
+    )
+}
+
+/// Increment the counter by `amount`
+pub fn increment(accounts: &IncrementAccounts, amount: u32) -> ProgramResult {
+    increment_signed(accounts, amount, &[])
+}
+
+/// [`increment`], with the actor signing through `signer_seeds`
+pub fn increment_signed(
+    accounts: &IncrementAccounts,
+    amount: u32,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    accounts.invoke(&CounterInstruction::Increment(amount), signer_seeds)
+}
+
+/// Decrement the counter by `amount`
+pub fn decrement(accounts: &AuthorityAccounts, amount: u32) -> ProgramResult {
+    decrement_signed(accounts, amount, &[])
+}
+
+/// [`decrement`], with the authority signing through `signer_seeds`
+pub fn decrement_signed(
+    accounts: &AuthorityAccounts,
+    amount: u32,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    accounts.invoke(&CounterInstruction::Decrement(amount), signer_seeds)
+}
+
+/// Multiply the counter by `factor`
+pub fn multiply(accounts: &AuthorityAccounts, factor: u32) -> ProgramResult {
+    multiply_signed(accounts, factor, &[])
+}
+
+/// [`multiply`], with the authority signing through `signer_seeds`
+pub fn multiply_signed(
+    accounts: &AuthorityAccounts,
+    factor: u32,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    accounts.invoke(&CounterInstruction::Multiply(factor), signer_seeds)
+}
+
+/// Divide the counter by `divisor`
+pub fn divide(accounts: &AuthorityAccounts, divisor: u32) -> ProgramResult {
+    divide_signed(accounts, divisor, &[])
+}
+
+/// [`divide`], with the authority signing through `signer_seeds`
+pub fn divide_signed(
+    accounts: &AuthorityAccounts,
+    divisor: u32,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    accounts.invoke(&CounterInstruction::Divide(divisor), signer_seeds)
+}
+
+/// Write `new` to the counter if it currently holds `expected`
+pub fn compare_and_swap(accounts: &AuthorityAccounts, expected: u64, new: u64) -> ProgramResult {
+    compare_and_swap_signed(accounts, expected, new, &[])
+}
+
+/// [`compare_and_swap`], with the authority signing through `signer_seeds`
+pub fn compare_and_swap_signed(
+    accounts: &AuthorityAccounts,
+    expected: u64,
+    new: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    accounts.invoke(
+        &CounterInstruction::CompareAndSwap { expected, new },
+        signer_seeds,
+    )
+}
+
+/// Set the counter back to zero
+pub fn reset(accounts: &AuthorityAccounts) -> ProgramResult {
+    reset_signed(accounts, &[])
+}
+
+/// [`reset`], with the authority signing through `signer_seeds`
+pub fn reset_signed(accounts: &AuthorityAccounts, signer_seeds: &[&[&[u8]]]) -> ProgramResult {
+    accounts.invoke(&CounterInstruction::Reset, signer_seeds)
+}
+
+/// Overwrite the counter with `value`
+pub fn set(accounts: &AuthorityAccounts, value: u64) -> ProgramResult {
+    set_signed(accounts, value, &[])
+}
+
+/// [`set`], with the authority signing through `signer_seeds`
+pub fn set_signed(
+    accounts: &AuthorityAccounts,
+    value: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    accounts.invoke(&CounterInstruction::Set(value), signer_seeds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ID, builders::*, find_config_address};
+    use solana_program::{program_stubs, system_program};
+    use std::{cell::RefCell, sync::Once};
+
+    thread_local! {
+        /// The instructions the helpers invoked on this thread
+        static INVOKED: RefCell<Vec<Instruction>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Syscall stubs recording each invoked instruction instead of running it
+    struct Recorder;
+
+    impl program_stubs::SyscallStubs for Recorder {
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            _account_infos: &[AccountInfo],
+            _signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            INVOKED.with(|invoked| invoked.borrow_mut().push(instruction.clone()));
+            Ok(())
+        }
+    }
+
+    /// The instruction `call` invokes
+    fn invoked(call: impl FnOnce() -> ProgramResult) -> Instruction {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(Recorder));
+        });
+        call().unwrap();
+        INVOKED.with(|invoked| invoked.borrow_mut().pop().unwrap())
+    }
+
+    /// Backing storage for an [`AccountInfo`] at `key`
+    struct Account {
+        key: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+    }
+
+    impl Account {
+        fn new(key: Pubkey) -> Self {
+            Self {
+                key,
+                lamports: 0,
+                data: Vec::new(),
+            }
+        }
+
+        fn info(&mut self) -> AccountInfo<'_> {
+            AccountInfo::new(
+                &self.key,
+                false,
+                false,
+                &mut self.lamports,
+                &mut self.data,
+                &ID,
+                false,
+                0,
+            )
+        }
+    }
+
+    #[test]
+    fn increment_invokes_the_builders_instruction() {
+        let counter_key = Pubkey::new_unique();
+        let mut program = Account::new(ID);
+        let mut counter = Account::new(counter_key);
+        let mut config = Account::new(find_config_address(&ID).0);
+        let (program, counter, config) = (program.info(), counter.info(), config.info());
+        let accounts = IncrementAccounts::new(&program, &counter, &config);
+
+        assert_eq!(
+            invoked(|| increment(&accounts, 5)),
+            increment_ix(&ID, &counter_key, 5)
+        );
+    }
+
+    #[test]
+    fn authority_changes_invoke_the_builders_instructions() {
+        let (counter_key, authority_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut program = Account::new(ID);
+        let mut counter = Account::new(counter_key);
+        let mut authority = Account::new(authority_key);
+        let mut config = Account::new(find_config_address(&ID).0);
+        let (program, counter, authority, config) = (
+            program.info(),
+            counter.info(),
+            authority.info(),
+            config.info(),
+        );
+        let accounts = AuthorityAccounts::new(&program, &counter, &authority, &config);
+        let (counter, authority) = (&counter_key, &authority_key);
+
+        assert_eq!(
+            invoked(|| decrement(&accounts, 3)),
+            decrement_ix(&ID, counter, authority, 3)
+        );
+        assert_eq!(
+            invoked(|| multiply(&accounts, 4)),
+            multiply_ix(&ID, counter, authority, 4)
+        );
+        assert_eq!(
+            invoked(|| divide(&accounts, 2)),
+            divide_ix(&ID, counter, authority, 2)
+        );
+        assert_eq!(
+            invoked(|| compare_and_swap(&accounts, 1, 9)),
+            compare_and_swap_ix(&ID, counter, authority, 1, 9)
+        );
+        assert_eq!(
+            invoked(|| reset(&accounts)),
+            reset_ix(&ID, counter, authority)
+        );
+        assert_eq!(
+            invoked(|| set(&accounts, 42)),
+            set_ix(&ID, counter, authority, 42)
+        );
+    }
+
+    #[test]
+    fn initialize_pda_invokes_the_builders_instruction_and_the_config() {
+        let payer_key = Pubkey::new_unique();
+        let config_key = find_config_address(&ID).0;
+        let mut program = Account::new(ID);
+        let mut counter = Account::new(crate::find_counter_address(&ID, &payer_key, "votes").0);
+        let mut payer = Account::new(payer_key);
+        let mut system = Account::new(system_program::ID);
+        let mut config = Account::new(config_key);
+        let (program, counter, payer, system, config) = (
+            program.info(),
+            counter.info(),
+            payer.info(),
+            system.info(),
+            config.info(),
+        );
+        let accounts = InitializePdaAccounts::new(&program, &counter, &payer, &system, &config);
+        let args = InitializeArgs::default();
+
+        // The builder leaves out the config, which only the caller guard reads
+        let mut expected = initialize_pda_ix(&ID, &payer_key, "votes", args);
+        expected
+            .accounts
+            .push(AccountMeta::new_readonly(config_key, false));
+        assert_eq!(
+            invoked(|| initialize_pda(&accounts, "votes", args)),
+            expected
+        );
+    }
+}
@@ -0,0 +1,37 @@
+//! The program's entrypoint, compiled unless the `no-entrypoint` feature is on
+
+use crate::{error::CounterError, processor::process_instruction};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint,                       // Macro to define program entry point
+    entrypoint::ProgramResult,        // Result type for program execution
+    program_error::PrintProgramError, // Logs a program error in readable form
+    pubkey::Pubkey,
+};
+#[cfg(target_os = "solana")]
+use solana_security_txt::security_txt;
+
+/// Run the instruction and, if it fails, log which `CounterError` it was
+fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    process_instruction(program_id, accounts, instruction_data)
+        .inspect_err(|error| error.print::<CounterError>())
+}
+
+// Security contact details embedded in the deployed binary, where explorers
+// and `query-security-txt` find them. Only the on-chain build carries them.
+#[cfg(target_os = "solana")]
+security_txt! {
+    name: "Solana Counter Program",
+    project_url: "https://github.com/bluntbrain/solana-counter-program",
+    contacts: "link:https://github.com/bluntbrain/solana-counter-program/security/advisories/new",
+    policy: "https://github.com/bluntbrain/solana-counter-program/blob/main/SECURITY.md",
+    source_code: "https://github.com/bluntbrain/solana-counter-program"
+}
+
+// Define the entry point for our Solana program
+// This macro tells Solana that `process` is the main function to call
+entrypoint!(process);
@@ -0,0 +1,298 @@
+use num_derive::FromPrimitive;
+use solana_program::{
+    decode_error::DecodeError,
+    program_error::{PrintProgramError, ProgramError},
+};
+use thiserror::Error;
+
+/// Errors that may be returned by the counter program
+///
+/// Each variant is surfaced to clients as `ProgramError::Custom(n)`, where `n`
+/// is the code written next to it. Codes are part of the program's interface
+/// and never change: new errors are appended with the next free code, and a
+/// variant that falls out of use keeps its code reserved. Off-chain, a code
+/// maps back to its variant with `FromPrimitive::from_u32` or
+/// [`DecodeError::decode_custom_error_to_enum`].
+#[derive(Clone, Copy, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum CounterError {
+    /// The operation would push the count above its maximum value
+    #[error("Counter overflow")]
+    Overflow = 0,
+    /// The operation would push the count below its minimum value
+    #[error("Counter underflow")]
+    Underflow = 1,
+    /// The signer does not match the authority stored in the counter
+    #[error("Signer is not the counter authority")]
+    Unauthorized = 2,
+    /// AcceptAuthority was called but no transfer has been proposed
+    #[error("No authority transfer is pending")]
+    NoPendingAuthority = 3,
+    /// The counter account is not owned by this program
+    #[error("Counter account is not owned by this program")]
+    IncorrectOwner = 4,
+    /// An account the instruction modifies was not passed as writable
+    #[error("Account is not writable")]
+    AccountNotWritable = 5,
+    /// The counter account's data does not have the size of a Counter
+    #[error("Counter account has the wrong data length")]
+    InvalidAccountLength = 6,
+    /// More accounts were passed than the instruction uses
+    #[error("Unexpected extra accounts")]
+    UnexpectedAccounts = 7,
+    /// The counter account has not been initialized
+    #[error("Counter account is not initialized")]
+    UninitializedAccount = 8,
+    /// The account data does not start with the counter discriminator
+    #[error("Account is not a counter")]
+    InvalidDiscriminator = 9,
+    /// The seed of a PDA counter is longer than a PDA seed may be
+    #[error("Counter seed is too long")]
+    InvalidSeed = 10,
+    /// The counter account is not the PDA derived from the given seeds
+    #[error("Counter address does not match its seeds")]
+    InvalidCounterAddress = 11,
+    /// Divide was called with a divisor of zero
+    #[error("Division by zero")]
+    DivisionByZero = 12,
+    /// CompareAndSwap found a different value than the caller expected
+    #[error("Counter value does not match the expected value")]
+    ValueMismatch = 13,
+    /// A Batch instruction carried no operations
+    #[error("Batch contains no operations")]
+    EmptyBatch = 14,
+    /// The counter account uses a layout version this program does not support
+    #[error("Unsupported counter layout version")]
+    UnsupportedVersion = 15,
+    /// The operation would push the count below the counter's configured minimum
+    #[error("Counter value below its minimum")]
+    BelowMinimum = 16,
+    /// The operation would push the count above the counter's configured maximum
+    #[error("Counter value above its maximum")]
+    AboveMaximum = 17,
+    /// The requested bounds are unordered, outside the counter's range, exclude its
+    /// value or cannot hold every value below the modulus
+    #[error("Invalid counter bounds")]
+    InvalidBounds = 18,
+    /// The increment or decrement amount is not a multiple of the counter's step
+    #[error("Amount is not a multiple of the counter step")]
+    InvalidStep = 19,
+    /// The counter is paused and rejects every change to its value
+    #[error("Counter is paused")]
+    CounterPaused = 20,
+    /// The config account is not the program's config PDA
+    #[error("Config account does not match the derived address")]
+    InvalidConfigAddress = 21,
+    /// The program-wide pause switch is on
+    #[error("Program is paused")]
+    ProgramPaused = 22,
+    /// The increment or decrement amount exceeds the program-wide limit
+    #[error("Amount exceeds the configured limit")]
+    AmountExceedsLimit = 23,
+    /// The multisig threshold is zero or exceeds the number of signers, or there are too many signers
+    #[error("Invalid multisig configuration")]
+    InvalidMultisig = 24,
+    /// Fewer multisig signers than its threshold signed the transaction
+    #[error("Not enough multisig signers")]
+    NotEnoughSigners = 25,
+    /// The delegate or an operator tried to decrement by more than its
+    /// remaining allowance
+    #[error("Amount exceeds the delegate's allowance")]
+    AllowanceExceeded = 26,
+    /// The counter map already has an entry with this name
+    #[error("Map entry already exists")]
+    EntryExists = 27,
+    /// The counter map has no entry with this name
+    #[error("Map entry not found")]
+    EntryNotFound = 28,
+    /// The instruction tag does not name any instruction of this program
+    #[error("Unknown instruction")]
+    UnknownInstruction = 29,
+    /// The instruction data continues after a complete instruction
+    ///
+    /// No longer returned: trailing bytes now fail with the standard
+    /// `InvalidInstructionData`. Kept so that later codes stay the same.
+    #[error("Instruction data has trailing bytes")]
+    TrailingBytes = 30,
+    /// The instruction data ends before the instruction's payload does
+    #[error("Instruction data is truncated")]
+    TruncatedInstruction = 31,
+    /// A counter's name or description is longer than allowed
+    #[error("Metadata too long")]
+    MetadataTooLong = 32,
+    /// A Batch instruction carried more than `MAX_BATCH_OPS` operations
+    #[error("Batch contains too many operations")]
+    BatchTooLarge = 33,
+    /// A shard account is not at its derived address or belongs to another counter
+    #[error("Invalid shard account")]
+    InvalidShard = 34,
+    /// The counter keeps a history, but its history account was not passed
+    #[error("History account missing")]
+    MissingHistory = 35,
+    /// A history account is not at its derived address or belongs to another counter
+    #[error("Invalid history account")]
+    InvalidHistory = 36,
+    /// A newly created account does not hold enough lamports to be rent-exempt
+    #[error("Account is not rent-exempt")]
+    NotRentExempt = 37,
+    /// The account to initialize already holds data written by this program
+    #[error("Account is already initialized")]
+    AlreadyInitialized = 38,
+    /// The instruction arrived through a CPI from a program the caller guard does not allow
+    #[error("Calling program is not allowed")]
+    CallerNotAllowed = 39,
+    /// The transaction already changes this exactly-once counter in another instruction
+    #[error("Counter changed more than once in one transaction")]
+    DuplicateMutation = 40,
+    /// The transaction includes a program the caller guard forbids
+    #[error("Transaction includes a forbidden program")]
+    ForbiddenProgram = 41,
+    /// The account holds another kind of program account than the one expected
+    #[error("Account has the wrong type")]
+    WrongAccountType = 42,
+    /// An increment or decrement of zero was requested
+    #[error("Amount must not be zero")]
+    ZeroAmount = 43,
+    /// More thresholds than a counter can watch, or one outside its range
+    #[error("Invalid thresholds")]
+    InvalidThresholds = 44,
+    /// A memo longer than `MAX_MEMO_LEN` bytes was passed
+    #[error("Memo too long")]
+    MemoTooLong = 45,
+    /// The counter's callback is armed, but its accounts were not passed
+    #[error("Callback accounts missing")]
+    MissingCallback = 46,
+    /// A callback account, program or registered account does not match the
+    /// counter's callback
+    #[error("Invalid callback account")]
+    InvalidCallback = 47,
+    /// The signer is not one of the counter's operators, or the operator list
+    /// does not belong to the counter
+    #[error("Not an operator")]
+    UnknownOperator = 48,
+    /// The operator's expiry slot has passed
+    #[error("Operator expired")]
+    OperatorExpired = 49,
+    /// The counter already has `MAX_OPERATORS` operators
+    #[error("Too many operators")]
+    TooManyOperators = 50,
+    /// The counter has hooks, but their accounts were not passed
+    #[error("Hook accounts missing")]
+    MissingHooks = 51,
+    /// A hooks account or hook program does not match the counter's hooks
+    #[error("Invalid hook account")]
+    InvalidHooks = 52,
+    /// The config charges a token or lamport fee, but the accounts paying it
+    /// were not passed
+    #[error("Fee accounts missing")]
+    MissingFee = 53,
+    /// The mint or treasury passed for the fee is not the config's, or the
+    /// fee vault is not at its address
+    #[error("Invalid fee account")]
+    InvalidFeeAccount = 54,
+    /// The counter is token-gated, but its gate was not passed
+    #[error("Token gate missing")]
+    MissingTokenGate = 55,
+    /// The actor's token account does not hold enough of the gate's mint
+    #[error("Insufficient token balance")]
+    InsufficientTokens = 56,
+    /// Increments of the counter burn tokens, but the accounts burning them
+    /// were not passed
+    #[error("Burn accounts missing")]
+    MissingBurn = 57,
+    /// Only increments, which burn tokens, can change the counter's value
+    #[error("Counter is burn-backed")]
+    BurnBacked = 58,
+    /// Changes of the counter are rewarded, but the accounts minting the
+    /// reward were not passed
+    #[error("Reward accounts missing")]
+    MissingRewards = 59,
+    /// The mint, token program or token account passed for a reward is not
+    /// the rewards' mint or the actor's associated token account
+    #[error("Invalid reward account")]
+    InvalidRewardAccount = 60,
+    /// The escrow's target has not been reached and its deadline has not
+    /// passed, so it can be neither released nor refunded
+    #[error("Escrow still locked")]
+    EscrowLocked = 61,
+    /// The escrow is settled or past its deadline and takes no deposits
+    #[error("Escrow closed")]
+    EscrowClosed = 62,
+    /// Increments of the counter need a permit, but the permit nonce account
+    /// or the Instructions sysvar was not passed
+    #[error("Permit missing")]
+    MissingPermit = 63,
+    /// No Ed25519 instruction of the transaction holds a permit from the
+    /// counter's authority for this increment, or the permit expired or
+    /// its nonce was used
+    #[error("Invalid permit")]
+    InvalidPermit = 64,
+    /// Increments of the counter have a cooldown, but the cooldown, the
+    /// actor's stamp or the system program was not passed
+    #[error("Cooldown accounts missing")]
+    MissingCooldown = 65,
+    /// The actor incremented the counter too recently
+    #[error("Actor is cooling down")]
+    CooldownActive = 66,
+    /// The increment would take what the actor added today past the
+    /// cooldown's daily cap
+    #[error("Daily cap exceeded")]
+    DailyCapExceeded = 67,
+    /// The change would take how far the counter moved this epoch past the
+    /// config's epoch cap
+    #[error("Epoch cap exceeded")]
+    EpochCapExceeded = 68,
+    /// The counter decays, but its decay was not passed
+    #[error("Decay missing")]
+    MissingDecay = 69,
+    /// The counter is locked and rejects every change to its value until
+    /// its `locked_until` slot
+    #[error("Counter is locked")]
+    CounterLocked = 70,
+    /// The slot a lock runs until has passed, or comes before that of the
+    /// counter's current lock
+    #[error("Invalid lock slot")]
+    InvalidLock = 71,
+    /// The counter has expired and its value never changes again
+    #[error("Counter has expired")]
+    CounterExpired = 72,
+    /// The expiry has already passed
+    #[error("Invalid expiry")]
+    InvalidExpiry = 73,
+    /// The smoothing factor of a moving average is above 10,000 basis points
+    #[error("Invalid smoothing factor")]
+    InvalidSmoothing = 74,
+    /// The counter's escrow is still open, so closing the counter would lock
+    /// its deposits away; settle or refund it first
+    #[error("Escrow still open")]
+    EscrowOpen = 75,
+    /// Increments of the counter are checked, charged or followed up in a
+    /// way increments of a shard would bypass, such as by a token gate or
+    /// hooks, or the config charges fees
+    #[error("Counter cannot be sharded")]
+    NotShardable = 76,
+}
+
+impl From<CounterError> for ProgramError {
+    fn from(e: CounterError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl<T> DecodeError<T> for CounterError {
+    fn type_of() -> &'static str {
+        "CounterError"
+    }
+}
+
+impl PrintProgramError for CounterError {
+    fn print<E>(&self)
+    where
+        E: 'static
+            + std::error::Error
+            + DecodeError<E>
+            + PrintProgramError
+            + num_traits::FromPrimitive,
+    {
+        msg!("Error: {}", self);
+    }
+}
@@ -0,0 +1,345 @@
+//! Structured events the program emits alongside its log messages
+//!
+//! Each event is logged with `sol_log_data`, so it appears in the
+//! transaction's logs as one `Program data: <base64>` line. Decoded, the data
+//! is the Borsh encoding of an [`EventRecord`]: the [`EVENT_SCHEMA_VERSION`]
+//! byte, then the [`Event`]'s variant index byte followed by the variant's
+//! fields, integers little-endian and keys as their 32 bytes. Indexers decode
+//! that instead of parsing log text; `event_schema` in [`crate::schema`]
+//! describes it.
+//!
+//! Every change to a counter's value emits one [`Event::CounterUpdated`],
+//! which decodes as:
+//!
+//! | Offset | Size | Field                               |
+//! |--------|------|-------------------------------------|
+//! | 0      | 1    | `schema_version`, `16`              |
+//! | 1      | 1    | variant index, `2`                  |
+//! | 2      | 32   | `counter`, the counter account      |
+//! | 34     | 1    | `operation`, an [`Operation`] index |
+//! | 35     | 16   | `old_value`, `i128`                 |
+//! | 51     | 16   | `new_value`, `i128`                 |
+//! | 67     | 32   | `actor`                             |
+//! | 99     | 8    | `slot`, `u64`                       |
+//! | 107    | 1    | `op_id` present, `0` or `1`         |
+//! | 108    | 16   | `op_id`, if present                 |
+//!
+//! New variants and operations are only ever appended, so existing indexes
+//! keep their meaning. Anything that changes the fields of an existing
+//! variant, adding one included, bumps the schema version, so a parser that
+//! checks it first never misreads a layout it was not written for.
+//!
+//! With the `anchor` feature events follow Anchor's convention instead, which
+//! off-the-shelf indexers and explorers already decode: the data starts with
+//! the event's `sha256("event:<Name>")[..8]` discriminator, and the schema
+//! version and fields follow unchanged. Each variant is then an Anchor event
+//! struct named after it whose first field is `schema_version: u8`, so
+//! `CounterUpdated` fields start at offset 9 rather than 2.
+//!
+//! The other payload the program emits, the return data of an instruction,
+//! is either a [`CounterView`] or the new count, see
+//! [`decode_return_data`](crate::decode_return_data).
+
+use crate::state::{Counter, CounterFlag, CounterKind, LockStatus, OverflowMode};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use solana_program::{clock::Clock, log::sol_log_data, pubkey::Pubkey};
+
+/// A client's id for a change, passed with `WithOpId` and echoed in its
+/// [`Event::CounterUpdated`]
+///
+/// Any 16 bytes will do, a UUID for instance; the program never looks inside.
+pub type OpId = [u8; 16];
+
+/// Version of the event layout, logged ahead of every event
+///
+/// History:
+/// - `1`: the first versioned layout, with `CounterUpdated` carrying `op_id`
+/// - `2`: `CounterViewed` reports `has_callback`
+/// - `3`: `CounterViewed` reports `has_hooks`
+/// - `4`: `CounterViewed` reports `token_gated`
+/// - `5`: `CounterViewed` reports `nft_authority`
+/// - `6`: `CounterViewed` reports `burn_backed`
+/// - `7`: `CounterViewed` reports `has_rewards`
+/// - `8`: `CounterViewed` reports `permit_only`
+/// - `9`: `CounterViewed` reports `has_cooldown`
+/// - `10`: `CounterViewed` reports `epoch_change` and `epoch`
+/// - `11`: `CounterViewed` reports `epoch_reset`
+/// - `12`: `CounterViewed` reports `decays`
+/// - `13`: `CounterViewed` reports `cumulative_value`
+/// - `14`: `CounterViewed` reports `lock_status` and `locked_until`
+/// - `15`: `CounterViewed` reports `expires_at` and `expired`
+/// - `16`: `CounterViewed` reports `ema`
+pub const EVENT_SCHEMA_VERSION: u8 = 16;
+
+/// An event as it is logged, for decoding a `Program data:` line
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Clone, Debug, PartialEq, Eq)]
+pub struct EventRecord {
+    /// The [`EVENT_SCHEMA_VERSION`] of the program that logged the event
+    pub schema_version: u8,
+    pub event: Event,
+}
+
+/// Something worth telling indexers and incident tooling about
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The config admin halted every counter change program-wide
+    EmergencyHalt { admin: Pubkey, slot: u64 },
+    /// The config admin lifted an emergency halt
+    EmergencyResume { admin: Pubkey, slot: u64 },
+    /// An instruction changed a counter's value
+    CounterUpdated {
+        counter: Pubkey,
+        operation: Operation,
+        old_value: i128,
+        new_value: i128,
+        /// The authority or delegate that signed, or for increments the
+        /// optional actor signer and otherwise the default key
+        actor: Pubkey,
+        slot: u64,
+        /// The id the change was tagged with, if it came in a `WithOpId`
+        op_id: Option<OpId>,
+    },
+    /// A change to a counter crossed one of its thresholds, emitted after its
+    /// `CounterUpdated`, once per threshold crossed
+    ThresholdCrossed {
+        counter: Pubkey,
+        threshold: i128,
+        direction: Direction,
+        old_value: i128,
+        new_value: i128,
+        slot: u64,
+    },
+    /// A `View` of a counter's state
+    CounterViewed(Box<CounterView>),
+    /// A change reached the target of a counter's callback, which is invoked
+    /// right after, see `SetCallback`
+    TargetReached {
+        counter: Pubkey,
+        target: i128,
+        program: Pubkey,
+        old_value: i128,
+        new_value: i128,
+        slot: u64,
+    },
+    /// The first change in a new epoch zeroed a counter set to start over
+    /// every epoch, see `SetEpochReset`, emitted before that change's
+    /// `CounterUpdated`
+    EpochReset {
+        counter: Pubkey,
+        /// The epoch `final_value` was reached in
+        epoch: u64,
+        final_value: i128,
+        slot: u64,
+    },
+}
+
+impl Event {
+    /// The variant's name, which is also its Anchor event name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::EmergencyHalt { .. } => "EmergencyHalt",
+            Event::EmergencyResume { .. } => "EmergencyResume",
+            Event::CounterUpdated { .. } => "CounterUpdated",
+            Event::ThresholdCrossed { .. } => "ThresholdCrossed",
+            Event::CounterViewed(_) => "CounterViewed",
+            Event::TargetReached { .. } => "TargetReached",
+            Event::EpochReset { .. } => "EpochReset",
+        }
+    }
+
+    /// Anchor's `sha256("event:<Name>")[..8]` for the variant
+    pub fn anchor_discriminator(&self) -> [u8; 8] {
+        match self {
+            Event::EmergencyHalt { .. } => [20, 163, 154, 115, 99, 71, 54, 24],
+            Event::EmergencyResume { .. } => [31, 112, 49, 122, 243, 247, 237, 191],
+            Event::CounterUpdated { .. } => [56, 210, 136, 13, 88, 67, 151, 167],
+            Event::ThresholdCrossed { .. } => [163, 192, 107, 184, 128, 177, 90, 1],
+            Event::CounterViewed(_) => [210, 147, 29, 77, 92, 144, 31, 11],
+            Event::TargetReached { .. } => [149, 209, 57, 9, 106, 52, 127, 219],
+            Event::EpochReset { .. } => [195, 97, 94, 206, 206, 29, 16, 86],
+        }
+    }
+}
+
+/// The instruction behind an [`Event::CounterUpdated`]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[borsh(use_discriminant = true)]
+#[repr(u8)]
+pub enum Operation {
+    /// `Increment` or `IncrementByOne`
+    Increment = 0,
+    Decrement = 1,
+    Multiply = 2,
+    Divide = 3,
+    CompareAndSwap = 4,
+    Reset = 5,
+    Set = 6,
+    /// A whole `Batch`, reported once with its net effect
+    Batch = 7,
+    /// `Merge`, reported for both the destination and the zeroed source
+    Merge = 8,
+    /// `Aggregate`, which names no actor
+    Aggregate = 9,
+    /// `Crank`, when a new epoch started the counter over
+    Crank = 10,
+    /// The counter's decay, settled by a change, `Crank`, `SetDecay` or
+    /// `ClearDecay`, which names no actor
+    Decay = 11,
+}
+
+/// Which way an [`Event::ThresholdCrossed`] went
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[borsh(use_discriminant = true)]
+#[repr(u8)]
+pub enum Direction {
+    /// The count rose from below the threshold to it or above
+    Up = 0,
+    /// The count fell from the threshold or above to below it
+    Down = 1,
+}
+
+/// A counter's state as `View` reports it
+///
+/// Values are decoded according to the counter's kind, and unset keys are
+/// the default key, so nothing here depends on how the account stores them.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Clone, Debug, PartialEq, Eq)]
+pub struct CounterView {
+    pub counter: Pubkey,
+    pub version: u8,
+    pub value: i128,
+    pub kind: CounterKind,
+    pub overflow_mode: OverflowMode,
+    pub min: i128,
+    pub max: i128,
+    pub step: u32,
+    pub modulus: u64,
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub delegate: Pubkey,
+    pub allowance: u64,
+    pub freeze_authority: Pubkey,
+    pub paused: bool,
+    pub clamp_decrements: bool,
+    pub exactly_once: bool,
+    pub has_history: bool,
+    pub thresholds: Vec<i128>,
+    pub total_increments: u64,
+    pub total_decrements: u64,
+    pub high_water: i128,
+    pub low_water: i128,
+    pub last_updated_slot: u64,
+    pub last_updated_ts: i64,
+    /// Whether a callback is armed, see `SetCallback`
+    pub has_callback: bool,
+    /// Whether hook programs are set, see `SetHooks`
+    pub has_hooks: bool,
+    /// Whether increments require holding tokens, see `SetTokenGate`
+    pub token_gated: bool,
+    /// Whether `authority` is the mint of an NFT whose holder is the
+    /// authority, see `SetNftAuthority`
+    pub nft_authority: bool,
+    /// Whether every increment burns tokens, see `SetBurnMint`
+    pub burn_backed: bool,
+    /// Whether increments reaching milestones mint rewards, see `SetRewards`
+    pub has_rewards: bool,
+    /// Whether increments need a permit from the authority, see `RequirePermits`
+    pub permit_only: bool,
+    /// Whether each actor's increments are throttled, see `SetCooldown`
+    pub has_cooldown: bool,
+    /// How far changes moved the counter in `epoch`, see `SetEpochCap`
+    pub epoch_change: u64,
+    /// The epoch `epoch_change` was tallied in
+    pub epoch: u64,
+    /// Whether each new epoch starts the counter over, see `SetEpochReset`
+    pub epoch_reset: bool,
+    /// Whether the value fades towards zero between changes, see
+    /// `SetDecay`; `value` is the one as of the last change
+    pub decays: bool,
+    /// The counter's cumulative value as of `last_updated_ts`, see
+    /// [`Counter::cumulative_value`]
+    pub cumulative_value: i128,
+    /// Whether changes are locked as of the slot of the view, see `Lock`
+    pub lock_status: LockStatus,
+    /// The slot the counter's last lock runs until, 0 if it was never locked
+    pub locked_until: u64,
+    /// The unix timestamp from which the counter is read-only, 0 if it
+    /// never expires, see `SetExpiry`
+    pub expires_at: i64,
+    /// Whether the counter had expired as of the time of the view
+    pub expired: bool,
+    /// The moving average of the value, if one is kept, see
+    /// [`Counter::ema`]
+    pub ema: Option<i128>,
+}
+
+impl CounterView {
+    /// The view of `state`, stored at `counter`, as of `clock`
+    pub(crate) fn new(counter: Pubkey, state: &Counter, clock: &Clock) -> Self {
+        let kind = state.kind();
+        Self {
+            counter,
+            version: state.version,
+            value: state.value(),
+            kind,
+            overflow_mode: state.overflow_mode(),
+            min: state.min_value(),
+            max: state.max_value(),
+            step: state.step,
+            modulus: state.modulus,
+            authority: state.authority,
+            pending_authority: state.pending_authority,
+            delegate: state.delegate,
+            allowance: state.allowance,
+            freeze_authority: state.freeze_authority,
+            paused: state.paused != 0,
+            clamp_decrements: state.has_flag(CounterFlag::ClampDecrements),
+            exactly_once: state.has_flag(CounterFlag::ExactlyOnce),
+            has_history: state.has_flag(CounterFlag::History),
+            thresholds: state.thresholds().collect(),
+            total_increments: state.total_increments,
+            total_decrements: state.total_decrements,
+            high_water: kind.decode(state.high_water),
+            low_water: kind.decode(state.low_water),
+            last_updated_slot: state.last_updated_slot,
+            last_updated_ts: state.last_updated_ts,
+            has_callback: state.has_flag(CounterFlag::Callback),
+            has_hooks: state.has_flag(CounterFlag::Hooks),
+            token_gated: state.has_flag(CounterFlag::TokenGate),
+            nft_authority: state.has_flag(CounterFlag::NftAuthority),
+            burn_backed: state.has_flag(CounterFlag::Burn),
+            has_rewards: state.has_flag(CounterFlag::Rewards),
+            permit_only: state.has_flag(CounterFlag::Permits),
+            has_cooldown: state.has_flag(CounterFlag::Cooldown),
+            epoch_change: state.epoch_change,
+            epoch: state.epoch,
+            epoch_reset: state.has_flag(CounterFlag::EpochReset),
+            decays: state.has_flag(CounterFlag::Decay),
+            cumulative_value: state.cumulative_value(),
+            lock_status: state.lock_status(clock.slot),
+            locked_until: state.locked_until,
+            expires_at: state.expires_at,
+            expired: state.is_expired(clock.unix_timestamp),
+            ema: state.ema(),
+        }
+    }
+}
+
+/// Log `event` as a `Program data:` line
+pub(crate) fn emit(event: &Event) {
+    // The encoding of an `EventRecord`, without cloning the event into one;
+    // encoding into a Vec cannot fail
+    let mut data = vec![EVENT_SCHEMA_VERSION];
+    if event.serialize(&mut data).is_err() {
+        return;
+    }
+    #[cfg(not(feature = "anchor"))]
+    sol_log_data(&[&data]);
+    // Swap the variant index for the discriminator, keeping one base64 blob
+    #[cfg(feature = "anchor")]
+    sol_log_data(&[&[&event.anchor_discriminator()[..], &data[..1], &data[2..]].concat()]);
+}
@@ -0,0 +1,1544 @@
+use crate::{
+    error::CounterError,
+    events::OpId,
+    state::{
+        CallbackAccount, CallerGuard, Counter, CounterCallback, CounterKind, CounterMetadata,
+        DecayUnit, FeeConfig, MAX_MILESTONES, MAX_SIGNERS, OverflowMode,
+    },
+};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use solana_program::{
+    program_error::ProgramError,
+    pubkey::{MAX_SEED_LEN, Pubkey},
+};
+use std::io::Read;
+
+/// Settings chosen when a counter is created
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Clone, Copy, Debug, Default)]
+pub struct InitializeArgs {
+    /// How increments, decrements and multiplications handle overflow
+    pub overflow_mode: OverflowMode,
+    /// Whether the counter is unsigned or may go negative
+    pub kind: CounterKind,
+    /// Smallest value the counter may take, if narrower than its kind allows
+    pub min: Option<i128>,
+    /// Largest value the counter may take, if narrower than its kind allows
+    pub max: Option<i128>,
+    /// Increments and decrements must be multiples of this; 0 or 1 allows any amount
+    pub step: u32,
+    /// Wrap every result into `0..modulus`, like a clock; 0 disables
+    pub modulus: u64,
+    /// Stop decrements that would pass the counter's lower bound at that
+    /// bound, zero unless `min` says otherwise, instead of failing them
+    pub clamp_decrements: bool,
+}
+
+impl InitializeArgs {
+    /// Number of bytes the encoded arguments take when both bounds are set
+    pub const MAX_LEN: usize = 1 + 1 + (1 + 16) + (1 + 16) + 4 + 8 + 1;
+}
+
+/// Enum representing different instructions our program can handle
+/// Each variant represents a different operation the program can perform
+///
+/// Wherever an instruction expects the counter's authority as a `[signer]`,
+/// the authority may instead be a `Multisig` account, passed without a
+/// signature and followed, after every other account, by the `[signer]`
+/// accounts approving the instruction. A `[signer]` may also be a
+/// program-derived address its program signs for with `invoke_signed`, which
+/// is how another program owns a counter; `examples/counter-owner` is one.
+/// A counter handed to an NFT takes the holder's token account in its place,
+/// see `SetNftAuthority`, and one handed to a DAO is signed for by SPL
+/// Governance when a proposal passes, see `SetGovernanceAuthority`.
+///
+/// Instructions that change a single counter's value with an operation of
+/// their own (Increment, IncrementByOne, Decrement, Multiply, Divide,
+/// CompareAndSwap, Reset, Set and Batch) may be followed by the counter's
+/// `[writable]` history account, before any multisig signers, and must be
+/// once the counter has one, see `InitializeHistory`. While the counter's
+/// callback is armed they must then pass its callback account, the callback
+/// program and the accounts registered with it, see `SetCallback`.
+/// Increments, which need no authority, may pass one `[signer]` after those
+/// to be recorded as the actor, and must once the counter is token-gated, see
+/// `SetTokenGate`, or has a cooldown, see `SetCooldown`. While the counter
+/// decays they must pass its decay account, see `SetDecay`. Once the counter
+/// has hooks, these instructions must end with its hooks account and hook
+/// programs, after every other account they take, see `SetHooks`.
+///
+/// Any instruction may end with the Instructions sysvar, which is required
+/// when it arrives through a CPI while the config's caller guard is on, see
+/// `SetCallerGuard`, and by the instructions listed above once the counter is
+/// exactly-once or the guard forbids any program, see `SetExactlyOnce`, and
+/// by increments once the counter needs permits, see `RequirePermits`.
+/// While the config charges a fee, they must also pass the accounts paying
+/// it, see `SetFee` and `SetLamportFee`, and together they may move a
+/// counter by no more than the config's epoch cap per epoch, see
+/// `SetEpochCap`. None of them goes through while the counter is locked, see
+/// `Lock`, or once it has expired, see `SetExpiry`.
+///
+/// The instructions listed above, as well as Merge and Aggregate, set the
+/// program's return data to the counter's new value, so a program invoking
+/// them reads the result with `get_return_data`, see [`decode_return_data`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Clone)]
+pub enum CounterInstruction {
+    /// Increment the counter by a specified amount
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[]` The program config
+    Increment(u32),
+    /// Decrement the counter by a specified amount
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority or its delegate, or `[writable]`
+    ///    its operator list, passed without a signature and followed, after
+    ///    every other account, by the operator's `[signer]`
+    /// 2. `[]` The program config
+    Decrement(u32),
+    /// Create the counter account through the system program and set the count to zero
+    ///
+    /// Accounts expected:
+    /// 0. `[writable, signer]` The new counter account
+    /// 1. `[writable, signer]` The payer funding the account's rent
+    /// 2. `[]` The system program
+    /// 3. `[signer]` Optional: the counter's authority, if not the payer
+    ///
+    /// Without the optional account the payer becomes the counter's authority.
+    Initialize(InitializeArgs),
+    /// Set the counter back to zero
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[]` The program config
+    Reset,
+    /// Overwrite the counter with the given value
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[]` The program config
+    Set(u64),
+    /// Propose handing the counter to `new_authority`
+    ///
+    /// The transfer only takes effect once the new authority signs
+    /// `AcceptAuthority`, so a typo in the key cannot lock the counter.
+    /// Proposing `Pubkey::default()` cancels a pending transfer.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's current authority
+    TransferAuthority { new_authority: Pubkey },
+    /// Complete a pending authority transfer
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The pending authority
+    AcceptAuthority,
+    /// Close the counter account and reclaim its rent
+    ///
+    /// Fails with `EscrowOpen` while the counter's escrow is open, see
+    /// `InitializeEscrow`.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The account receiving the counter's lamports
+    Close,
+    /// Rewrite a counter created before accounts carried a discriminator
+    ///
+    /// The account is grown to the current layout, with the payer topping up
+    /// the rent-exempt balance for the extra bytes.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The legacy counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable, signer]` The payer funding the additional rent
+    /// 3. `[]` The system program
+    MigrateLegacy,
+    /// Create a counter at the PDA derived from its authority's key and `seed`
+    ///
+    /// See `find_counter_address` for the derivation. The seed must be at
+    /// most 32 bytes long.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter PDA
+    /// 1. `[writable, signer]` The payer, who becomes the counter's authority
+    /// 2. `[]` The system program
+    /// 3. `[signer]` Optional: the counter's authority instead of the payer,
+    ///    whose key the address is then derived from
+    InitializePda { seed: String, args: InitializeArgs },
+    /// Multiply the counter by a specified factor
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[]` The program config
+    Multiply(u32),
+    /// Divide the counter by a specified divisor, rounding down
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[]` The program config
+    Divide(u32),
+    /// Overwrite the counter with `new` only if it currently holds `expected`
+    ///
+    /// Clients that read a value and write a result derived from it can use
+    /// this to detect that another transaction changed the counter in between.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[]` The program config
+    CompareAndSwap { expected: u64, new: u64 },
+    /// Apply a sequence of operations to the counter in one instruction
+    ///
+    /// Every operation is checked before anything is written, so the counter
+    /// either reflects the whole batch or is left untouched.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority, unless every operation is an increment
+    /// 2. `[]` The program config (index 1 when the authority is omitted)
+    Batch(Vec<CounterOp>),
+    /// Increment the counter by exactly one
+    ///
+    /// Encoded as the single byte `14` with no payload, which is also its
+    /// Borsh encoding. The processor recognises this byte before attempting
+    /// Borsh deserialization, making it the cheapest way to bump a counter.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[]` The program config
+    IncrementByOne,
+    /// Rewrite an outdated counter in the current layout
+    ///
+    /// Kept for existing clients; this is now the same as `Migrate`.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The outdated counter account
+    /// 1. `[writable, signer]` The payer funding the additional rent
+    /// 2. `[]` The system program
+    MigrateV1ToV2,
+    /// Stop every instruction that changes the count until `Unpause`
+    ///
+    /// Authority management and `Close` keep working while paused.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's freeze authority, or its authority if it
+    ///    has none, see `SetFreezeAuthority`
+    Pause,
+    /// Allow changes to the count again after `Pause`
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's freeze authority, or its authority if it
+    ///    has none
+    Unpause,
+    /// Create the program config PDA at `find_config_address`
+    ///
+    /// The payer becomes the config admin, so this should be sent right after
+    /// the program is deployed.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The config PDA
+    /// 1. `[writable, signer]` The payer, who becomes the admin
+    /// 2. `[]` The system program
+    InitializeConfig { max_amount: u32 },
+    /// Replace the program config
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The config PDA
+    /// 1. `[signer]` The current admin
+    UpdateConfig {
+        admin: Pubkey,
+        paused: bool,
+        max_amount: u32,
+    },
+    /// Create an account holding an M-of-N multisig that can be made a counter's authority
+    ///
+    /// `signers` must hold between `m` and 11 keys, and `m` must be at least one.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable, signer]` The new multisig account
+    /// 1. `[writable, signer]` The payer funding the account's rent
+    /// 2. `[]` The system program
+    InitializeMultisig { m: u8, signers: Vec<Pubkey> },
+    /// Let `delegate` decrement the counter by up to `allowance` in total
+    ///
+    /// Each `Decrement` signed by the delegate uses up part of the allowance.
+    /// Approving again replaces the previous delegate and allowance.
+    /// Increments need no approval, since anyone may send them.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    Approve { delegate: Pubkey, allowance: u64 },
+    /// Remove the counter's delegate and any remaining allowance
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    Revoke,
+    /// Add the value of a source counter to a destination counter and zero the source
+    ///
+    /// Both counters stay open; the source can be closed separately. Each
+    /// side is a change like those of the instructions listed above: a
+    /// counter starting over each epoch is zeroed first, both changes count
+    /// towards the epoch cap, and the accounts may be followed by the
+    /// destination's history and callback accounts and then the source's,
+    /// each required once that counter has a history or an armed callback.
+    /// A destination with milestone rewards takes its reward accounts last,
+    /// as described at `SetRewards`, minting to its authority.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The destination counter
+    /// 1. `[signer]` The destination counter's authority
+    /// 2. `[writable]` The source counter
+    /// 3. `[signer]` The source counter's authority
+    /// 4. `[]` The program config
+    Merge,
+    /// Create a new counter holding a snapshot of an existing one
+    ///
+    /// The copy has the same value, authority and settings but no pending
+    /// authority transfer or delegate, and none of the source's history,
+    /// callback, hooks, token gate or decay, which live in accounts of the
+    /// source's own. The copy of a counter owned through an NFT belongs to
+    /// the NFT's holder instead. With a `seed` it is created at the PDA the
+    /// copy's authority owns under that seed, otherwise at a fresh keypair
+    /// account.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` The source counter
+    /// 1. `[signer]` The source counter's authority
+    /// 2. `[writable, signer]` The new counter account, which only signs without a seed
+    /// 3. `[writable, signer]` The payer funding the new account's rent
+    /// 4. `[]` The system program
+    Clone { seed: Option<String> },
+    /// Create an account holding an upvote and a downvote tally, both zero
+    ///
+    /// Accounts expected:
+    /// 0. `[writable, signer]` The new vote account
+    /// 1. `[writable, signer]` The payer funding the account's rent
+    /// 2. `[]` The system program
+    InitializeVotes,
+    /// Add to the upvote tally
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The vote account
+    /// 1. `[]` The program config
+    VoteUp(u32),
+    /// Add to the downvote tally
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The vote account
+    /// 1. `[]` The program config
+    VoteDown(u32),
+    /// Create an empty counter map
+    ///
+    /// Accounts expected:
+    /// 0. `[writable, signer]` The new map account
+    /// 1. `[writable, signer]` The payer, who becomes the map's authority
+    /// 2. `[]` The system program
+    InitializeMap,
+    /// Add a counter called `name`, starting at zero, growing the account
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The map account
+    /// 1. `[signer]` The map's authority
+    /// 2. `[writable, signer]` The payer funding the additional rent
+    /// 3. `[]` The system program
+    AddMapEntry { name: [u8; 32] },
+    /// Remove the counter called `name`, shrinking the account
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The map account
+    /// 1. `[signer]` The map's authority
+    /// 2. `[writable]` The account receiving the freed rent
+    RemoveMapEntry { name: [u8; 32] },
+    /// Add `amount` to the counter called `name`
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The map account
+    /// 1. `[]` The program config
+    IncrementMapEntry { name: [u8; 32], amount: u32 },
+    /// Subtract `amount` from the counter called `name`
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The map account
+    /// 1. `[signer]` The map's authority
+    /// 2. `[]` The program config
+    DecrementMapEntry { name: [u8; 32], amount: u32 },
+    /// Rewrite a counter stored in any older layout in the current one, in place
+    ///
+    /// Version 1 and 2 counters carry their discriminator, so their value and
+    /// settings are trusted and anyone may pay for the migration. Legacy
+    /// counters predate the discriminator; for those the payer must be the
+    /// stored authority. A counter already in the current layout is left as is.
+    /// Anything stored after a version 5 counter, such as its metadata, moves
+    /// along with it.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[writable, signer]` The payer funding any additional rent
+    /// 2. `[]` The system program
+    Migrate,
+    /// Grow the counter account to `len` bytes, so that optional data stored
+    /// after the counter, such as metadata, fits in an existing account
+    ///
+    /// The new bytes start zeroed. An account can not shrink below its
+    /// current size, and grows by at most 10 KiB per instruction.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable, signer]` The payer funding the additional rent
+    /// 3. `[]` The system program
+    Resize { len: u32 },
+    /// Label the counter with a `name` of at most 32 bytes and a
+    /// `description` of at most 200, replacing any previous labels
+    ///
+    /// The account grows to fit them if needed; empty strings clear them.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable, signer]` The payer funding any additional rent
+    /// 3. `[]` The system program
+    SetMetadata { name: String, description: String },
+    /// Create shard `index` of a counter, an account that takes increments
+    /// in parallel with the counter's other shards, see `Aggregate`
+    ///
+    /// Fails with `NotShardable` while the counter has a token gate, permits,
+    /// a cooldown, a burn mint, rewards, hooks or decay, whose accounts
+    /// increments of a shard do not pass.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The new shard account, at `find_shard_address(counter, index)`
+    /// 3. `[writable, signer]` The payer funding the shard's rent
+    /// 4. `[]` The system program
+    InitializeShard { index: u16 },
+    /// Add to a shard of a counter, checked against the counter's step and
+    /// pause state and the program config
+    ///
+    /// The counter is only read, so increments to different shards of the
+    /// same counter do not contend for a write lock. Its bounds apply when the
+    /// shard is aggregated. Fails with `NotShardable` while the counter has
+    /// any of the modes `InitializeShard` refuses, or the config charges
+    /// fees.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The shard account
+    /// 1. `[]` The counter account
+    /// 2. `[]` The program config
+    IncrementShard(u32),
+    /// Drain the given shards into their counter, adding up their tallies and
+    /// setting them back to zero
+    ///
+    /// Anyone may aggregate, since the result only depends on increments that
+    /// were already accepted. It fails while the counter or the program is
+    /// paused, or the counter has any of the modes `InitializeShard` refuses,
+    /// and is otherwise a change like any other: a counter starting over each
+    /// epoch is zeroed first, the total counts towards the epoch cap, and the
+    /// shards must be followed by the counter's history account and callback
+    /// accounts as for the instructions listed above.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[]` The program config
+    /// 2. `[writable]` The shard accounts to drain, from one up to
+    ///    [`MAX_AGGREGATE_SHARDS`]
+    Aggregate,
+    /// Create the counter's history account, which keeps the last `capacity`
+    /// changes, at most 64, with their kind, argument, actor and slot
+    ///
+    /// From then on every instruction listed above must pass it, and so must
+    /// `Aggregate`, which records the total it drained, and `Merge`, which
+    /// records the value moved in the histories of both counters.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The new history account, at `find_history_address(counter)`
+    /// 3. `[writable, signer]` The payer funding the history's rent
+    /// 4. `[]` The system program
+    InitializeHistory { capacity: u16 },
+    /// Turn the caller guard on or off and replace its allowlist and its
+    /// list of forbidden programs, of at most eight programs each
+    ///
+    /// While it is on, an instruction that arrives through a CPI fails with
+    /// `CallerNotAllowed` unless the transaction's top-level instruction, read
+    /// from the Instructions sysvar passed as the last account, targets an
+    /// allowed program. So the guard cannot be bypassed by leaving the config
+    /// out, every instruction arriving through a CPI must pass it, initialized
+    /// or not: one that does not take the config passes it after its own
+    /// accounts, right before the sysvar. Instructions sent directly are never
+    /// affected.
+    ///
+    /// Whether or not it is on, an instruction that changes a counter and
+    /// takes the config fails with `ForbiddenProgram` if any top-level
+    /// instruction of its transaction targets a forbidden program. The
+    /// Instructions sysvar does not list CPIs, so a forbidden program invoked
+    /// by another one is not caught.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The program config
+    /// 1. `[signer]` The config admin
+    /// 2. `[writable, signer]` The payer funding any additional rent
+    /// 3. `[]` The system program
+    SetCallerGuard {
+        enabled: bool,
+        allowed_programs: Vec<Pubkey>,
+        forbidden_programs: Vec<Pubkey>,
+    },
+    /// Let a transaction change the count with at most one instruction, the
+    /// exactly-once behaviour raffles need, or lift that limit again
+    ///
+    /// While it is set, an instruction listed above fails with
+    /// `DuplicateMutation` if another top-level instruction of its
+    /// transaction, read from the Instructions sysvar passed as the last
+    /// account, also changes this counter. Changes made through a CPI are not
+    /// listed in the sysvar, so pair this with the caller guard.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    SetExactlyOnce { enabled: bool },
+    /// Hand pausing and unpausing to `freeze_authority`, such as an
+    /// operations key, while the authority keeps every other power, or take
+    /// them back with `Pubkey::default()`
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    SetFreezeAuthority { freeze_authority: Pubkey },
+    /// Halt every change to a count program-wide, the incident-response lever
+    ///
+    /// Sets the same switch as `UpdateConfig`'s `paused`, so every instruction
+    /// that changes a value, `Aggregate` and `Crank` included, fails with
+    /// `ProgramPaused` until `EmergencyResume`. It touches nothing but that switch: the admin
+    /// cannot change any counter's value, settings or authority through it.
+    /// Emits `Event::EmergencyHalt`.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The program config
+    /// 1. `[signer]` The config admin
+    EmergencyHalt,
+    /// Lift an `EmergencyHalt`, emitting `Event::EmergencyResume`
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The program config
+    /// 1. `[signer]` The config admin
+    EmergencyResume,
+    /// Watch up to `Counter::MAX_THRESHOLDS` values of the counter, replacing
+    /// any watched before; an empty list stops watching
+    ///
+    /// Every change that takes the count from below a threshold to it or
+    /// above, or back, emits an `Event::ThresholdCrossed` with the direction.
+    /// Each threshold must lie within the range of the counter's kind.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    SetThresholds { thresholds: Vec<i128> },
+    /// Run `instruction`, one of those that change the count, with `memo`
+    /// forwarded to the SPL Memo program so explorers show the annotation
+    ///
+    /// The memo is logged by the Memo program through a CPI before the change
+    /// is made, and both stand or fall together. It holds at most
+    /// [`MAX_MEMO_LEN`] bytes of UTF-8; a `WithMemo` cannot wrap another, but
+    /// may wrap a `WithOpId`.
+    ///
+    /// Accounts expected:
+    /// 0. .. The accounts `instruction` expects, including any history
+    ///    account and multisig signers
+    /// 1. `[]` The SPL Memo program, followed only by the Instructions
+    ///    sysvar if `instruction` takes one
+    WithMemo {
+        memo: String,
+        instruction: Box<CounterInstruction>,
+    },
+    /// Report the counter's full state without changing anything
+    ///
+    /// The state is logged as an `Event::CounterViewed` and set as the return
+    /// data, a Borsh `CounterView` either way, so wallets, explorers and CPI
+    /// callers read it without knowing the account layout.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` The counter account
+    View,
+    /// Run `instruction`, one that changes the count, tagged with `op_id`
+    ///
+    /// The id is echoed in the `Event::CounterUpdated` the change emits, so a
+    /// client that lost track of a submission, say after an RPC timeout, can
+    /// find out whether it landed by looking for its id in the counter's
+    /// events. The program does not interpret or store it. Only a `WithMemo`
+    /// may wrap a `WithOpId`, not the other way round.
+    ///
+    /// Accounts expected:
+    /// 0. .. The accounts `instruction` expects, unchanged
+    WithOpId {
+        op_id: OpId,
+        instruction: Box<CounterInstruction>,
+    },
+    /// Arm the counter's callback: once a change listed above moves the count
+    /// onto or past `target`, from either side, `program` is invoked with
+    /// `accounts`, see [`OnTargetReached`](crate::cpi::OnTargetReached)
+    ///
+    /// The callback fires once, then has to be armed again; until it fires,
+    /// every change listed above must pass the callback's accounts. Creates
+    /// the callback account on first use and otherwise replaces what it held.
+    /// At most [`MAX_CALLBACK_ACCOUNTS`](crate::MAX_CALLBACK_ACCOUNTS) accounts, and not this
+    /// program, which cannot be reentered.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The callback account, at `find_callback_address(counter)`
+    /// 3. `[writable, signer]` The payer funding the callback account's rent
+    /// 4. `[]` The system program
+    SetCallback {
+        target: i128,
+        program: Pubkey,
+        accounts: Vec<CallbackAccount>,
+    },
+    /// Disarm the counter's callback and close its callback account
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The callback account
+    /// 3. `[writable]` The account receiving the callback account's lamports
+    ClearCallback,
+    /// Let `operator` decrement the counter by up to `allowance` in total
+    /// until `expiry_slot`, or for good if it is 0
+    ///
+    /// Operators work like the delegate, alongside it and each other: a
+    /// `Decrement` passes the operator list in place of the authority and is
+    /// signed by the operator, see `Decrement`. Creates the operator list on
+    /// first use. Adding an existing operator replaces its allowance and
+    /// expiry; a counter has at most [`MAX_OPERATORS`](crate::MAX_OPERATORS).
+    ///
+    /// Accounts expected:
+    /// 0. `[]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The operator list, at `find_operators_address(counter)`
+    /// 3. `[writable, signer]` The payer funding the operator list's rent
+    /// 4. `[]` The system program
+    AddOperator {
+        operator: Pubkey,
+        allowance: u64,
+        expiry_slot: u64,
+    },
+    /// Remove `operator` and whatever allowance it had left
+    ///
+    /// Accounts expected:
+    /// 0. `[]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The operator list
+    RemoveOperator { operator: Pubkey },
+    /// Invoke `pre` before and `post` after every change listed above, see
+    /// [`OnCounterChange`](crate::cpi::OnCounterChange)
+    ///
+    /// A failing pre hook rejects the change and a failing post hook reverts
+    /// it, so hooks can add validation or accounting of their own. Either may
+    /// be left out, but not be this program, which cannot be reentered.
+    /// Creates the hooks account on first use and otherwise replaces what it
+    /// held; the changes then end with the hooks account followed by the pre
+    /// and post hook programs that are set.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The hooks account, at `find_hooks_address(counter)`
+    /// 3. `[writable, signer]` The payer funding the hooks account's rent
+    /// 4. `[]` The system program
+    SetHooks {
+        pre: Option<Pubkey>,
+        post: Option<Pubkey>,
+    },
+    /// Remove the counter's hooks and close its hooks account
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The hooks account
+    /// 3. `[writable]` The account receiving the hooks account's lamports
+    ClearHooks,
+    /// Charge `fee` for every change listed above, or stop charging with `None`
+    ///
+    /// While a fee is set, those changes must pass the fee accounts after any
+    /// multisig signers and before any lamport fee accounts: the `[writable]` token account
+    /// paying the fee, its `[signer]` owner, the `[writable]` treasury, the
+    /// mint and the SPL Token or Token-2022 program. The fee is transferred
+    /// with `TransferChecked` before the change is applied, and the accounts
+    /// are ignored while no fee is set.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The program config
+    /// 1. `[signer]` The config admin
+    /// 2. `[writable, signer]` The payer funding any additional rent
+    /// 3. `[]` The system program
+    SetFee { fee: Option<FeeConfig> },
+    /// Charge `lamports` for every change listed above, collected in the fee
+    /// vault, or stop charging with 0
+    ///
+    /// While it is positive, those changes must pass the `[writable, signer]`
+    /// payer of the fee, the `[writable]` fee vault and the system program
+    /// after any token fee accounts and before the hooks. The accounts are
+    /// ignored while no lamport fee is set. Creates the fee vault on first
+    /// use.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The program config
+    /// 1. `[signer]` The config admin
+    /// 2. `[writable]` The fee vault, at `find_fee_vault_address()`
+    /// 3. `[writable, signer]` The payer funding any additional rent
+    /// 4. `[]` The system program
+    SetLamportFee { lamports: u64 },
+    /// Move `amount` collected lamports out of the fee vault, which keeps
+    /// its rent-exempt minimum
+    ///
+    /// Accounts expected:
+    /// 0. `[]` The program config
+    /// 1. `[signer]` The config admin
+    /// 2. `[writable]` The fee vault
+    /// 3. `[writable]` The account receiving the lamports
+    WithdrawFees { amount: u64 },
+    /// Let only holders of at least `min_balance` tokens of `mint` increment
+    /// the counter, replacing any gate it had
+    ///
+    /// Every increment, including a `Batch` of increments only, must then be
+    /// signed by the actor and pass the counter's token gate followed by the
+    /// actor's token account for `mint`, after any multisig signers and
+    /// before the fee accounts. Other changes need the authority and are not
+    /// gated.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The token gate, at `find_token_gate_address(counter)`
+    /// 3. `[writable, signer]` The payer funding the gate's rent
+    /// 4. `[]` The system program
+    SetTokenGate { mint: Pubkey, min_balance: u64 },
+    /// Open the counter's increments to anyone again and close its token gate
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The token gate
+    /// 3. `[writable]` The account receiving the gate's lamports
+    ClearTokenGate,
+    /// Make whoever holds the NFT `mint`, a mint with a supply of one and no
+    /// decimals, the counter's authority, so selling the NFT hands it over
+    ///
+    /// From then on, instructions take the holder's token account for `mint`
+    /// where they expect the authority, passed without a signature and
+    /// followed, after every other account, by the holder's `[signer]`, as
+    /// for a multisig. `TransferAuthority` and `AcceptAuthority` move the
+    /// counter back to a plain authority.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[]` The NFT's mint
+    SetNftAuthority { mint: Pubkey },
+    /// Make an SPL Governance governance the authority of a counter, or the
+    /// admin of the program config, so only its passed proposals can change it
+    ///
+    /// `governance` is a governance account of any SPL Governance deployment.
+    /// With `treasury` the new authority is its native treasury instead, the
+    /// governance program's address for `["native-treasury", governance]`.
+    /// The governance program signs for both when it executes a proposal's
+    /// transaction, and for nothing else, so from then on `Set`, `Reset`,
+    /// `Close` and every other instruction needing the authority succeed only
+    /// as part of a passed proposal. Such a transaction holds the instruction
+    /// with the accounts it lists, the governance or its treasury marked as
+    /// the `[signer]` authority; `ExecuteTransaction` passes them on as they
+    /// were proposed, so whatever they name is what voters approved.
+    /// Increments stay open to anyone unless the counter has a caller guard
+    /// or token gate of its own.
+    ///
+    /// Unlike `TransferAuthority` this takes effect at once, the account
+    /// being proof enough that the new authority is a governance.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account, or the program config
+    /// 1. `[signer]` The counter's authority, or the config admin
+    /// 2. `[]` The governance account, owned by the governance program
+    SetGovernanceAuthority { governance: Pubkey, treasury: bool },
+    /// Make every increment of the counter burn as many base units of `mint`
+    /// as it adds, so the count always equals the tokens burned through it
+    ///
+    /// The counter must be at zero and in `Checked` overflow mode without a
+    /// modulus, so an increment adds exactly its amount or fails, and the
+    /// mint cannot be changed or removed. Increments must then be signed by
+    /// the actor, whose tokens are burned, and pass the counter's burn
+    /// account, the token program, the actor's token account for `mint` and
+    /// `mint` itself, after any multisig signers and before the token gate.
+    /// No other instruction can change the count of such a counter, and its
+    /// clones are not burn-backed.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The burn account, at `find_burn_address(counter)`
+    /// 3. `[writable, signer]` The payer funding the burn account's rent
+    /// 4. `[]` The system program
+    SetBurnMint { mint: Pubkey },
+    /// Mint `amount` base units of `mint` to the actor whose change takes
+    /// the counter past each of `milestones`, replacing any rewards it had
+    ///
+    /// Up to `MAX_MILESTONES` milestones, strictly ascending; those the count
+    /// has already reached are never rewarded. Each is rewarded once, to the
+    /// change that takes the count from below it to at or above it, whether
+    /// an increment, a batch, another change of the authority's or a `Merge`
+    /// into the counter; sharding such a counter is refused. The actor is the
+    /// authority of an authority's change and otherwise the first signer
+    /// after the listed accounts. The mint's authority must be the counter's
+    /// rewards account, at `find_rewards_address(counter)`, which signs for
+    /// the minting. Changes raising the count must then pass the rewards
+    /// account, the token program, `mint` and the actor's associated token
+    /// account for `mint`, after any multisig signers and before the burn
+    /// accounts, and a `Merge` into the counter passes them last.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The rewards account, at `find_rewards_address(counter)`
+    /// 3. `[writable, signer]` The payer funding the rewards account's rent
+    /// 4. `[]` The system program
+    SetRewards {
+        mint: Pubkey,
+        amount: u64,
+        milestones: Vec<i128>,
+    },
+    /// Stop rewarding milestones and close the counter's rewards account
+    ///
+    /// The mint's authority stays the rewards account's address, which no
+    /// longer signs for anything.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The rewards account
+    /// 3. `[writable]` The account receiving the rewards account's lamports
+    ClearRewards,
+    /// Open an escrow of lamports that `Settle` releases to `beneficiary`
+    /// once the count reaches `target`, or refunds to its depositors if it
+    /// has not when `deadline_slot` has passed
+    ///
+    /// The count must be below `target`, and reaching it means reaching it
+    /// at any point: the counter's high water mark is what is compared, so
+    /// the authority cannot hold a payout back by decrementing. A counter has
+    /// at most one escrow, and cannot be closed while it is open, that is
+    /// until it is released or, past its deadline, every deposit is refunded.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The escrow, at `find_escrow_address(counter)`
+    /// 3. `[writable, signer]` The payer funding the escrow's rent
+    /// 4. `[]` The system program
+    InitializeEscrow {
+        beneficiary: Pubkey,
+        target: i128,
+        deadline_slot: u64,
+    },
+    /// Deposit `lamports` into the counter's escrow, recorded under the
+    /// depositor for a refund
+    ///
+    /// Only while the escrow is neither settled nor past its deadline.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` The counter account
+    /// 1. `[writable]` The escrow
+    /// 2. `[writable]` The depositor's deposit record, at
+    ///    `find_escrow_deposit_address(escrow, depositor)`
+    /// 3. `[writable, signer]` The depositor, also paying the record's rent
+    /// 4. `[]` The system program
+    DepositEscrow { lamports: u64 },
+    /// Release the counter's escrow to its beneficiary, or refund a deposit
+    ///
+    /// Once the count has reached the target, every lamport the escrow holds
+    /// above its rent goes to the beneficiary and the escrow is settled for
+    /// good. Until then, once the deadline has passed, each deposit record
+    /// passed is refunded to its depositor and closed. Records of a settled
+    /// escrow are closed without a refund, returning their rent. Past the
+    /// deadline with nothing left to refund, settling without a deposit
+    /// record just closes the escrow, so the counter can be closed. Anyone may
+    /// settle, as the lamports only ever go where the escrow says.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[writable]` The escrow
+    /// 2. `[writable]` The beneficiary, or for a refund the depositor
+    /// 3. `[writable]` For a refund only, the depositor's deposit record
+    Settle,
+    /// Require every increment to carry a permit signed by the counter's
+    /// authority, so the authority key can stay offline
+    ///
+    /// A permit is the authority's Ed25519 signature over `permit_message`
+    /// with the counter, the amount, a nonce and an expiry slot, verified by
+    /// an Ed25519 program instruction of the increment's transaction. The
+    /// increment must pass the counter's permit nonce account after any
+    /// multisig signers and before any reward accounts, and end with the
+    /// Instructions sysvar. Nonces only go up, so each permit works once.
+    /// Using a permit also voids every unused permit with a lower nonce, so
+    /// the authority should issue nonces in the order the permits will be
+    /// used, and reissue any it skipped with a fresh nonce. Increments by
+    /// `Batch` are refused, as they would carry no amount of their own.
+    /// Requiring permits again keeps the nonces already used.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The permit nonce account, at `find_permits_address(counter)`
+    /// 3. `[writable, signer]` The payer funding the permit nonce account's rent
+    /// 4. `[]` The system program
+    RequirePermits,
+    /// Let increments go without permits again and close the counter's
+    /// permit nonce account
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The permit nonce account
+    /// 3. `[writable]` The account receiving the permit nonce account's lamports
+    ClearPermits,
+    /// Make each actor wait `slots` slots between increments of the counter
+    /// and add at most `daily_cap` to it per day, replacing any cooldown it
+    /// had
+    ///
+    /// Either limit may be zero to leave it off, but not both. Days are UTC
+    /// days of the cluster's unix timestamp, and a `Batch` counts its
+    /// increments together.
+    ///
+    /// Every increment, including a `Batch` of increments only, must then be
+    /// signed by the actor and pass the cooldown, the actor's writable stamp
+    /// at `find_cooldown_stamp_address(counter, actor)` and the system
+    /// program, after any multisig signers and before any permit nonce
+    /// account. The actor's first increment creates their stamp, so the
+    /// actor must be writable and pays its rent; an increment less than
+    /// `slots` slots after the actor's last, or taking the actor's total for
+    /// the day past `daily_cap`, fails. Changes signed by the authority are
+    /// not throttled.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The cooldown, at `find_cooldown_address(counter)`
+    /// 3. `[writable, signer]` The payer funding the cooldown's rent
+    /// 4. `[]` The system program
+    SetCooldown { slots: u64, daily_cap: u64 },
+    /// Stop throttling increments and close the counter's cooldown
+    ///
+    /// Actors' stamps stay, and count again if a cooldown is set later.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The cooldown
+    /// 3. `[writable]` The account receiving the cooldown's lamports
+    ClearCooldown,
+    /// Cap the total amount by which the changes listed above may move any
+    /// one counter within an epoch at `cap`, or lift the cap with 0
+    ///
+    /// Every counter tallies how far its changes moved it in the current
+    /// epoch, the size of each change's net effect, and starts over when the
+    /// epoch advances. A change taking the tally past the cap fails, so a
+    /// cap set mid-epoch counts the changes already made in it.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The program config
+    /// 1. `[signer]` The config admin
+    /// 2. `[writable, signer]` The payer funding any additional rent
+    /// 3. `[]` The system program
+    SetEpochCap { cap: u64 },
+    /// Make the first change in each new epoch start the counter over from
+    /// zero, for rolling per-epoch metrics, or stop doing so
+    ///
+    /// The change finds the value the counter ended the last epoch with,
+    /// which it logs in an `EpochReset` event before zeroing the count, and
+    /// then applies to zero. Nothing happens in epochs without changes, so a
+    /// counter last changed in an earlier epoch still holds that epoch's
+    /// value, as its view's `epoch` tells. Fails for a counter whose
+    /// minimum is above zero, which could never start over, and for a
+    /// burn-backed counter, which only changes by burning tokens.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    SetEpochReset { enabled: bool },
+    /// Make the counter's value fade towards zero by `rate` per slot or per
+    /// second, replacing any decay it had, for scores that fade out
+    ///
+    /// `rate` must be positive. The decay is applied lazily: every change
+    /// listed above first moves the value towards zero by `rate` for each
+    /// unit since the counter last changed, stopping at zero, and applies to
+    /// the result. The stored value, and so its view, is the one as of the
+    /// last change. Decay owed under a replaced rate is applied first. Each
+    /// decay is a change of its own, reported as `Operation::Decay`, and
+    /// fails while the counter is locked or expired. A burn-backed counter
+    /// cannot decay.
+    ///
+    /// Those changes must pass the decay, after any multisig signers and
+    /// before any cooldown accounts.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The decay, at `find_decay_address(counter)`
+    /// 3. `[writable, signer]` The payer funding the decay's rent
+    /// 4. `[]` The system program
+    SetDecay { rate: u64, unit: DecayUnit },
+    /// Stop the counter's value from decaying and close its decay, applying
+    /// what it owes first
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    /// 2. `[writable]` The decay
+    /// 3. `[writable]` The account receiving the decay's lamports
+    ClearDecay,
+    /// Pay whoever sends a `Crank` that had work to do `lamports` out of
+    /// the fee vault, or stop paying with 0, and pay out no more than
+    /// `epoch_budget` lamports in crank rewards in any one epoch
+    ///
+    /// What was already paid in the current epoch still counts against the
+    /// new budget.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The program config
+    /// 1. `[signer]` The config admin
+    /// 2. `[writable, signer]` The payer funding any additional rent
+    /// 3. `[]` The system program
+    SetCrankReward { lamports: u64, epoch_budget: u64 },
+    /// Bring a counter up to date without changing it, so keepers can keep
+    /// counters current between changes; anyone may send it
+    ///
+    /// Applies the decay the counter owes, starts it over if it is set to in
+    /// a new epoch and starts its epoch tally over, see `SetEpochCap`, then
+    /// fires its callback if that took the count to its target. A crank that
+    /// did any of this pays the cranker the config's crank reward, see
+    /// `SetCrankReward`, or as much of it as the fee vault holds above its
+    /// rent-exempt minimum and the epoch's budget has left. Only a counter
+    /// that paid a fee since its last rewarded crank earns a reward, and at
+    /// most once per epoch, so the vault pays out no more than the fees
+    /// flowing into it.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[writable]` The program config, tallying the epoch's rewards
+    /// 2. `[writable, signer]` The cranker, paid the reward
+    /// 3. `[writable]` The fee vault, at `find_fee_vault_address()`
+    /// 4. `[]` The counter's decay, if it decays
+    /// 5. `[]` The callback account, the callback program and the accounts
+    ///    registered with it, if the callback is armed
+    Crank,
+    /// Lock the counter until `until_slot`, so every change to its value
+    /// fails with `CounterLocked` before that slot, to freeze a count while
+    /// it is being judged
+    ///
+    /// `until_slot` must be in the future and cannot come before the end of
+    /// a lock already in place, so a lock can be extended but not lifted
+    /// early. Settings may still change while the counter is locked.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    Lock { until_slot: u64 },
+    /// Make the counter read-only from the unix timestamp `expires_at`
+    /// onwards, or never with 0, so a campaign stops taking entries at its
+    /// deadline
+    ///
+    /// From then on every change to its value fails with `CounterExpired`,
+    /// and so does this instruction: an expired counter can only be viewed
+    /// and closed. Before then the expiry may be moved or removed, but not
+    /// to a time that has already passed.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    SetExpiry { expires_at: i64 },
+    /// Keep an exponential moving average of the counter's value, moved
+    /// `alpha_bps` basis points of the way to the new value by every change,
+    /// or stop keeping one with 0
+    ///
+    /// `alpha_bps` may be at most 10,000, which makes the average the last
+    /// value. Starting to keep an average starts it at the current value;
+    /// changing the smoothing factor keeps the average so far. Programs
+    /// reading the counter find it with `Counter::ema`.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The counter account
+    /// 1. `[signer]` The counter's authority
+    SetEma { alpha_bps: u16 },
+}
+
+/// Length of the return data set by instructions that change a count
+pub const RETURN_DATA_LEN: usize = 16;
+
+/// The new count in the return data of an instruction that changed one, as
+/// a CPI caller reads it with `get_return_data`
+///
+/// The data is the counter's value as a little-endian `i128`, which holds
+/// unsigned and signed counts alike. Merge sets the destination's value.
+/// `None` if `data` is not [`RETURN_DATA_LEN`] bytes long.
+pub fn decode_return_data(data: &[u8]) -> Option<i128> {
+    Some(i128::from_le_bytes(data.try_into().ok()?))
+}
+
+/// Longest memo a `CounterInstruction::WithMemo` may carry, in bytes
+pub const MAX_MEMO_LEN: usize = 256;
+
+/// Address of the SPL Memo program, which `CounterInstruction::WithMemo` invokes
+pub const MEMO_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+/// Number of variants in [`CounterInstruction`]; any larger tag byte is unknown
+///
+/// Must be bumped whenever a variant is appended, together with
+/// [`ANCHOR_DISCRIMINATORS`] and `MAX_INSTRUCTION_LENS`.
+pub(crate) const INSTRUCTION_COUNT: u8 = 81;
+
+/// Anchor's discriminator for each instruction, indexed by variant
+///
+/// Anchor prefixes instruction data with `sha256("global:<name>")[..8]`,
+/// where `<name>` is the instruction in snake case, followed by its arguments
+/// in Borsh, which are exactly the fields of our variant. With the `anchor`
+/// feature, data starting with one of these is accepted as that instruction.
+#[cfg_attr(not(feature = "anchor"), allow(dead_code))]
+pub(crate) const ANCHOR_DISCRIMINATORS: [[u8; 8]; INSTRUCTION_COUNT as usize] = [
+    [11, 18, 104, 9, 104, 174, 59, 33],       // increment
+    [106, 227, 168, 59, 248, 27, 150, 101],   // decrement
+    [175, 175, 109, 31, 13, 152, 155, 237],   // initialize
+    [23, 81, 251, 84, 138, 183, 240, 214],    // reset
+    [198, 51, 53, 241, 116, 29, 126, 194],    // set
+    [48, 169, 76, 72, 229, 180, 55, 161],     // transfer_authority
+    [107, 86, 198, 91, 33, 12, 107, 160],     // accept_authority
+    [98, 165, 201, 177, 108, 65, 206, 96],    // close
+    [109, 3, 226, 207, 75, 90, 27, 25],       // migrate_legacy
+    [178, 254, 136, 212, 127, 85, 171, 210],  // initialize_pda
+    [110, 145, 192, 50, 23, 151, 245, 130],   // multiply
+    [20, 209, 165, 165, 178, 152, 253, 210],  // divide
+    [233, 146, 192, 163, 241, 3, 36, 72],     // compare_and_swap
+    [198, 211, 248, 40, 165, 37, 21, 148],    // batch
+    [224, 39, 123, 214, 207, 150, 32, 232],   // increment_by_one
+    [77, 42, 189, 71, 155, 40, 52, 120],      // migrate_v1_to_v2
+    [211, 22, 221, 251, 74, 121, 193, 47],    // pause
+    [169, 144, 4, 38, 10, 141, 188, 255],     // unpause
+    [208, 127, 21, 1, 194, 190, 196, 70],     // initialize_config
+    [29, 158, 252, 191, 10, 83, 219, 99],     // update_config
+    [220, 130, 117, 21, 27, 227, 78, 213],    // initialize_multisig
+    [69, 74, 217, 36, 115, 117, 97, 76],      // approve
+    [170, 23, 31, 34, 133, 173, 93, 242],     // revoke
+    [148, 141, 236, 47, 174, 126, 69, 111],   // merge
+    [153, 50, 26, 120, 125, 12, 36, 151],     // clone
+    [56, 251, 243, 208, 236, 231, 143, 62],   // initialize_votes
+    [166, 101, 34, 156, 165, 70, 117, 20],    // vote_up
+    [170, 223, 185, 29, 44, 161, 6, 137],     // vote_down
+    [203, 106, 208, 248, 80, 172, 12, 68],    // initialize_map
+    [131, 91, 155, 18, 176, 180, 23, 8],      // add_map_entry
+    [212, 51, 207, 181, 52, 251, 60, 38],     // remove_map_entry
+    [158, 129, 162, 116, 86, 58, 107, 42],    // increment_map_entry
+    [20, 233, 14, 86, 85, 212, 239, 19],      // decrement_map_entry
+    [155, 234, 231, 146, 236, 158, 162, 30],  // migrate
+    [74, 27, 74, 155, 56, 134, 175, 125],     // resize
+    [78, 157, 75, 242, 151, 20, 121, 144],    // set_metadata
+    [100, 96, 88, 58, 225, 178, 9, 147],      // initialize_shard
+    [138, 157, 149, 182, 5, 229, 29, 164],    // increment_shard
+    [211, 113, 179, 217, 7, 100, 44, 8],      // aggregate
+    [16, 143, 228, 87, 255, 10, 177, 154],    // initialize_history
+    [236, 21, 14, 66, 207, 228, 238, 156],    // set_caller_guard
+    [63, 89, 208, 133, 65, 53, 8, 206],       // set_exactly_once
+    [159, 131, 149, 192, 109, 186, 68, 227],  // set_freeze_authority
+    [182, 75, 142, 253, 96, 137, 255, 67],    // emergency_halt
+    [0, 243, 48, 185, 6, 73, 190, 83],        // emergency_resume
+    [4, 43, 52, 220, 35, 12, 178, 232],       // set_thresholds
+    [127, 226, 196, 225, 180, 15, 122, 20],   // with_memo
+    [240, 120, 227, 253, 169, 6, 216, 140],   // view
+    [101, 56, 197, 230, 140, 200, 88, 25],    // with_op_id
+    [173, 40, 156, 83, 183, 154, 99, 60],     // set_callback
+    [47, 30, 79, 14, 161, 146, 61, 215],      // clear_callback
+    [149, 142, 187, 68, 33, 250, 87, 105],    // add_operator
+    [84, 183, 126, 251, 137, 150, 214, 134],  // remove_operator
+    [54, 122, 235, 222, 156, 84, 10, 61],     // set_hooks
+    [94, 226, 215, 21, 174, 97, 4, 48],       // clear_hooks
+    [18, 154, 24, 18, 237, 214, 19, 80],      // set_fee
+    [63, 141, 9, 147, 176, 97, 229, 36],      // set_lamport_fee
+    [198, 212, 171, 109, 144, 215, 174, 89],  // withdraw_fees
+    [181, 246, 120, 133, 255, 105, 150, 113], // set_token_gate
+    [64, 119, 71, 197, 91, 121, 75, 52],      // clear_token_gate
+    [147, 117, 177, 43, 38, 69, 62, 206],     // set_nft_authority
+    [0, 113, 148, 253, 151, 181, 148, 42],    // set_governance_authority
+    [245, 243, 127, 223, 152, 205, 137, 210], // set_burn_mint
+    [235, 52, 209, 28, 95, 215, 236, 137],    // set_rewards
+    [65, 56, 119, 167, 249, 94, 110, 103],    // clear_rewards
+    [243, 160, 77, 153, 11, 92, 48, 209],     // initialize_escrow
+    [226, 112, 158, 176, 178, 118, 153, 128], // deposit_escrow
+    [175, 42, 185, 87, 144, 131, 102, 212],   // settle
+    [70, 180, 59, 173, 232, 241, 7, 104],     // require_permits
+    [175, 191, 84, 145, 70, 58, 252, 64],     // clear_permits
+    [57, 78, 91, 178, 112, 152, 211, 87],     // set_cooldown
+    [25, 123, 95, 43, 214, 9, 19, 133],       // clear_cooldown
+    [74, 166, 117, 171, 182, 251, 191, 25],   // set_epoch_cap
+    [245, 67, 184, 238, 111, 139, 97, 27],    // set_epoch_reset
+    [132, 95, 23, 90, 52, 217, 157, 150],     // set_decay
+    [207, 221, 30, 47, 141, 44, 100, 121],    // clear_decay
+    [69, 49, 43, 161, 24, 99, 117, 129],      // set_crank_reward
+    [0, 232, 3, 195, 124, 117, 105, 53],      // crank
+    [21, 19, 208, 43, 237, 62, 255, 87],      // lock
+    [3, 38, 117, 33, 73, 152, 213, 179],      // set_expiry
+    [237, 206, 30, 245, 42, 143, 28, 111],    // set_ema
+];
+
+/// Largest number of operations a single `CounterInstruction::Batch` may carry
+pub const MAX_BATCH_OPS: usize = 32;
+
+/// Largest number of shards a single `CounterInstruction::Aggregate` may drain
+pub const MAX_AGGREGATE_SHARDS: usize = 16;
+
+/// Largest instruction data each instruction can take, indexed by variant
+///
+/// Variable-length payloads are counted at their limits: seeds at
+/// `MAX_SEED_LEN`, batches at [`MAX_BATCH_OPS`], multisigs at `MAX_SIGNERS`,
+/// metadata at its maximum name and description lengths, program lists at
+/// `CallerGuard::MAX_ALLOWED_PROGRAMS` and memos at [`MAX_MEMO_LEN`] around
+/// the longest instruction they can wrap.
+const MAX_INSTRUCTION_LENS: [usize; INSTRUCTION_COUNT as usize] = [
+    1 + 4,                                                                            // increment
+    1 + 4,                                                                            // decrement
+    1 + InitializeArgs::MAX_LEN,                                                      // initialize
+    1,                                                                                // reset
+    1 + 8,                                                                            // set
+    1 + 32,                                         // transfer_authority
+    1,                                              // accept_authority
+    1,                                              // close
+    1,                                              // migrate_legacy
+    1 + 4 + MAX_SEED_LEN + InitializeArgs::MAX_LEN, // initialize_pda
+    1 + 4,                                          // multiply
+    1 + 4,                                          // divide
+    1 + 8 + 8,                                      // compare_and_swap
+    1 + 4 + MAX_BATCH_OPS * (1 + 8),                // batch
+    1,                                              // increment_by_one
+    1,                                              // migrate_v1_to_v2
+    1,                                              // pause
+    1,                                              // unpause
+    1 + 4,                                          // initialize_config
+    1 + 32 + 1 + 4,                                 // update_config
+    1 + 1 + 4 + 32 * MAX_SIGNERS,                   // initialize_multisig
+    1 + 32 + 8,                                     // approve
+    1,                                              // revoke
+    1,                                              // merge
+    1 + 1 + 4 + MAX_SEED_LEN,                       // clone
+    1,                                              // initialize_votes
+    1 + 4,                                          // vote_up
+    1 + 4,                                          // vote_down
+    1,                                              // initialize_map
+    1 + 32,                                         // add_map_entry
+    1 + 32,                                         // remove_map_entry
+    1 + 32 + 4,                                     // increment_map_entry
+    1 + 32 + 4,                                     // decrement_map_entry
+    1,                                              // migrate
+    1 + 4,                                          // resize
+    1 + 4 + CounterMetadata::MAX_NAME_LEN + 4 + CounterMetadata::MAX_DESCRIPTION_LEN, // set_metadata
+    1 + 2,                                                           // initialize_shard
+    1 + 4,                                                           // increment_shard
+    1,                                                               // aggregate
+    1 + 2,                                                           // initialize_history
+    1 + 1 + 2 * (4 + 32 * CallerGuard::MAX_ALLOWED_PROGRAMS),        // set_caller_guard
+    1 + 1,                                                           // set_exactly_once
+    1 + 32,                                                          // set_freeze_authority
+    1,                                                               // emergency_halt
+    1,                                                               // emergency_resume
+    1 + 4 + 16 * Counter::MAX_THRESHOLDS,                            // set_thresholds
+    1 + 4 + MAX_MEMO_LEN + 1 + 16 + 1 + 4 + MAX_BATCH_OPS * (1 + 8), // with_memo, around with_op_id
+    1,                                                               // view
+    1 + 16 + 1 + 4 + MAX_BATCH_OPS * (1 + 8),                        // with_op_id, wrapping a batch
+    1 + 16 + 32 + 4 + CounterCallback::MAX_ACCOUNTS * (32 + 1),      // set_callback
+    1,                                                               // clear_callback
+    1 + 32 + 8 + 8,                                                  // add_operator
+    1 + 32,                                                          // remove_operator
+    1 + (1 + 32) + (1 + 32),                                         // set_hooks
+    1,                                                               // clear_hooks
+    1 + FeeConfig::LEN,                                              // set_fee
+    1 + 8,                                                           // set_lamport_fee
+    1 + 8,                                                           // withdraw_fees
+    1 + 32 + 8,                                                      // set_token_gate
+    1,                                                               // clear_token_gate
+    1 + 32,                                                          // set_nft_authority
+    1 + 32 + 1,                                                      // set_governance_authority
+    1 + 32,                                                          // set_burn_mint
+    1 + 32 + 8 + 4 + 16 * MAX_MILESTONES,                            // set_rewards
+    1,                                                               // clear_rewards
+    1 + 32 + 16 + 8,                                                 // initialize_escrow
+    1 + 8,                                                           // deposit_escrow
+    1,                                                               // settle
+    1,                                                               // require_permits
+    1,                                                               // clear_permits
+    1 + 8 + 8,                                                       // set_cooldown
+    1,                                                               // clear_cooldown
+    1 + 8,                                                           // set_epoch_cap
+    1 + 1,                                                           // set_epoch_reset
+    1 + 8 + 1,                                                       // set_decay
+    1,                                                               // clear_decay
+    1 + 8 + 8,                                                       // set_crank_reward
+    1,                                                               // crank
+    1 + 8,                                                           // lock
+    1 + 8,                                                           // set_expiry
+    1 + 2,                                                           // set_ema
+];
+
+/// Largest instruction data the instruction with variant index `tag` can
+/// take, for clients sizing buffers or transactions, or `None` if no
+/// instruction has that index
+pub fn max_instruction_len(tag: u8) -> Option<usize> {
+    MAX_INSTRUCTION_LENS.get(usize::from(tag)).copied()
+}
+
+/// Instruction data of `CounterInstruction::IncrementByOne`: its variant index
+///
+/// Must stay in sync with the variant's position in [`CounterInstruction`].
+pub(crate) const INCREMENT_BY_ONE_TAG: u8 = 14;
+
+/// Variant index of `CounterInstruction::WithMemo`
+///
+/// Must stay in sync with the variant's position in [`CounterInstruction`].
+pub(crate) const WITH_MEMO_TAG: u8 = 46;
+
+/// Variant index of `CounterInstruction::WithOpId`
+///
+/// Must stay in sync with the variant's position in [`CounterInstruction`].
+pub(crate) const WITH_OP_ID_TAG: u8 = 48;
+
+/// A single step of a `CounterInstruction::Batch`
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Clone)]
+pub enum CounterOp {
+    /// Add the amount to the counter
+    Increment(u32),
+    /// Subtract the amount from the counter
+    Decrement(u32),
+    /// Overwrite the counter with the value
+    Set(u64),
+}
+
+impl CounterOp {
+    /// Whether the operation needs the counter authority's signature
+    pub fn requires_authority(&self) -> bool {
+        !matches!(self, CounterOp::Increment(_))
+    }
+}
+
+/// Reader over instruction data that remembers whether decoding ran out of bytes
+///
+/// Borsh reports a short input the same way as other malformed data, so this
+/// is what tells a truncated payload apart from, say, invalid UTF-8.
+struct TrackingReader<'a> {
+    data: &'a [u8],
+    exhausted: bool,
+}
+
+impl Read for TrackingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.len() > self.data.len() {
+            self.exhausted = true;
+        }
+        self.data.read(buf)
+    }
+}
+
+impl CounterInstruction {
+    /// Whether the instruction changes a single counter's value with an
+    /// operation of its own, which the counter's history records and
+    /// `SetExactlyOnce` limits to one per transaction, or wraps one in a memo
+    /// or an op id
+    pub fn changes_count(&self) -> bool {
+        matches!(
+            self,
+            CounterInstruction::Increment(_)
+                | CounterInstruction::IncrementByOne
+                | CounterInstruction::Decrement(_)
+                | CounterInstruction::Multiply(_)
+                | CounterInstruction::Divide(_)
+                | CounterInstruction::CompareAndSwap { .. }
+                | CounterInstruction::Reset
+                | CounterInstruction::Set(_)
+                | CounterInstruction::Batch(_)
+        ) || matches!(
+            self,
+            CounterInstruction::WithMemo { instruction, .. }
+                | CounterInstruction::WithOpId { instruction, .. }
+                if instruction.changes_count()
+        )
+    }
+
+    /// Decode instruction data, distinguishing the ways it can be malformed
+    ///
+    /// - an empty buffer or one that ends mid-payload is `TruncatedInstruction`
+    /// - a tag byte past the last variant is `UnknownInstruction`
+    /// - anything else, including bytes left over after a complete
+    ///   instruction and whatever Borsh rejects, such as invalid UTF-8 in a
+    ///   seed, is `InvalidInstructionData`
+    ///
+    /// Only data exactly as long as the encoding of its instruction is
+    /// accepted, so a client appending garbage finds out immediately.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        // Rewrite Anchor-style data as our own: the tag byte, then the same payload
+        #[cfg(feature = "anchor")]
+        let native;
+        #[cfg(feature = "anchor")]
+        let data = match Self::anchor_tag(data) {
+            Some(tag) => {
+                native = [&[tag], &data[8..]].concat();
+                &native[..]
+            }
+            None => data,
+        };
+
+        let Some(&tag) = data.first() else {
+            msg!("Instruction data is empty");
+            return Err(CounterError::TruncatedInstruction.into());
+        };
+        if tag >= INSTRUCTION_COUNT {
+            msg!(
+                "Unknown instruction tag {}, expected less than {}",
+                tag,
+                INSTRUCTION_COUNT
+            );
+            return Err(CounterError::UnknownInstruction.into());
+        }
+
+        let mut reader = TrackingReader {
+            data,
+            exhausted: false,
+        };
+        let instruction = match Self::deserialize_reader(&mut reader) {
+            Ok(instruction) => instruction,
+            Err(_) if reader.exhausted => {
+                msg!("Instruction {} is truncated ({} bytes)", tag, data.len());
+                return Err(CounterError::TruncatedInstruction.into());
+            }
+            Err(error) => {
+                msg!("Instruction {} is malformed: {}", tag, error);
+                return Err(ProgramError::InvalidInstructionData);
+            }
+        };
+        if !reader.data.is_empty() {
+            msg!(
+                "{} is {} bytes long, but has {} trailing bytes",
+                instruction.name(),
+                data.len(),
+                reader.data.len()
+            );
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        Ok(instruction)
+    }
+
+    /// The variant index of Anchor-style instruction data, if it starts with
+    /// one of [`ANCHOR_DISCRIMINATORS`]
+    #[cfg(feature = "anchor")]
+    fn anchor_tag(data: &[u8]) -> Option<u8> {
+        let prefix = data.get(..8)?;
+        ANCHOR_DISCRIMINATORS
+            .iter()
+            .position(|discriminator| discriminator == prefix)
+            .map(|index| index as u8)
+    }
+
+    /// Human-readable instruction name used in log messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            CounterInstruction::Increment(_) => "Increment",
+            CounterInstruction::Decrement(_) => "Decrement",
+            CounterInstruction::Initialize(_) => "Initialize",
+            CounterInstruction::Reset => "Reset",
+            CounterInstruction::Set(_) => "Set",
+            CounterInstruction::TransferAuthority { .. } => "TransferAuthority",
+            CounterInstruction::AcceptAuthority => "AcceptAuthority",
+            CounterInstruction::Close => "Close",
+            CounterInstruction::MigrateLegacy => "MigrateLegacy",
+            CounterInstruction::InitializePda { .. } => "InitializePda",
+            CounterInstruction::Multiply(_) => "Multiply",
+            CounterInstruction::Divide(_) => "Divide",
+            CounterInstruction::CompareAndSwap { .. } => "CompareAndSwap",
+            CounterInstruction::Batch(_) => "Batch",
+            CounterInstruction::IncrementByOne => "IncrementByOne",
+            CounterInstruction::MigrateV1ToV2 => "MigrateV1ToV2",
+            CounterInstruction::Pause => "Pause",
+            CounterInstruction::Unpause => "Unpause",
+            CounterInstruction::InitializeConfig { .. } => "InitializeConfig",
+            CounterInstruction::UpdateConfig { .. } => "UpdateConfig",
+            CounterInstruction::InitializeMultisig { .. } => "InitializeMultisig",
+            CounterInstruction::Approve { .. } => "Approve",
+            CounterInstruction::Revoke => "Revoke",
+            CounterInstruction::Merge => "Merge",
+            CounterInstruction::Clone { .. } => "Clone",
+            CounterInstruction::InitializeVotes => "InitializeVotes",
+            CounterInstruction::VoteUp(_) => "VoteUp",
+            CounterInstruction::VoteDown(_) => "VoteDown",
+            CounterInstruction::InitializeMap => "InitializeMap",
+            CounterInstruction::AddMapEntry { .. } => "AddMapEntry",
+            CounterInstruction::RemoveMapEntry { .. } => "RemoveMapEntry",
+            CounterInstruction::IncrementMapEntry { .. } => "IncrementMapEntry",
+            CounterInstruction::DecrementMapEntry { .. } => "DecrementMapEntry",
+            CounterInstruction::Migrate => "Migrate",
+            CounterInstruction::Resize { .. } => "Resize",
+            CounterInstruction::SetMetadata { .. } => "SetMetadata",
+            CounterInstruction::InitializeShard { .. } => "InitializeShard",
+            CounterInstruction::IncrementShard(_) => "IncrementShard",
+            CounterInstruction::Aggregate => "Aggregate",
+            CounterInstruction::InitializeHistory { .. } => "InitializeHistory",
+            CounterInstruction::SetCallerGuard { .. } => "SetCallerGuard",
+            CounterInstruction::SetExactlyOnce { .. } => "SetExactlyOnce",
+            CounterInstruction::SetFreezeAuthority { .. } => "SetFreezeAuthority",
+            CounterInstruction::EmergencyHalt => "EmergencyHalt",
+            CounterInstruction::EmergencyResume => "EmergencyResume",
+            CounterInstruction::SetThresholds { .. } => "SetThresholds",
+            CounterInstruction::WithMemo { .. } => "WithMemo",
+            CounterInstruction::View => "View",
+            CounterInstruction::WithOpId { .. } => "WithOpId",
+            CounterInstruction::SetCallback { .. } => "SetCallback",
+            CounterInstruction::ClearCallback => "ClearCallback",
+            CounterInstruction::AddOperator { .. } => "AddOperator",
+            CounterInstruction::RemoveOperator { .. } => "RemoveOperator",
+            CounterInstruction::SetHooks { .. } => "SetHooks",
+            CounterInstruction::ClearHooks => "ClearHooks",
+            CounterInstruction::SetFee { .. } => "SetFee",
+            CounterInstruction::SetLamportFee { .. } => "SetLamportFee",
+            CounterInstruction::WithdrawFees { .. } => "WithdrawFees",
+            CounterInstruction::SetTokenGate { .. } => "SetTokenGate",
+            CounterInstruction::ClearTokenGate => "ClearTokenGate",
+            CounterInstruction::SetNftAuthority { .. } => "SetNftAuthority",
+            CounterInstruction::SetGovernanceAuthority { .. } => "SetGovernanceAuthority",
+            CounterInstruction::SetBurnMint { .. } => "SetBurnMint",
+            CounterInstruction::SetRewards { .. } => "SetRewards",
+            CounterInstruction::ClearRewards => "ClearRewards",
+            CounterInstruction::InitializeEscrow { .. } => "InitializeEscrow",
+            CounterInstruction::DepositEscrow { .. } => "DepositEscrow",
+            CounterInstruction::Settle => "Settle",
+            CounterInstruction::RequirePermits => "RequirePermits",
+            CounterInstruction::ClearPermits => "ClearPermits",
+            CounterInstruction::SetCooldown { .. } => "SetCooldown",
+            CounterInstruction::ClearCooldown => "ClearCooldown",
+            CounterInstruction::SetEpochCap { .. } => "SetEpochCap",
+            CounterInstruction::SetEpochReset { .. } => "SetEpochReset",
+            CounterInstruction::SetDecay { .. } => "SetDecay",
+            CounterInstruction::ClearDecay => "ClearDecay",
+            CounterInstruction::SetCrankReward { .. } => "SetCrankReward",
+            CounterInstruction::Crank => "Crank",
+            CounterInstruction::Lock { .. } => "Lock",
+            CounterInstruction::SetExpiry { .. } => "SetExpiry",
+            CounterInstruction::SetEma { .. } => "SetEma",
+        }
+    }
+}
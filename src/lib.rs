@@ -1,77 +1,62 @@
-// Import necessary dependencies from borsh for serialization/deserialization
-use borsh::{BorshDeserialize, BorshSerialize};
-// Import Solana program utilities
-use solana_program::{
-    account_info::{AccountInfo, next_account_info},
-    entrypoint,                // Macro to define program entry point
-    entrypoint::ProgramResult, // Result type for program execution
-    msg,                       // Macro for logging messages on-chain
-    pubkey::Pubkey,            // Public key type
-};
-
-/// Counter data structure that will be stored in a Solana account
-/// This struct represents the state of our counter program
-#[derive(BorshSerialize, BorshDeserialize)]
-struct Counter {
-    count: u32, // The current counter value (32-bit unsigned integer)
-}
-
-/// Enum representing different instructions our program can handle
-/// Each variant represents a different operation the program can perform
-#[derive(BorshSerialize, BorshDeserialize)]
-enum CounterInstruction {
-    /// Increment the counter by a specified amount
-    Increment(u32),
-    /// Decrement the counter by a specified amount  
-    Decrement(u32),
-}
-
-// Define the entry point for our Solana program
-// This macro tells Solana that process_instruction is the main function to call
-entrypoint!(process_instruction);
-
-/// Main instruction processing function
-/// This function is called whenever a transaction invokes our program
-///
-/// Arguments:
-/// - _program_id: The public key of our deployed program (unused in this example)
-/// - accounts: Array of accounts involved in the transaction
-/// - instruction_data: Raw bytes containing the instruction to execute
+/// Log like `solana_program::msg!`, but only in builds with the
+/// `verbose-logs` feature
 ///
-/// Returns: ProgramResult (Ok() on success, Err() on failure)
-pub fn process_instruction(
-    _program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    instruction_data: &[u8], // Raw instruction bytes (e.g., [0, 0, 0, 1] for increment by 1)
-) -> ProgramResult {
-    // Get the first account from the accounts array - this is our counter data account
-    // The ? operator propagates any error if the account doesn't exist
-    let account = next_account_info(&mut accounts.iter())?;
-
-    // Deserialize the current counter data from the account's data field
-    // try_from_slice converts the raw bytes back into our Counter struct
-    let mut counter = Counter::try_from_slice(&account.data.borrow())?;
-
-    // Parse the instruction data to determine what operation to perform
-    // The instruction data contains serialized CounterInstruction enum
-    match CounterInstruction::try_from_slice(instruction_data)? {
-        CounterInstruction::Increment(amount) => {
-            // Add the specified amount to the current counter value
-            counter.count += amount;
+/// Formatting a message costs hundreds of compute units, so lean deployments
+/// build without it. The arguments are type-checked either way, and the
+/// structured events in [`events`] are logged regardless. Defined ahead of
+/// the modules, which all log through it instead of the Solana macro.
+macro_rules! msg {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "verbose-logs")]
+        ::solana_program::msg!($($arg)*);
+        #[cfg(not(feature = "verbose-logs"))]
+        if false {
+            let _ = format_args!($($arg)*);
         }
-        CounterInstruction::Decrement(amount) => {
-            // Subtract the specified amount from the current counter value
-            counter.count -= amount;
-        }
-    }
-
-    // Serialize the updated counter back to the account's data field
-    // This persists the new counter value on the blockchain
-    counter.serialize(&mut *account.data.borrow_mut())?;
-
-    // Log the updated counter value (visible in transaction logs)
-    msg!("Counter updated to {}", counter.count);
-
-    // Return success
-    Ok(())
+    }};
 }
+
+mod arithmetic;
+pub mod builders;
+pub mod cpi;
+// Left out with the `no-entrypoint` feature, so another on-chain program can
+// depend on this crate for its types and builders without linking a second
+// entrypoint
+#[cfg(not(feature = "no-entrypoint"))]
+mod entrypoint;
+pub mod error;
+pub mod events;
+mod instruction;
+mod processor;
+pub mod schema;
+mod state;
+mod validation;
+
+use solana_program::declare_id; // Macro defining the program's own address as `ID`
+
+// The address the program is deployed at
+declare_id!("CQQP6NYLvbwwZd9hssQM5p45tumGQGGc5m7wGBiXHpgU");
+
+pub use crate::{
+    instruction::{
+        CounterInstruction, CounterOp, InitializeArgs, MAX_AGGREGATE_SHARDS, MAX_BATCH_OPS,
+        MAX_MEMO_LEN, MEMO_PROGRAM_ID, RETURN_DATA_LEN, decode_return_data, max_instruction_len,
+    },
+    processor::process_instruction,
+    state::{
+        BURN_SEED, CALLBACK_SEED, CONFIG_SEED, COOLDOWN_SEED, COOLDOWN_STAMP_SEED,
+        COUNTER_AUTHORITY_OFFSET, COUNTER_DISCRIMINATOR, COUNTER_DISCRIMINATOR_OFFSET, COUNTER_LEN,
+        COUNTER_SEED, COUNTER_VERSION, COUNTER_VERSION_OFFSET, CallbackAccount, Counter,
+        CounterFlag, CounterKind, DECAY_SEED, DecayUnit, ESCROW_DEPOSIT_SEED, ESCROW_SEED,
+        FEE_VAULT_SEED, FeeConfig, HISTORY_SEED, HOOKS_SEED, LockStatus, MAX_CALLBACK_ACCOUNTS,
+        MAX_MILESTONES, MAX_OPERATORS, NATIVE_TREASURY_SEED, OPERATORS_SEED, Operator,
+        OverflowMode, PERMIT_MESSAGE_LEN, PERMITS_SEED, REWARDS_SEED, SHARD_SEED, TOKEN_GATE_SEED,
+        create_config_address, create_counter_address, find_burn_address, find_callback_address,
+        find_config_address, find_cooldown_address, find_cooldown_stamp_address,
+        find_counter_address, find_decay_address, find_escrow_address, find_escrow_deposit_address,
+        find_fee_vault_address, find_history_address, find_hooks_address,
+        find_native_treasury_address, find_operators_address, find_permits_address,
+        find_rewards_address, find_shard_address, find_token_gate_address, permit_message,
+        required_lamports, time_weighted_average,
+    },
+};
@@ -0,0 +1,6714 @@
+use crate::{
+    cpi::{HookPhase, OnCounterChange, OnTargetReached},
+    error::CounterError,
+    events::{self, CounterView, Event, OpId, Operation},
+    instruction::{
+        CounterInstruction, CounterOp, INCREMENT_BY_ONE_TAG, InitializeArgs, MAX_BATCH_OPS,
+        MEMO_PROGRAM_ID,
+    },
+    state::{
+        AccountType, BURN_SEED, CALLBACK_SEED, CONFIG_SEED, COOLDOWN_SEED, COOLDOWN_STAMP_SEED,
+        COUNTER_DISCRIMINATOR_OFFSET, COUNTER_SEED, COUNTER_VERSION_OFFSET, CallbackAccount,
+        CallerGuard, Config, CooldownStamp, Counter, CounterBurn, CounterCallback, CounterCooldown,
+        CounterDecay, CounterEscrow, CounterFlag, CounterGate, CounterHistory, CounterHooks,
+        CounterMap, CounterMetadata, CounterOperators, CounterPermits, CounterRewards,
+        CounterShard, CounterV1, CounterV2, CounterV4, CounterV5, CounterV6, CounterV7, CounterV8,
+        CounterV9, CounterV10, CounterV11, CounterV12, CounterV13, CrankRewards, DECAY_SEED,
+        DecayUnit, ESCROW_DEPOSIT_SEED, ESCROW_SEED, EscrowDeposit, FEE_VAULT_SEED, FeeConfig,
+        FeeVault, Fees, GOVERNANCE_ACCOUNT_TYPES, HISTORY_SEED, HOOKS_SEED, HistoryEntry,
+        HistoryOp, LegacyCounter, LockStatus, MAX_MILESTONES, MAX_SIGNERS, MINT_DECIMALS_OFFSET,
+        MINT_SUPPLY_OFFSET, MapEntry, Multisig, OPERATORS_SEED, Operator, OverflowMode,
+        PERMIT_MESSAGE_LEN, PERMITS_SEED, REWARDS_SEED, SHARD_SEED, TOKEN_2022_PROGRAM_ID,
+        TOKEN_ACCOUNT_AMOUNT_OFFSET, TOKEN_ACCOUNT_MINT_OFFSET, TOKEN_ACCOUNT_OWNER_OFFSET,
+        TOKEN_GATE_SEED, TOKEN_PROGRAM_ID, VoteCounter, associated_token_address,
+        find_burn_address, find_callback_address, find_config_address, find_cooldown_address,
+        find_cooldown_stamp_address, find_counter_address, find_decay_address, find_escrow_address,
+        find_escrow_deposit_address, find_fee_vault_address, find_history_address,
+        find_hooks_address, find_native_treasury_address, find_operators_address,
+        find_permits_address, find_rewards_address, find_shard_address, find_token_gate_address,
+        permit_message,
+    },
+    validation::{
+        callback_group_len, listed_accounts, split_burn, split_cooldown, split_decay, split_fee,
+        split_fee_vault, split_hooks, split_permits, split_rewards, split_token_gate, takes_config,
+        validate_accounts, validate_memo, validate_op_id,
+    },
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use core::cell::RefMut;
+// Import Solana program utilities
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    clock::Clock,              // Clock sysvar providing the current slot and time
+    ed25519_program,           // Id of the precompile verifying permit signatures
+    entrypoint::ProgramResult, // Result type for program execution
+    instruction::{AccountMeta, Instruction, TRANSACTION_LEVEL_STACK_HEIGHT, get_stack_height}, // CPI depth
+    program::{invoke, invoke_signed, set_return_data}, // CPI helpers, and results for CPI callers
+    program_error::ProgramError,                       // Generic program error type
+    pubkey::{MAX_SEED_LEN, Pubkey},                    // Public key type and PDA seed limit
+    rent::Rent,         // Rent sysvar used to compute rent-exempt balances
+    system_instruction, // Instruction builders for the system program
+    system_program,     // System program id, the owner of closed accounts
+    sysvar::{
+        Sysvar, // Trait providing `Rent::get()`
+        instructions::{self, load_current_index_checked, load_instruction_at_checked},
+    },
+};
+
+/// Main instruction processing function
+/// This function is called whenever a transaction invokes our program
+///
+/// Arguments:
+/// - program_id: The public key of our deployed program
+/// - accounts: Array of accounts involved in the transaction
+/// - instruction_data: Raw bytes containing the instruction to execute
+///
+/// Returns: ProgramResult (Ok() on success, Err() on failure)
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8], // Raw instruction bytes (e.g., [0, 1, 0, 0, 0] for increment by 1)
+) -> ProgramResult {
+    // The Instructions sysvar is only there for the caller guard and
+    // `check_transaction`
+    let (accounts, instructions_sysvar) = match accounts.split_last() {
+        Some((last, rest)) if *last.key == instructions::ID => (rest, Some(last)),
+        _ => (accounts, None),
+    };
+
+    // Fast path: a lone IncrementByOne tag needs no Borsh decoding at all
+    if instruction_data == [INCREMENT_BY_ONE_TAG] {
+        let accounts = check_caller(
+            program_id,
+            &CounterInstruction::IncrementByOne,
+            accounts,
+            instructions_sysvar,
+        )?;
+        let (accounts, extras) = split_extras(accounts);
+        validate_accounts(&CounterInstruction::IncrementByOne, accounts)?;
+        check_transaction(
+            program_id,
+            &CounterInstruction::IncrementByOne,
+            accounts,
+            instructions_sysvar,
+        )?;
+        return run_change(
+            program_id,
+            accounts,
+            &extras,
+            instructions_sysvar,
+            CounterInstruction::IncrementByOne,
+            |_| {
+                profile("IncrementByOne", || {
+                    process_increment(program_id, accounts, 1, None)
+                })
+            },
+        );
+    }
+
+    // Parse the instruction data to determine what operation to perform
+    // The instruction data contains serialized CounterInstruction enum
+    let instruction = CounterInstruction::unpack(instruction_data)?;
+    let accounts = check_caller(program_id, &instruction, accounts, instructions_sysvar)?;
+
+    // Log a memo through the Memo program, then run the instruction it wraps
+    // as if it had been sent on its own
+    let (instruction, accounts) = match instruction {
+        CounterInstruction::WithMemo { memo, instruction } => {
+            let (accounts, memo_program) = validate_memo(&instruction, &memo, accounts)?;
+            invoke(
+                &Instruction {
+                    program_id: MEMO_PROGRAM_ID,
+                    accounts: Vec::new(),
+                    data: memo.into_bytes(),
+                },
+                core::slice::from_ref(memo_program),
+            )?;
+            (*instruction, accounts)
+        }
+        instruction => (instruction, accounts),
+    };
+    // Run the instruction an op id is wrapped around, echoing the id in its
+    // event
+    let (instruction, op_id) = match instruction {
+        CounterInstruction::WithOpId { op_id, instruction } => {
+            validate_op_id(&instruction)?;
+            (*instruction, Some(op_id))
+        }
+        instruction => (instruction, None),
+    };
+
+    // The extra accounts of a change are split off for `run_change`, so the
+    // handler never sees them
+    let changes_count = instruction.changes_count();
+    let (accounts, extras) = if changes_count {
+        split_extras(accounts)
+    } else {
+        (accounts, ExtraAccounts::default())
+    };
+
+    // Check the number, order and signer/writable flags of the accounts
+    // before any handler looks at them
+    validate_accounts(&instruction, accounts)?;
+    check_transaction(program_id, &instruction, accounts, instructions_sysvar)?;
+
+    let name = instruction.name();
+    if !changes_count {
+        return profile(name, || dispatch(program_id, accounts, instruction, op_id));
+    }
+    run_change(
+        program_id,
+        accounts,
+        &extras,
+        instructions_sysvar,
+        instruction,
+        |instruction| profile(name, || dispatch(program_id, accounts, instruction, op_id)),
+    )
+}
+
+/// The groups of accounts a change may pass after its own, each empty if
+/// not passed
+///
+/// In the order they are passed: decay, cooldown, permits, rewards, burn,
+/// token gate, token fee, lamport fee and hooks, all after any multisig
+/// signers.
+#[derive(Default)]
+struct ExtraAccounts<'a, 'b> {
+    decay: &'b [AccountInfo<'a>],
+    cooldown: &'b [AccountInfo<'a>],
+    permits: &'b [AccountInfo<'a>],
+    rewards: &'b [AccountInfo<'a>],
+    burn: &'b [AccountInfo<'a>],
+    gate: &'b [AccountInfo<'a>],
+    fee: &'b [AccountInfo<'a>],
+    vault: &'b [AccountInfo<'a>],
+    hooks: &'b [AccountInfo<'a>],
+}
+
+/// Split the groups of a change's accounts that its handler does not take
+/// off their end, last first
+fn split_extras<'a, 'b>(
+    accounts: &'b [AccountInfo<'a>],
+) -> (&'b [AccountInfo<'a>], ExtraAccounts<'a, 'b>) {
+    let (accounts, hooks) = split_hooks(accounts);
+    let (accounts, vault) = split_fee_vault(accounts);
+    let (accounts, fee) = split_fee(accounts);
+    let (accounts, gate) = split_token_gate(accounts);
+    let (accounts, burn) = split_burn(accounts);
+    let (accounts, rewards) = split_rewards(accounts);
+    let (accounts, permits) = split_permits(accounts);
+    let (accounts, cooldown) = split_cooldown(accounts);
+    let (accounts, decay) = split_decay(accounts);
+    let extras = ExtraAccounts {
+        decay,
+        cooldown,
+        permits,
+        rewards,
+        burn,
+        gate,
+        fee,
+        vault,
+        hooks,
+    };
+    (accounts, extras)
+}
+
+/// Run a change through `handler` with everything around it: the permit,
+/// cooldown, token gate and burn are checked and the fees charged before it,
+/// the counter decays and, starting over in a new epoch, is zeroed, the hooks run around
+/// it, the epoch cap is checked right after it and the milestones it crossed
+/// are rewarded after that
+fn run_change<'a>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    extras: &ExtraAccounts<'a, '_>,
+    instructions_sysvar: Option<&AccountInfo>,
+    instruction: CounterInstruction,
+    handler: impl FnOnce(CounterInstruction) -> ProgramResult,
+) -> ProgramResult {
+    check_permit(
+        program_id,
+        &instruction,
+        accounts,
+        extras.permits,
+        instructions_sysvar,
+    )?;
+    check_cooldown(program_id, &instruction, accounts, extras.cooldown)?;
+    check_token_gate(program_id, &instruction, accounts, extras.gate)?;
+    burn_tokens(program_id, &instruction, accounts, extras.burn)?;
+    if charge_fees(program_id, accounts, extras.fee, extras.vault)? {
+        // Validation put the counter first
+        load_counter(program_id, &accounts[0])?.set_flag(CounterFlag::FeePaid, true);
+    }
+    let actor = change_actor(&instruction, accounts);
+    // Validation put the counter first
+    apply_decay(program_id, &accounts[0], extras.decay)?;
+    reset_for_epoch(program_id, &accounts[0])?;
+    let old_value = load_counter(program_id, &accounts[0])?.value();
+    run_hooks(
+        program_id,
+        accounts,
+        extras.hooks,
+        instruction,
+        |instruction| {
+            handler(instruction)?;
+            track_epoch_change(program_id, accounts, &accounts[0], old_value)
+        },
+    )?;
+    mint_rewards(program_id, &accounts[0], actor, extras.rewards, old_value)
+}
+
+/// The signer a change is credited to: the authority if it signs for a
+/// change of the authority's, otherwise the first signer after the history
+/// and callback accounts, such as a multisig's or an NFT holder
+fn change_actor<'a, 'b>(
+    instruction: &CounterInstruction,
+    accounts: &'b [AccountInfo<'a>],
+) -> Option<&'b AccountInfo<'a>> {
+    let listed = listed_accounts(instruction);
+    // Changes of the authority's list it right after the counter
+    if listed > 2 && accounts[1].is_signer {
+        return Some(&accounts[1]);
+    }
+    let (_, _, signers) = split_trailing(&accounts[listed..]);
+    signers.first()
+}
+
+/// Run a change through `handler`, invoking the counter's pre hook before it
+/// and its post hook after it
+///
+/// `hooks` holds the accounts `split_hooks` set aside, which are ignored
+/// while the counter has no hooks.
+fn run_hooks<'a>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    hooks: &[AccountInfo<'a>],
+    instruction: CounterInstruction,
+    handler: impl FnOnce(CounterInstruction) -> ProgramResult,
+) -> ProgramResult {
+    // Validation put the counter first
+    let counter_account = &accounts[0];
+    let counter = *load_counter(program_id, counter_account)?;
+    if !counter.has_flag(CounterFlag::Hooks) {
+        return handler(instruction);
+    }
+    let Some((hooks_account, programs)) = hooks.split_first() else {
+        msg!(
+            "Counter {} has hooks; pass its hooks account and hook programs last",
+            counter_account.key
+        );
+        return Err(CounterError::MissingHooks.into());
+    };
+    let state = load_hooks(program_id, hooks_account, counter_account.key)?;
+    if !programs
+        .iter()
+        .map(|program| *program.key)
+        .eq(state.programs())
+    {
+        msg!(
+            "The hook programs passed do not match the hooks of counter {}",
+            counter_account.key
+        );
+        return Err(CounterError::InvalidHooks.into());
+    }
+
+    let mut change = OnCounterChange {
+        counter: *counter_account.key,
+        phase: HookPhase::Pre,
+        instruction: instruction.clone(),
+        old_value: counter.value(),
+        new_value: None,
+    };
+    if let Some(program) = state.pre.and(programs.first()) {
+        invoke_hook(program, hooks_account, counter_account, state.bump, &change)?;
+    }
+    handler(instruction)?;
+    if let Some(program) = state.post.and(programs.last()) {
+        let new_value = load_counter(program_id, counter_account)?.value();
+        change.phase = HookPhase::Post;
+        change.new_value = Some(new_value);
+        invoke_hook(program, hooks_account, counter_account, state.bump, &change)?;
+        // The hook may have set return data of its own
+        set_return_data(&new_value.to_le_bytes());
+    }
+    Ok(())
+}
+
+/// Invoke the hook `program` with `change`, signed by the counter's hooks account
+fn invoke_hook<'a>(
+    program: &AccountInfo<'a>,
+    hooks_account: &AccountInfo<'a>,
+    counter_account: &AccountInfo<'a>,
+    bump: u8,
+    change: &OnCounterChange,
+) -> ProgramResult {
+    let mut data = OnCounterChange::DISCRIMINATOR.to_vec();
+    change.serialize(&mut data)?;
+    invoke_signed(
+        &Instruction {
+            program_id: *program.key,
+            accounts: vec![
+                AccountMeta::new_readonly(*hooks_account.key, true),
+                AccountMeta::new_readonly(*counter_account.key, false),
+            ],
+            data,
+        },
+        &[
+            hooks_account.clone(),
+            counter_account.clone(),
+            program.clone(),
+        ],
+        &[&[HOOKS_SEED, counter_account.key.as_ref(), &[bump]]],
+    )
+}
+
+/// Run `handler` and, with the `profiling` feature, log the compute units it
+/// consumed, for budgeting transactions that compose several instructions
+///
+/// Only the handler is measured, not the decoding and account checks before
+/// it, and the figure includes the one syscall reading the meter again.
+#[cfg_attr(not(feature = "profiling"), allow(unused_variables))]
+fn profile(name: &str, handler: impl FnOnce() -> ProgramResult) -> ProgramResult {
+    #[cfg(feature = "profiling")]
+    use solana_program::compute_units::sol_remaining_compute_units;
+    #[cfg(feature = "profiling")]
+    let remaining = sol_remaining_compute_units();
+    let result = handler();
+    // Logged even without `verbose-logs`, as asking for it is the point
+    #[cfg(feature = "profiling")]
+    solana_program::msg!(
+        "{} consumed {} compute units",
+        name,
+        remaining.saturating_sub(sol_remaining_compute_units())
+    );
+    result
+}
+
+/// Hand a decoded and validated instruction to its handler
+fn dispatch(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction: CounterInstruction,
+    op_id: Option<OpId>,
+) -> ProgramResult {
+    match instruction {
+        CounterInstruction::Increment(amount) => {
+            process_increment(program_id, accounts, amount, op_id)
+        }
+        CounterInstruction::Decrement(amount) => {
+            process_decrement(program_id, accounts, amount, op_id)
+        }
+        CounterInstruction::Initialize(args) => process_initialize(program_id, accounts, args),
+        CounterInstruction::Reset => process_reset(program_id, accounts, op_id),
+        CounterInstruction::Set(value) => process_set(program_id, accounts, value, op_id),
+        CounterInstruction::TransferAuthority { new_authority } => {
+            process_transfer_authority(program_id, accounts, new_authority)
+        }
+        CounterInstruction::AcceptAuthority => process_accept_authority(program_id, accounts),
+        CounterInstruction::Close => process_close(program_id, accounts),
+        CounterInstruction::MigrateLegacy => process_migrate_legacy(program_id, accounts),
+        CounterInstruction::Migrate | CounterInstruction::MigrateV1ToV2 => {
+            process_migrate(program_id, accounts)
+        }
+        CounterInstruction::Resize { len } => process_resize(program_id, accounts, len),
+        CounterInstruction::SetMetadata { name, description } => {
+            process_set_metadata(program_id, accounts, name, description)
+        }
+        CounterInstruction::InitializeShard { index } => {
+            process_initialize_shard(program_id, accounts, index)
+        }
+        CounterInstruction::IncrementShard(amount) => {
+            process_increment_shard(program_id, accounts, amount)
+        }
+        CounterInstruction::Aggregate => process_aggregate(program_id, accounts),
+        CounterInstruction::InitializeHistory { capacity } => {
+            process_initialize_history(program_id, accounts, capacity)
+        }
+        CounterInstruction::SetCallerGuard {
+            enabled,
+            allowed_programs,
+            forbidden_programs,
+        } => process_set_caller_guard(
+            program_id,
+            accounts,
+            enabled,
+            allowed_programs,
+            forbidden_programs,
+        ),
+        CounterInstruction::SetExactlyOnce { enabled } => {
+            process_set_exactly_once(program_id, accounts, enabled)
+        }
+        CounterInstruction::SetFreezeAuthority { freeze_authority } => {
+            process_set_freeze_authority(program_id, accounts, freeze_authority)
+        }
+        CounterInstruction::EmergencyHalt => process_emergency(program_id, accounts, true),
+        CounterInstruction::EmergencyResume => process_emergency(program_id, accounts, false),
+        CounterInstruction::SetThresholds { thresholds } => {
+            process_set_thresholds(program_id, accounts, thresholds)
+        }
+        CounterInstruction::View => process_view(program_id, accounts),
+        CounterInstruction::SetCallback {
+            target,
+            program,
+            accounts: callback_accounts,
+        } => process_set_callback(program_id, accounts, target, program, callback_accounts),
+        CounterInstruction::ClearCallback => process_clear_callback(program_id, accounts),
+        CounterInstruction::AddOperator {
+            operator,
+            allowance,
+            expiry_slot,
+        } => process_add_operator(
+            program_id,
+            accounts,
+            Operator {
+                key: operator,
+                allowance,
+                expiry_slot,
+            },
+        ),
+        CounterInstruction::RemoveOperator { operator } => {
+            process_remove_operator(program_id, accounts, &operator)
+        }
+        CounterInstruction::SetHooks { pre, post } => {
+            process_set_hooks(program_id, accounts, pre, post)
+        }
+        CounterInstruction::ClearHooks => process_clear_hooks(program_id, accounts),
+        CounterInstruction::SetFee { fee } => process_set_fee(program_id, accounts, fee),
+        CounterInstruction::SetLamportFee { lamports } => {
+            process_set_lamport_fee(program_id, accounts, lamports)
+        }
+        CounterInstruction::WithdrawFees { amount } => {
+            process_withdraw_fees(program_id, accounts, amount)
+        }
+        CounterInstruction::SetTokenGate { mint, min_balance } => {
+            process_set_token_gate(program_id, accounts, mint, min_balance)
+        }
+        CounterInstruction::ClearTokenGate => process_clear_token_gate(program_id, accounts),
+        CounterInstruction::SetNftAuthority { mint } => {
+            process_set_nft_authority(program_id, accounts, mint)
+        }
+        CounterInstruction::SetGovernanceAuthority {
+            governance,
+            treasury,
+        } => process_set_governance_authority(program_id, accounts, governance, treasury),
+        CounterInstruction::SetBurnMint { mint } => {
+            process_set_burn_mint(program_id, accounts, mint)
+        }
+        CounterInstruction::SetRewards {
+            mint,
+            amount,
+            milestones,
+        } => process_set_rewards(program_id, accounts, mint, amount, milestones),
+        CounterInstruction::ClearRewards => process_clear_rewards(program_id, accounts),
+        CounterInstruction::InitializeEscrow {
+            beneficiary,
+            target,
+            deadline_slot,
+        } => process_initialize_escrow(program_id, accounts, beneficiary, target, deadline_slot),
+        CounterInstruction::DepositEscrow { lamports } => {
+            process_deposit_escrow(program_id, accounts, lamports)
+        }
+        CounterInstruction::Settle => process_settle(program_id, accounts),
+        CounterInstruction::RequirePermits => process_require_permits(program_id, accounts),
+        CounterInstruction::ClearPermits => process_clear_permits(program_id, accounts),
+        CounterInstruction::SetCooldown { slots, daily_cap } => {
+            process_set_cooldown(program_id, accounts, slots, daily_cap)
+        }
+        CounterInstruction::ClearCooldown => process_clear_cooldown(program_id, accounts),
+        CounterInstruction::SetEpochCap { cap } => process_set_epoch_cap(program_id, accounts, cap),
+        CounterInstruction::SetEpochReset { enabled } => {
+            process_set_epoch_reset(program_id, accounts, enabled)
+        }
+        CounterInstruction::SetDecay { rate, unit } => {
+            process_set_decay(program_id, accounts, rate, unit)
+        }
+        CounterInstruction::ClearDecay => process_clear_decay(program_id, accounts),
+        CounterInstruction::SetCrankReward {
+            lamports,
+            epoch_budget,
+        } => process_set_crank_reward(program_id, accounts, lamports, epoch_budget),
+        CounterInstruction::Crank => process_crank(program_id, accounts),
+        CounterInstruction::Lock { until_slot } => process_lock(program_id, accounts, until_slot),
+        CounterInstruction::SetExpiry { expires_at } => {
+            process_set_expiry(program_id, accounts, expires_at)
+        }
+        CounterInstruction::SetEma { alpha_bps } => {
+            process_set_ema(program_id, accounts, alpha_bps)
+        }
+        // Unwrapped above, and `validate_memo` and `validate_op_id` reject
+        // nesting them any other way
+        CounterInstruction::WithMemo { .. } | CounterInstruction::WithOpId { .. } => {
+            Err(ProgramError::InvalidInstructionData)
+        }
+        CounterInstruction::InitializePda { seed, args } => {
+            process_initialize_pda(program_id, accounts, seed, args)
+        }
+        CounterInstruction::Multiply(factor) => {
+            process_multiply(program_id, accounts, factor, op_id)
+        }
+        CounterInstruction::Divide(divisor) => process_divide(program_id, accounts, divisor, op_id),
+        CounterInstruction::CompareAndSwap { expected, new } => {
+            process_compare_and_swap(program_id, accounts, expected, new, op_id)
+        }
+        CounterInstruction::Batch(ops) => process_batch(program_id, accounts, ops, op_id),
+        CounterInstruction::Pause => process_set_paused(program_id, accounts, true),
+        CounterInstruction::Unpause => process_set_paused(program_id, accounts, false),
+        CounterInstruction::InitializeConfig { max_amount } => {
+            process_initialize_config(program_id, accounts, max_amount)
+        }
+        CounterInstruction::UpdateConfig {
+            admin,
+            paused,
+            max_amount,
+        } => process_update_config(program_id, accounts, admin, paused, max_amount),
+        CounterInstruction::InitializeMultisig { m, signers } => {
+            process_initialize_multisig(program_id, accounts, m, signers)
+        }
+        CounterInstruction::Approve {
+            delegate,
+            allowance,
+        } => process_approve(program_id, accounts, delegate, allowance),
+        CounterInstruction::Revoke => process_approve(program_id, accounts, Pubkey::default(), 0),
+        CounterInstruction::Merge => process_merge(program_id, accounts),
+        CounterInstruction::Clone { seed } => process_clone(program_id, accounts, seed),
+        CounterInstruction::InitializeVotes => process_initialize_votes(program_id, accounts),
+        CounterInstruction::VoteUp(amount) => process_vote(program_id, accounts, amount, true),
+        CounterInstruction::VoteDown(amount) => process_vote(program_id, accounts, amount, false),
+        CounterInstruction::InitializeMap => process_initialize_map(program_id, accounts),
+        CounterInstruction::AddMapEntry { name } => {
+            process_add_map_entry(program_id, accounts, name)
+        }
+        CounterInstruction::RemoveMapEntry { name } => {
+            process_remove_map_entry(program_id, accounts, name)
+        }
+        CounterInstruction::IncrementMapEntry { name, amount } => {
+            process_increment_map_entry(program_id, accounts, name, amount)
+        }
+        CounterInstruction::DecrementMapEntry { name, amount } => {
+            process_decrement_map_entry(program_id, accounts, name, amount)
+        }
+        // Normally taken by the fast path above; kept for exhaustiveness
+        CounterInstruction::IncrementByOne => process_increment(program_id, accounts, 1, op_id),
+    }
+}
+
+/// Add `amount` to the counter stored in the first account
+fn process_increment(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u32,
+    op_id: Option<OpId>,
+) -> ProgramResult {
+    // Get the first account from the accounts array - this is our counter data account
+    // The ? operator propagates any error if the account doesn't exist
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let (history, callback, signers) = split_trailing(accounts_iter.as_slice());
+    let actor = signers
+        .first()
+        .map_or(Pubkey::default(), |signer| *signer.key);
+
+    // Refuse the change if the program is paused or the amount is over its limit
+    let config = load_config(program_id, config_account)?;
+    config.check_active()?;
+    config.check_amount(amount)?;
+
+    // Verify the account really is one of our counters, then view its data
+    // as a Counter in place; changes through it land directly in the account
+    let mut counter = load_counter(program_id, account)?;
+    let old_value = counter.value();
+
+    // Add the specified amount to the current counter value, handling a
+    // result that does not fit in a u32 as the counter's overflow mode says
+    counter.add(amount)?;
+    record_update(&mut counter, old_value)?;
+    record_history(
+        program_id,
+        account,
+        &counter,
+        history,
+        &[(HistoryOp::Increment, amount.into())],
+        &actor,
+    )?;
+
+    // Report the change as a structured event (visible in transaction logs)
+    emit_update(
+        account,
+        &counter,
+        Operation::Increment,
+        old_value,
+        &actor,
+        op_id,
+    );
+
+    // Released first, as the callback may be passed the counter
+    drop(counter);
+    fire_callback(program_id, account, old_value, callback)
+}
+
+/// Subtract `amount` from the counter, provided the stored authority, its
+/// delegate or one of its operators signed the transaction
+///
+/// Increments stay permissionless so anyone can bump a shared counter, but
+/// only the authority, or a delegate or operator within its allowance, may
+/// take value away from it.
+fn process_decrement(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u32,
+    op_id: Option<OpId>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let (history, callback, signers) = split_trailing(accounts_iter.as_slice());
+
+    let config = load_config(program_id, config_account)?;
+    config.check_active()?;
+    config.check_amount(amount)?;
+
+    let mut counter = load_counter(program_id, account)?;
+    let actor = if authority.owner == program_id
+        && AccountType::of(&authority.data.borrow()) == Some(AccountType::CounterOperators)
+    {
+        spend_operator_allowance(program_id, account.key, authority, signers, amount)?
+    } else if counter.authority != *authority.key
+        && counter.delegate != Pubkey::default()
+        && counter.delegate == *authority.key
+    {
+        // The authority itself never draws on the allowance
+        assert_approved(program_id, authority, signers)?;
+        counter.allowance = counter
+            .allowance
+            .checked_sub(amount.into())
+            .ok_or_else(|| {
+                msg!(
+                    "Delegate allowance is {}, cannot decrement by {}",
+                    counter.allowance,
+                    amount
+                );
+                CounterError::AllowanceExceeded
+            })?;
+        *authority.key
+    } else {
+        assert_authority(program_id, &counter, authority, signers)?;
+        *authority.key
+    };
+    let old_value = counter.value();
+
+    // Subtract the specified amount from the current counter value, handling
+    // a result below zero as the counter's overflow mode says
+    counter.sub(amount)?;
+    record_update(&mut counter, old_value)?;
+    record_history(
+        program_id,
+        account,
+        &counter,
+        history,
+        &[(HistoryOp::Decrement, amount.into())],
+        &actor,
+    )?;
+
+    emit_update(
+        account,
+        &counter,
+        Operation::Decrement,
+        old_value,
+        &actor,
+        op_id,
+    );
+
+    // Released first, as the callback may be passed the counter
+    drop(counter);
+    fire_callback(program_id, account, old_value, callback)
+}
+
+/// Draw `amount` from the allowance of whichever of `signers` is an operator
+/// in `operators_account`, the operator list of `counter`, and return its key
+fn spend_operator_allowance(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    operators_account: &AccountInfo,
+    signers: &[AccountInfo],
+    amount: u32,
+) -> Result<Pubkey, ProgramError> {
+    if !operators_account.is_writable {
+        msg!(
+            "Operator list {} must be writable to draw on an allowance",
+            operators_account.key
+        );
+        return Err(CounterError::AccountNotWritable.into());
+    }
+    let mut list = load_operators(program_id, operators_account, counter)?;
+    let Some(operator) = list.operators.iter_mut().find(|operator| {
+        signers
+            .iter()
+            .any(|signer| signer.is_signer && *signer.key == operator.key)
+    }) else {
+        msg!("No signer is an operator of counter {}", counter);
+        return Err(CounterError::UnknownOperator.into());
+    };
+    if !operator.is_active(Clock::get()?.slot) {
+        msg!(
+            "Operator {} expired at slot {}",
+            operator.key,
+            operator.expiry_slot
+        );
+        return Err(CounterError::OperatorExpired.into());
+    }
+    operator.allowance = operator
+        .allowance
+        .checked_sub(amount.into())
+        .ok_or_else(|| {
+            msg!(
+                "Operator allowance is {}, cannot decrement by {}",
+                operator.allowance,
+                amount
+            );
+            CounterError::AllowanceExceeded
+        })?;
+    let key = operator.key;
+    list.serialize(&mut &mut operators_account.data.borrow_mut()[..])?;
+    Ok(key)
+}
+
+/// Multiply the counter by `factor`, provided the stored authority signed the transaction
+///
+/// Multiplying by zero clears the counter, so unlike increments this is not
+/// open to everyone.
+fn process_multiply(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    factor: u32,
+    op_id: Option<OpId>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let (history, callback, signers) = split_trailing(accounts_iter.as_slice());
+
+    let config = load_config(program_id, config_account)?;
+    config.check_active()?;
+
+    let mut counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, signers)?;
+    let old_value = counter.value();
+
+    counter.mul(factor)?;
+    record_update(&mut counter, old_value)?;
+    record_history(
+        program_id,
+        account,
+        &counter,
+        history,
+        &[(HistoryOp::Multiply, factor.into())],
+        authority.key,
+    )?;
+
+    emit_update(
+        account,
+        &counter,
+        Operation::Multiply,
+        old_value,
+        authority.key,
+        op_id,
+    );
+
+    // Released first, as the callback may be passed the counter
+    drop(counter);
+    fire_callback(program_id, account, old_value, callback)
+}
+
+/// Divide the counter by `divisor`, provided the stored authority signed the transaction
+fn process_divide(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    divisor: u32,
+    op_id: Option<OpId>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let (history, callback, signers) = split_trailing(accounts_iter.as_slice());
+
+    let config = load_config(program_id, config_account)?;
+    config.check_active()?;
+
+    let mut counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, signers)?;
+    let old_value = counter.value();
+
+    counter.div(divisor)?;
+    record_update(&mut counter, old_value)?;
+    record_history(
+        program_id,
+        account,
+        &counter,
+        history,
+        &[(HistoryOp::Divide, divisor.into())],
+        authority.key,
+    )?;
+
+    emit_update(
+        account,
+        &counter,
+        Operation::Divide,
+        old_value,
+        authority.key,
+        op_id,
+    );
+
+    // Released first, as the callback may be passed the counter
+    drop(counter);
+    fire_callback(program_id, account, old_value, callback)
+}
+
+/// Write `new` to the counter if it currently equals `expected`
+fn process_compare_and_swap(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    expected: u64,
+    new: u64,
+    op_id: Option<OpId>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let (history, callback, signers) = split_trailing(accounts_iter.as_slice());
+
+    let config = load_config(program_id, config_account)?;
+    config.check_active()?;
+
+    let mut counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, signers)?;
+    let old_value = counter.value();
+
+    if counter.value() != i128::from(expected) {
+        msg!("Counter holds {}, expected {}", counter.value(), expected);
+        return Err(CounterError::ValueMismatch.into());
+    }
+    counter.set(new)?;
+    record_update(&mut counter, old_value)?;
+    record_history(
+        program_id,
+        account,
+        &counter,
+        history,
+        &[(HistoryOp::CompareAndSwap, new)],
+        authority.key,
+    )?;
+
+    emit_update(
+        account,
+        &counter,
+        Operation::CompareAndSwap,
+        old_value,
+        authority.key,
+        op_id,
+    );
+
+    // Released first, as the callback may be passed the counter
+    drop(counter);
+    fire_callback(program_id, account, old_value, callback)
+}
+
+/// Apply every operation in `ops` to the counter and write the result once
+///
+/// The authority only has to sign when the batch contains something other
+/// than increments; `validate_accounts` has already checked that it was
+/// passed in that case.
+fn process_batch(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    ops: Vec<CounterOp>,
+    op_id: Option<OpId>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+
+    if ops.is_empty() {
+        msg!("Batch contains no operations");
+        return Err(CounterError::EmptyBatch.into());
+    }
+    if ops.len() > MAX_BATCH_OPS {
+        msg!(
+            "Batch contains {} operations, at most {} are allowed",
+            ops.len(),
+            MAX_BATCH_OPS
+        );
+        return Err(CounterError::BatchTooLarge.into());
+    }
+
+    let mut counter = load_counter(program_id, account)?;
+    let authority = if ops.iter().any(CounterOp::requires_authority) {
+        Some(next_account_info(accounts_iter)?)
+    } else {
+        None
+    };
+    let config = load_config(program_id, next_account_info(accounts_iter)?)?;
+    config.check_active()?;
+    let (history, callback, signers) = split_trailing(accounts_iter.as_slice());
+    let actor = match authority {
+        Some(authority) => {
+            assert_authority(program_id, &counter, authority, signers)?;
+            *authority.key
+        }
+        None => signers
+            .first()
+            .map_or(Pubkey::default(), |signer| *signer.key),
+    };
+
+    // Work on a copy so a failing operation leaves the stored value untouched
+    let old_value = counter.value();
+    let mut updated = *counter;
+    for (index, op) in ops.iter().enumerate() {
+        match *op {
+            CounterOp::Increment(amount) => config
+                .check_amount(amount)
+                .and_then(|()| updated.add(amount)),
+            CounterOp::Decrement(amount) => config
+                .check_amount(amount)
+                .and_then(|()| updated.sub(amount)),
+            CounterOp::Set(value) => updated.set(value),
+        }
+        .inspect_err(|_| msg!("Batch operation {} failed", index))?;
+    }
+    *counter = updated;
+    record_update(&mut counter, old_value)?;
+    let entries: Vec<_> = ops
+        .iter()
+        .map(|op| match *op {
+            CounterOp::Increment(amount) => (HistoryOp::Increment, amount.into()),
+            CounterOp::Decrement(amount) => (HistoryOp::Decrement, amount.into()),
+            CounterOp::Set(value) => (HistoryOp::Set, value),
+        })
+        .collect();
+    record_history(program_id, account, &counter, history, &entries, &actor)?;
+
+    emit_update(
+        account,
+        &counter,
+        Operation::Batch,
+        old_value,
+        &actor,
+        op_id,
+    );
+
+    // Released first, as the callback may be passed the counter
+    drop(counter);
+    fire_callback(program_id, account, old_value, callback)
+}
+
+/// Add the source counter's value to the destination and zero the source,
+/// provided both authorities approved
+///
+/// A destination overflow or a source that cannot go to zero fails the whole
+/// instruction, so the runtime discards the changes to both accounts. Both
+/// changes are otherwise like any other: each counter starts over in a new
+/// epoch first, and each change counts towards the epoch cap, goes into the
+/// counter's history and may fire its callback.
+fn process_merge(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    // The destination's reward accounts come last, as after a change
+    let (accounts, rewards) = split_rewards(accounts);
+    let accounts_iter = &mut accounts.iter();
+    let destination = next_account_info(accounts_iter)?;
+    let destination_authority = next_account_info(accounts_iter)?;
+    let source = next_account_info(accounts_iter)?;
+    let source_authority = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    let config = load_config(program_id, config_account)?;
+    config.check_active()?;
+
+    if destination.key == source.key {
+        msg!("Cannot merge a counter into itself");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let (into, from) = (
+        *load_counter(program_id, destination)?,
+        *load_counter(program_id, source)?,
+    );
+    let (into_history, into_callback, rest) = split_merged(&into, accounts_iter.as_slice());
+    let (from_history, from_callback, signers) = split_merged(&from, rest);
+    assert_authority(program_id, &into, destination_authority, signers)?;
+    assert_authority(program_id, &from, source_authority, signers)?;
+    let actor = if destination_authority.is_signer {
+        Some(destination_authority)
+    } else {
+        signers.first()
+    };
+    if into.has_flag(CounterFlag::Burn) || from.has_flag(CounterFlag::Burn) {
+        msg!("A burn-backed counter only changes by burning tokens");
+        return Err(CounterError::BurnBacked.into());
+    }
+    reset_for_epoch(program_id, destination)?;
+    reset_for_epoch(program_id, source)?;
+
+    let mut into = load_counter(program_id, destination)?;
+    let mut from = load_counter(program_id, source)?;
+    let amount = from.value();
+    // Both histories record the source's value as it was stored
+    let moved = [(HistoryOp::Merge, from.count)];
+    let old_value = into.value();
+    into.merge(&mut from)?;
+    record_update(&mut into, old_value)?;
+    record_update(&mut from, amount)?;
+    record_history(
+        program_id,
+        destination,
+        &into,
+        into_history,
+        &moved,
+        destination_authority.key,
+    )?;
+    record_history(
+        program_id,
+        source,
+        &from,
+        from_history,
+        &moved,
+        source_authority.key,
+    )?;
+    emit_update(
+        source,
+        &from,
+        Operation::Merge,
+        amount,
+        source_authority.key,
+        None,
+    );
+    emit_update(
+        destination,
+        &into,
+        Operation::Merge,
+        old_value,
+        destination_authority.key,
+        None,
+    );
+
+    msg!(
+        "Merged {} from {} into {}, which now holds {}",
+        amount,
+        source.key,
+        destination.key,
+        into.value()
+    );
+
+    // Released first, as the callbacks may be passed the counters
+    let new_value = into.value();
+    drop((into, from));
+    track_epoch_change(program_id, accounts, destination, old_value)?;
+    track_epoch_change(program_id, accounts, source, amount)?;
+    fire_callback(program_id, source, amount, from_callback)?;
+    fire_callback(program_id, destination, old_value, into_callback)?;
+    mint_rewards(program_id, destination, actor, rewards, old_value)?;
+    // The source's callback may have set return data of its own
+    set_return_data(&new_value.to_le_bytes());
+    Ok(())
+}
+
+/// Split the accounts after a merge's fixed list into `counter`'s history
+/// account and callback accounts, and the accounts that follow them
+///
+/// Unlike `split_trailing`, the history account is only taken if the
+/// counter has one and the callback accounts only while its callback is
+/// armed, so the destination's can be followed by the source's.
+fn split_merged<'a, 'b>(
+    counter: &Counter,
+    accounts: &'b [AccountInfo<'a>],
+) -> (
+    Option<&'b AccountInfo<'a>>,
+    &'b [AccountInfo<'a>],
+    &'b [AccountInfo<'a>],
+) {
+    let (history, rest) = match accounts.split_first() {
+        Some((history, rest)) if counter.has_flag(CounterFlag::History) && !history.is_signer => {
+            (Some(history), rest)
+        }
+        _ => (None, accounts),
+    };
+    let callback_len = match rest.first() {
+        Some(callback) if counter.has_flag(CounterFlag::Callback) && !callback.is_signer => {
+            callback_group_len(callback).unwrap_or(0).min(rest.len())
+        }
+        _ => 0,
+    };
+    let (callback, rest) = rest.split_at(callback_len);
+    (history, callback, rest)
+}
+
+/// Split the accounts after an instruction's fixed list into the counter's
+/// history account and its callback's accounts, each if passed, and the
+/// signers that follow them
+///
+/// `validate_accounts` has already checked that only a non-signer can be the
+/// history account; a callback account says how many accounts follow it.
+fn split_trailing<'a, 'b>(
+    accounts: &'b [AccountInfo<'a>],
+) -> (
+    Option<&'b AccountInfo<'a>>,
+    &'b [AccountInfo<'a>],
+    &'b [AccountInfo<'a>],
+) {
+    let (history, rest) = match accounts.split_first() {
+        Some((history, rest)) if !history.is_signer && callback_group_len(history).is_none() => {
+            (Some(history), rest)
+        }
+        _ => (None, accounts),
+    };
+    let callback_len = match rest.first() {
+        Some(callback) if !callback.is_signer => {
+            callback_group_len(callback).unwrap_or(0).min(rest.len())
+        }
+        _ => 0,
+    };
+    let (callback, signers) = rest.split_at(callback_len);
+    (history, callback, signers)
+}
+
+/// Invoke the counter's callback if it is armed and the change from
+/// `old_value` reached its target, disarming it first
+///
+/// `callback` holds the accounts `split_trailing` set aside for it, which
+/// are ignored while no callback is armed, so clients may keep passing them
+/// after it fired. The counter must not be borrowed.
+fn fire_callback<'a>(
+    program_id: &Pubkey,
+    counter_account: &AccountInfo<'a>,
+    old_value: i128,
+    callback: &[AccountInfo<'a>],
+) -> ProgramResult {
+    let counter = *load_counter(program_id, counter_account)?;
+    if !counter.has_flag(CounterFlag::Callback) {
+        return Ok(());
+    }
+    let Some((callback_account, rest)) = callback.split_first() else {
+        msg!(
+            "Counter {} has a callback armed; pass its callback accounts",
+            counter_account.key
+        );
+        return Err(CounterError::MissingCallback.into());
+    };
+    let state = load_callback(program_id, callback_account, counter_account.key)?;
+    let new_value = counter.value();
+    if !state.is_reached(old_value, new_value) {
+        return Ok(());
+    }
+    let Some((program, registered)) = rest.split_first() else {
+        return Err(CounterError::MissingCallback.into());
+    };
+    let matches = *program.key == state.program
+        && registered.len() == state.accounts.len()
+        && registered
+            .iter()
+            .zip(&state.accounts)
+            .all(|(account, expected)| *account.key == expected.address);
+    if !matches {
+        msg!(
+            "The accounts passed do not match the callback of counter {}",
+            counter_account.key
+        );
+        return Err(CounterError::InvalidCallback.into());
+    }
+
+    load_counter(program_id, counter_account)?.set_flag(CounterFlag::Callback, false);
+    events::emit(&Event::TargetReached {
+        counter: *counter_account.key,
+        target: state.target,
+        program: state.program,
+        old_value,
+        new_value,
+        slot: counter.last_updated_slot,
+    });
+
+    let mut data = OnTargetReached::DISCRIMINATOR.to_vec();
+    OnTargetReached {
+        counter: *counter_account.key,
+        target: state.target,
+        old_value,
+        new_value,
+        slot: counter.last_updated_slot,
+    }
+    .serialize(&mut data)?;
+    let mut metas = vec![
+        AccountMeta::new_readonly(*callback_account.key, true),
+        AccountMeta::new_readonly(*counter_account.key, false),
+    ];
+    metas.extend(state.accounts.iter().map(|account| AccountMeta {
+        pubkey: account.address,
+        is_signer: false,
+        is_writable: account.writable,
+    }));
+    let mut infos = vec![callback_account.clone(), counter_account.clone()];
+    infos.extend(registered.iter().cloned());
+    infos.push(program.clone());
+    invoke_signed(
+        &Instruction {
+            program_id: state.program,
+            accounts: metas,
+            data,
+        },
+        &infos,
+        &[&[CALLBACK_SEED, counter_account.key.as_ref(), &[state.bump]]],
+    )?;
+
+    // The callback may have set return data of its own
+    set_return_data(&new_value.to_le_bytes());
+    msg!(
+        "Counter {} reached {}, invoked callback {}",
+        counter_account.key,
+        state.target,
+        state.program
+    );
+    Ok(())
+}
+
+/// Append `ops`, each a kind of change and its argument, to the counter's
+/// history with `actor` and the slot of the update
+///
+/// Counters without a history skip this, unless a history account was passed
+/// anyway; once a counter has one, leaving it out fails the instruction.
+fn record_history(
+    program_id: &Pubkey,
+    counter_account: &AccountInfo,
+    counter: &Counter,
+    history: Option<&AccountInfo>,
+    ops: &[(HistoryOp, u64)],
+    actor: &Pubkey,
+) -> ProgramResult {
+    let Some(history_account) = history else {
+        if counter.has_flag(CounterFlag::History) {
+            msg!(
+                "Counter {} keeps a history; pass its history account",
+                counter_account.key
+            );
+            return Err(CounterError::MissingHistory.into());
+        }
+        return Ok(());
+    };
+
+    let mut history = load_history(program_id, history_account, counter_account.key)?;
+    for &(op, amount) in ops {
+        history.push(HistoryEntry {
+            op,
+            amount,
+            actor: *actor,
+            slot: counter.last_updated_slot,
+        });
+    }
+    history.serialize(&mut *history_account.data.borrow_mut())?;
+    Ok(())
+}
+
+/// Emit an [`Event::CounterUpdated`] for a change `record_update` has
+/// stamped, followed by an [`Event::ThresholdCrossed`] for each threshold it
+/// crossed, and return the new value to the caller
+fn emit_update(
+    account: &AccountInfo,
+    counter: &Counter,
+    operation: Operation,
+    old_value: i128,
+    actor: &Pubkey,
+    op_id: Option<OpId>,
+) {
+    events::emit(&Event::CounterUpdated {
+        counter: *account.key,
+        operation,
+        old_value,
+        new_value: counter.value(),
+        actor: *actor,
+        slot: counter.last_updated_slot,
+        op_id,
+    });
+    for (threshold, direction) in counter.crossings(old_value) {
+        events::emit(&Event::ThresholdCrossed {
+            counter: *account.key,
+            threshold,
+            direction,
+            old_value,
+            new_value: counter.value(),
+            slot: counter.last_updated_slot,
+        });
+    }
+    set_return_data(&counter.value().to_le_bytes());
+}
+
+/// Stamp `counter` with the current slot and time, for instructions that
+/// changed its value from `old_value`, counting that value in its cumulative
+/// value up to now and moving its moving average towards the new one
+///
+/// Fails once the counter has expired or while it is locked, which every
+/// change goes through here to check.
+fn record_update(counter: &mut Counter, old_value: i128) -> ProgramResult {
+    let clock = Clock::get()?;
+    if counter.is_expired(clock.unix_timestamp) {
+        msg!("Counter expired at {}", counter.expires_at);
+        return Err(CounterError::CounterExpired.into());
+    }
+    if counter.lock_status(clock.slot) == LockStatus::Locked {
+        msg!("Counter is locked until slot {}", counter.locked_until);
+        return Err(CounterError::CounterLocked.into());
+    }
+    counter.accumulate(old_value, clock.unix_timestamp);
+    counter.update_ema();
+    counter.last_updated_slot = clock.slot;
+    counter.last_updated_ts = clock.unix_timestamp;
+    Ok(())
+}
+
+/// Create a new counter holding a copy of the source counter, provided its authority approved
+///
+/// The copy keeps the source's value, authority and settings. A pending
+/// authority transfer or delegate approval does not carry over, so they stay
+/// scoped to the counter they were granted on, and neither do its history,
+/// callback, hooks, token gate or decay, whose accounts are derived from the
+/// source's address. The copy of a counter owned through an NFT belongs to
+/// the NFT's holder.
+fn process_clone(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    seed: Option<String>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let source = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let counter_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    let original = load_counter(program_id, source)?;
+    assert_authority(program_id, &original, authority, accounts_iter.as_slice())?;
+    // The NFT stays with the source, so its holder owns the copy outright
+    let owner = if original.has_flag(CounterFlag::NftAuthority) {
+        read_token_account(authority)?.1
+    } else {
+        original.authority
+    };
+
+    // Nothing was burned for the copy's count, it has no history, callback,
+    // hooks, token gate, rewards, permit nonce account, cooldown or decay, it
+    // has not moved this epoch, it is neither locked nor expiring, it paid no
+    // fee towards a crank reward and it has no escrow
+    let copy = Counter {
+        authority: owner,
+        pending_authority: Pubkey::default(),
+        delegate: Pubkey::default(),
+        allowance: 0,
+        // Only the settings that need no companion account carry over
+        flags: original.flags
+            & (CounterFlag::ClampDecrements as u16
+                | CounterFlag::ExactlyOnce as u16
+                | CounterFlag::EpochReset as u16),
+        epoch: 0,
+        epoch_change: 0,
+        locked_until: 0,
+        expires_at: 0,
+        next_crank_epoch: 0,
+        ..*original
+    };
+    match seed {
+        Some(seed) => {
+            let bump = check_counter_pda(program_id, counter_account, &owner, &seed)?;
+            create_counter_account(
+                program_id,
+                counter_account,
+                payer,
+                system_program,
+                &copy,
+                &[&[COUNTER_SEED, owner.as_ref(), seed.as_bytes(), &[bump]]],
+            )?;
+        }
+        None => create_counter_account(
+            program_id,
+            counter_account,
+            payer,
+            system_program,
+            &copy,
+            &[],
+        )?,
+    }
+
+    msg!("Counter {} cloned into {}", source.key, counter_account.key);
+
+    Ok(())
+}
+
+/// Create a new counter account owned by this program and write an initial count of zero
+///
+/// The account is created with a CPI to the system program, which allocates
+/// `Counter::LEN` bytes, funds it with the rent-exempt minimum from the payer
+/// and assigns ownership to `program_id` in a single step.
+fn process_initialize(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    args: InitializeArgs,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter).unwrap_or(payer);
+
+    let counter = new_counter(*authority.key, args)?;
+
+    // Both the payer and the new account have signed the transaction, so no
+    // program signature is needed for the system program to create it
+    create_counter_account(
+        program_id,
+        counter_account,
+        payer,
+        system_program,
+        &counter,
+        &[],
+    )
+}
+
+/// Create a counter at the address derived from its authority's key and `seed`
+///
+/// The counter account is a PDA, so the program signs for it with
+/// `invoke_signed` instead of requiring a keypair for the new account. The
+/// authority has to sign, so no one can take a seed under another's key.
+fn process_initialize_pda(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    seed: String,
+    args: InitializeArgs,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter).unwrap_or(payer);
+
+    let bump = check_counter_pda(program_id, counter_account, authority.key, &seed)?;
+    let counter = new_counter(*authority.key, args)?;
+
+    create_counter_account(
+        program_id,
+        counter_account,
+        payer,
+        system_program,
+        &counter,
+        &[&[
+            COUNTER_SEED,
+            authority.key.as_ref(),
+            seed.as_bytes(),
+            &[bump],
+        ]],
+    )
+}
+
+/// Check that `counter_account` is the PDA counter `authority` owns under
+/// `seed` and return its bump seed
+fn check_counter_pda(
+    program_id: &Pubkey,
+    counter_account: &AccountInfo,
+    authority: &Pubkey,
+    seed: &str,
+) -> Result<u8, ProgramError> {
+    if seed.len() > MAX_SEED_LEN {
+        msg!(
+            "Seed is {} bytes, at most {} are allowed",
+            seed.len(),
+            MAX_SEED_LEN
+        );
+        return Err(CounterError::InvalidSeed.into());
+    }
+
+    let (address, bump) = find_counter_address(program_id, authority, seed);
+    if *counter_account.key != address {
+        msg!(
+            "Counter account {} does not match the derived address {}",
+            counter_account.key,
+            address
+        );
+        return Err(CounterError::InvalidCounterAddress.into());
+    }
+    Ok(bump)
+}
+
+/// A zeroed counter owned by `authority` and configured by `args`
+fn new_counter(authority: Pubkey, args: InitializeArgs) -> Result<Counter, ProgramError> {
+    let mut counter = Counter::new(authority, args.overflow_mode, args.kind);
+    counter.step = args.step;
+    if args.min.is_some() || args.max.is_some() {
+        let min = args.min.unwrap_or(counter.min_value());
+        let max = args.max.unwrap_or(counter.max_value());
+        counter.set_bounds(min, max).inspect_err(|_| {
+            msg!(
+                "Bounds {}..={} must be ordered, include zero and fit a {:?} counter",
+                min,
+                max,
+                args.kind
+            )
+        })?;
+    }
+    // Every value below the modulus must be representable within the bounds
+    if args.modulus > 0 && i128::from(args.modulus - 1) > counter.max_value() {
+        msg!(
+            "Modulus {} exceeds the largest value {} the counter may hold",
+            args.modulus,
+            counter.max_value()
+        );
+        return Err(CounterError::InvalidBounds.into());
+    }
+    counter.modulus = args.modulus;
+    counter.set_flag(CounterFlag::ClampDecrements, args.clamp_decrements);
+    Ok(counter)
+}
+
+/// Allocate a rent-exempt counter account through the system program and
+/// write `counter` into it
+///
+/// `signer_seeds` are the seeds of the counter account when it is a PDA, or
+/// empty when the account signed the transaction itself.
+fn create_counter_account<'a>(
+    program_id: &Pubkey,
+    counter_account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    counter: &Counter,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    create_program_account(
+        program_id,
+        counter_account,
+        payer,
+        system_program,
+        Counter::LEN,
+        signer_seeds,
+    )?;
+
+    // Write the starting state into the freshly allocated account
+    store_counter(counter_account, counter);
+
+    msg!("Counter initialized to {}", counter.value());
+
+    Ok(())
+}
+
+/// Allocate a rent-exempt account of `len` bytes owned by this program, funded by `payer`
+///
+/// Fails with `AlreadyInitialized` if the account already holds one of the
+/// program's accounts, and with `NotRentExempt` if the new account somehow
+/// ends up below the exemption threshold, for example because the rent
+/// parameters changed.
+fn create_program_account<'a>(
+    program_id: &Pubkey,
+    account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    len: usize,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    // The system program would refuse too, but with a generic error; this
+    // says what went wrong and never gets as far as touching the old data
+    let initialized = account.owner == program_id
+        && account
+            .data
+            .borrow()
+            .get(..8)
+            .is_some_and(|discriminator| discriminator != [0; 8]);
+    if initialized {
+        msg!("Account {} is already initialized", account.key);
+        return Err(CounterError::AlreadyInitialized.into());
+    }
+
+    // Compute the balance needed for the account to be exempt from rent
+    let rent = Rent::get()?;
+    let lamports = rent.minimum_balance(len);
+
+    // Ask the system program to create the account, funded by the payer
+    invoke_signed(
+        &system_instruction::create_account(
+            payer.key,
+            account.key,
+            lamports,
+            len as u64,
+            program_id,
+        ),
+        &[payer.clone(), account.clone(), system_program.clone()],
+        signer_seeds,
+    )?;
+
+    // An account below the exemption threshold would eventually be purged
+    // by the runtime, silently losing its state
+    if !rent.is_exempt(account.lamports(), account.data_len()) {
+        msg!(
+            "Account {} holds {} lamports, {} are needed to be rent-exempt",
+            account.key,
+            account.lamports(),
+            rent.minimum_balance(account.data_len())
+        );
+        return Err(CounterError::NotRentExempt.into());
+    }
+    Ok(())
+}
+
+/// Close one of the program's accounts, sending all of its lamports to
+/// `destination`, and return how many were sent
+///
+/// The data is wiped and ownership handed back to the system program so the
+/// account cannot be mistaken for a live one again, even before the runtime
+/// garbage-collects it at the end of the transaction.
+fn close_program_account(
+    account: &AccountInfo,
+    destination: &AccountInfo,
+) -> Result<u64, ProgramError> {
+    if account.key == destination.key {
+        msg!("Cannot close account {} into itself", account.key);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let lamports = account.lamports();
+    **destination.lamports.borrow_mut() = destination
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(CounterError::Overflow)?;
+    **account.lamports.borrow_mut() = 0;
+
+    account.data.borrow_mut().fill(0);
+    account.realloc(0, false)?;
+    account.assign(&system_program::id());
+    Ok(lamports)
+}
+
+/// Switch off one of the counter's modes and close the companion account
+/// backing it, provided the counter's authority signed
+///
+/// `clear` checks that the companion, the third account, belongs to the
+/// counter and updates the counter; `what` names the companion in the log.
+fn clear_companion(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    what: &str,
+    clear: impl FnOnce(&AccountInfo, &AccountInfo, &mut Counter) -> ProgramResult,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let companion = next_account_info(accounts_iter)?;
+    let destination = next_account_info(accounts_iter)?;
+
+    let mut counter = load_counter(program_id, counter_account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+    clear(counter_account, companion, &mut counter)?;
+    let lamports = close_program_account(companion, destination)?;
+
+    msg!(
+        "{} of counter {} cleared, {} lamports sent to {}",
+        what,
+        counter_account.key,
+        lamports,
+        destination.key
+    );
+
+    Ok(())
+}
+
+/// Set the counter back to zero, provided the stored authority signed the transaction
+fn process_reset(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    op_id: Option<OpId>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let (history, callback, signers) = split_trailing(accounts_iter.as_slice());
+
+    let config = load_config(program_id, config_account)?;
+    config.check_active()?;
+
+    let mut counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, signers)?;
+    let old_value = counter.value();
+
+    // Goes through the bounds check, so a counter whose minimum is above zero
+    // cannot be reset
+    counter.set(0)?;
+    record_update(&mut counter, old_value)?;
+    record_history(
+        program_id,
+        account,
+        &counter,
+        history,
+        &[(HistoryOp::Reset, 0)],
+        authority.key,
+    )?;
+
+    emit_update(
+        account,
+        &counter,
+        Operation::Reset,
+        old_value,
+        authority.key,
+        op_id,
+    );
+
+    // Released first, as the callback may be passed the counter
+    drop(counter);
+    fire_callback(program_id, account, old_value, callback)
+}
+
+/// Overwrite the counter with `value`, provided the stored authority signed the transaction
+fn process_set(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    value: u64,
+    op_id: Option<OpId>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let (history, callback, signers) = split_trailing(accounts_iter.as_slice());
+
+    let config = load_config(program_id, config_account)?;
+    config.check_active()?;
+
+    let mut counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, signers)?;
+    let old_value = counter.value();
+
+    counter.set(value)?;
+    record_update(&mut counter, old_value)?;
+    record_history(
+        program_id,
+        account,
+        &counter,
+        history,
+        &[(HistoryOp::Set, value)],
+        authority.key,
+    )?;
+
+    emit_update(
+        account,
+        &counter,
+        Operation::Set,
+        old_value,
+        authority.key,
+        op_id,
+    );
+
+    // Released first, as the callback may be passed the counter
+    drop(counter);
+    fire_callback(program_id, account, old_value, callback)
+}
+
+/// Pause or unpause the counter, provided its freeze authority, or its
+/// authority if it has none, signed the transaction
+///
+/// Pausing acts as a circuit breaker: the pause check sits in the counter's
+/// arithmetic, so every instruction that changes the count fails with
+/// `CounterPaused` until the counter is unpaused.
+fn process_set_paused(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    paused: bool,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+
+    let mut counter = load_counter(program_id, account)?;
+    if counter.pause_authority() != *authority.key {
+        msg!("{} may not pause or unpause this counter", authority.key);
+        return Err(CounterError::Unauthorized.into());
+    }
+    assert_approved(program_id, authority, accounts_iter.as_slice())?;
+
+    counter.paused = paused.into();
+
+    msg!("Counter {}", if paused { "paused" } else { "unpaused" });
+
+    Ok(())
+}
+
+/// Set or clear the counter's exactly-once flag, provided the stored
+/// authority signed the transaction
+///
+/// The flag itself is enforced by `check_transaction`.
+fn process_set_exactly_once(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    enabled: bool,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+
+    let mut counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    counter.set_flag(CounterFlag::ExactlyOnce, enabled);
+
+    msg!(
+        "Counter is {}",
+        if enabled {
+            "exactly-once"
+        } else {
+            "no longer exactly-once"
+        }
+    );
+
+    Ok(())
+}
+
+/// Make each new epoch start the counter over from zero, or stop doing so,
+/// provided the stored authority signed the transaction
+///
+/// The counter counts as changed in the current epoch from then on, so the
+/// next epoch is the first to start over.
+fn process_set_epoch_reset(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    enabled: bool,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+
+    let mut counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+    if counter.has_flag(CounterFlag::Burn) {
+        msg!(
+            "Counter {} is burn-backed and only changes by burning tokens",
+            account.key
+        );
+        return Err(CounterError::BurnBacked.into());
+    }
+    if enabled {
+        // A counter that cannot hold zero could never start over
+        let mut reset = *counter;
+        reset.set(0)?;
+    }
+
+    let epoch = Clock::get()?.epoch;
+    if counter.epoch != epoch {
+        counter.epoch = epoch;
+        counter.epoch_change = 0;
+    }
+    counter.set_flag(CounterFlag::EpochReset, enabled);
+
+    msg!(
+        "Counter {}",
+        if enabled {
+            "starts over every epoch"
+        } else {
+            "no longer starts over every epoch"
+        }
+    );
+
+    Ok(())
+}
+
+/// Lock the counter against changes until `until_slot`, provided the
+/// authority approved
+fn process_lock(program_id: &Pubkey, accounts: &[AccountInfo], until_slot: u64) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+
+    let mut counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    let slot = Clock::get()?.slot;
+    if until_slot <= slot {
+        msg!(
+            "Slot {} is not in the future, the current slot is {}",
+            until_slot,
+            slot
+        );
+        return Err(CounterError::InvalidLock.into());
+    }
+    if until_slot < counter.locked_until {
+        msg!(
+            "Counter {} is locked until slot {}, which a lock cannot bring forward",
+            account.key,
+            counter.locked_until
+        );
+        return Err(CounterError::InvalidLock.into());
+    }
+    counter.locked_until = until_slot;
+
+    msg!("Counter {} locked until slot {}", account.key, until_slot);
+
+    Ok(())
+}
+
+/// Set the unix timestamp from which the counter is read-only, or remove it
+/// with 0, provided the authority approved and the counter has not expired
+fn process_set_expiry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    expires_at: i64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+
+    let mut counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    let now = Clock::get()?.unix_timestamp;
+    if counter.is_expired(now) {
+        msg!("Counter {} expired at {}", account.key, counter.expires_at);
+        return Err(CounterError::CounterExpired.into());
+    }
+    if expires_at != 0 && expires_at <= now {
+        msg!(
+            "Expiry {} is not in the future, the time is {}",
+            expires_at,
+            now
+        );
+        return Err(CounterError::InvalidExpiry.into());
+    }
+    counter.expires_at = expires_at;
+
+    if expires_at == 0 {
+        msg!("Counter {} no longer expires", account.key);
+    } else {
+        msg!("Counter {} expires at {}", account.key, expires_at);
+    }
+
+    Ok(())
+}
+
+/// Keep a moving average of the counter's value with the smoothing factor
+/// `alpha_bps`, or stop keeping one with 0, provided the authority approved
+fn process_set_ema(program_id: &Pubkey, accounts: &[AccountInfo], alpha_bps: u16) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+
+    let mut counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    let alpha = u64::from(alpha_bps);
+    if alpha > Counter::MAX_EMA_ALPHA {
+        msg!(
+            "A smoothing factor of {} basis points is above {}",
+            alpha,
+            Counter::MAX_EMA_ALPHA
+        );
+        return Err(CounterError::InvalidSmoothing.into());
+    }
+    // A new average starts at the current value
+    let ema = match counter.ema() {
+        _ if alpha == 0 => 0,
+        Some(ema) => ema,
+        None => counter.value() * Counter::EMA_SCALE,
+    };
+    counter.ema = ema.to_le_bytes();
+    counter.ema_alpha = alpha;
+
+    if alpha == 0 {
+        msg!("Counter {} no longer keeps a moving average", account.key);
+    } else {
+        msg!(
+            "Counter {} keeps a moving average with a smoothing factor of {} basis points",
+            account.key,
+            alpha
+        );
+    }
+
+    Ok(())
+}
+
+/// Log the counter's state and return it to the caller, changing nothing
+fn process_view(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+
+    let counter = *load_counter(program_id, account)?;
+    let view = CounterView::new(*account.key, &counter, &Clock::get()?);
+    set_return_data(&borsh::to_vec(&view)?);
+    events::emit(&Event::CounterViewed(Box::new(view)));
+
+    Ok(())
+}
+
+/// Watch `thresholds`, provided the stored authority signed the transaction
+fn process_set_thresholds(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    thresholds: Vec<i128>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+
+    let mut counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    counter.set_thresholds(&thresholds).inspect_err(|_| {
+        msg!(
+            "A counter watches at most {} thresholds, each within the range of its kind",
+            Counter::MAX_THRESHOLDS
+        )
+    })?;
+
+    msg!("Counter watches thresholds {:?}", thresholds);
+
+    Ok(())
+}
+
+/// Hand pausing and unpausing to `freeze_authority`, provided the stored
+/// authority signed the transaction
+fn process_set_freeze_authority(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    freeze_authority: Pubkey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+
+    let mut counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    counter.freeze_authority = freeze_authority;
+
+    if freeze_authority == Pubkey::default() {
+        msg!("Freeze authority cleared");
+    } else {
+        msg!("Freeze authority set to {}", freeze_authority);
+    }
+
+    Ok(())
+}
+
+/// Record `delegate` and its `allowance`, provided the stored authority signed the transaction
+///
+/// `Revoke` is handled here too, approving nobody for nothing.
+fn process_approve(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    delegate: Pubkey,
+    allowance: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+
+    let mut counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    counter.delegate = delegate;
+    counter.allowance = allowance;
+
+    if delegate == Pubkey::default() {
+        msg!("Delegate revoked");
+    } else {
+        msg!("{} may decrement by up to {}", delegate, allowance);
+    }
+
+    Ok(())
+}
+
+/// Record `new_authority` as the pending authority of the counter
+fn process_transfer_authority(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_authority: Pubkey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+
+    let mut counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    counter.pending_authority = new_authority;
+
+    msg!("Authority transfer to {} proposed", new_authority);
+
+    Ok(())
+}
+
+/// Make the pending authority the counter's authority once it has signed
+fn process_accept_authority(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let new_authority = next_account_info(accounts_iter)?;
+
+    let mut counter = load_counter(program_id, account)?;
+    if counter.pending_authority == Pubkey::default() {
+        return Err(CounterError::NoPendingAuthority.into());
+    }
+    if counter.pending_authority != *new_authority.key {
+        msg!("{} is not the pending authority", new_authority.key);
+        return Err(CounterError::Unauthorized.into());
+    }
+    // A multisig becomes the authority only once its signers approve
+    assert_approved(program_id, new_authority, accounts_iter.as_slice())?;
+
+    counter.authority = counter.pending_authority;
+    counter.pending_authority = Pubkey::default();
+    counter.set_flag(CounterFlag::NftAuthority, false);
+
+    msg!("Counter authority is now {}", counter.authority);
+
+    Ok(())
+}
+
+/// Close the counter, sending all of its lamports to the destination account
+fn process_close(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let destination = next_account_info(accounts_iter)?;
+
+    let counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    // Settling and refunding need the counter, so its escrow would be stuck
+    if counter.has_flag(CounterFlag::Escrow) {
+        msg!(
+            "Counter {} has an open escrow; settle or refund it first",
+            account.key
+        );
+        return Err(CounterError::EscrowOpen.into());
+    }
+    drop(counter);
+    let lamports = close_program_account(account, destination)?;
+
+    msg!(
+        "Counter closed, {} lamports sent to {}",
+        lamports,
+        destination.key
+    );
+
+    Ok(())
+}
+
+/// Create the program config PDA, making the payer its admin
+fn process_initialize_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    max_amount: u32,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    let (address, bump) = find_config_address(program_id);
+    if *config_account.key != address {
+        msg!(
+            "Config account {} does not match the derived address {}",
+            config_account.key,
+            address
+        );
+        return Err(CounterError::InvalidConfigAddress.into());
+    }
+
+    create_program_account(
+        program_id,
+        config_account,
+        payer,
+        system_program,
+        Config::LEN,
+        &[&[CONFIG_SEED, &[bump]]],
+    )?;
+
+    let config = Config::new(*payer.key, max_amount);
+    config.serialize(&mut *config_account.data.borrow_mut())?;
+    msg!("Config initialized with admin {}", config.admin);
+
+    Ok(())
+}
+
+/// Overwrite the program config, provided the current admin signed the transaction
+fn process_update_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    admin: Pubkey,
+    paused: bool,
+    max_amount: u32,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let current_admin = next_account_info(accounts_iter)?;
+
+    let mut config = load_config(program_id, config_account)?;
+    if config.discriminator != Config::DISCRIMINATOR {
+        msg!("Config account {} is not initialized", config_account.key);
+        return Err(CounterError::UninitializedAccount.into());
+    }
+    if config.admin != *current_admin.key {
+        msg!("{} is not the config admin", current_admin.key);
+        return Err(CounterError::Unauthorized.into());
+    }
+
+    config.admin = admin;
+    config.paused = paused;
+    config.max_amount = max_amount;
+
+    config.serialize(&mut *config_account.data.borrow_mut())?;
+    msg!(
+        "Config updated: admin {}, paused {}, max amount {}",
+        config.admin,
+        config.paused,
+        config.max_amount
+    );
+
+    Ok(())
+}
+
+/// Halt or resume every counter change program-wide, provided the admin signed
+///
+/// Only the config's `paused` switch changes, and an event records who
+/// flipped it and when.
+fn process_emergency(program_id: &Pubkey, accounts: &[AccountInfo], halted: bool) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let admin = next_account_info(accounts_iter)?;
+
+    let mut config = load_config(program_id, config_account)?;
+    if config.discriminator != Config::DISCRIMINATOR {
+        msg!("Config account {} is not initialized", config_account.key);
+        return Err(CounterError::UninitializedAccount.into());
+    }
+    if config.admin != *admin.key {
+        msg!("{} is not the config admin", admin.key);
+        return Err(CounterError::Unauthorized.into());
+    }
+
+    config.paused = halted;
+    config.serialize(&mut *config_account.data.borrow_mut())?;
+
+    let slot = Clock::get()?.slot;
+    msg!(
+        "Emergency {} by {}",
+        if halted { "halt" } else { "resume" },
+        admin.key
+    );
+    events::emit(&if halted {
+        Event::EmergencyHalt {
+            admin: *admin.key,
+            slot,
+        }
+    } else {
+        Event::EmergencyResume {
+            admin: *admin.key,
+            slot,
+        }
+    });
+
+    Ok(())
+}
+
+/// Replace the caller guard stored after the config, provided the admin signed
+///
+/// The config account grows to fit the program lists if needed and never shrinks.
+fn process_set_caller_guard(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    enabled: bool,
+    allowed_programs: Vec<Pubkey>,
+    forbidden_programs: Vec<Pubkey>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let admin = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    let config = load_config(program_id, config_account)?;
+    if config.discriminator != Config::DISCRIMINATOR {
+        msg!("Config account {} is not initialized", config_account.key);
+        return Err(CounterError::UninitializedAccount.into());
+    }
+    if config.admin != *admin.key {
+        msg!("{} is not the config admin", admin.key);
+        return Err(CounterError::Unauthorized.into());
+    }
+    for (list, programs) in [
+        ("Allowlist", &allowed_programs),
+        ("Forbidden list", &forbidden_programs),
+    ] {
+        if programs.len() > CallerGuard::MAX_ALLOWED_PROGRAMS {
+            msg!(
+                "{} holds {} programs, at most {} are allowed",
+                list,
+                programs.len(),
+                CallerGuard::MAX_ALLOWED_PROGRAMS
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+    }
+
+    let guard = CallerGuard {
+        enabled,
+        allowed_programs,
+        forbidden_programs,
+    };
+    // The fees follow the guard, so they move with it
+    let fees = load_fees(config_account)?;
+    let epoch_cap = load_epoch_cap(config_account)?;
+    let crank = load_crank_rewards(config_account)?;
+    store_config_tail(
+        config_account,
+        payer,
+        system_program,
+        &guard,
+        &fees,
+        epoch_cap,
+        &crank,
+    )?;
+
+    msg!(
+        "Caller guard {}, allowing {} programs and forbidding {}",
+        if guard.enabled { "on" } else { "off" },
+        guard.allowed_programs.len(),
+        guard.forbidden_programs.len()
+    );
+
+    Ok(())
+}
+
+/// Start or stop charging a fee for every change to a counter's value,
+/// provided the config admin signed
+fn process_set_fee<'a>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    fee: Option<FeeConfig>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let admin = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    let config = load_config(program_id, config_account)?;
+    if config.discriminator != Config::DISCRIMINATOR {
+        msg!("Config account {} is not initialized", config_account.key);
+        return Err(CounterError::UninitializedAccount.into());
+    }
+    if config.admin != *admin.key {
+        msg!("{} is not the config admin", admin.key);
+        return Err(CounterError::Unauthorized.into());
+    }
+    if fee.is_some_and(|fee| fee.amount == 0) {
+        msg!("A fee must be positive; pass None to stop charging one");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let guard = load_caller_guard(config_account)?;
+    let fees = Fees {
+        token: fee,
+        ..load_fees(config_account)?
+    };
+    let epoch_cap = load_epoch_cap(config_account)?;
+    let crank = load_crank_rewards(config_account)?;
+    store_config_tail(
+        config_account,
+        payer,
+        system_program,
+        &guard,
+        &fees,
+        epoch_cap,
+        &crank,
+    )?;
+
+    match fee {
+        Some(fee) => msg!(
+            "Charging {} of mint {} per change, paid to {}",
+            fee.amount,
+            fee.mint,
+            fee.treasury
+        ),
+        None => msg!("No longer charging a fee"),
+    }
+
+    Ok(())
+}
+
+/// Start or stop charging a lamport fee for every change to a counter's
+/// value, provided the config admin signed, creating the fee vault it is
+/// collected in on first use
+fn process_set_lamport_fee<'a>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    lamports: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let admin = next_account_info(accounts_iter)?;
+    let vault = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    let config = load_config(program_id, config_account)?;
+    if config.discriminator != Config::DISCRIMINATOR {
+        msg!("Config account {} is not initialized", config_account.key);
+        return Err(CounterError::UninitializedAccount.into());
+    }
+    if config.admin != *admin.key {
+        msg!("{} is not the config admin", admin.key);
+        return Err(CounterError::Unauthorized.into());
+    }
+    let (address, bump) = find_fee_vault_address(program_id);
+    if *vault.key != address {
+        msg!(
+            "Fee vault {} does not match the derived address {}",
+            vault.key,
+            address
+        );
+        return Err(CounterError::InvalidFeeAccount.into());
+    }
+
+    if vault.owner != program_id {
+        create_program_account(
+            program_id,
+            vault,
+            payer,
+            system_program,
+            FeeVault::LEN,
+            &[&[FEE_VAULT_SEED, &[bump]]],
+        )?;
+        FeeVault {
+            discriminator: FeeVault::DISCRIMINATOR,
+            bump,
+            collected: 0,
+        }
+        .serialize(&mut &mut vault.data.borrow_mut()[..])?;
+    }
+
+    let guard = load_caller_guard(config_account)?;
+    let fees = Fees {
+        lamports,
+        ..load_fees(config_account)?
+    };
+    let epoch_cap = load_epoch_cap(config_account)?;
+    let crank = load_crank_rewards(config_account)?;
+    store_config_tail(
+        config_account,
+        payer,
+        system_program,
+        &guard,
+        &fees,
+        epoch_cap,
+        &crank,
+    )?;
+
+    if lamports == 0 {
+        msg!("No longer charging a lamport fee");
+    } else {
+        msg!("Charging {} lamports per change", lamports);
+    }
+
+    Ok(())
+}
+
+/// Cap how far changes may move any one counter per epoch, provided the
+/// config admin signed
+fn process_set_epoch_cap<'a>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    cap: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let admin = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    let config = load_config(program_id, config_account)?;
+    if config.discriminator != Config::DISCRIMINATOR {
+        msg!("Config account {} is not initialized", config_account.key);
+        return Err(CounterError::UninitializedAccount.into());
+    }
+    if config.admin != *admin.key {
+        msg!("{} is not the config admin", admin.key);
+        return Err(CounterError::Unauthorized.into());
+    }
+
+    let guard = load_caller_guard(config_account)?;
+    let fees = load_fees(config_account)?;
+    let crank = load_crank_rewards(config_account)?;
+    store_config_tail(
+        config_account,
+        payer,
+        system_program,
+        &guard,
+        &fees,
+        cap,
+        &crank,
+    )?;
+
+    if cap == 0 {
+        msg!("No longer capping changes per epoch");
+    } else {
+        msg!("Capping each counter's changes at {} per epoch", cap);
+    }
+
+    Ok(())
+}
+
+/// Set the reward a crank with work to do pays out of the fee vault and
+/// the most paid out in rewards per epoch, provided the config admin signed
+///
+/// What was paid so far in the current epoch is kept, so lowering the budget
+/// cannot be used to pay it out again.
+fn process_set_crank_reward<'a>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    lamports: u64,
+    epoch_budget: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let admin = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    let config = load_config(program_id, config_account)?;
+    if config.discriminator != Config::DISCRIMINATOR {
+        msg!("Config account {} is not initialized", config_account.key);
+        return Err(CounterError::UninitializedAccount.into());
+    }
+    if config.admin != *admin.key {
+        msg!("{} is not the config admin", admin.key);
+        return Err(CounterError::Unauthorized.into());
+    }
+
+    let guard = load_caller_guard(config_account)?;
+    let fees = load_fees(config_account)?;
+    let epoch_cap = load_epoch_cap(config_account)?;
+    let crank = CrankRewards {
+        reward: lamports,
+        epoch_budget,
+        ..load_crank_rewards(config_account)?
+    };
+    store_config_tail(
+        config_account,
+        payer,
+        system_program,
+        &guard,
+        &fees,
+        epoch_cap,
+        &crank,
+    )?;
+
+    if lamports == 0 {
+        msg!("No longer rewarding cranks");
+    } else {
+        msg!(
+            "Rewarding each crank with work to do {} lamports, at most {} per epoch",
+            lamports,
+            epoch_budget
+        );
+    }
+
+    Ok(())
+}
+
+/// Move `amount` lamports out of the fee vault to the destination, provided
+/// the config admin signed
+fn process_withdraw_fees(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let admin = next_account_info(accounts_iter)?;
+    let vault = next_account_info(accounts_iter)?;
+    let destination = next_account_info(accounts_iter)?;
+
+    let config = load_config(program_id, config_account)?;
+    if config.discriminator != Config::DISCRIMINATOR {
+        msg!("Config account {} is not initialized", config_account.key);
+        return Err(CounterError::UninitializedAccount.into());
+    }
+    if config.admin != *admin.key {
+        msg!("{} is not the config admin", admin.key);
+        return Err(CounterError::Unauthorized.into());
+    }
+    load_fee_vault(program_id, vault)?;
+
+    // The vault keeps its rent-exempt minimum
+    let available = vault
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(FeeVault::LEN));
+    if amount > available {
+        msg!(
+            "The fee vault holds {} withdrawable lamports, not {}",
+            available,
+            amount
+        );
+        return Err(ProgramError::InsufficientFunds);
+    }
+    **vault.lamports.borrow_mut() -= amount;
+    **destination.lamports.borrow_mut() = destination
+        .lamports()
+        .checked_add(amount)
+        .ok_or(CounterError::Overflow)?;
+
+    msg!(
+        "Withdrew {} lamports of fees to {}",
+        amount,
+        destination.key
+    );
+
+    Ok(())
+}
+
+/// Write `guard`, `fees`, `epoch_cap` and then `crank` after the config in
+/// `account`, growing it first if they no longer fit
+fn store_config_tail<'a>(
+    account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    guard: &CallerGuard,
+    fees: &Fees,
+    epoch_cap: u64,
+    crank: &CrankRewards,
+) -> ProgramResult {
+    let len = account
+        .data_len()
+        .max(Config::LEN + guard.len() + fees.len() + 8 + CrankRewards::LEN);
+    resize_account(account, payer, system_program, len)?;
+
+    let mut data = account.data.borrow_mut();
+    let region = &mut data[Config::LEN..];
+    region.fill(0);
+    let writer = &mut &mut region[..];
+    guard.serialize(writer)?;
+    fees.serialize(writer)?;
+    epoch_cap.serialize(writer)?;
+    crank.serialize(writer)?;
+    Ok(())
+}
+
+/// Move a decaying counter's value towards zero by what its decay owes
+/// since the counter last changed
+///
+/// `decay_accounts` holds the account `split_decay` set aside, which is
+/// ignored while the counter does not decay.
+fn apply_decay(
+    program_id: &Pubkey,
+    counter_account: &AccountInfo,
+    decay_accounts: &[AccountInfo],
+) -> ProgramResult {
+    let mut counter = load_counter(program_id, counter_account)?;
+    if !counter.has_flag(CounterFlag::Decay) {
+        return Ok(());
+    }
+    let [decay_account] = decay_accounts else {
+        msg!(
+            "Counter {} decays; pass its decay account",
+            counter_account.key
+        );
+        return Err(CounterError::MissingDecay.into());
+    };
+    let decay = load_decay(program_id, decay_account, counter_account.key)?;
+    settle_decay(counter_account, &mut counter, &decay)
+}
+
+/// Apply what `decay` owes to `counter`, stored at `counter_account`
+///
+/// Decaying is a change like any other: it goes through [`record_update`],
+/// so it fails while the counter is locked or expired, and is reported with
+/// [`Operation::Decay`].
+fn settle_decay(
+    counter_account: &AccountInfo,
+    counter: &mut Counter,
+    decay: &CounterDecay,
+) -> ProgramResult {
+    let clock = Clock::get()?;
+    let old_value = counter.value();
+    counter.decay(decay.owed(counter, clock.slot, clock.unix_timestamp))?;
+    if counter.value() == old_value {
+        return Ok(());
+    }
+    record_update(counter, old_value)?;
+    emit_update(
+        counter_account,
+        counter,
+        Operation::Decay,
+        old_value,
+        &Pubkey::default(),
+        None,
+    );
+    msg!(
+        "Counter {} decayed from {} to {}",
+        counter_account.key,
+        old_value,
+        counter.value()
+    );
+    Ok(())
+}
+
+/// Zero a counter set to start over every epoch if this is its first change
+/// in the current epoch, logging the value it ends the last one with
+///
+/// The tally of `track_epoch_change` starts over along with it, so the reset
+/// itself does not count towards the epoch cap.
+fn reset_for_epoch(program_id: &Pubkey, counter_account: &AccountInfo) -> ProgramResult {
+    let mut counter = load_counter(program_id, counter_account)?;
+    if !counter.has_flag(CounterFlag::EpochReset) {
+        return Ok(());
+    }
+    let clock = Clock::get()?;
+    if counter.epoch == clock.epoch {
+        return Ok(());
+    }
+
+    let final_value = counter.value();
+    let ended = counter.epoch;
+    counter.set(0)?;
+    counter.epoch = clock.epoch;
+    counter.epoch_change = 0;
+
+    msg!(
+        "Counter {} starts epoch {} over, ending epoch {} at {}",
+        counter_account.key,
+        clock.epoch,
+        ended,
+        final_value
+    );
+    events::emit(&Event::EpochReset {
+        counter: *counter_account.key,
+        epoch: ended,
+        final_value,
+        slot: clock.slot,
+    });
+    Ok(())
+}
+
+/// Add how far the change just made moved `counter_account` to its tally
+/// for the current epoch, failing if that takes it past the epoch cap of
+/// the config among `accounts`
+///
+/// The tally starts over whenever the epoch has advanced since it was last
+/// added to, and is kept even while no cap is set.
+fn track_epoch_change(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    counter_account: &AccountInfo,
+    old_value: i128,
+) -> ProgramResult {
+    let (config_address, _) = find_config_address(program_id);
+    let cap = match accounts
+        .iter()
+        .find(|account| *account.key == config_address)
+    {
+        Some(config_account) if config_account.owner == program_id => {
+            load_epoch_cap(config_account)?
+        }
+        _ => 0,
+    };
+    let epoch = Clock::get()?.epoch;
+
+    let mut counter = load_counter(program_id, counter_account)?;
+    if counter.epoch != epoch {
+        counter.epoch = epoch;
+        counter.epoch_change = 0;
+    }
+    let moved = u64::try_from(counter.value().abs_diff(old_value)).unwrap_or(u64::MAX);
+    counter.epoch_change = counter.epoch_change.saturating_add(moved);
+    if cap != 0 && counter.epoch_change > cap {
+        msg!(
+            "Counter {} would move by {} in epoch {}, past the cap of {}",
+            counter_account.key,
+            counter.epoch_change,
+            epoch,
+            cap
+        );
+        return Err(CounterError::EpochCapExceeded.into());
+    }
+    Ok(())
+}
+
+/// Charge the fees the config sets for a change: the token fee from the
+/// token account in `fee_accounts` and the lamport fee from the payer in
+/// `vault_accounts`, the accounts `split_fee` and `split_fee_vault` set aside
+///
+/// Nothing is charged, and the accounts of a fee that is not set are
+/// ignored, while the config is not initialized or sets no fees. Returns
+/// whether a lamport fee went into the fee vault, which lets the counter earn
+/// its next crank reward.
+fn charge_fees(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    fee_accounts: &[AccountInfo],
+    vault_accounts: &[AccountInfo],
+) -> Result<bool, ProgramError> {
+    let (config_address, _) = find_config_address(program_id);
+    let fees = match accounts
+        .iter()
+        .find(|account| *account.key == config_address)
+    {
+        Some(config_account) if config_account.owner == program_id => load_fees(config_account)?,
+        _ => Fees::default(),
+    };
+    if let Some(fee) = fees.token {
+        charge_token_fee(&fee, fee_accounts)?;
+    }
+    if fees.lamports > 0 {
+        charge_lamport_fee(program_id, fees.lamports, vault_accounts)?;
+    }
+    Ok(fees.lamports > 0)
+}
+
+/// Transfer `lamports` from the payer in `vault_accounts` into the fee vault
+fn charge_lamport_fee(
+    program_id: &Pubkey,
+    lamports: u64,
+    vault_accounts: &[AccountInfo],
+) -> ProgramResult {
+    let [payer, vault, system_program] = vault_accounts else {
+        msg!(
+            "A fee of {} lamports is due; pass the payer, the fee vault and the system program",
+            lamports
+        );
+        return Err(CounterError::MissingFee.into());
+    };
+    let mut state = load_fee_vault(program_id, vault)?;
+    if !payer.is_signer || !payer.is_writable {
+        msg!(
+            "The payer {} of the fee must sign and be writable",
+            payer.key
+        );
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    invoke(
+        &system_instruction::transfer(payer.key, vault.key, lamports),
+        &[payer.clone(), vault.clone(), system_program.clone()],
+    )?;
+    state.collected = state.collected.saturating_add(lamports);
+    state.serialize(&mut &mut vault.data.borrow_mut()[..])?;
+    Ok(())
+}
+
+/// Transfer the token fee `fee` from the token account in `fee_accounts` to
+/// the fee's treasury
+fn charge_token_fee(fee: &FeeConfig, fee_accounts: &[AccountInfo]) -> ProgramResult {
+    let [source, owner, treasury, mint, token_program] = fee_accounts else {
+        msg!(
+            "A fee of {} is due; pass the paying token account, its owner, the treasury, the mint and the token program",
+            fee.amount
+        );
+        return Err(CounterError::MissingFee.into());
+    };
+    if *mint.key != fee.mint || *treasury.key != fee.treasury {
+        msg!(
+            "The fee is paid in mint {} to {}, not in {} to {}",
+            fee.mint,
+            fee.treasury,
+            mint.key,
+            treasury.key
+        );
+        return Err(CounterError::InvalidFeeAccount.into());
+    }
+    if !owner.is_signer {
+        msg!(
+            "The owner {} of the paying token account must sign",
+            owner.key
+        );
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    // The token program checks the mint it is passed, decimals included
+    let Some(&decimals) = mint.data.borrow().get(MINT_DECIMALS_OFFSET) else {
+        msg!("Account {} is not a mint", mint.key);
+        return Err(CounterError::InvalidFeeAccount.into());
+    };
+
+    // SPL Token's `TransferChecked`: its tag, the amount and the decimals
+    let mut data = vec![12];
+    data.extend_from_slice(&fee.amount.to_le_bytes());
+    data.push(decimals);
+    invoke(
+        &Instruction {
+            program_id: *token_program.key,
+            accounts: vec![
+                AccountMeta::new(*source.key, false),
+                AccountMeta::new_readonly(*mint.key, false),
+                AccountMeta::new(*treasury.key, false),
+                AccountMeta::new_readonly(*owner.key, true),
+            ],
+            data,
+        },
+        &[
+            source.clone(),
+            mint.clone(),
+            treasury.clone(),
+            owner.clone(),
+            token_program.clone(),
+        ],
+    )
+}
+
+/// Reject an instruction that arrived through a CPI if the caller guard does
+/// not allow the calling program, returning its accounts without a config
+/// passed only for the guard
+///
+/// The guard fails closed: an instruction arriving through a CPI must pass
+/// the config, initialized or not, so the guard is read for every one of
+/// them. One that does not take the config among its own accounts passes it
+/// last, before the Instructions sysvar. The caller is taken to be the
+/// program targeted by the transaction's top-level instruction, as recorded
+/// in the Instructions sysvar.
+fn check_caller<'a, 'b>(
+    program_id: &Pubkey,
+    instruction: &CounterInstruction,
+    accounts: &'b [AccountInfo<'a>],
+    instructions_sysvar: Option<&AccountInfo>,
+) -> Result<&'b [AccountInfo<'a>], ProgramError> {
+    if get_stack_height() <= TRANSACTION_LEVEL_STACK_HEIGHT {
+        return Ok(accounts);
+    }
+    let (config_address, _) = find_config_address(program_id);
+    let (accounts, config_account) = match accounts.split_last() {
+        Some((last, rest)) if *last.key == config_address && !takes_config(instruction) => {
+            (rest, Some(last))
+        }
+        _ => (
+            accounts,
+            accounts
+                .iter()
+                .find(|account| *account.key == config_address),
+        ),
+    };
+    let Some(config_account) = config_account else {
+        msg!(
+            "{} arrived through a CPI; pass the program config for the caller guard",
+            instruction.name()
+        );
+        return Err(CounterError::CallerNotAllowed.into());
+    };
+    // An uninitialized config has no guard
+    if config_account.owner != program_id {
+        return Ok(accounts);
+    }
+    let guard = load_caller_guard(config_account)?;
+    if !guard.enabled {
+        return Ok(accounts);
+    }
+
+    let Some(sysvar) = instructions_sysvar else {
+        msg!("The caller guard is on; pass the Instructions sysvar as the last account");
+        return Err(CounterError::CallerNotAllowed.into());
+    };
+    let index = load_current_index_checked(sysvar)?;
+    let caller = load_instruction_at_checked(index.into(), sysvar)?.program_id;
+    if !guard.allows(&caller) {
+        msg!("Program {} may not invoke this program", caller);
+        return Err(CounterError::CallerNotAllowed.into());
+    }
+    Ok(accounts)
+}
+
+/// Check an instruction that changes the count against the other top-level
+/// instructions of its transaction, read from the Instructions sysvar
+///
+/// An exactly-once counter may be changed by only one of them, and no
+/// instruction may target a program the caller guard forbids. Neither check
+/// needs the sysvar while it is off.
+///
+/// The sysvar lists top-level instructions only, so neither check sees the
+/// instructions another program issues through a CPI: a program invoking
+/// this one twice, or a forbidden program invoked by a listed one, goes
+/// unnoticed. The caller guard's allow list is what keeps such programs out.
+fn check_transaction(
+    program_id: &Pubkey,
+    instruction: &CounterInstruction,
+    accounts: &[AccountInfo],
+    instructions_sysvar: Option<&AccountInfo>,
+) -> ProgramResult {
+    if !instruction.changes_count() {
+        return Ok(());
+    }
+    // Validation put the counter first
+    let counter_account = &accounts[0];
+    let exactly_once =
+        load_counter(program_id, counter_account)?.has_flag(CounterFlag::ExactlyOnce);
+    let (config_address, _) = find_config_address(program_id);
+    let forbidden_programs = match accounts
+        .iter()
+        .find(|account| *account.key == config_address)
+    {
+        Some(config_account) if config_account.owner == program_id => {
+            load_caller_guard(config_account)?.forbidden_programs
+        }
+        _ => Vec::new(),
+    };
+    if !exactly_once && forbidden_programs.is_empty() {
+        return Ok(());
+    }
+
+    let Some(sysvar) = instructions_sysvar else {
+        msg!(
+            "This change is checked against its transaction; pass the Instructions sysvar as the last account"
+        );
+        return Err(if exactly_once {
+            CounterError::DuplicateMutation
+        } else {
+            CounterError::ForbiddenProgram
+        }
+        .into());
+    };
+    let mut changes = 0;
+    let mut index = 0;
+    while let Ok(other) = load_instruction_at_checked(index, sysvar) {
+        if forbidden_programs.contains(&other.program_id) {
+            msg!(
+                "Program {} may not appear in a transaction changing a counter",
+                other.program_id
+            );
+            return Err(CounterError::ForbiddenProgram.into());
+        }
+        if other.program_id == *program_id
+            && other
+                .accounts
+                .first()
+                .is_some_and(|meta| meta.pubkey == *counter_account.key)
+            && CounterInstruction::unpack(&other.data).is_ok_and(|other| other.changes_count())
+        {
+            changes += 1;
+        }
+        index += 1;
+    }
+    if exactly_once && changes > 1 {
+        msg!(
+            "Counter {} is changed by {} instructions of this transaction",
+            counter_account.key,
+            changes
+        );
+        return Err(CounterError::DuplicateMutation.into());
+    }
+    Ok(())
+}
+
+/// The caller guard stored after the config in `account`, which has
+/// already been validated by [`load_config`]
+fn load_caller_guard(account: &AccountInfo) -> Result<CallerGuard, ProgramError> {
+    let data = account.data.borrow();
+    let guard: CallerGuard = match data.get(Config::LEN..) {
+        Some(region) if !region.is_empty() => deserialize_account(account, region)?,
+        _ => return Ok(CallerGuard::default()),
+    };
+    let longest = guard
+        .allowed_programs
+        .len()
+        .max(guard.forbidden_programs.len());
+    if longest > CallerGuard::MAX_ALLOWED_PROGRAMS {
+        msg!(
+            "Caller guard in {} lists {} programs, at most {} are allowed",
+            account.key,
+            longest,
+            CallerGuard::MAX_ALLOWED_PROGRAMS
+        );
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(guard)
+}
+
+/// The fees stored after the caller guard in `account`, which has already
+/// been validated by [`load_config`]
+fn load_fees(account: &AccountInfo) -> Result<Fees, ProgramError> {
+    let start = Config::LEN + load_caller_guard(account)?.len();
+    let data = account.data.borrow();
+    let token: Option<FeeConfig> = match data.get(start..) {
+        Some(region) if !region.is_empty() => deserialize_account(account, region)?,
+        _ => return Ok(Fees::default()),
+    };
+    let mut fees = Fees { token, lamports: 0 };
+    // Configs from before the lamport fee end after the token fee
+    let start = start + fees.len() - 8;
+    if let Some(bytes) = data.get(start..start + 8) {
+        fees.lamports = u64::from_le_bytes(bytes.try_into().unwrap());
+    }
+    Ok(fees)
+}
+
+/// The epoch cap stored after the fees in `account`, which has already been
+/// validated by [`load_config`], or 0 if the config ends before it
+fn load_epoch_cap(account: &AccountInfo) -> Result<u64, ProgramError> {
+    let start = Config::LEN + load_caller_guard(account)?.len() + load_fees(account)?.len();
+    let data = account.data.borrow();
+    Ok(data
+        .get(start..start + 8)
+        .map_or(0, |bytes| u64::from_le_bytes(bytes.try_into().unwrap())))
+}
+
+/// The crank rewards stored after the epoch cap in `account`, which has
+/// already been validated by [`load_config`], reading any field the config
+/// ends before as 0
+fn load_crank_rewards(account: &AccountInfo) -> Result<CrankRewards, ProgramError> {
+    let start = Config::LEN + load_caller_guard(account)?.len() + load_fees(account)?.len() + 8;
+    let data = account.data.borrow();
+    let mut bytes = [0; CrankRewards::LEN];
+    if let Some(region) = data.get(start..) {
+        let len = region.len().min(CrankRewards::LEN);
+        bytes[..len].copy_from_slice(&region[..len]);
+    }
+    Ok(CrankRewards::try_from_slice(&bytes)?)
+}
+
+/// Overwrite the crank rewards after the epoch cap in `account`, which
+/// `store_config_tail` already made room for
+fn store_crank_rewards(account: &AccountInfo, crank: &CrankRewards) -> ProgramResult {
+    let start = Config::LEN + load_caller_guard(account)?.len() + load_fees(account)?.len() + 8;
+    let mut data = account.data.borrow_mut();
+    let region = data
+        .get_mut(start..start + CrankRewards::LEN)
+        .ok_or(CounterError::InvalidAccountLength)?;
+    crank.serialize(&mut &mut region[..])?;
+    Ok(())
+}
+
+/// The mint, owner and balance of the SPL Token or Token-2022 account `account`
+fn read_token_account(account: &AccountInfo) -> Result<(Pubkey, Pubkey, u64), ProgramError> {
+    if ![TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID].contains(account.owner) {
+        msg!(
+            "Token account {} is owned by {}, not a token program",
+            account.key,
+            account.owner
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    let data = account.data.borrow();
+    let key_at = |offset: usize| data.get(offset..offset + 32).map(Pubkey::try_from);
+    let balance = data
+        .get(TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()));
+    let (Some(Ok(mint)), Some(Ok(owner)), Some(balance)) = (
+        key_at(TOKEN_ACCOUNT_MINT_OFFSET),
+        key_at(TOKEN_ACCOUNT_OWNER_OFFSET),
+        balance,
+    ) else {
+        msg!("Account {} is not a token account", account.key);
+        return Err(ProgramError::InvalidAccountData);
+    };
+    Ok((mint, owner, balance))
+}
+
+/// Validate that `account` holds the burn account of `counter` and deserialize it
+fn load_burn(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    counter: &Pubkey,
+) -> Result<CounterBurn, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Burn account {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::CounterBurn)?;
+    if account.data_len() != CounterBurn::LEN {
+        msg!(
+            "Burn account {} holds {} bytes, expected {}",
+            account.key,
+            account.data_len(),
+            CounterBurn::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+
+    let burn: CounterBurn = deserialize_account(account, &account.data.borrow())?;
+    if burn.counter != *counter {
+        msg!(
+            "Burn account {} belongs to counter {}",
+            account.key,
+            burn.counter
+        );
+        return Err(CounterError::MissingBurn.into());
+    }
+    Ok(burn)
+}
+
+/// Validate that `account` holds the rewards account of `counter` and
+/// deserialize it
+fn load_rewards(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    counter: &Pubkey,
+) -> Result<CounterRewards, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Rewards account {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::CounterRewards)?;
+    if account.data_len() != CounterRewards::LEN {
+        msg!(
+            "Rewards account {} holds {} bytes, expected {}",
+            account.key,
+            account.data_len(),
+            CounterRewards::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+
+    // The account is sized for the most milestones, so unused space follows them
+    let rewards: CounterRewards = deserialize_account(account, &account.data.borrow())?;
+    if rewards.counter != *counter {
+        msg!(
+            "Rewards account {} belongs to counter {}",
+            account.key,
+            rewards.counter
+        );
+        return Err(CounterError::MissingRewards.into());
+    }
+    Ok(rewards)
+}
+
+/// Validate that `account` holds the escrow of `counter` and deserialize it
+fn load_escrow(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    counter: &Pubkey,
+) -> Result<CounterEscrow, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Escrow {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::CounterEscrow)?;
+    if account.data_len() != CounterEscrow::LEN {
+        msg!(
+            "Escrow {} holds {} bytes, expected {}",
+            account.key,
+            account.data_len(),
+            CounterEscrow::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+
+    let escrow: CounterEscrow = deserialize_account(account, &account.data.borrow())?;
+    if escrow.counter != *counter {
+        msg!(
+            "Escrow {} belongs to counter {}",
+            account.key,
+            escrow.counter
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(escrow)
+}
+
+/// Validate that `account` holds a deposit record of `escrow` and deserialize it
+fn load_escrow_deposit(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    escrow: &Pubkey,
+) -> Result<EscrowDeposit, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Deposit record {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::EscrowDeposit)?;
+    if account.data_len() != EscrowDeposit::LEN {
+        msg!(
+            "Deposit record {} holds {} bytes, expected {}",
+            account.key,
+            account.data_len(),
+            EscrowDeposit::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+
+    let deposit: EscrowDeposit = deserialize_account(account, &account.data.borrow())?;
+    if deposit.escrow != *escrow {
+        msg!(
+            "Deposit record {} belongs to escrow {}",
+            account.key,
+            deposit.escrow
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(deposit)
+}
+
+/// Validate that `account` holds the permit nonce account of `counter` and
+/// deserialize it
+fn load_permits(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    counter: &Pubkey,
+) -> Result<CounterPermits, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Permit nonce account {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::CounterPermits)?;
+    if account.data_len() != CounterPermits::LEN {
+        msg!(
+            "Permit nonce account {} holds {} bytes, expected {}",
+            account.key,
+            account.data_len(),
+            CounterPermits::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+
+    let permits: CounterPermits = deserialize_account(account, &account.data.borrow())?;
+    if permits.counter != *counter {
+        msg!(
+            "Permit nonce account {} belongs to counter {}",
+            account.key,
+            permits.counter
+        );
+        return Err(CounterError::MissingPermit.into());
+    }
+    Ok(permits)
+}
+
+/// Validate that `account` holds the cooldown of `counter` and deserialize it
+fn load_cooldown(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    counter: &Pubkey,
+) -> Result<CounterCooldown, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Cooldown {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::CounterCooldown)?;
+    if account.data_len() != CounterCooldown::LEN {
+        msg!(
+            "Cooldown {} holds {} bytes, expected {}",
+            account.key,
+            account.data_len(),
+            CounterCooldown::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+
+    let cooldown: CounterCooldown = deserialize_account(account, &account.data.borrow())?;
+    if cooldown.counter != *counter {
+        msg!(
+            "Cooldown {} belongs to counter {}",
+            account.key,
+            cooldown.counter
+        );
+        return Err(CounterError::MissingCooldown.into());
+    }
+    Ok(cooldown)
+}
+
+/// Validate that `account` holds the decay of `counter` and deserialize it
+fn load_decay(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    counter: &Pubkey,
+) -> Result<CounterDecay, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Decay {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::CounterDecay)?;
+    if account.data_len() != CounterDecay::LEN {
+        msg!(
+            "Decay {} holds {} bytes, expected {}",
+            account.key,
+            account.data_len(),
+            CounterDecay::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+
+    let decay: CounterDecay = deserialize_account(account, &account.data.borrow())?;
+    if decay.counter != *counter {
+        msg!("Decay {} belongs to counter {}", account.key, decay.counter);
+        return Err(CounterError::MissingDecay.into());
+    }
+    Ok(decay)
+}
+
+/// Validate that `account` holds a cooldown stamp and deserialize it
+///
+/// The caller has checked its address, which is derived from the counter
+/// and the actor.
+fn load_cooldown_stamp(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+) -> Result<CooldownStamp, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Cooldown stamp {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::CooldownStamp)?;
+    if account.data_len() != CooldownStamp::LEN {
+        msg!(
+            "Cooldown stamp {} holds {} bytes, expected {}",
+            account.key,
+            account.data_len(),
+            CooldownStamp::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+    deserialize_account(account, &account.data.borrow())
+}
+
+/// Validate that `account` holds the token gate of `counter` and deserialize it
+fn load_token_gate(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    counter: &Pubkey,
+) -> Result<CounterGate, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Token gate {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::CounterGate)?;
+    if account.data_len() != CounterGate::LEN {
+        msg!(
+            "Token gate {} holds {} bytes, expected {}",
+            account.key,
+            account.data_len(),
+            CounterGate::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+
+    let gate: CounterGate = deserialize_account(account, &account.data.borrow())?;
+    if gate.counter != *counter {
+        msg!(
+            "Token gate {} belongs to counter {}",
+            account.key,
+            gate.counter
+        );
+        return Err(CounterError::MissingTokenGate.into());
+    }
+    Ok(gate)
+}
+
+/// Validate that `account` is the program's fee vault and deserialize it
+fn load_fee_vault(program_id: &Pubkey, account: &AccountInfo) -> Result<FeeVault, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Fee vault {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::FeeVault)?;
+    if account.data_len() != FeeVault::LEN {
+        msg!(
+            "Fee vault {} holds {} bytes, expected {}",
+            account.key,
+            account.data_len(),
+            FeeVault::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+    let (address, _) = find_fee_vault_address(program_id);
+    if *account.key != address {
+        msg!(
+            "Fee vault {} does not match the derived address {}",
+            account.key,
+            address
+        );
+        return Err(CounterError::InvalidFeeAccount.into());
+    }
+    deserialize_account(account, &account.data.borrow())
+}
+
+/// Create a multisig account that requires `m` of `signers` to approve
+fn process_initialize_multisig(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    m: u8,
+    signers: Vec<Pubkey>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let multisig_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    let multisig = Multisig::new(m, &signers).inspect_err(|_| {
+        msg!(
+            "A multisig needs 1 <= m <= n <= {} signers, got m = {} and n = {}",
+            MAX_SIGNERS,
+            m,
+            signers.len()
+        )
+    })?;
+
+    create_program_account(
+        program_id,
+        multisig_account,
+        payer,
+        system_program,
+        Multisig::LEN,
+        &[],
+    )?;
+    multisig.serialize(&mut *multisig_account.data.borrow_mut())?;
+
+    msg!(
+        "Multisig initialized requiring {} of {} signers",
+        m,
+        multisig.n
+    );
+
+    Ok(())
+}
+
+/// Create a vote account with both tallies at zero
+fn process_initialize_votes(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let votes_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    create_program_account(
+        program_id,
+        votes_account,
+        payer,
+        system_program,
+        VoteCounter::LEN,
+        &[],
+    )?;
+    VoteCounter::new().serialize(&mut *votes_account.data.borrow_mut())?;
+
+    msg!("Vote account {} initialized", votes_account.key);
+
+    Ok(())
+}
+
+/// Add `amount` to the upvote tally if `up` is set, otherwise to the downvote tally
+///
+/// Like increments, votes are open to everyone.
+fn process_vote(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u32,
+    up: bool,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let votes_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    let config = load_config(program_id, config_account)?;
+    config.check_active()?;
+    config.check_amount(amount)?;
+
+    let mut votes = load_votes(program_id, votes_account)?;
+    let tally = if up { &mut votes.up } else { &mut votes.down };
+    *tally = tally
+        .checked_add(amount.into())
+        .ok_or(CounterError::Overflow)?;
+
+    votes.serialize(&mut *votes_account.data.borrow_mut())?;
+    msg!(
+        "Votes: {} up, {} down, net {}",
+        votes.up,
+        votes.down,
+        votes.net()
+    );
+
+    Ok(())
+}
+
+/// Create an empty counter map, making the payer its authority
+fn process_initialize_map(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let map_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    create_program_account(
+        program_id,
+        map_account,
+        payer,
+        system_program,
+        CounterMap::BASE_LEN,
+        &[],
+    )?;
+    CounterMap::new(*payer.key).serialize(&mut *map_account.data.borrow_mut())?;
+
+    msg!("Counter map {} initialized", map_account.key);
+
+    Ok(())
+}
+
+/// Add a zeroed entry called `name` to the map, growing the account to fit
+fn process_add_map_entry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    name: [u8; 32],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let map_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    let mut map = load_map(program_id, map_account)?;
+    assert_map_authority(program_id, &map, authority, accounts_iter.as_slice())?;
+    if map.position(&name).is_some() {
+        return Err(CounterError::EntryExists.into());
+    }
+
+    map.entries.push(MapEntry { name, count: 0 });
+    resize_account(map_account, payer, system_program, map.len())?;
+    map.serialize(&mut *map_account.data.borrow_mut())?;
+
+    msg!("Map now holds {} entries", map.entries.len());
+
+    Ok(())
+}
+
+/// Remove the entry called `name` from the map, shrinking the account and
+/// sending the rent it no longer needs to the destination
+fn process_remove_map_entry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    name: [u8; 32],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let map_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let destination = next_account_info(accounts_iter)?;
+
+    let mut map = load_map(program_id, map_account)?;
+    assert_map_authority(program_id, &map, authority, accounts_iter.as_slice())?;
+    let index = map.position(&name).ok_or(CounterError::EntryNotFound)?;
+    if map_account.key == destination.key {
+        msg!("Cannot refund a map into itself");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    map.entries.remove(index);
+    map_account.realloc(map.len(), false)?;
+    map.serialize(&mut *map_account.data.borrow_mut())?;
+
+    let surplus = map_account
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(map.len()));
+    **map_account.lamports.borrow_mut() -= surplus;
+    **destination.lamports.borrow_mut() = destination
+        .lamports()
+        .checked_add(surplus)
+        .ok_or(CounterError::Overflow)?;
+
+    msg!(
+        "Map now holds {} entries, {} lamports refunded",
+        map.entries.len(),
+        surplus
+    );
+
+    Ok(())
+}
+
+/// Add `amount` to the map entry called `name`
+fn process_increment_map_entry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    name: [u8; 32],
+    amount: u32,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let map_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    let config = load_config(program_id, config_account)?;
+    config.check_active()?;
+    config.check_amount(amount)?;
+
+    let mut map = load_map(program_id, map_account)?;
+    let entry = map.entry_mut(&name)?;
+    entry.count = entry
+        .count
+        .checked_add(amount.into())
+        .ok_or(CounterError::Overflow)?;
+    let count = entry.count;
+
+    map.serialize(&mut *map_account.data.borrow_mut())?;
+    msg!("Map entry updated to {}", count);
+
+    Ok(())
+}
+
+/// Subtract `amount` from the map entry called `name`, provided the map's authority approved
+fn process_decrement_map_entry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    name: [u8; 32],
+    amount: u32,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let map_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    let config = load_config(program_id, config_account)?;
+    config.check_active()?;
+    config.check_amount(amount)?;
+
+    let mut map = load_map(program_id, map_account)?;
+    assert_map_authority(program_id, &map, authority, accounts_iter.as_slice())?;
+    let entry = map.entry_mut(&name)?;
+    entry.count = entry
+        .count
+        .checked_sub(amount.into())
+        .ok_or(CounterError::Underflow)?;
+    let count = entry.count;
+
+    map.serialize(&mut *map_account.data.borrow_mut())?;
+    msg!("Map entry updated to {}", count);
+
+    Ok(())
+}
+
+/// Upgrade a counter written in the pre-discriminator layout to the current one
+fn process_migrate_legacy(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    if account.owner != program_id {
+        msg!(
+            "Counter account {} is not owned by this program",
+            account.key
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    if account.data_len() != LegacyCounter::LEN {
+        msg!(
+            "Counter account {} holds {} bytes, expected a {}-byte legacy counter",
+            account.key,
+            account.data_len(),
+            LegacyCounter::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+
+    let legacy: LegacyCounter = deserialize_account(account, &account.data.borrow())?;
+    let counter = Counter::from(legacy);
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    resize_account(account, payer, system_program, Counter::LEN)?;
+    store_counter(account, &counter);
+
+    msg!("Counter {} migrated to the current layout", account.key);
+
+    Ok(())
+}
+
+/// Upgrade a counter stored in any older layout to the current one
+///
+/// The layout is recognised by the account's size, then by its discriminator
+/// and version byte where the layout has them.
+fn process_migrate(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    if account.owner != program_id {
+        msg!(
+            "Counter account {} is not owned by this program",
+            account.key
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+
+    let data_len = account.data_len();
+    let version = account.data.borrow().get(COUNTER_VERSION_OFFSET).copied();
+    // Bytes stored after the counter itself, which move along with it
+    let mut trailing = Vec::new();
+    let counter = if data_len >= Counter::LEN && version == Some(Counter::VERSION) {
+        // Still validates the discriminator and the rest of the layout
+        load_counter(program_id, account)?;
+        msg!("Counter {} already uses the current layout", account.key);
+        return Ok(());
+    } else if data_len >= CounterV13::LEN && version == Some(13) {
+        let data = account.data.borrow();
+        let v13: CounterV13 = bytemuck::pod_read_unaligned(&data[..CounterV13::LEN]);
+        check_discriminator(account, &v13.discriminator)?;
+        trailing = data[CounterV13::LEN..].to_vec();
+        Counter::from(v13)
+    } else if data_len >= CounterV12::LEN && version == Some(12) {
+        let data = account.data.borrow();
+        let v12: CounterV12 = bytemuck::pod_read_unaligned(&data[..CounterV12::LEN]);
+        check_discriminator(account, &v12.discriminator)?;
+        trailing = data[CounterV12::LEN..].to_vec();
+        Counter::from(CounterV13::from(v12))
+    } else if data_len >= CounterV11::LEN && version == Some(11) {
+        let data = account.data.borrow();
+        let v11: CounterV11 = bytemuck::pod_read_unaligned(&data[..CounterV11::LEN]);
+        check_discriminator(account, &v11.discriminator)?;
+        trailing = data[CounterV11::LEN..].to_vec();
+        Counter::from(CounterV13::from(v11))
+    } else if data_len >= CounterV10::LEN && version == Some(10) {
+        let data = account.data.borrow();
+        let v10: CounterV10 = bytemuck::pod_read_unaligned(&data[..CounterV10::LEN]);
+        check_discriminator(account, &v10.discriminator)?;
+        trailing = data[CounterV10::LEN..].to_vec();
+        Counter::from(CounterV13::from(v10))
+    } else if data_len >= CounterV9::LEN && version == Some(9) {
+        let data = account.data.borrow();
+        let v9: CounterV9 = bytemuck::pod_read_unaligned(&data[..CounterV9::LEN]);
+        check_discriminator(account, &v9.discriminator)?;
+        trailing = data[CounterV9::LEN..].to_vec();
+        Counter::from(CounterV13::from(v9))
+    } else if data_len >= CounterV8::LEN && version == Some(8) {
+        let data = account.data.borrow();
+        let v8: CounterV8 = bytemuck::pod_read_unaligned(&data[..CounterV8::LEN]);
+        check_discriminator(account, &v8.discriminator)?;
+        trailing = data[CounterV8::LEN..].to_vec();
+        Counter::from(CounterV13::from(v8))
+    } else if data_len >= CounterV7::LEN && version == Some(7) {
+        let data = account.data.borrow();
+        let v7: CounterV7 = bytemuck::pod_read_unaligned(&data[..CounterV7::LEN]);
+        check_discriminator(account, &v7.discriminator)?;
+        trailing = data[CounterV7::LEN..].to_vec();
+        Counter::from(CounterV13::from(v7))
+    } else if data_len >= CounterV6::LEN && version == Some(6) {
+        let data = account.data.borrow();
+        let v6: CounterV6 = bytemuck::pod_read_unaligned(&data[..CounterV6::LEN]);
+        check_discriminator(account, &v6.discriminator)?;
+        trailing = data[CounterV6::LEN..].to_vec();
+        Counter::from(CounterV13::from(v6))
+    } else if data_len >= CounterV5::LEN && version == Some(5) {
+        // Checked before version 4, which has the same size
+        let data = account.data.borrow();
+        let v5: CounterV5 = bytemuck::pod_read_unaligned(&data[..CounterV5::LEN]);
+        check_discriminator(account, &v5.discriminator)?;
+        trailing = data[CounterV5::LEN..].to_vec();
+        Counter::from(CounterV13::from(v5))
+    } else if data_len == CounterV4::LEN || data_len == CounterV4::V3_LEN {
+        // Version 3 is version 4 without the reserved block, which reads as zeroes
+        let mut data = account.data.borrow().to_vec();
+        data.resize(CounterV4::LEN, 0);
+        let v4: CounterV4 = deserialize_account(account, &data)?;
+        check_discriminator(account, &v4.discriminator)?;
+        let expected = if data_len == CounterV4::LEN { 4 } else { 3 };
+        if v4.version != expected {
+            msg!(
+                "Counter account {} is {} bytes long but has layout version {}",
+                account.key,
+                data_len,
+                v4.version
+            );
+            return Err(CounterError::UnsupportedVersion.into());
+        }
+        Counter::from(v4)
+    } else if data_len == LegacyCounter::LEN {
+        // Nothing marks these bytes as a counter, so only its stored authority
+        // may vouch for them
+        let legacy: LegacyCounter = deserialize_account(account, &account.data.borrow())?;
+        let counter = Counter::from(legacy);
+        assert_authority(program_id, &counter, payer, &[])?;
+        counter
+    } else if data_len == CounterV1::LEN {
+        let v1: CounterV1 = deserialize_account(account, &account.data.borrow())?;
+        check_discriminator(account, &v1.discriminator)?;
+        Counter::from(v1)
+    } else if data_len == CounterV2::LEN {
+        let v2: CounterV2 = deserialize_account(account, &account.data.borrow())?;
+        check_discriminator(account, &v2.discriminator)?;
+        if v2.version != 2 {
+            msg!(
+                "Counter account {} is {} bytes long but has layout version {}",
+                account.key,
+                data_len,
+                v2.version
+            );
+            return Err(CounterError::UnsupportedVersion.into());
+        }
+        Counter::from(v2)
+    } else {
+        msg!(
+            "Counter account {} holds {} bytes, which matches no known layout",
+            account.key,
+            data_len
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    };
+
+    resize_account(
+        account,
+        payer,
+        system_program,
+        Counter::LEN + trailing.len(),
+    )?;
+    store_counter(account, &counter);
+    account.data.borrow_mut()[Counter::LEN..].copy_from_slice(&trailing);
+
+    msg!(
+        "Counter {} migrated to version {}",
+        account.key,
+        Counter::VERSION
+    );
+
+    Ok(())
+}
+
+/// Grow the counter account to `len` bytes, provided its authority approved
+///
+/// Optional data kept after the counter needs room that accounts created
+/// before it existed do not have; the payer tops up the rent for it.
+fn process_resize(program_id: &Pubkey, accounts: &[AccountInfo], len: u32) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    // Copied out, as the data cannot stay borrowed across the realloc
+    let counter = *load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    let len = len as usize;
+    if len < account.data_len() {
+        msg!(
+            "Counter account {} holds {} bytes and cannot shrink to {}",
+            account.key,
+            account.data_len(),
+            len
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+    resize_account(account, payer, system_program, len)?;
+
+    msg!("Counter account {} resized to {} bytes", account.key, len);
+
+    Ok(())
+}
+
+/// Store `name` and `description` after the counter, provided its authority approved
+///
+/// The account only ever grows here, so space added by `Resize` is kept;
+/// whatever follows the new metadata is zeroed.
+fn process_set_metadata(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    name: String,
+    description: String,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    let counter = *load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    let metadata = CounterMetadata::new(name, description).inspect_err(|_| {
+        msg!(
+            "Names hold at most {} bytes and descriptions at most {}",
+            CounterMetadata::MAX_NAME_LEN,
+            CounterMetadata::MAX_DESCRIPTION_LEN
+        )
+    })?;
+    let len = account.data_len().max(Counter::LEN + metadata.len());
+    resize_account(account, payer, system_program, len)?;
+
+    let mut data = account.data.borrow_mut();
+    let region = &mut data[Counter::LEN..];
+    region.fill(0);
+    metadata.serialize(&mut &mut region[..])?;
+
+    msg!("Counter {} labelled {:?}", account.key, metadata.name);
+
+    Ok(())
+}
+
+/// Create an empty shard `index` of the counter, provided its authority approved
+fn process_initialize_shard(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    index: u16,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let shard_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    let counter = *load_counter(program_id, counter_account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+    check_shardable(counter_account, &counter)?;
+
+    let (address, bump) = find_shard_address(program_id, counter_account.key, index);
+    if *shard_account.key != address {
+        msg!(
+            "Shard account {} does not match the derived address {}",
+            shard_account.key,
+            address
+        );
+        return Err(CounterError::InvalidShard.into());
+    }
+
+    create_program_account(
+        program_id,
+        shard_account,
+        payer,
+        system_program,
+        CounterShard::LEN,
+        &[&[
+            SHARD_SEED,
+            counter_account.key.as_ref(),
+            &index.to_le_bytes(),
+            &[bump],
+        ]],
+    )?;
+    CounterShard::new(*counter_account.key, index)
+        .serialize(&mut *shard_account.data.borrow_mut())?;
+
+    msg!(
+        "Shard {} of counter {} initialized",
+        index,
+        counter_account.key
+    );
+
+    Ok(())
+}
+
+/// Add `amount` to a shard, under the same rules as an increment of its counter
+///
+/// The counter is only read, so that increments to different shards can be
+/// processed in parallel. Nothing is charged for them, so they are refused
+/// while the config charges fees.
+fn process_increment_shard(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u32,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let shard_account = next_account_info(accounts_iter)?;
+    let counter_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    let config = load_config(program_id, config_account)?;
+    config.check_active()?;
+    config.check_amount(amount)?;
+    let fees = load_fees(config_account)?;
+    if fees.token.is_some() || fees.lamports > 0 {
+        msg!("The config charges fees for every increment, which shards would bypass");
+        return Err(CounterError::NotShardable.into());
+    }
+
+    let counter = *load_counter(program_id, counter_account)?;
+    check_shardable(counter_account, &counter)?;
+    counter.check_step(amount)?;
+
+    let mut shard = load_shard(program_id, shard_account, counter_account.key)?;
+    shard.count = shard
+        .count
+        .checked_add(amount.into())
+        .ok_or(CounterError::Overflow)?;
+
+    shard.serialize(&mut *shard_account.data.borrow_mut())?;
+    msg!("Shard {} updated to {}", shard.index, shard.count);
+
+    Ok(())
+}
+
+/// Move the tallies of the given shards into their counter, leaving the shards at zero
+///
+/// A shard passed twice is drained the first time and contributes nothing
+/// the second. The total is a change like any other: it starts the counter
+/// over in a new epoch, counts towards the epoch cap, goes into the history
+/// and may fire the callback.
+fn process_aggregate(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    let config = load_config(program_id, config_account)?;
+    config.check_active()?;
+
+    // Validation put a shard first; the history and callback accounts after
+    // the shards are told apart by their data
+    let rest = accounts_iter.as_slice();
+    let shards = 1 + rest[1..]
+        .iter()
+        .take_while(|account| {
+            account
+                .try_borrow_data()
+                .is_ok_and(|data| CounterShard::is_shard(&data))
+        })
+        .count();
+    let (shard_accounts, trailing) = rest.split_at(shards);
+    let (history, callback, _) = split_trailing(trailing);
+
+    // Drain the shards before borrowing the counter, so passing the counter
+    // as a shard fails the shard checks instead of a double borrow
+    let mut total: u64 = 0;
+    for shard_account in shard_accounts {
+        let mut shard = load_shard(program_id, shard_account, counter_account.key)?;
+        total = total
+            .checked_add(shard.count)
+            .ok_or(CounterError::Overflow)?;
+        shard.count = 0;
+        shard.serialize(&mut *shard_account.data.borrow_mut())?;
+    }
+
+    check_shardable(
+        counter_account,
+        &*load_counter(program_id, counter_account)?,
+    )?;
+    reset_for_epoch(program_id, counter_account)?;
+    let mut counter = load_counter(program_id, counter_account)?;
+    let old_value = counter.value();
+    counter.absorb(total)?;
+    record_update(&mut counter, old_value)?;
+    record_history(
+        program_id,
+        counter_account,
+        &counter,
+        history,
+        &[(HistoryOp::Aggregate, total)],
+        &Pubkey::default(),
+    )?;
+    emit_update(
+        counter_account,
+        &counter,
+        Operation::Aggregate,
+        old_value,
+        &Pubkey::default(),
+        None,
+    );
+
+    msg!(
+        "Aggregated {} from {} shards, counter now holds {}",
+        total,
+        shards,
+        counter.value()
+    );
+
+    // Released first, as the callback may be passed the counter
+    drop(counter);
+    track_epoch_change(program_id, accounts, counter_account, old_value)?;
+    fire_callback(program_id, counter_account, old_value, callback)
+}
+
+/// Reject sharding a counter whose increments are checked, charged or
+/// followed up by accounts that increments of a shard do not pass: a token
+/// gate, permits, a cooldown, burning, milestone rewards, hooks or decay
+///
+/// Shards filled before one of them was set cannot be aggregated until it
+/// is cleared again.
+fn check_shardable(counter_account: &AccountInfo, counter: &Counter) -> ProgramResult {
+    let modes = [
+        (CounterFlag::TokenGate, "a token gate"),
+        (CounterFlag::Permits, "permits"),
+        (CounterFlag::Cooldown, "a cooldown"),
+        (CounterFlag::Burn, "burning"),
+        (CounterFlag::Rewards, "milestone rewards"),
+        (CounterFlag::Hooks, "hooks"),
+        (CounterFlag::Decay, "decay"),
+    ];
+    if let Some((_, mode)) = modes.into_iter().find(|&(flag, _)| counter.has_flag(flag)) {
+        msg!(
+            "Counter {} has {}, which increments of its shards would bypass",
+            counter_account.key,
+            mode
+        );
+        return Err(CounterError::NotShardable.into());
+    }
+    Ok(())
+}
+
+/// Create the counter's history account, provided its authority approved
+fn process_initialize_history(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    capacity: u16,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let history_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    // Copied out, as the data cannot stay borrowed across the CPI
+    let counter = *load_counter(program_id, counter_account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    if capacity == 0 || capacity > CounterHistory::MAX_CAPACITY {
+        msg!(
+            "History capacity must be between 1 and {}, got {}",
+            CounterHistory::MAX_CAPACITY,
+            capacity
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (address, bump) = find_history_address(program_id, counter_account.key);
+    if *history_account.key != address {
+        msg!(
+            "History account {} does not match the derived address {}",
+            history_account.key,
+            address
+        );
+        return Err(CounterError::InvalidHistory.into());
+    }
+
+    create_program_account(
+        program_id,
+        history_account,
+        payer,
+        system_program,
+        CounterHistory::len(capacity),
+        &[&[HISTORY_SEED, counter_account.key.as_ref(), &[bump]]],
+    )?;
+    CounterHistory::new(*counter_account.key, capacity)
+        .serialize(&mut &mut history_account.data.borrow_mut()[..])?;
+    load_counter(program_id, counter_account)?.set_flag(CounterFlag::History, true);
+
+    msg!(
+        "History of counter {} keeps the last {} changes",
+        counter_account.key,
+        capacity
+    );
+
+    Ok(())
+}
+
+/// Arm the counter's callback, creating its callback account on first use,
+/// provided the counter's authority signed
+fn process_set_callback(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    target: i128,
+    program: Pubkey,
+    callback_accounts: Vec<CallbackAccount>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let callback_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    // Copied out, as the data cannot stay borrowed across the CPI
+    let counter = *load_counter(program_id, counter_account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    if callback_accounts.len() > CounterCallback::MAX_ACCOUNTS {
+        msg!(
+            "A callback takes at most {} accounts, got {}",
+            CounterCallback::MAX_ACCOUNTS,
+            callback_accounts.len()
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    if program == *program_id {
+        msg!("A counter's callback cannot be this program");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if !(counter.min_value()..=counter.max_value()).contains(&target) {
+        msg!(
+            "Target {} is outside the counter's range {}..={}",
+            target,
+            counter.min_value(),
+            counter.max_value()
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (address, bump) = find_callback_address(program_id, counter_account.key);
+    if *callback_account.key != address {
+        msg!(
+            "Callback account {} does not match the derived address {}",
+            callback_account.key,
+            address
+        );
+        return Err(CounterError::InvalidCallback.into());
+    }
+
+    // Replaced in place once it exists
+    if callback_account.owner != program_id {
+        create_program_account(
+            program_id,
+            callback_account,
+            payer,
+            system_program,
+            CounterCallback::LEN,
+            &[&[CALLBACK_SEED, counter_account.key.as_ref(), &[bump]]],
+        )?;
+    } else {
+        load_callback(program_id, callback_account, counter_account.key)?;
+    }
+    CounterCallback {
+        discriminator: CounterCallback::DISCRIMINATOR,
+        counter: *counter_account.key,
+        bump,
+        target,
+        program,
+        accounts: callback_accounts,
+    }
+    .serialize(&mut &mut callback_account.data.borrow_mut()[..])?;
+    load_counter(program_id, counter_account)?.set_flag(CounterFlag::Callback, true);
+
+    msg!(
+        "Counter {} invokes {} once it reaches {}",
+        counter_account.key,
+        program,
+        target
+    );
+
+    Ok(())
+}
+
+/// Add `operator` to the counter's operator list, or replace its entry,
+/// provided the counter's authority signed
+fn process_add_operator(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    operator: Operator,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let operators_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    // Copied out, as the data cannot stay borrowed across the CPI
+    let counter = *load_counter(program_id, counter_account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    let (address, bump) = find_operators_address(program_id, counter_account.key);
+    if *operators_account.key != address {
+        msg!(
+            "Operator list {} does not match the derived address {}",
+            operators_account.key,
+            address
+        );
+        return Err(CounterError::UnknownOperator.into());
+    }
+    let mut list = if operators_account.owner != program_id {
+        create_program_account(
+            program_id,
+            operators_account,
+            payer,
+            system_program,
+            CounterOperators::LEN,
+            &[&[OPERATORS_SEED, counter_account.key.as_ref(), &[bump]]],
+        )?;
+        CounterOperators {
+            discriminator: CounterOperators::DISCRIMINATOR,
+            counter: *counter_account.key,
+            bump,
+            operators: Vec::new(),
+        }
+    } else {
+        load_operators(program_id, operators_account, counter_account.key)?
+    };
+    match list
+        .operators
+        .iter()
+        .position(|existing| existing.key == operator.key)
+    {
+        Some(index) => list.operators[index] = operator,
+        None if list.operators.len() >= CounterOperators::MAX_OPERATORS => {
+            msg!(
+                "Counter {} already has {} operators",
+                counter_account.key,
+                CounterOperators::MAX_OPERATORS
+            );
+            return Err(CounterError::TooManyOperators.into());
+        }
+        None => list.operators.push(operator),
+    }
+    list.serialize(&mut &mut operators_account.data.borrow_mut()[..])?;
+
+    msg!(
+        "{} may decrement by up to {} until slot {}",
+        operator.key,
+        operator.allowance,
+        operator.expiry_slot
+    );
+
+    Ok(())
+}
+
+/// Remove `operator` from the counter's operator list, provided the
+/// counter's authority signed
+fn process_remove_operator(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    operator: &Pubkey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let operators_account = next_account_info(accounts_iter)?;
+
+    let counter = load_counter(program_id, counter_account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+    let mut list = load_operators(program_id, operators_account, counter_account.key)?;
+    let Some(index) = list
+        .operators
+        .iter()
+        .position(|existing| existing.key == *operator)
+    else {
+        msg!(
+            "{} is not an operator of counter {}",
+            operator,
+            counter_account.key
+        );
+        return Err(CounterError::UnknownOperator.into());
+    };
+    list.operators.remove(index);
+    // Zero what the shorter list leaves behind, keeping the data canonical
+    let mut data = operators_account.data.borrow_mut();
+    data.fill(0);
+    list.serialize(&mut &mut data[..])?;
+
+    msg!(
+        "{} is no longer an operator of {}",
+        operator,
+        counter_account.key
+    );
+
+    Ok(())
+}
+
+/// Disarm the counter's callback and close its callback account, provided
+/// the counter's authority signed
+fn process_clear_callback(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    clear_companion(
+        program_id,
+        accounts,
+        "Callback",
+        |counter_account, callback_account, counter| {
+            load_callback(program_id, callback_account, counter_account.key)?;
+            counter.set_flag(CounterFlag::Callback, false);
+            Ok(())
+        },
+    )
+}
+
+/// Set the counter's hook programs, provided the counter's authority signed
+fn process_set_hooks(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    pre: Option<Pubkey>,
+    post: Option<Pubkey>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let hooks_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    // Copied out, as the data cannot stay borrowed across the CPI
+    let counter = *load_counter(program_id, counter_account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    if pre.is_none() && post.is_none() {
+        msg!("Set at least one hook; ClearHooks removes them");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if pre == Some(*program_id) || post == Some(*program_id) {
+        msg!("A counter's hook cannot be this program");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (address, bump) = find_hooks_address(program_id, counter_account.key);
+    if *hooks_account.key != address {
+        msg!(
+            "Hooks account {} does not match the derived address {}",
+            hooks_account.key,
+            address
+        );
+        return Err(CounterError::InvalidHooks.into());
+    }
+
+    // Replaced in place once it exists
+    if hooks_account.owner != program_id {
+        create_program_account(
+            program_id,
+            hooks_account,
+            payer,
+            system_program,
+            CounterHooks::LEN,
+            &[&[HOOKS_SEED, counter_account.key.as_ref(), &[bump]]],
+        )?;
+    } else {
+        load_hooks(program_id, hooks_account, counter_account.key)?;
+    }
+    let mut data = hooks_account.data.borrow_mut();
+    // Zero what a shorter encoding leaves behind
+    data.fill(0);
+    CounterHooks {
+        discriminator: CounterHooks::DISCRIMINATOR,
+        counter: *counter_account.key,
+        bump,
+        pre,
+        post,
+    }
+    .serialize(&mut &mut data[..])?;
+    load_counter(program_id, counter_account)?.set_flag(CounterFlag::Hooks, true);
+
+    msg!(
+        "Counter {} hooked, pre {:?}, post {:?}",
+        counter_account.key,
+        pre,
+        post
+    );
+
+    Ok(())
+}
+
+/// Remove the counter's hooks and close its hooks account, provided the
+/// counter's authority signed
+fn process_clear_hooks(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    clear_companion(
+        program_id,
+        accounts,
+        "Hooks",
+        |counter_account, hooks_account, counter| {
+            load_hooks(program_id, hooks_account, counter_account.key)?;
+            counter.set_flag(CounterFlag::Hooks, false);
+            Ok(())
+        },
+    )
+}
+
+/// Gate the counter's increments on holding `min_balance` tokens of `mint`,
+/// provided the counter's authority signed
+fn process_set_token_gate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    mint: Pubkey,
+    min_balance: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let gate_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    // Copied out, as the data cannot stay borrowed across the CPI
+    let counter = *load_counter(program_id, counter_account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    if min_balance == 0 {
+        msg!("A token gate needs a positive balance; ClearTokenGate removes it");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (address, bump) = find_token_gate_address(program_id, counter_account.key);
+    if *gate_account.key != address {
+        msg!(
+            "Token gate {} does not match the derived address {}",
+            gate_account.key,
+            address
+        );
+        return Err(CounterError::MissingTokenGate.into());
+    }
+
+    // Replaced in place once it exists
+    if gate_account.owner != program_id {
+        create_program_account(
+            program_id,
+            gate_account,
+            payer,
+            system_program,
+            CounterGate::LEN,
+            &[&[TOKEN_GATE_SEED, counter_account.key.as_ref(), &[bump]]],
+        )?;
+    } else {
+        load_token_gate(program_id, gate_account, counter_account.key)?;
+    }
+    CounterGate {
+        discriminator: CounterGate::DISCRIMINATOR,
+        counter: *counter_account.key,
+        mint,
+        min_balance,
+    }
+    .serialize(&mut &mut gate_account.data.borrow_mut()[..])?;
+    load_counter(program_id, counter_account)?.set_flag(CounterFlag::TokenGate, true);
+
+    msg!(
+        "Increments of counter {} need {} tokens of mint {}",
+        counter_account.key,
+        min_balance,
+        mint
+    );
+
+    Ok(())
+}
+
+/// Remove the counter's token gate and close its account, provided the
+/// counter's authority signed
+fn process_clear_token_gate(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    clear_companion(
+        program_id,
+        accounts,
+        "Token gate",
+        |counter_account, gate_account, counter| {
+            load_token_gate(program_id, gate_account, counter_account.key)?;
+            counter.set_flag(CounterFlag::TokenGate, false);
+            Ok(())
+        },
+    )
+}
+
+/// Hand the counter to whoever holds the NFT `mint`, provided the counter's
+/// authority signed
+fn process_set_nft_authority(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    mint: Pubkey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let mint_account = next_account_info(accounts_iter)?;
+
+    let mut counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    if *mint_account.key != mint
+        || ![TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID].contains(mint_account.owner)
+    {
+        msg!(
+            "Account {} is not the token mint {}",
+            mint_account.key,
+            mint
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    // Only a single indivisible token makes the holder unambiguous
+    let data = mint_account.data.borrow();
+    let supply = data
+        .get(MINT_SUPPLY_OFFSET..MINT_SUPPLY_OFFSET + 8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()));
+    let decimals = data.get(MINT_DECIMALS_OFFSET).copied();
+    if supply != Some(1) || decimals != Some(0) {
+        msg!(
+            "Mint {} is not an NFT: supply {:?} with {:?} decimals",
+            mint,
+            supply,
+            decimals
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    counter.authority = mint;
+    counter.pending_authority = Pubkey::default();
+    counter.set_flag(CounterFlag::NftAuthority, true);
+
+    msg!("Counter authority is now the holder of NFT {}", mint);
+
+    Ok(())
+}
+
+/// Hand a counter or the program config to an SPL Governance governance, or
+/// its native treasury, provided the current authority or admin signed
+fn process_set_governance_authority(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    governance: Pubkey,
+    treasury: bool,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let governance_account = next_account_info(accounts_iter)?;
+
+    // A governance program writes the account type first and the realm the
+    // governance belongs to right after it
+    let data = governance_account.data.borrow();
+    let is_governance = data
+        .first()
+        .is_some_and(|account_type| GOVERNANCE_ACCOUNT_TYPES.contains(account_type));
+    let realm = data
+        .get(1..33)
+        .and_then(|bytes| Pubkey::try_from(bytes).ok())
+        .filter(|realm| *realm != Pubkey::default());
+    let Some(realm) = realm.filter(|_| *governance_account.key == governance && is_governance)
+    else {
+        msg!(
+            "Account {} is not the governance account {}",
+            governance_account.key,
+            governance
+        );
+        return Err(ProgramError::InvalidArgument);
+    };
+    let new_authority = if treasury {
+        find_native_treasury_address(governance_account.owner, &governance).0
+    } else {
+        governance
+    };
+
+    if AccountType::of(&account.data.borrow()) == Some(AccountType::Config) {
+        let mut config = load_config(program_id, account)?;
+        if config.discriminator != Config::DISCRIMINATOR {
+            msg!("Config account {} is not initialized", account.key);
+            return Err(CounterError::UninitializedAccount.into());
+        }
+        if config.admin != *authority.key {
+            msg!("{} is not the config admin", authority.key);
+            return Err(CounterError::Unauthorized.into());
+        }
+        if !authority.is_signer {
+            msg!("The config admin {} must sign", authority.key);
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        config.admin = new_authority;
+        config.serialize(&mut *account.data.borrow_mut())?;
+        msg!(
+            "Config admin is now {}, of governance {} in realm {}",
+            new_authority,
+            governance,
+            realm
+        );
+        return Ok(());
+    }
+
+    let mut counter = load_counter(program_id, account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    counter.authority = new_authority;
+    counter.pending_authority = Pubkey::default();
+    counter.set_flag(CounterFlag::NftAuthority, false);
+
+    msg!(
+        "Counter authority is now {}, of governance {} in realm {}",
+        new_authority,
+        governance,
+        realm
+    );
+
+    Ok(())
+}
+
+/// Make a burn-backed counter's mint the tokens increments of it burn,
+/// provided the counter's authority signed
+fn process_set_burn_mint(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    mint: Pubkey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let burn_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    // Copied out, as the data cannot stay borrowed across the CPI
+    let counter = *load_counter(program_id, counter_account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    // Anything else could let the count drift from the tokens burned
+    if counter.value() != 0
+        || counter.overflow_mode() != OverflowMode::Checked
+        || counter.modulus != 0
+    {
+        msg!(
+            "Counter {} must be at zero and checked, without a modulus, to be burn-backed",
+            counter_account.key
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (address, bump) = find_burn_address(program_id, counter_account.key);
+    if *burn_account.key != address {
+        msg!(
+            "Burn account {} does not match the derived address {}",
+            burn_account.key,
+            address
+        );
+        return Err(CounterError::MissingBurn.into());
+    }
+
+    // Fails if the counter is burn-backed already, so its mint never changes
+    create_program_account(
+        program_id,
+        burn_account,
+        payer,
+        system_program,
+        CounterBurn::LEN,
+        &[&[BURN_SEED, counter_account.key.as_ref(), &[bump]]],
+    )?;
+    CounterBurn {
+        discriminator: CounterBurn::DISCRIMINATOR,
+        counter: *counter_account.key,
+        mint,
+        burned: 0,
+    }
+    .serialize(&mut &mut burn_account.data.borrow_mut()[..])?;
+    load_counter(program_id, counter_account)?.set_flag(CounterFlag::Burn, true);
+
+    msg!(
+        "Increments of counter {} now burn tokens of mint {}",
+        counter_account.key,
+        mint
+    );
+
+    Ok(())
+}
+
+/// Open the counter's escrow, provided the counter's authority signed
+fn process_initialize_escrow(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    beneficiary: Pubkey,
+    target: i128,
+    deadline_slot: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let escrow_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    let counter = *load_counter(program_id, counter_account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    // A target already reached would release the first deposit at once
+    let high_water = counter.kind().decode(counter.high_water);
+    if target <= high_water || deadline_slot <= Clock::get()?.slot {
+        msg!(
+            "An escrow needs a target above the high water mark {} and a deadline in the future",
+            high_water
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (address, bump) = find_escrow_address(program_id, counter_account.key);
+    if *escrow_account.key != address {
+        msg!(
+            "Escrow {} does not match the derived address {}",
+            escrow_account.key,
+            address
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // Fails if the counter has an escrow already
+    create_program_account(
+        program_id,
+        escrow_account,
+        payer,
+        system_program,
+        CounterEscrow::LEN,
+        &[&[ESCROW_SEED, counter_account.key.as_ref(), &[bump]]],
+    )?;
+    CounterEscrow {
+        discriminator: CounterEscrow::DISCRIMINATOR,
+        counter: *counter_account.key,
+        beneficiary,
+        target,
+        deadline_slot,
+        deposited: 0,
+        settled: false,
+    }
+    .serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+    load_counter(program_id, counter_account)?.set_flag(CounterFlag::Escrow, true);
+
+    msg!(
+        "Escrow {} pays {} once counter {} reaches {}, or refunds after slot {}",
+        escrow_account.key,
+        beneficiary,
+        counter_account.key,
+        target,
+        deadline_slot
+    );
+
+    Ok(())
+}
+
+/// Move `lamports` from the depositor into the counter's escrow and record
+/// them under the depositor
+fn process_deposit_escrow(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lamports: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let escrow_account = next_account_info(accounts_iter)?;
+    let deposit_account = next_account_info(accounts_iter)?;
+    let depositor = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    if lamports == 0 {
+        return Err(CounterError::ZeroAmount.into());
+    }
+    let mut escrow = load_escrow(program_id, escrow_account, counter_account.key)?;
+    if escrow.settled || Clock::get()?.slot > escrow.deadline_slot {
+        msg!(
+            "Escrow {} is settled or past its deadline",
+            escrow_account.key
+        );
+        return Err(CounterError::EscrowClosed.into());
+    }
+
+    let (address, bump) =
+        find_escrow_deposit_address(program_id, escrow_account.key, depositor.key);
+    if *deposit_account.key != address {
+        msg!(
+            "Deposit record {} does not match the derived address {}",
+            deposit_account.key,
+            address
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    let mut deposit = if deposit_account.owner != program_id {
+        create_program_account(
+            program_id,
+            deposit_account,
+            depositor,
+            system_program,
+            EscrowDeposit::LEN,
+            &[&[
+                ESCROW_DEPOSIT_SEED,
+                escrow_account.key.as_ref(),
+                depositor.key.as_ref(),
+                &[bump],
+            ]],
+        )?;
+        EscrowDeposit {
+            discriminator: EscrowDeposit::DISCRIMINATOR,
+            escrow: *escrow_account.key,
+            depositor: *depositor.key,
+            lamports: 0,
+        }
+    } else {
+        load_escrow_deposit(program_id, deposit_account, escrow_account.key)?
+    };
+
+    invoke(
+        &system_instruction::transfer(depositor.key, escrow_account.key, lamports),
+        &[
+            depositor.clone(),
+            escrow_account.clone(),
+            system_program.clone(),
+        ],
+    )?;
+    deposit.lamports = deposit
+        .lamports
+        .checked_add(lamports)
+        .ok_or(CounterError::Overflow)?;
+    escrow.deposited = escrow
+        .deposited
+        .checked_add(lamports)
+        .ok_or(CounterError::Overflow)?;
+    deposit.serialize(&mut &mut deposit_account.data.borrow_mut()[..])?;
+    escrow.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+
+    msg!(
+        "{} deposited {} lamports into escrow {}",
+        depositor.key,
+        lamports,
+        escrow_account.key
+    );
+
+    Ok(())
+}
+
+/// Release the escrow to its beneficiary once the counter reached the
+/// target, or refund a deposit once the deadline passed without it
+fn process_settle(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let escrow_account = next_account_info(accounts_iter)?;
+    let recipient = next_account_info(accounts_iter)?;
+
+    let counter = *load_counter(program_id, counter_account)?;
+    let mut escrow = load_escrow(program_id, escrow_account, counter_account.key)?;
+    let reached = counter.kind().decode(counter.high_water) >= escrow.target;
+
+    let Some(deposit_account) = accounts_iter.next() else {
+        // Past the deadline with every deposit refunded, the escrow is done
+        if !escrow.settled
+            && !reached
+            && escrow.deposited == 0
+            && Clock::get()?.slot > escrow.deadline_slot
+        {
+            load_counter(program_id, counter_account)?.set_flag(CounterFlag::Escrow, false);
+            msg!(
+                "Escrow {} expired with nothing left to refund",
+                escrow_account.key
+            );
+            return Ok(());
+        }
+        if escrow.settled || !reached {
+            msg!(
+                "Escrow {} is settled, or counter {} has not reached {} yet",
+                escrow_account.key,
+                counter_account.key,
+                escrow.target
+            );
+            return Err(CounterError::EscrowLocked.into());
+        }
+        if *recipient.key != escrow.beneficiary {
+            msg!(
+                "The escrow pays {}, not {}",
+                escrow.beneficiary,
+                recipient.key
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        // Anything sent to the escrow directly goes to the beneficiary too
+        let lamports = escrow_account
+            .lamports()
+            .saturating_sub(Rent::get()?.minimum_balance(CounterEscrow::LEN));
+        **escrow_account.lamports.borrow_mut() -= lamports;
+        **recipient.lamports.borrow_mut() = recipient
+            .lamports()
+            .checked_add(lamports)
+            .ok_or(CounterError::Overflow)?;
+        escrow.deposited = 0;
+        escrow.settled = true;
+        escrow.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+        load_counter(program_id, counter_account)?.set_flag(CounterFlag::Escrow, false);
+        msg!(
+            "Escrow {} released {} lamports to {}",
+            escrow_account.key,
+            lamports,
+            recipient.key
+        );
+        return Ok(());
+    };
+
+    let deposit = load_escrow_deposit(program_id, deposit_account, escrow_account.key)?;
+    if *recipient.key != deposit.depositor {
+        msg!(
+            "Deposit record {} refunds {}, not {}",
+            deposit_account.key,
+            deposit.depositor,
+            recipient.key
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    // A settled escrow owes nothing; its records only hand back their rent
+    let refund = if escrow.settled {
+        0
+    } else if !reached && Clock::get()?.slot > escrow.deadline_slot {
+        deposit.lamports
+    } else {
+        msg!(
+            "Escrow {} refunds only after slot {} without reaching {}",
+            escrow_account.key,
+            escrow.deadline_slot,
+            escrow.target
+        );
+        return Err(CounterError::EscrowLocked.into());
+    };
+    **escrow_account.lamports.borrow_mut() -= refund;
+    escrow.deposited -= refund;
+    escrow.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+    if escrow.deposited == 0 {
+        // The last refund leaves nothing the counter must stay open for
+        load_counter(program_id, counter_account)?.set_flag(CounterFlag::Escrow, false);
+    }
+
+    **recipient.lamports.borrow_mut() = recipient
+        .lamports()
+        .checked_add(refund)
+        .ok_or(CounterError::Overflow)?;
+    // The record's rent goes along with the refund
+    close_program_account(deposit_account, recipient)?;
+
+    msg!(
+        "Refunded {} lamports from escrow {} to {}",
+        refund,
+        escrow_account.key,
+        recipient.key
+    );
+
+    Ok(())
+}
+
+/// Reward the counter's milestones with tokens of `mint`, replacing any
+/// rewards it had, provided the counter's authority signed
+fn process_set_rewards(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    mint: Pubkey,
+    amount: u64,
+    milestones: Vec<i128>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let rewards_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    // Copied out, as the data cannot stay borrowed across the CPI
+    let counter = *load_counter(program_id, counter_account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    if amount == 0
+        || milestones.is_empty()
+        || milestones.len() > MAX_MILESTONES
+        || !milestones.windows(2).all(|pair| pair[0] < pair[1])
+    {
+        msg!(
+            "Rewards need a positive amount and 1 to {} strictly ascending milestones; ClearRewards removes them",
+            MAX_MILESTONES
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (address, bump) = find_rewards_address(program_id, counter_account.key);
+    if *rewards_account.key != address {
+        msg!(
+            "Rewards account {} does not match the derived address {}",
+            rewards_account.key,
+            address
+        );
+        return Err(CounterError::MissingRewards.into());
+    }
+
+    // Replaced in place once it exists
+    if rewards_account.owner != program_id {
+        create_program_account(
+            program_id,
+            rewards_account,
+            payer,
+            system_program,
+            CounterRewards::LEN,
+            &[&[REWARDS_SEED, counter_account.key.as_ref(), &[bump]]],
+        )?;
+    } else {
+        load_rewards(program_id, rewards_account, counter_account.key)?;
+    }
+    let mut rewards = CounterRewards {
+        discriminator: CounterRewards::DISCRIMINATOR,
+        counter: *counter_account.key,
+        bump,
+        mint,
+        amount,
+        reached: 0,
+        milestones,
+    };
+    // Milestones the count is already at are not for the next increment
+    let value = counter.value();
+    rewards.reached = rewards
+        .milestones
+        .iter()
+        .filter(|milestone| **milestone <= value)
+        .count() as u8;
+    rewards.serialize(&mut &mut rewards_account.data.borrow_mut()[..])?;
+    load_counter(program_id, counter_account)?.set_flag(CounterFlag::Rewards, true);
+
+    msg!(
+        "Increments of counter {} reaching {:?} mint {} tokens of mint {}",
+        counter_account.key,
+        rewards.milestones,
+        amount,
+        mint
+    );
+
+    Ok(())
+}
+
+/// Stop rewarding the counter's milestones and close its rewards account,
+/// provided the counter's authority signed
+fn process_clear_rewards(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    clear_companion(
+        program_id,
+        accounts,
+        "Rewards",
+        |counter_account, rewards_account, counter| {
+            load_rewards(program_id, rewards_account, counter_account.key)?;
+            counter.set_flag(CounterFlag::Rewards, false);
+            Ok(())
+        },
+    )
+}
+
+/// Require permits from the authority for every increment of the counter,
+/// provided the authority signed
+fn process_require_permits(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let permits_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    // Copied out, as the data cannot stay borrowed across the CPI
+    let counter = *load_counter(program_id, counter_account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    let (address, bump) = find_permits_address(program_id, counter_account.key);
+    if *permits_account.key != address {
+        msg!(
+            "Permit nonce account {} does not match the derived address {}",
+            permits_account.key,
+            address
+        );
+        return Err(CounterError::MissingPermit.into());
+    }
+
+    // Kept as it is once it exists, so used nonces stay used
+    if permits_account.owner != program_id {
+        create_program_account(
+            program_id,
+            permits_account,
+            payer,
+            system_program,
+            CounterPermits::LEN,
+            &[&[PERMITS_SEED, counter_account.key.as_ref(), &[bump]]],
+        )?;
+        CounterPermits {
+            discriminator: CounterPermits::DISCRIMINATOR,
+            counter: *counter_account.key,
+            next_nonce: 0,
+        }
+        .serialize(&mut &mut permits_account.data.borrow_mut()[..])?;
+    } else {
+        load_permits(program_id, permits_account, counter_account.key)?;
+    }
+    load_counter(program_id, counter_account)?.set_flag(CounterFlag::Permits, true);
+
+    msg!(
+        "Increments of counter {} need permits from {}",
+        counter_account.key,
+        counter.authority
+    );
+
+    Ok(())
+}
+
+/// Let increments of the counter go without permits and close its permit
+/// nonce account, provided the counter's authority signed
+fn process_clear_permits(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    clear_companion(
+        program_id,
+        accounts,
+        "Permits",
+        |counter_account, permits_account, counter| {
+            load_permits(program_id, permits_account, counter_account.key)?;
+            counter.set_flag(CounterFlag::Permits, false);
+            Ok(())
+        },
+    )
+}
+
+/// Make each actor wait `slots` slots between increments of the counter and
+/// add at most `daily_cap` per day, provided the counter's authority signed
+fn process_set_cooldown(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    slots: u64,
+    daily_cap: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let cooldown_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    // Copied out, as the data cannot stay borrowed across the CPI
+    let counter = *load_counter(program_id, counter_account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    if slots == 0 && daily_cap == 0 {
+        msg!("A cooldown needs a slot count or a daily cap; ClearCooldown removes it");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (address, bump) = find_cooldown_address(program_id, counter_account.key);
+    if *cooldown_account.key != address {
+        msg!(
+            "Cooldown {} does not match the derived address {}",
+            cooldown_account.key,
+            address
+        );
+        return Err(CounterError::MissingCooldown.into());
+    }
+
+    // Replaced in place once it exists
+    if cooldown_account.owner != program_id {
+        create_program_account(
+            program_id,
+            cooldown_account,
+            payer,
+            system_program,
+            CounterCooldown::LEN,
+            &[&[COOLDOWN_SEED, counter_account.key.as_ref(), &[bump]]],
+        )?;
+    } else {
+        load_cooldown(program_id, cooldown_account, counter_account.key)?;
+    }
+    CounterCooldown {
+        discriminator: CounterCooldown::DISCRIMINATOR,
+        counter: *counter_account.key,
+        slots,
+        daily_cap,
+    }
+    .serialize(&mut &mut cooldown_account.data.borrow_mut()[..])?;
+    load_counter(program_id, counter_account)?.set_flag(CounterFlag::Cooldown, true);
+
+    msg!(
+        "Each actor of counter {} waits {} slots between increments and adds at most {} a day",
+        counter_account.key,
+        slots,
+        daily_cap
+    );
+
+    Ok(())
+}
+
+/// Stop throttling the counter's increments and close its cooldown, provided
+/// the counter's authority signed
+fn process_clear_cooldown(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    clear_companion(
+        program_id,
+        accounts,
+        "Cooldown",
+        |counter_account, cooldown_account, counter| {
+            load_cooldown(program_id, cooldown_account, counter_account.key)?;
+            counter.set_flag(CounterFlag::Cooldown, false);
+            Ok(())
+        },
+    )
+}
+
+/// Make the counter's value fade towards zero by `rate` per `unit`, provided
+/// the counter's authority signed
+fn process_set_decay(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    rate: u64,
+    unit: DecayUnit,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let decay_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+
+    // Copied out, as the data cannot stay borrowed across the CPI
+    let mut counter = *load_counter(program_id, counter_account)?;
+    assert_authority(program_id, &counter, authority, accounts_iter.as_slice())?;
+
+    if counter.has_flag(CounterFlag::Burn) {
+        msg!(
+            "Counter {} is burn-backed and only changes by burning tokens",
+            counter_account.key
+        );
+        return Err(CounterError::BurnBacked.into());
+    }
+    if rate == 0 {
+        msg!("A decay rate must be positive; ClearDecay removes it");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (address, bump) = find_decay_address(program_id, counter_account.key);
+    if *decay_account.key != address {
+        msg!(
+            "Decay {} does not match the derived address {}",
+            decay_account.key,
+            address
+        );
+        return Err(CounterError::MissingDecay.into());
+    }
+
+    // Replaced in place once it exists, after settling what it owes
+    if decay_account.owner != program_id {
+        create_program_account(
+            program_id,
+            decay_account,
+            payer,
+            system_program,
+            CounterDecay::LEN,
+            &[&[DECAY_SEED, counter_account.key.as_ref(), &[bump]]],
+        )?;
+    } else if counter.has_flag(CounterFlag::Decay) {
+        let decay = load_decay(program_id, decay_account, counter_account.key)?;
+        settle_decay(counter_account, &mut counter, &decay)?;
+    }
+    let clock = Clock::get()?;
+    CounterDecay {
+        discriminator: CounterDecay::DISCRIMINATOR,
+        counter: *counter_account.key,
+        rate,
+        unit,
+        since_slot: clock.slot,
+        since_ts: clock.unix_timestamp,
+    }
+    .serialize(&mut &mut decay_account.data.borrow_mut()[..])?;
+    counter.set_flag(CounterFlag::Decay, true);
+    store_counter(counter_account, &counter);
+
+    msg!(
+        "Counter {} decays by {} per {:?}",
+        counter_account.key,
+        rate,
+        unit
+    );
+
+    Ok(())
+}
+
+/// Stop the counter's value from decaying and close its decay, provided the
+/// counter's authority signed
+fn process_clear_decay(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    clear_companion(
+        program_id,
+        accounts,
+        "Decay",
+        |counter_account, decay_account, counter| {
+            let decay = load_decay(program_id, decay_account, counter_account.key)?;
+            if counter.has_flag(CounterFlag::Decay) {
+                settle_decay(counter_account, counter, &decay)?;
+            }
+            counter.set_flag(CounterFlag::Decay, false);
+            Ok(())
+        },
+    )
+}
+
+/// Bring a counter up to date: apply its decay, start it over and its epoch
+/// tally in a new epoch and fire its callback if that reached the target,
+/// paying the cranker the crank reward if there was any of this to do
+///
+/// Rolling the epoch tally over alone earns nothing, as every change does
+/// that anyway. A reward is only paid for a counter that paid a lamport fee
+/// since it was last rewarded, once per epoch and within the config's
+/// epoch budget, so cranking counters set up to need it cannot drain the
+/// vault.
+fn process_crank<'a>(program_id: &Pubkey, accounts: &[AccountInfo<'a>]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let cranker = next_account_info(accounts_iter)?;
+    let vault = next_account_info(accounts_iter)?;
+    // Validation told the decay apart by its data
+    let rest = accounts_iter.as_slice();
+    let (decay, callback) = match rest.split_first() {
+        Some((decay, callback))
+            if decay
+                .try_borrow_data()
+                .is_ok_and(|data| CounterDecay::is_decay(&data)) =>
+        {
+            (core::slice::from_ref(decay), callback)
+        }
+        _ => (&[][..], rest),
+    };
+
+    let config = load_config(program_id, config_account)?;
+    config.check_active()?;
+
+    let counter = *load_counter(program_id, counter_account)?;
+    let old_value = counter.value();
+    let epoch = Clock::get()?.epoch;
+    let starts_over = counter.has_flag(CounterFlag::EpochReset) && counter.epoch != epoch;
+    // The decay records and reports itself
+    apply_decay(program_id, counter_account, decay)?;
+    let decayed = load_counter(program_id, counter_account)?.value();
+    reset_for_epoch(program_id, counter_account)?;
+
+    let mut counter = load_counter(program_id, counter_account)?;
+    if counter.epoch != epoch {
+        counter.epoch = epoch;
+        counter.epoch_change = 0;
+    }
+    let moved = counter.value() != old_value;
+    if counter.value() != decayed {
+        record_update(&mut counter, decayed)?;
+        emit_update(
+            counter_account,
+            &counter,
+            Operation::Crank,
+            decayed,
+            cranker.key,
+            None,
+        );
+    }
+    let armed = counter.has_flag(CounterFlag::Callback);
+    // Released first, as the callback may be passed the counter
+    drop(counter);
+    fire_callback(program_id, counter_account, old_value, callback)?;
+    let fired =
+        armed && !load_counter(program_id, counter_account)?.has_flag(CounterFlag::Callback);
+
+    if !(moved || starts_over || fired) {
+        msg!("Counter {} is up to date", counter_account.key);
+        return Ok(());
+    }
+    if config.discriminator != Config::DISCRIMINATOR {
+        return Ok(());
+    }
+    let mut crank = load_crank_rewards(config_account)?;
+    if crank.reward == 0 {
+        return Ok(());
+    }
+    // Only the fees a counter paid in fund its rewards, once per epoch
+    let mut counter = load_counter(program_id, counter_account)?;
+    if !counter.has_flag(CounterFlag::FeePaid) || epoch < counter.next_crank_epoch {
+        msg!(
+            "Counter {} paid no fee since its last rewarded crank",
+            counter_account.key
+        );
+        return Ok(());
+    }
+    if crank.epoch != epoch {
+        crank.epoch = epoch;
+        crank.paid = 0;
+    }
+
+    // The vault keeps its rent-exempt minimum
+    load_fee_vault(program_id, vault)?;
+    let available = vault
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(FeeVault::LEN));
+    let paid = crank
+        .reward
+        .min(available)
+        .min(crank.epoch_budget.saturating_sub(crank.paid));
+    if paid == 0 {
+        msg!("No crank rewards left to pay in epoch {}", epoch);
+        return Ok(());
+    }
+    counter.set_flag(CounterFlag::FeePaid, false);
+    counter.next_crank_epoch = epoch.saturating_add(1);
+    drop(counter);
+    crank.paid = crank.paid.saturating_add(paid);
+    store_crank_rewards(config_account, &crank)?;
+    **vault.lamports.borrow_mut() -= paid;
+    **cranker.lamports.borrow_mut() = cranker
+        .lamports()
+        .checked_add(paid)
+        .ok_or(CounterError::Overflow)?;
+
+    msg!(
+        "Cranked counter {}, paying {} lamports to {}",
+        counter_account.key,
+        paid,
+        cranker.key
+    );
+
+    Ok(())
+}
+
+/// Reject an increment of a counter with a cooldown if its actor incremented
+/// it less than the cooldown ago or would pass the daily cap, and stamp the
+/// actor with this slot and amount
+///
+/// `cooldown_accounts` holds the accounts `split_cooldown` set aside, which
+/// are ignored by changes the authority signs and while the counter has no
+/// cooldown.
+fn check_cooldown<'a>(
+    program_id: &Pubkey,
+    instruction: &CounterInstruction,
+    accounts: &[AccountInfo<'a>],
+    cooldown_accounts: &[AccountInfo<'a>],
+) -> ProgramResult {
+    let amount = match instruction {
+        CounterInstruction::Increment(amount) => u64::from(*amount),
+        CounterInstruction::IncrementByOne => 1,
+        CounterInstruction::Batch(ops) if !ops.iter().any(CounterOp::requires_authority) => ops
+            .iter()
+            .map(|op| match op {
+                CounterOp::Increment(amount) => u64::from(*amount),
+                _ => 0,
+            })
+            .sum(),
+        _ => return Ok(()),
+    };
+    // Validation put the counter first and the config second
+    let counter_account = &accounts[0];
+    if !load_counter(program_id, counter_account)?.has_flag(CounterFlag::Cooldown) {
+        return Ok(());
+    }
+    let [cooldown_account, stamp_account, system_program] = cooldown_accounts else {
+        msg!(
+            "Counter {} has a cooldown; pass it, the actor's stamp and the system program",
+            counter_account.key
+        );
+        return Err(CounterError::MissingCooldown.into());
+    };
+    let cooldown = load_cooldown(program_id, cooldown_account, counter_account.key)?;
+    let (_, _, signers) = split_trailing(&accounts[2..]);
+    let Some(actor) = signers.first() else {
+        msg!(
+            "Counter {} has a cooldown; the actor must sign",
+            counter_account.key
+        );
+        return Err(ProgramError::MissingRequiredSignature);
+    };
+    let (address, bump) = find_cooldown_stamp_address(program_id, counter_account.key, actor.key);
+    if *stamp_account.key != address {
+        msg!(
+            "Cooldown stamp {} does not match the derived address {}",
+            stamp_account.key,
+            address
+        );
+        return Err(CounterError::MissingCooldown.into());
+    }
+
+    let clock = Clock::get()?;
+    let (slot, day) = (clock.slot, CooldownStamp::day_of(clock.unix_timestamp));
+    // The actor's first increment has nothing to wait for
+    let mut stamp = if stamp_account.owner != program_id {
+        create_program_account(
+            program_id,
+            stamp_account,
+            actor,
+            system_program,
+            CooldownStamp::LEN,
+            &[&[
+                COOLDOWN_STAMP_SEED,
+                counter_account.key.as_ref(),
+                actor.key.as_ref(),
+                &[bump],
+            ]],
+        )?;
+        CooldownStamp {
+            discriminator: CooldownStamp::DISCRIMINATOR,
+            counter: *counter_account.key,
+            actor: *actor.key,
+            last_slot: slot,
+            day,
+            contributed: 0,
+        }
+    } else {
+        let stamp = load_cooldown_stamp(program_id, stamp_account)?;
+        let ready = stamp.last_slot.saturating_add(cooldown.slots);
+        if slot < ready {
+            msg!(
+                "{} last incremented counter {} at slot {}, and may again from slot {}",
+                actor.key,
+                counter_account.key,
+                stamp.last_slot,
+                ready
+            );
+            return Err(CounterError::CooldownActive.into());
+        }
+        stamp
+    };
+    if stamp.day != day {
+        stamp.day = day;
+        stamp.contributed = 0;
+    }
+    stamp.contributed = stamp
+        .contributed
+        .checked_add(amount)
+        .ok_or(CounterError::Overflow)?;
+    if cooldown.daily_cap != 0 && stamp.contributed > cooldown.daily_cap {
+        msg!(
+            "{} would add {} to counter {} today, at most {} are allowed",
+            actor.key,
+            stamp.contributed,
+            counter_account.key,
+            cooldown.daily_cap
+        );
+        return Err(CounterError::DailyCapExceeded.into());
+    }
+    stamp.last_slot = slot;
+    stamp.serialize(&mut &mut stamp_account.data.borrow_mut()[..])?;
+    Ok(())
+}
+
+/// Reject an increment of a counter that needs permits unless an Ed25519
+/// instruction of its transaction verified one from the authority, and use
+/// up the permit's nonce
+///
+/// `permits` holds the account `split_permits` set aside, which is ignored
+/// by other changes and while the counter needs no permits.
+fn check_permit(
+    program_id: &Pubkey,
+    instruction: &CounterInstruction,
+    accounts: &[AccountInfo],
+    permits: &[AccountInfo],
+    instructions_sysvar: Option<&AccountInfo>,
+) -> ProgramResult {
+    let amount = match instruction {
+        CounterInstruction::Increment(amount) => u64::from(*amount),
+        CounterInstruction::IncrementByOne => 1,
+        // Checked below, as only increments go without the authority
+        CounterInstruction::Batch(_) => 0,
+        _ => return Ok(()),
+    };
+    // Validation put the counter first
+    let counter_account = &accounts[0];
+    let counter = *load_counter(program_id, counter_account)?;
+    if !counter.has_flag(CounterFlag::Permits) {
+        return Ok(());
+    }
+    if let CounterInstruction::Batch(ops) = instruction {
+        if ops.iter().any(CounterOp::requires_authority) {
+            return Ok(());
+        }
+        msg!(
+            "Counter {} needs permits, which a Batch of increments cannot carry",
+            counter_account.key
+        );
+        return Err(CounterError::InvalidPermit.into());
+    }
+    let (Some(permits_account), Some(sysvar)) = (permits.first(), instructions_sysvar) else {
+        msg!(
+            "Counter {} needs permits; pass its permit nonce account and end with the Instructions sysvar",
+            counter_account.key
+        );
+        return Err(CounterError::MissingPermit.into());
+    };
+    let mut state = load_permits(program_id, permits_account, counter_account.key)?;
+
+    // The first permit for this increment that is still good, so several
+    // increments of one transaction can each bring their own
+    let slot = Clock::get()?.slot;
+    let mut permit = None;
+    let mut index = 0;
+    while let Ok(other) = load_instruction_at_checked(index, sysvar) {
+        index += 1;
+        if other.program_id != ed25519_program::ID {
+            continue;
+        }
+        permit = ed25519_signatures(&other.data)
+            .into_iter()
+            .find_map(|(key, message)| {
+                if key != counter.authority.as_ref() || message.len() != PERMIT_MESSAGE_LEN {
+                    return None;
+                }
+                let nonce = u64::from_le_bytes(message[40..48].try_into().unwrap());
+                let expiry_slot = u64::from_le_bytes(message[48..].try_into().unwrap());
+                (*message == permit_message(counter_account.key, amount, nonce, expiry_slot)
+                    && nonce >= state.next_nonce
+                    && slot <= expiry_slot)
+                    .then_some(nonce)
+            });
+        if permit.is_some() {
+            break;
+        }
+    }
+    let Some(nonce) = permit else {
+        msg!(
+            "No unused, unexpired permit from {} allows incrementing counter {} by {}",
+            counter.authority,
+            counter_account.key,
+            amount
+        );
+        return Err(CounterError::InvalidPermit.into());
+    };
+    state.next_nonce = nonce.checked_add(1).ok_or(CounterError::Overflow)?;
+    state.serialize(&mut &mut permits_account.data.borrow_mut()[..])?;
+    Ok(())
+}
+
+/// The public keys and messages of the signatures an Ed25519 program
+/// instruction verified, in order
+///
+/// Signatures whose parts the instruction read from another instruction are
+/// left out, so what is returned is exactly what was verified.
+fn ed25519_signatures(data: &[u8]) -> Vec<(&[u8], &[u8])> {
+    // A count and a padding byte, then 14 bytes of offsets per signature
+    let count = data.first().copied().unwrap_or(0);
+    (0..usize::from(count))
+        .filter_map(|signature| {
+            let offsets = data.get(2 + 14 * signature..2 + 14 * (signature + 1))?;
+            let field =
+                |index: usize| u16::from_le_bytes([offsets[2 * index], offsets[2 * index + 1]]);
+            // The signature's, key's and message's instruction indexes, where
+            // `u16::MAX` means the Ed25519 instruction itself
+            if [1, 3, 6].into_iter().any(|index| field(index) != u16::MAX) {
+                return None;
+            }
+            let key = data.get(usize::from(field(2))..)?.get(..32)?;
+            let start = usize::from(field(4));
+            let message = data.get(start..start + usize::from(field(5)))?;
+            Some((key, message))
+        })
+        .collect()
+}
+
+/// Mint the rewards of the milestones a change from `old_value` crossed to
+/// `actor`'s associated token account in `reward_accounts`
+///
+/// `reward_accounts` holds the accounts `split_rewards` set aside, which are
+/// ignored while the counter has no rewards or unless the change raised it.
+fn mint_rewards<'a>(
+    program_id: &Pubkey,
+    counter_account: &AccountInfo<'a>,
+    actor: Option<&AccountInfo<'a>>,
+    reward_accounts: &[AccountInfo<'a>],
+    old_value: i128,
+) -> ProgramResult {
+    let value = {
+        let counter = load_counter(program_id, counter_account)?;
+        // Only a change raising the count crosses a milestone
+        if !counter.has_flag(CounterFlag::Rewards) || counter.value() <= old_value {
+            return Ok(());
+        }
+        counter.value()
+    };
+    let [rewards_account, token_program, mint, destination] = reward_accounts else {
+        msg!(
+            "Counter {} rewards milestones; pass its rewards account, the token program, the mint and the actor's token account",
+            counter_account.key
+        );
+        return Err(CounterError::MissingRewards.into());
+    };
+    let mut rewards = load_rewards(program_id, rewards_account, counter_account.key)?;
+    let due = rewards.crossed(old_value, value);
+    if due == 0 {
+        return Ok(());
+    }
+    let Some(actor) = actor else {
+        msg!(
+            "Counter {} reached a milestone; the actor receiving the reward must sign",
+            counter_account.key
+        );
+        return Err(ProgramError::MissingRequiredSignature);
+    };
+    if *mint.key != rewards.mint
+        || ![TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID].contains(token_program.key)
+        || *destination.key != associated_token_address(actor.key, token_program.key, mint.key)
+    {
+        msg!(
+            "Rewards are minted in {} to the associated token account of {}, not in {} to {}",
+            rewards.mint,
+            actor.key,
+            mint.key,
+            destination.key
+        );
+        return Err(CounterError::InvalidRewardAccount.into());
+    }
+    // The token program checks the mint it is passed, decimals included
+    let Some(&decimals) = mint.data.borrow().get(MINT_DECIMALS_OFFSET) else {
+        msg!("Account {} is not a mint", mint.key);
+        return Err(CounterError::InvalidRewardAccount.into());
+    };
+    let amount = rewards
+        .amount
+        .checked_mul(due as u64)
+        .ok_or(CounterError::Overflow)?;
+
+    // Recorded first, as the data cannot stay borrowed across the CPI. Any
+    // milestone the count was already past is never rewarded either.
+    rewards.reached += rewards.due(value) as u8;
+    rewards.serialize(&mut &mut rewards_account.data.borrow_mut()[..])?;
+
+    // SPL Token's `MintToChecked`: its tag, the amount and the decimals
+    let mut data = vec![14];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(decimals);
+    invoke_signed(
+        &Instruction {
+            program_id: *token_program.key,
+            accounts: vec![
+                AccountMeta::new(*mint.key, false),
+                AccountMeta::new(*destination.key, false),
+                AccountMeta::new_readonly(*rewards_account.key, true),
+            ],
+            data,
+        },
+        &[
+            mint.clone(),
+            destination.clone(),
+            rewards_account.clone(),
+            token_program.clone(),
+        ],
+        &[&[REWARDS_SEED, counter_account.key.as_ref(), &[rewards.bump]]],
+    )?;
+
+    msg!(
+        "Counter {} reached {} milestones, minted {} tokens to {}",
+        counter_account.key,
+        due,
+        amount,
+        actor.key
+    );
+
+    Ok(())
+}
+
+/// Burn the tokens an increment of a burn-backed counter adds, from the
+/// actor's token account in `burn_accounts`, and reject any other change
+///
+/// `burn_accounts` holds the accounts `split_burn` set aside, which are
+/// ignored while the counter is not burn-backed.
+fn burn_tokens<'a>(
+    program_id: &Pubkey,
+    instruction: &CounterInstruction,
+    accounts: &[AccountInfo<'a>],
+    burn_accounts: &[AccountInfo<'a>],
+) -> ProgramResult {
+    // Validation put the counter first and, for increments, the config second
+    let counter_account = &accounts[0];
+    if !load_counter(program_id, counter_account)?.has_flag(CounterFlag::Burn) {
+        return Ok(());
+    }
+    let amount = match instruction {
+        CounterInstruction::Increment(amount) => u64::from(*amount),
+        CounterInstruction::IncrementByOne => 1,
+        _ => {
+            msg!(
+                "Counter {} is burn-backed and only changes by burning tokens",
+                counter_account.key
+            );
+            return Err(CounterError::BurnBacked.into());
+        }
+    };
+    let [burn_account, token_program, token_account, mint] = burn_accounts else {
+        msg!(
+            "Counter {} is burn-backed; pass its burn account, the token program, the actor's token account and the mint",
+            counter_account.key
+        );
+        return Err(CounterError::MissingBurn.into());
+    };
+    let mut burn = load_burn(program_id, burn_account, counter_account.key)?;
+    if *mint.key != burn.mint
+        || ![TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID].contains(token_program.key)
+    {
+        msg!(
+            "Increments burn mint {} through a token program, not {} through {}",
+            burn.mint,
+            mint.key,
+            token_program.key
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (_, _, signers) = split_trailing(&accounts[2..]);
+    let Some(actor) = signers.first() else {
+        msg!(
+            "Counter {} is burn-backed; the owner of the burned tokens must sign as the actor",
+            counter_account.key
+        );
+        return Err(ProgramError::MissingRequiredSignature);
+    };
+    // The token program checks the mint it is passed, decimals included
+    let Some(&decimals) = mint.data.borrow().get(MINT_DECIMALS_OFFSET) else {
+        msg!("Account {} is not a mint", mint.key);
+        return Err(ProgramError::InvalidArgument);
+    };
+
+    // SPL Token's `BurnChecked`: its tag, the amount and the decimals
+    let mut data = vec![15];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(decimals);
+    invoke(
+        &Instruction {
+            program_id: *token_program.key,
+            accounts: vec![
+                AccountMeta::new(*token_account.key, false),
+                AccountMeta::new(*mint.key, false),
+                AccountMeta::new_readonly(*actor.key, true),
+            ],
+            data,
+        },
+        &[
+            token_account.clone(),
+            mint.clone(),
+            actor.clone(),
+            token_program.clone(),
+        ],
+    )?;
+    burn.burned = burn
+        .burned
+        .checked_add(amount)
+        .ok_or(CounterError::Overflow)?;
+    burn.serialize(&mut &mut burn_account.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Reject an increment of a token-gated counter, `Batch`es of increments
+/// only included, unless its actor holds enough of the gate's mint, as the
+/// token account in `gate_accounts` shows
+///
+/// `gate_accounts` holds the accounts `split_token_gate` set aside, which
+/// are ignored by other changes and while the counter is not gated.
+fn check_token_gate(
+    program_id: &Pubkey,
+    instruction: &CounterInstruction,
+    accounts: &[AccountInfo],
+    gate_accounts: &[AccountInfo],
+) -> ProgramResult {
+    let gated = match instruction {
+        CounterInstruction::Increment(_) | CounterInstruction::IncrementByOne => true,
+        CounterInstruction::Batch(ops) => !ops.iter().any(CounterOp::requires_authority),
+        _ => false,
+    };
+    if !gated {
+        return Ok(());
+    }
+    // Validation put the counter first and the config second
+    let counter_account = &accounts[0];
+    if !load_counter(program_id, counter_account)?.has_flag(CounterFlag::TokenGate) {
+        return Ok(());
+    }
+    let [gate_account, token_account] = gate_accounts else {
+        msg!(
+            "Counter {} is token-gated; pass its token gate and the actor's token account",
+            counter_account.key
+        );
+        return Err(CounterError::MissingTokenGate.into());
+    };
+    let gate = load_token_gate(program_id, gate_account, counter_account.key)?;
+    let (_, _, signers) = split_trailing(&accounts[2..]);
+    let Some(actor) = signers.first() else {
+        msg!(
+            "Counter {} is token-gated; the holder must sign as the actor",
+            counter_account.key
+        );
+        return Err(ProgramError::MissingRequiredSignature);
+    };
+
+    let (mint, owner, balance) = read_token_account(token_account)?;
+    if mint != gate.mint || owner != *actor.key {
+        msg!(
+            "Token account {} holds mint {} for {}, expected mint {} for the actor {}",
+            token_account.key,
+            mint,
+            owner,
+            gate.mint,
+            actor.key
+        );
+        return Err(CounterError::InsufficientTokens.into());
+    }
+    if balance < gate.min_balance {
+        msg!(
+            "{} holds {} tokens of mint {}, increments need {}",
+            actor.key,
+            balance,
+            gate.mint,
+            gate.min_balance
+        );
+        return Err(CounterError::InsufficientTokens.into());
+    }
+    Ok(())
+}
+
+/// Reject `account` if it holds another kind of this program's accounts than
+/// `expected`, such as a config passed where a counter belongs
+///
+/// Runs before any length check, so the mistake is named as such. Data that
+/// starts with no known discriminator is left to the caller to judge.
+fn check_account_type(account: &AccountInfo, expected: AccountType) -> ProgramResult {
+    match AccountType::of(&account.try_borrow_data()?) {
+        Some(found) if found != expected => {
+            msg!(
+                "Account {} is a {}, expected a {}",
+                account.key,
+                found.name(),
+                expected.name()
+            );
+            Err(CounterError::WrongAccountType.into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check that an account's leading bytes are the counter discriminator
+fn check_discriminator(account: &AccountInfo, discriminator: &[u8; 8]) -> ProgramResult {
+    if *discriminator != Counter::DISCRIMINATOR {
+        if *discriminator == [0; 8] {
+            msg!("Counter account {} is not initialized", account.key);
+            return Err(CounterError::UninitializedAccount.into());
+        }
+        msg!("Account {} is not a counter", account.key);
+        return Err(CounterError::InvalidDiscriminator.into());
+    }
+    Ok(())
+}
+
+/// Grow or shrink `account` to `new_len` bytes, with `payer` topping up the
+/// balance so that the account stays rent-exempt at its new size
+fn resize_account<'a>(
+    account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    new_len: usize,
+) -> ProgramResult {
+    let required = Rent::get()?.minimum_balance(new_len);
+    let shortfall = required.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, shortfall),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+
+    // Zero any new bytes so that reserved space and added fields start unset
+    account.realloc(new_len, true)
+}
+
+/// Validate that `account` holds one of this program's counters and borrow
+/// its data as a [`Counter`]
+///
+/// Every handler that touches an existing counter goes through here so that a
+/// foreign or wrongly sized account is rejected before its bytes are
+/// interpreted, rather than failing late when the runtime refuses the write.
+/// Writability has already been checked by [`validate_accounts`].
+///
+/// The counter is not copied: the returned reference points into the account
+/// data, so assigning through it updates the account directly. Accounts grown
+/// by `Resize` keep the counter in their first [`Counter::LEN`] bytes.
+fn load_counter<'a>(
+    program_id: &Pubkey,
+    account: &'a AccountInfo,
+) -> Result<RefMut<'a, Counter>, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Counter account {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::Counter)?;
+    if account.data_len() < Counter::LEN {
+        msg!(
+            "Counter account {} holds {} bytes, expected at least {}",
+            account.key,
+            account.data_len(),
+            Counter::LEN
+        );
+        let outdated = [
+            LegacyCounter::LEN,
+            CounterV1::LEN,
+            CounterV2::LEN,
+            CounterV4::V3_LEN,
+            CounterV5::LEN,
+            CounterV6::LEN,
+            CounterV7::LEN,
+            CounterV8::LEN,
+            CounterV9::LEN,
+            CounterV10::LEN,
+            CounterV11::LEN,
+            CounterV12::LEN,
+        ];
+        if outdated.contains(&account.data_len()) {
+            msg!("This looks like an outdated counter; run Migrate first");
+        }
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+
+    let mut discriminator = [0; 8];
+    discriminator.copy_from_slice(
+        &account.data.borrow()[COUNTER_DISCRIMINATOR_OFFSET..COUNTER_DISCRIMINATOR_OFFSET + 8],
+    );
+    check_discriminator(account, &discriminator)?;
+    let version = account.data.borrow()[COUNTER_VERSION_OFFSET];
+    if version != Counter::VERSION {
+        msg!(
+            "Counter account {} has layout version {}, expected {}",
+            account.key,
+            version,
+            Counter::VERSION
+        );
+        if version < Counter::VERSION {
+            msg!("This looks like an outdated counter; run Migrate first");
+        }
+        return Err(CounterError::UnsupportedVersion.into());
+    }
+
+    // Fails only if the runtime handed us misaligned account data
+    let counter = RefMut::filter_map(account.data.borrow_mut(), |data| {
+        bytemuck::try_from_bytes_mut::<Counter>(&mut data[..Counter::LEN]).ok()
+    })
+    .map_err(|_| ProgramError::InvalidAccountData)?;
+    if !counter.is_valid() {
+        msg!(
+            "Counter account {} holds an unknown mode or kind",
+            account.key
+        );
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(counter)
+}
+
+/// Deserialize the account type `T` from the start of `data`, read from
+/// `account`, whose length has already been checked against the layout of `T`
+///
+/// Bytes Borsh cannot decode, such as an unknown enum tag, fail with
+/// `InvalidAccountData` and a log line naming the account, rather than with
+/// Borsh's own error.
+fn deserialize_account<T: BorshDeserialize>(
+    account: &AccountInfo,
+    data: &[u8],
+) -> Result<T, ProgramError> {
+    T::deserialize(&mut &data[..]).map_err(|error| {
+        msg!("Account {} holds malformed data: {}", account.key, error);
+        ProgramError::InvalidAccountData
+    })
+}
+
+/// Write `counter` over the first [`Counter::LEN`] bytes of `account`
+fn store_counter(account: &AccountInfo, counter: &Counter) {
+    account.data.borrow_mut()[..Counter::LEN].copy_from_slice(bytemuck::bytes_of(counter));
+}
+
+/// Validate that `account` holds one of this program's vote accounts and deserialize it
+fn load_votes(program_id: &Pubkey, account: &AccountInfo) -> Result<VoteCounter, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Vote account {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::VoteCounter)?;
+    if account.data_len() != VoteCounter::LEN {
+        msg!(
+            "Vote account {} holds {} bytes, expected {}",
+            account.key,
+            account.data_len(),
+            VoteCounter::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+
+    let votes: VoteCounter = deserialize_account(account, &account.data.borrow())?;
+    if votes.discriminator != VoteCounter::DISCRIMINATOR {
+        msg!("Account {} is not a vote account", account.key);
+        return Err(CounterError::InvalidDiscriminator.into());
+    }
+    Ok(votes)
+}
+
+/// Validate that `account` holds the history of `counter` and deserialize it
+fn load_history(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    counter: &Pubkey,
+) -> Result<CounterHistory, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "History account {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::CounterHistory)?;
+    // Fails rather than panics if the counter itself was passed as its history
+    let data = account.try_borrow_data()?;
+    if data.len() < CounterHistory::BASE_LEN || data[..8] != CounterHistory::DISCRIMINATOR {
+        msg!("Account {} is not a history account", account.key);
+        return Err(CounterError::InvalidDiscriminator.into());
+    }
+
+    // The account is sized for a full history, so unused space follows the entries
+    let (_, _, capacity, _, entries) =
+        <([u8; 8], Pubkey, u16, u16, u32)>::deserialize(&mut &data[..])?;
+    if capacity > CounterHistory::MAX_CAPACITY
+        || entries > u32::from(capacity)
+        || data.len() != CounterHistory::len(capacity)
+    {
+        msg!(
+            "History account {} holds {} bytes and {} entries, expected {} bytes for at most {} entries",
+            account.key,
+            data.len(),
+            entries,
+            CounterHistory::len(capacity),
+            capacity
+        );
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let history: CounterHistory = deserialize_account(account, &data)?;
+    if history.counter != *counter {
+        msg!(
+            "History {} belongs to counter {}",
+            account.key,
+            history.counter
+        );
+        return Err(CounterError::InvalidHistory.into());
+    }
+    Ok(history)
+}
+
+/// Validate that `account` holds the callback of `counter` and deserialize it
+fn load_callback(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    counter: &Pubkey,
+) -> Result<CounterCallback, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Callback account {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::CounterCallback)?;
+    if account.data_len() != CounterCallback::LEN {
+        msg!(
+            "Callback account {} holds {} bytes, expected {}",
+            account.key,
+            account.data_len(),
+            CounterCallback::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+
+    // The account is sized for the most accounts, so unused space follows them
+    let callback: CounterCallback = deserialize_account(account, &account.data.borrow())?;
+    if callback.counter != *counter {
+        msg!(
+            "Callback account {} belongs to counter {}",
+            account.key,
+            callback.counter
+        );
+        return Err(CounterError::InvalidCallback.into());
+    }
+    Ok(callback)
+}
+
+/// Validate that `account` holds the operator list of `counter` and deserialize it
+fn load_operators(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    counter: &Pubkey,
+) -> Result<CounterOperators, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Operator list {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::CounterOperators)?;
+    if account.data_len() != CounterOperators::LEN {
+        msg!(
+            "Operator list {} holds {} bytes, expected {}",
+            account.key,
+            account.data_len(),
+            CounterOperators::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+
+    // The account is sized for the most operators, so unused space follows them
+    let list: CounterOperators = deserialize_account(account, &account.data.borrow())?;
+    if list.counter != *counter {
+        msg!(
+            "Operator list {} belongs to counter {}",
+            account.key,
+            list.counter
+        );
+        return Err(CounterError::UnknownOperator.into());
+    }
+    Ok(list)
+}
+
+/// Validate that `account` holds the hooks of `counter` and deserialize it
+fn load_hooks(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    counter: &Pubkey,
+) -> Result<CounterHooks, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Hooks account {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::CounterHooks)?;
+    if account.data_len() != CounterHooks::LEN {
+        msg!(
+            "Hooks account {} holds {} bytes, expected {}",
+            account.key,
+            account.data_len(),
+            CounterHooks::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+
+    // Unset hooks leave unused space after the encoding
+    let hooks: CounterHooks = deserialize_account(account, &account.data.borrow())?;
+    if hooks.counter != *counter {
+        msg!(
+            "Hooks account {} belongs to counter {}",
+            account.key,
+            hooks.counter
+        );
+        return Err(CounterError::InvalidHooks.into());
+    }
+    Ok(hooks)
+}
+
+/// Validate that `account` holds a shard of `counter` and deserialize it
+fn load_shard(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    counter: &Pubkey,
+) -> Result<CounterShard, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Shard account {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::CounterShard)?;
+    if account.data_len() != CounterShard::LEN {
+        msg!(
+            "Shard account {} holds {} bytes, expected {}",
+            account.key,
+            account.data_len(),
+            CounterShard::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+
+    let shard: CounterShard = deserialize_account(account, &account.data.borrow())?;
+    if shard.discriminator != CounterShard::DISCRIMINATOR {
+        msg!("Account {} is not a shard", account.key);
+        return Err(CounterError::InvalidDiscriminator.into());
+    }
+    if shard.counter != *counter {
+        msg!("Shard {} belongs to counter {}", account.key, shard.counter);
+        return Err(CounterError::InvalidShard.into());
+    }
+    Ok(shard)
+}
+
+/// Validate that `account` holds one of this program's counter maps and deserialize it
+fn load_map(program_id: &Pubkey, account: &AccountInfo) -> Result<CounterMap, ProgramError> {
+    if account.owner != program_id {
+        msg!(
+            "Map account {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            program_id
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::CounterMap)?;
+    if account.data_len() < CounterMap::BASE_LEN
+        || account.data.borrow()[..8] != CounterMap::DISCRIMINATOR
+    {
+        msg!("Account {} is not a counter map", account.key);
+        return Err(CounterError::InvalidDiscriminator.into());
+    }
+
+    // Maps are resized with every entry, so the entry count fixes the length
+    let data = account.data.borrow();
+    let (_, _, entries) = <([u8; 8], Pubkey, u32)>::deserialize(&mut &data[..])?;
+    let expected = CounterMap::BASE_LEN + entries as usize * CounterMap::ENTRY_LEN;
+    if data.len() != expected {
+        msg!(
+            "Map account {} holds {} bytes, but its {} entries need {}",
+            account.key,
+            data.len(),
+            entries,
+            expected
+        );
+        return Err(ProgramError::InvalidAccountData);
+    }
+    deserialize_account(account, &data)
+}
+
+/// Check that `authority` is the map's stored authority and that it approved
+/// the instruction, see [`assert_approved`]
+fn assert_map_authority(
+    program_id: &Pubkey,
+    map: &CounterMap,
+    authority: &AccountInfo,
+    signers: &[AccountInfo],
+) -> ProgramResult {
+    if map.authority != *authority.key {
+        msg!("{} is not the map authority", authority.key);
+        return Err(CounterError::Unauthorized.into());
+    }
+    assert_approved(program_id, authority, signers)
+}
+
+/// Validate that `account` is the program's config PDA and deserialize it
+///
+/// A config that has not been initialized yet is still an empty system
+/// account; it reads as [`Config::default`], which imposes no restrictions.
+fn load_config(program_id: &Pubkey, account: &AccountInfo) -> Result<Config, ProgramError> {
+    let (address, _) = find_config_address(program_id);
+    if *account.key != address {
+        msg!(
+            "Config account {} does not match the derived address {}",
+            account.key,
+            address
+        );
+        return Err(CounterError::InvalidConfigAddress.into());
+    }
+    if account.owner != program_id {
+        if account.data_is_empty() {
+            return Ok(Config::default());
+        }
+        msg!(
+            "Config account {} is owned by {}",
+            account.key,
+            account.owner
+        );
+        return Err(CounterError::IncorrectOwner.into());
+    }
+    check_account_type(account, AccountType::Config)?;
+    if account.data_len() < Config::LEN {
+        msg!(
+            "Config account {} holds {} bytes, expected at least {}",
+            account.key,
+            account.data_len(),
+            Config::LEN
+        );
+        return Err(CounterError::InvalidAccountLength.into());
+    }
+
+    // A caller guard and a fee may follow the config itself
+    let config: Config = deserialize_account(account, &account.data.borrow())?;
+    if config.discriminator != Config::DISCRIMINATOR {
+        msg!("Account {} is not a config", account.key);
+        return Err(CounterError::InvalidDiscriminator.into());
+    }
+    Ok(config)
+}
+
+/// Check that `authority` is the counter's stored authority and that it approved
+/// the instruction, see [`assert_approved`]
+fn assert_authority(
+    program_id: &Pubkey,
+    counter: &Counter,
+    authority: &AccountInfo,
+    signers: &[AccountInfo],
+) -> ProgramResult {
+    if counter.has_flag(CounterFlag::NftAuthority) {
+        return assert_nft_holder(&counter.authority, authority, signers);
+    }
+    if counter.authority != *authority.key {
+        msg!("{} is not the counter authority", authority.key);
+        return Err(CounterError::Unauthorized.into());
+    }
+    assert_approved(program_id, authority, signers)
+}
+
+/// Check that `token_account` holds the NFT `mint` and that its owner is
+/// among the `signers`
+fn assert_nft_holder(
+    mint: &Pubkey,
+    token_account: &AccountInfo,
+    signers: &[AccountInfo],
+) -> ProgramResult {
+    let (held, owner, balance) = read_token_account(token_account)?;
+    if held != *mint || balance != 1 {
+        msg!(
+            "Token account {} does not hold the NFT {} the counter belongs to",
+            token_account.key,
+            mint
+        );
+        return Err(CounterError::Unauthorized.into());
+    }
+    if !signers
+        .iter()
+        .any(|signer| signer.is_signer && *signer.key == owner)
+    {
+        msg!(
+            "The holder {} of NFT {} must sign this instruction",
+            owner,
+            mint
+        );
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
+/// Check that `authority` approved the instruction
+///
+/// A plain key approves by signing. A [`Multisig`] account approves when at
+/// least `m` of its keys are among the `signers`, each counted once no matter
+/// how often it is passed. `signers` are ignored for plain keys, so an
+/// instruction with several authorities can pass the signers of all of them.
+fn assert_approved(
+    program_id: &Pubkey,
+    authority: &AccountInfo,
+    signers: &[AccountInfo],
+) -> ProgramResult {
+    let is_multisig = authority.owner == program_id
+        && authority.data_len() == Multisig::LEN
+        && authority.data.borrow()[..8] == Multisig::DISCRIMINATOR;
+    if !is_multisig {
+        if !authority.is_signer {
+            msg!("Authority {} must sign this instruction", authority.key);
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        return Ok(());
+    }
+
+    let multisig: Multisig = deserialize_account(authority, &authority.data.borrow())?;
+    let approvals = multisig
+        .signers()
+        .iter()
+        .filter(|key| {
+            signers
+                .iter()
+                .any(|signer| signer.is_signer && signer.key == *key)
+        })
+        .count();
+    if approvals < usize::from(multisig.m) {
+        msg!(
+            "Multisig {} needs {} signatures, got {}",
+            authority.key,
+            multisig.m,
+            approvals
+        );
+        return Err(CounterError::NotEnoughSigners.into());
+    }
+    Ok(())
+}
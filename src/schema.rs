@@ -0,0 +1,32 @@
+//! Machine-readable descriptions of the program's Borsh layouts
+//!
+//! Clients in other languages can generate decoders from these instead of
+//! hardcoding byte offsets. `cargo run --example schema` writes them to disk.
+
+use crate::{
+    events::EventRecord,
+    instruction::CounterInstruction,
+    state::{Counter, CounterMetadata},
+};
+use borsh::schema::BorshSchemaContainer;
+
+/// Layout of a counter account's first [`COUNTER_LEN`](crate::COUNTER_LEN) bytes
+pub fn counter_schema() -> BorshSchemaContainer {
+    BorshSchemaContainer::for_type::<Counter>()
+}
+
+/// Layout of the metadata `SetMetadata` stores right after the counter
+pub fn metadata_schema() -> BorshSchemaContainer {
+    BorshSchemaContainer::for_type::<CounterMetadata>()
+}
+
+/// Layout of the program's instruction data
+pub fn instruction_schema() -> BorshSchemaContainer {
+    BorshSchemaContainer::for_type::<CounterInstruction>()
+}
+
+/// Layout of the events logged as `Program data:` lines, each headed by its
+/// schema version
+pub fn event_schema() -> BorshSchemaContainer {
+    BorshSchemaContainer::for_type::<EventRecord>()
+}
@@ -0,0 +1,3300 @@
+use crate::{arithmetic, error::CounterError, events::Direction};
+// Import necessary dependencies from borsh for serialization/deserialization
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
+use solana_program::{
+    pubkey::{Pubkey, PubkeyError},
+    rent::Rent,
+};
+
+/// How a counter handles arithmetic results that do not fit in its range
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(
+    BorshSerialize, BorshDeserialize, BorshSchema, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+#[repr(u8)]
+pub enum OverflowMode {
+    /// Fail the instruction with `Overflow`/`Underflow`
+    #[default]
+    Checked,
+    /// Clamp the result to the smallest or largest representable value
+    Saturating,
+    /// Wrap around to the other end of the range
+    Wrapping,
+}
+
+impl OverflowMode {
+    /// The mode stored as `byte` in a [`Counter`], if it names one
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(OverflowMode::Checked),
+            1 => Some(OverflowMode::Saturating),
+            2 => Some(OverflowMode::Wrapping),
+            _ => None,
+        }
+    }
+}
+
+/// Which range of values a counter can hold
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(
+    BorshSerialize, BorshDeserialize, BorshSchema, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+#[repr(u8)]
+pub enum CounterKind {
+    /// `0..=u64::MAX`
+    #[default]
+    Unsigned,
+    /// `i64::MIN..=i64::MAX`, for running balances that may go negative
+    Signed,
+}
+
+impl CounterKind {
+    /// The kind stored as `byte` in a [`Counter`], if it names one
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(CounterKind::Unsigned),
+            1 => Some(CounterKind::Signed),
+            _ => None,
+        }
+    }
+
+    /// Smallest value a counter of this kind can hold
+    pub fn min(self) -> i128 {
+        match self {
+            CounterKind::Unsigned => 0,
+            CounterKind::Signed => i64::MIN.into(),
+        }
+    }
+
+    /// Largest value a counter of this kind can hold
+    pub fn max(self) -> i128 {
+        match self {
+            CounterKind::Unsigned => u64::MAX.into(),
+            CounterKind::Signed => i64::MAX.into(),
+        }
+    }
+
+    /// Raw storage bits of `value`, which must lie within this kind's range
+    pub fn encode(self, value: i128) -> u64 {
+        match self {
+            CounterKind::Unsigned => value as u64,
+            CounterKind::Signed => value as i64 as u64,
+        }
+    }
+
+    /// The value represented by the raw storage bits `raw`
+    pub fn decode(self, raw: u64) -> i128 {
+        match self {
+            CounterKind::Unsigned => raw.into(),
+            CounterKind::Signed => (raw as i64).into(),
+        }
+    }
+}
+
+/// Whether a counter's changes are locked at a given slot, see `Lock`
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(
+    BorshSerialize, BorshDeserialize, BorshSchema, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+#[repr(u8)]
+pub enum LockStatus {
+    /// Changes go through, the counter never having been locked or its lock
+    /// having run out
+    #[default]
+    Unlocked,
+    /// Changes fail until the counter's `locked_until` slot
+    Locked,
+}
+
+/// A mode a counter can have switched on, stored as one bit of
+/// [`Counter::flags`]
+///
+/// Bits are append-only: a new mode takes the next unused one, so counters
+/// written before it read as having the mode off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u16)]
+pub enum CounterFlag {
+    /// The counter has a history account, which changes must then update
+    History = 1 << 0,
+    /// Decrements past the lower bound stop at it instead of failing
+    ClampDecrements = 1 << 1,
+    /// A transaction may change the count with at most one instruction
+    ExactlyOnce = 1 << 2,
+    /// A callback is armed, which changes must then pass, see `SetCallback`
+    Callback = 1 << 3,
+    /// Hook programs are set, which changes must then pass, see `SetHooks`
+    Hooks = 1 << 4,
+    /// Increments require holding tokens, see `SetTokenGate`
+    TokenGate = 1 << 5,
+    /// `authority` is the mint of an NFT whose holder is the authority
+    NftAuthority = 1 << 6,
+    /// Every increment burns tokens, see `SetBurnMint`
+    Burn = 1 << 7,
+    /// Changes crossing milestones mint rewards, see `SetRewards`
+    Rewards = 1 << 8,
+    /// Increments need a permit signed by the authority, see `RequirePermits`
+    Permits = 1 << 9,
+    /// Each actor's increments are throttled, see `SetCooldown`
+    Cooldown = 1 << 10,
+    /// The first change in a new epoch starts from zero, see `SetEpochReset`
+    EpochReset = 1 << 11,
+    /// The value fades towards zero over time, see `SetDecay`
+    Decay = 1 << 12,
+    /// A change paid a lamport fee since the last rewarded crank, see `Crank`
+    FeePaid = 1 << 13,
+    /// The counter's escrow is open, which keeps the counter from being closed
+    Escrow = 1 << 14,
+}
+
+/// Counter data structure that will be stored in a Solana account
+/// This struct represents the state of our counter program
+///
+/// Every counter account starts with [`Counter::DISCRIMINATOR`], which lets
+/// the program tell its own initialized counters apart from empty or foreign
+/// accounts before trusting the rest of the data, followed by a version byte
+/// identifying the layout of the remaining fields.
+///
+/// The struct is `#[repr(C)]` and `Pod`, so handlers read and write it in
+/// place over the account data instead of deserializing a copy. Fields are
+/// ordered so that none needs padding, which also makes the layout identical
+/// to its Borsh encoding for clients.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct Counter {
+    pub discriminator: [u8; 8], // Always Counter::DISCRIMINATOR for an initialized counter
+    pub version: u8,            // Layout version, Counter::VERSION for this layout
+    pub overflow_mode: u8, // Arithmetic semantics chosen at initialization, see `overflow_mode()`
+    pub kind: u8,          // Whether the counter may go negative, see `kind()`
+    pub paused: u8,        // While non-zero, every instruction that changes the count fails
+    pub step: u32, // Increments and decrements must be multiples of this; 0 or 1 allows any amount
+    pub authority: Pubkey, // The key allowed to perform privileged operations like Reset
+    pub count: u64, // The raw counter value; signed counters store it as two's complement
+    pub min: u64,  // Smallest value mutations may produce, stored like `count`
+    pub max: u64,  // Largest value mutations may produce, stored like `count`
+    pub pending_authority: Pubkey, // Proposed new authority, or Pubkey::default() if none
+    pub delegate: Pubkey, // Key allowed to decrement on the authority's behalf, or Pubkey::default() if none
+    pub allowance: u64,   // Total amount the delegate may still decrement by
+    pub modulus: u64, // Results are reduced modulo this, so the count stays below it; 0 disables
+    pub total_increments: u64, // Sum of every amount ever added by increments, saturating
+    pub total_decrements: u64, // Sum of every amount ever subtracted by decrements, saturating
+    pub last_updated_slot: u64, // Slot of the last instruction that changed the count
+    pub high_water: u64, // Largest value the count has ever held, stored like `count`
+    pub low_water: u64, // Smallest value the count has ever held, stored like `count`
+    pub last_updated_ts: i64, // Unix timestamp of the last instruction that changed the count
+    pub thresholds: [u64; Counter::MAX_THRESHOLDS], // Values whose crossing emits an event, stored like `count`; the first `threshold_count` are set
+    pub epoch: u64,                                 // Epoch `epoch_change` was accumulated in
+    pub epoch_change: u64, // Total amount the count moved by in `epoch`, checked against the config's epoch cap
+    pub cumulative: [u8; 16], // Sum of each value times the seconds it was held until `last_updated_ts`, see `cumulative_value()`
+    pub locked_until: u64, // Changes fail before this slot, see `lock_status()`; 0 if never locked
+    pub expires_at: i64, // Unix timestamp from which changes fail for good, see `is_expired()`; 0 if never
+    pub ema: [u8; 16],   // Exponential moving average of the value after each change, see `ema()`
+    pub ema_alpha: u64, // Weight of each new value in `ema`, in basis points; 0 if no average is kept
+    pub next_crank_epoch: u64, // First epoch in which a crank of the counter may be rewarded again; 0 if never rewarded
+    pub flags: u16,            // Modes switched on for the counter, one bit per `CounterFlag`
+    pub freeze_authority: Pubkey, // Key that pauses and unpauses instead of the authority, or Pubkey::default() if none
+    pub threshold_count: u8,      // Number of entries of `thresholds` in use
+    pub reserved: [u8; Counter::RESERVED_LEN], // Zeroed space for future fields
+}
+
+impl Counter {
+    /// Number of bytes a Counter occupies in account data
+    ///
+    /// `Pod` rules out padding, so this is exactly the sum of the field sizes.
+    pub const LEN: usize = core::mem::size_of::<Self>();
+
+    /// Size of the zeroed block at the end of the layout
+    ///
+    /// New fields are carved out of it, with all-zero bytes meaning "unset",
+    /// so adding one needs no realloc; it still needs a version bump, since
+    /// an older program would keep the bytes zeroed without knowing them. A
+    /// new mode should take a spare bit of `flags` instead.
+    pub const RESERVED_LEN: usize = 13;
+
+    /// Most thresholds a counter can watch
+    pub const MAX_THRESHOLDS: usize = 4;
+
+    /// Fixed-point scale of [`Counter::ema`], which keeps nine decimals of
+    /// a value
+    pub const EMA_SCALE: i128 = 1_000_000_000;
+
+    /// Largest `ema_alpha`, with which the average is always the last value
+    pub const MAX_EMA_ALPHA: u64 = 10_000;
+
+    /// Tag written at the start of every initialized counter account
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"counter\0";
+    /// Anchor's `sha256("account:Counter")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [255, 176, 4, 245, 188, 253, 124, 25];
+
+    /// Layout version written by this program
+    ///
+    /// Bump this whenever a field is moved or resized, or added outside the
+    /// reserved block, and teach `Migrate` to upgrade the old layout. The
+    /// discriminator, version and authority must stay at the offsets exported
+    /// below, which clients rely on to filter accounts.
+    pub const VERSION: u8 = 14;
+
+    /// A freshly initialized counter owned by `authority`, bounded only by the range of `kind`
+    pub fn new(authority: Pubkey, overflow_mode: OverflowMode, kind: CounterKind) -> Self {
+        Self {
+            discriminator: Self::DISCRIMINATOR,
+            version: Self::VERSION,
+            overflow_mode: overflow_mode as u8,
+            kind: kind as u8,
+            paused: 0,
+            step: 0,
+            authority,
+            count: 0,
+            min: kind.encode(kind.min()),
+            max: kind.encode(kind.max()),
+            pending_authority: Pubkey::default(),
+            delegate: Pubkey::default(),
+            allowance: 0,
+            modulus: 0,
+            total_increments: 0,
+            total_decrements: 0,
+            last_updated_slot: 0,
+            high_water: 0,
+            low_water: 0,
+            last_updated_ts: 0,
+            thresholds: [0; Self::MAX_THRESHOLDS],
+            epoch: 0,
+            epoch_change: 0,
+            cumulative: [0; 16],
+            locked_until: 0,
+            expires_at: 0,
+            ema: [0; 16],
+            ema_alpha: 0,
+            next_crank_epoch: 0,
+            flags: 0,
+            freeze_authority: Pubkey::default(),
+            threshold_count: 0,
+            reserved: [0; Self::RESERVED_LEN],
+        }
+    }
+
+    /// Whether `flag` is switched on for the counter
+    pub fn has_flag(&self, flag: CounterFlag) -> bool {
+        self.flags & flag as u16 != 0
+    }
+
+    /// Switch `flag` on or off for the counter
+    pub fn set_flag(&mut self, flag: CounterFlag, on: bool) {
+        if on {
+            self.flags |= flag as u16;
+        } else {
+            self.flags &= !(flag as u16);
+        }
+    }
+
+    /// Whether the enum bytes hold values this program knows how to interpret
+    ///
+    /// Checked when a counter is loaded, so the accessors below can rely on it.
+    pub fn is_valid(&self) -> bool {
+        OverflowMode::from_byte(self.overflow_mode).is_some()
+            && CounterKind::from_byte(self.kind).is_some()
+    }
+
+    /// How the counter handles results that do not fit in its range
+    pub fn overflow_mode(&self) -> OverflowMode {
+        OverflowMode::from_byte(self.overflow_mode).unwrap_or_default()
+    }
+
+    /// Which range of values the counter can hold
+    pub fn kind(&self) -> CounterKind {
+        CounterKind::from_byte(self.kind).unwrap_or_default()
+    }
+
+    /// The counter value, interpreting the stored bits according to `kind`
+    pub fn value(&self) -> i128 {
+        self.kind().decode(self.count)
+    }
+
+    /// Smallest value mutations may produce
+    pub fn min_value(&self) -> i128 {
+        self.kind().decode(self.min)
+    }
+
+    /// Largest value mutations may produce
+    pub fn max_value(&self) -> i128 {
+        self.kind().decode(self.max)
+    }
+
+    /// Restrict the counter to `min..=max`, which must lie within the range of
+    /// its kind and include the current value
+    pub fn set_bounds(&mut self, min: i128, max: i128) -> Result<(), CounterError> {
+        let kind = self.kind();
+        let range = kind.min()..=kind.max();
+        if min > max
+            || !range.contains(&min)
+            || !range.contains(&max)
+            || !(min..=max).contains(&self.value())
+        {
+            return Err(CounterError::InvalidBounds);
+        }
+        self.min = kind.encode(min);
+        self.max = kind.encode(max);
+        Ok(())
+    }
+
+    /// Add `amount` to the count
+    pub fn add(&mut self, amount: u32) -> Result<(), CounterError> {
+        self.check_step(amount)?;
+        self.store(arithmetic::increment(self.value(), amount.into()))?;
+        self.total_increments = self.total_increments.saturating_add(amount.into());
+        Ok(())
+    }
+
+    /// Subtract `amount` from the count
+    ///
+    /// With `CounterFlag::ClampDecrements` set, a result below the lower bound becomes
+    /// the bound itself, except on modular counters, which wrap instead.
+    pub fn sub(&mut self, amount: u32) -> Result<(), CounterError> {
+        self.check_step(amount)?;
+        let mut result = arithmetic::decrement(self.value(), amount.into());
+        if self.has_flag(CounterFlag::ClampDecrements) && self.modulus == 0 {
+            result = result.max(self.min_value());
+        }
+        self.store(result)?;
+        self.total_decrements = self.total_decrements.saturating_add(amount.into());
+        Ok(())
+    }
+
+    /// Multiply the count by `factor`
+    pub fn mul(&mut self, factor: u32) -> Result<(), CounterError> {
+        self.store(arithmetic::multiply(self.value(), factor))
+    }
+
+    /// Divide the count by `divisor`, rounding towards zero
+    pub fn div(&mut self, divisor: u32) -> Result<(), CounterError> {
+        self.store(arithmetic::divide(self.value(), divisor)?)
+    }
+
+    /// Move the whole value of `source` into this counter, leaving `source` at zero
+    pub fn merge(&mut self, source: &mut Counter) -> Result<(), CounterError> {
+        let amount = source.value();
+        source.store(0)?;
+        self.store(self.value() + amount)
+    }
+
+    /// The exponential moving average of the value after each change, in
+    /// units of 1 / [`Counter::EMA_SCALE`], or `None` unless one is kept, see
+    /// `SetEma`
+    ///
+    /// Each change moves the average `ema_alpha` basis points of the way
+    /// from where it was to the new value.
+    pub fn ema(&self) -> Option<i128> {
+        (self.ema_alpha != 0).then(|| i128::from_le_bytes(self.ema))
+    }
+
+    /// Move the moving average towards the current value, if one is kept
+    pub fn update_ema(&mut self) {
+        let Some(ema) = self.ema() else {
+            return;
+        };
+        let target = self.value() * Self::EMA_SCALE;
+        let ema =
+            ema + (target - ema) * i128::from(self.ema_alpha) / i128::from(Self::MAX_EMA_ALPHA);
+        self.ema = ema.to_le_bytes();
+    }
+
+    /// Whether the counter's expiry has passed at `unix_timestamp`, after
+    /// which its value never changes again
+    pub fn is_expired(&self, unix_timestamp: i64) -> bool {
+        self.expires_at != 0 && unix_timestamp >= self.expires_at
+    }
+
+    /// Whether changes to the counter are locked at `slot`
+    pub fn lock_status(&self, slot: u64) -> LockStatus {
+        if slot < self.locked_until {
+            LockStatus::Locked
+        } else {
+            LockStatus::Unlocked
+        }
+    }
+
+    /// The sum of every value the counter held times the seconds it held it
+    /// for, up to `last_updated_ts`, wrapping on overflow
+    ///
+    /// Two readings taken apart in time give the counter's average value in
+    /// between, see [`time_weighted_average`]. Time before the counter first
+    /// changed is not counted.
+    pub fn cumulative_value(&self) -> i128 {
+        i128::from_le_bytes(self.cumulative)
+    }
+
+    /// The cumulative value as of `unix_timestamp`, with the current value
+    /// held since the last change
+    pub fn cumulative_value_at(&self, unix_timestamp: i64) -> i128 {
+        // Nothing is counted before the first change
+        if self.last_updated_ts == 0 {
+            return self.cumulative_value();
+        }
+        let elapsed = unix_timestamp.saturating_sub(self.last_updated_ts).max(0);
+        self.cumulative_value()
+            .wrapping_add(self.value().wrapping_mul(elapsed.into()))
+    }
+
+    /// Count `old_value`, the value before a change made at `unix_timestamp`,
+    /// as held since the last change
+    pub fn accumulate(&mut self, old_value: i128, unix_timestamp: i64) {
+        if self.last_updated_ts == 0 {
+            return;
+        }
+        let elapsed = unix_timestamp.saturating_sub(self.last_updated_ts).max(0);
+        let cumulative = self
+            .cumulative_value()
+            .wrapping_add(old_value.wrapping_mul(elapsed.into()));
+        self.cumulative = cumulative.to_le_bytes();
+    }
+
+    /// Move the count `amount` towards zero, stopping at zero or at the
+    /// bound on the way there
+    pub fn decay(&mut self, amount: u64) -> Result<(), CounterError> {
+        let value = self.value();
+        let amount = i128::from(amount);
+        let result = if value > 0 {
+            (value - amount).max(0).max(self.min_value())
+        } else {
+            (value + amount).min(0).min(self.max_value())
+        };
+        self.store(result)
+    }
+
+    /// Overwrite the count with `value`
+    pub fn set(&mut self, value: u64) -> Result<(), CounterError> {
+        self.store(value.into())
+    }
+
+    /// Add `amount` drained from the counter's shards, whose increments were
+    /// already checked against `step` one by one
+    pub fn absorb(&mut self, amount: u64) -> Result<(), CounterError> {
+        self.store(arithmetic::increment(self.value(), amount))?;
+        self.total_increments = self.total_increments.saturating_add(amount);
+        Ok(())
+    }
+
+    /// The values whose crossing emits an event
+    pub fn thresholds(&self) -> impl Iterator<Item = i128> + '_ {
+        let count = usize::from(self.threshold_count).min(Self::MAX_THRESHOLDS);
+        self.thresholds[..count]
+            .iter()
+            .map(|&raw| self.kind().decode(raw))
+    }
+
+    /// Watch `thresholds`, replacing any set before; an empty slice stops watching
+    ///
+    /// Each must lie within the range of the counter's kind.
+    pub fn set_thresholds(&mut self, thresholds: &[i128]) -> Result<(), CounterError> {
+        let kind = self.kind();
+        if thresholds.len() > Self::MAX_THRESHOLDS
+            || thresholds
+                .iter()
+                .any(|threshold| !(kind.min()..=kind.max()).contains(threshold))
+        {
+            return Err(CounterError::InvalidThresholds);
+        }
+        self.thresholds = [0; Self::MAX_THRESHOLDS];
+        for (slot, &threshold) in self.thresholds.iter_mut().zip(thresholds) {
+            *slot = kind.encode(threshold);
+        }
+        // At most MAX_THRESHOLDS, checked above
+        self.threshold_count = thresholds.len() as u8;
+        Ok(())
+    }
+
+    /// The thresholds a change from `old_value` to the current value crossed,
+    /// each with whether the count rose to it or fell below it
+    ///
+    /// A threshold splits values into those below it and those at or above
+    /// it, so reaching it exactly counts as crossing upwards. Only the values
+    /// before and after count: a wrapping counter that overflowed past zero
+    /// falls below the thresholds between its old and new value.
+    pub fn crossings(&self, old_value: i128) -> impl Iterator<Item = (i128, Direction)> + '_ {
+        let new_value = self.value();
+        self.thresholds().filter_map(move |threshold| {
+            match (old_value >= threshold, new_value >= threshold) {
+                (false, true) => Some((threshold, Direction::Up)),
+                (true, false) => Some((threshold, Direction::Down)),
+                _ => None,
+            }
+        })
+    }
+
+    /// The key allowed to pause and unpause the counter: its freeze authority
+    /// if it has one, like an SPL Token mint, and its authority otherwise
+    pub fn pause_authority(&self) -> Pubkey {
+        if self.freeze_authority == Pubkey::default() {
+            self.authority
+        } else {
+            self.freeze_authority
+        }
+    }
+
+    /// Reject increment and decrement amounts that are not a multiple of `step`
+    pub fn check_step(&self, amount: u32) -> Result<(), CounterError> {
+        self.check_active()?;
+        if self.step > 1 && !amount.is_multiple_of(self.step) {
+            return Err(CounterError::InvalidStep);
+        }
+        Ok(())
+    }
+
+    /// Reject mutations while the counter is paused
+    fn check_active(&self) -> Result<(), CounterError> {
+        if self.paused != 0 {
+            return Err(CounterError::CounterPaused);
+        }
+        Ok(())
+    }
+
+    /// Write the exact result of an operation, reducing it modulo `modulus`
+    /// for modular counters, applying the counter's overflow mode if it falls
+    /// outside the range of its kind and then rejecting it if it lies outside
+    /// the counter's own bounds
+    ///
+    /// Every change to the count goes through here, which keeps the high and
+    /// low water marks up to date.
+    fn store(&mut self, result: i128) -> Result<(), CounterError> {
+        self.check_active()?;
+        let result = arithmetic::reduce(result, self.modulus);
+        let kind = self.kind();
+        let value = arithmetic::fit(self.overflow_mode(), result, kind.min(), kind.max())?;
+        if value < self.min_value() {
+            return Err(CounterError::BelowMinimum);
+        }
+        if value > self.max_value() {
+            return Err(CounterError::AboveMaximum);
+        }
+        // `fit` keeps the value within the kind's range, so encoding is lossless
+        self.count = kind.encode(value);
+        if value > kind.decode(self.high_water) {
+            self.high_water = self.count;
+        }
+        if value < kind.decode(self.low_water) {
+            self.low_water = self.count;
+        }
+        Ok(())
+    }
+}
+
+/// Size of a counter account's data, for clients allocating or filtering accounts
+///
+/// Accounts grown by `Resize` or `SetMetadata` are larger, so filter on
+/// [`COUNTER_DISCRIMINATOR`] rather than on the exact size.
+pub const COUNTER_LEN: usize = Counter::LEN;
+
+/// Lamports a new counter account must hold to be rent-exempt under `rent`
+///
+/// `Initialize` funds the account itself; this is for clients that want to
+/// show or check the cost up front.
+pub fn required_lamports(rent: &Rent) -> u64 {
+    rent.minimum_balance(COUNTER_LEN)
+}
+
+/// Tag every counter account starts with, at [`COUNTER_DISCRIMINATOR_OFFSET`]
+pub const COUNTER_DISCRIMINATOR: [u8; 8] = Counter::DISCRIMINATOR;
+
+/// Current counter layout version, stored at [`COUNTER_VERSION_OFFSET`]
+pub const COUNTER_VERSION: u8 = Counter::VERSION;
+
+/// Byte offset of the discriminator in a counter account
+///
+/// This and the other offsets below are fixed from layout version 5 on and
+/// later versions keep them, so `getProgramAccounts` memcmp filters built on
+/// them keep matching after upgrades; counters in older layouts only match
+/// once migrated. For example, a wallet's counters are the accounts with
+/// [`COUNTER_DISCRIMINATOR`] at this offset and the wallet's key at
+/// [`COUNTER_AUTHORITY_OFFSET`].
+pub const COUNTER_DISCRIMINATOR_OFFSET: usize = core::mem::offset_of!(Counter, discriminator);
+
+/// Byte offset of the layout version byte in a counter account
+pub const COUNTER_VERSION_OFFSET: usize = core::mem::offset_of!(Counter, version);
+
+/// Byte offset of the authority's key in a counter account
+pub const COUNTER_AUTHORITY_OFFSET: usize = core::mem::offset_of!(Counter, authority);
+
+/// Human-readable labels for a counter, stored right after it in the account
+///
+/// Accounts start without room for metadata; `SetMetadata` grows them as
+/// needed. Zeroed space after the counter reads as an empty name and
+/// description, so an account that was only resized has no labels yet.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Default)]
+pub(crate) struct CounterMetadata {
+    pub name: String,        // Short label, at most MAX_NAME_LEN bytes of UTF-8
+    pub description: String, // Longer text, at most MAX_DESCRIPTION_LEN bytes of UTF-8
+}
+
+impl CounterMetadata {
+    /// Longest name allowed, in bytes
+    pub const MAX_NAME_LEN: usize = 32;
+
+    /// Longest description allowed, in bytes
+    pub const MAX_DESCRIPTION_LEN: usize = 200;
+
+    /// Metadata with the given labels, which must fit the length limits
+    pub fn new(name: String, description: String) -> Result<Self, CounterError> {
+        if name.len() > Self::MAX_NAME_LEN || description.len() > Self::MAX_DESCRIPTION_LEN {
+            return Err(CounterError::MetadataTooLong);
+        }
+        Ok(Self { name, description })
+    }
+
+    /// Number of bytes the metadata occupies in account data
+    pub fn len(&self) -> usize {
+        4 + self.name.len() + 4 + self.description.len()
+    }
+}
+
+/// Prefix of the seeds used to derive PDA counters
+pub const COUNTER_SEED: &[u8] = b"counter";
+
+/// Find the address of the counter `authority` owns under `seed`
+///
+/// PDA counters live at `["counter", authority, seed]`, so a wallet can keep
+/// any number of namespaced counters without managing extra keypairs. The
+/// seed must be at most 32 bytes long.
+pub fn find_counter_address(program_id: &Pubkey, authority: &Pubkey, seed: &str) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[COUNTER_SEED, authority.as_ref(), seed.as_bytes()],
+        program_id,
+    )
+}
+
+/// The address of the counter `authority` owns under `seed`, from the `bump`
+/// [`find_counter_address`] returned for it
+///
+/// A single hash instead of the search over bumps, for callers that keep the
+/// bump around. An error means `bump` gives no valid address; any bump other
+/// than the one found gives an address the program rejects.
+pub fn create_counter_address(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    seed: &str,
+    bump: u8,
+) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(
+        &[COUNTER_SEED, authority.as_ref(), seed.as_bytes(), &[bump]],
+        program_id,
+    )
+}
+
+/// Seed of the program's singleton config PDA
+pub const CONFIG_SEED: &[u8] = b"config";
+
+/// Find the address of the program's config account
+///
+/// There is exactly one config per deployment, at `["config"]`.
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+/// The address of the program's config account, from the `bump`
+/// [`find_config_address`] returned
+///
+/// Like [`create_counter_address`], this skips the search over bumps.
+pub fn create_config_address(program_id: &Pubkey, bump: u8) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(&[CONFIG_SEED, &[bump]], program_id)
+}
+
+/// Program-wide settings stored in the config PDA
+///
+/// Every instruction that changes a counter's value reads the config first,
+/// so the admin can halt the whole program or cap individual changes. Until
+/// the config is initialized, [`Config::default`] applies: no admin, not
+/// paused and no limits.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Default)]
+pub(crate) struct Config {
+    pub discriminator: [u8; 8], // Always Config::DISCRIMINATOR for an initialized config
+    pub admin: Pubkey,          // The key allowed to update the config
+    pub paused: bool,           // While set, no counter's value may change
+    pub max_amount: u32, // Largest amount a single increment or decrement may apply; 0 for no limit
+}
+
+impl Config {
+    /// Number of bytes a serialized Config occupies in account data
+    pub const LEN: usize = 8 + 32 + 1 + 4;
+
+    /// Tag written at the start of the config account
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"config\0\0";
+    /// Anchor's `sha256("account:Config")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [155, 12, 170, 224, 30, 250, 204, 130];
+
+    /// A freshly initialized, unpaused config administered by `admin`
+    pub fn new(admin: Pubkey, max_amount: u32) -> Self {
+        Self {
+            discriminator: Self::DISCRIMINATOR,
+            admin,
+            paused: false,
+            max_amount,
+        }
+    }
+
+    /// Reject counter mutations while the program is paused
+    pub fn check_active(&self) -> Result<(), CounterError> {
+        if self.paused {
+            return Err(CounterError::ProgramPaused);
+        }
+        Ok(())
+    }
+
+    /// Reject increments and decrements of zero or larger than `max_amount`
+    pub fn check_amount(&self, amount: u32) -> Result<(), CounterError> {
+        arithmetic::check_nonzero(amount)?;
+        if self.max_amount != 0 && amount > self.max_amount {
+            return Err(CounterError::AmountExceedsLimit);
+        }
+        Ok(())
+    }
+}
+
+/// Programs allowed to invoke this one through CPI while the caller guard is
+/// on, and programs no transaction changing a counter may include
+///
+/// Stored right after the [`Config`] in the config account. A config without
+/// one, including every config created before it existed, leaves the guard
+/// off, as do zeroed bytes.
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+pub(crate) struct CallerGuard {
+    pub enabled: bool,                   // Whether CPIs from other programs are checked
+    pub allowed_programs: Vec<Pubkey>,   // Programs whose CPIs are accepted while enabled
+    pub forbidden_programs: Vec<Pubkey>, // Programs whose presence in a transaction rejects counter changes
+}
+
+impl CallerGuard {
+    /// Largest number of programs each of the two lists can hold
+    pub const MAX_ALLOWED_PROGRAMS: usize = 8;
+
+    /// Number of bytes the guard occupies in account data
+    pub fn len(&self) -> usize {
+        1 + 4 + 32 * self.allowed_programs.len() + 4 + 32 * self.forbidden_programs.len()
+    }
+
+    /// Whether a CPI whose transaction-level instruction targets `program` is accepted
+    pub fn allows(&self, program: &Pubkey) -> bool {
+        !self.enabled || self.allowed_programs.contains(program)
+    }
+}
+
+/// A fee in SPL tokens charged for every change to a counter's value, as set
+/// by `SetFee`
+///
+/// Stored as an `Option` right after the caller guard in the config
+/// account, where zeroed or missing bytes mean no fee.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeConfig {
+    /// The mint of the tokens the fee is paid in
+    pub mint: Pubkey,
+    /// The fee, in the mint's base units
+    pub amount: u64,
+    /// The token account receiving the fees, usually the admin's associated
+    /// token account for `mint`
+    pub treasury: Pubkey,
+}
+
+impl FeeConfig {
+    /// Number of bytes an `Option<FeeConfig>` holding a fee occupies in account data
+    pub const LEN: usize = 1 + 32 + 8 + 32;
+}
+
+/// The fees the config charges for every change to a counter's value,
+/// stored right after the caller guard
+///
+/// A config that ends before them or holds zeroed bytes there charges
+/// nothing, and one that ends after the token fee charges no lamports.
+#[derive(BorshSerialize, Clone, Copy, Default)]
+pub(crate) struct Fees {
+    pub token: Option<FeeConfig>, // Set by `SetFee`, paid to the fee's treasury
+    pub lamports: u64,            // Set by `SetLamportFee`, paid into the fee vault
+}
+
+impl Fees {
+    /// Number of bytes the fees occupy in account data
+    pub fn len(&self) -> usize {
+        self.token.map_or(1, |_| FeeConfig::LEN) + 8
+    }
+}
+
+/// What the config pays for cranks, stored right after the epoch cap
+///
+/// A config that ends before any of the fields reads them as 0, so one from
+/// before the epoch budget pays no rewards until `SetCrankReward` sets one.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default)]
+pub(crate) struct CrankRewards {
+    pub reward: u64,       // Set by `SetCrankReward`, paid for each rewarded crank
+    pub epoch_budget: u64, // Set by `SetCrankReward`, the most paid out in rewards per epoch
+    pub epoch: u64,        // Epoch `paid` was tallied in
+    pub paid: u64,         // Total paid out in rewards in `epoch`
+}
+
+impl CrankRewards {
+    /// Number of bytes the crank rewards occupy in account data
+    pub const LEN: usize = 8 + 8 + 8 + 8;
+}
+
+/// Seed of the program's singleton fee vault PDA
+pub const FEE_VAULT_SEED: &[u8] = b"fee_vault";
+
+/// Find the address of the program's fee vault
+///
+/// The lamport fees set by `SetLamportFee` are collected at `["fee_vault"]`
+/// until the config admin withdraws them.
+pub fn find_fee_vault_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[FEE_VAULT_SEED], program_id)
+}
+
+/// The program-owned account collecting lamport fees
+///
+/// Its balance above the rent-exempt minimum is what `WithdrawFees` can take.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct FeeVault {
+    pub discriminator: [u8; 8], // Always FeeVault::DISCRIMINATOR
+    pub bump: u8,               // Bump seed of the vault's address
+    pub collected: u64,         // Lamports collected over the vault's lifetime, saturating
+}
+
+impl FeeVault {
+    /// Number of bytes a serialized FeeVault occupies in account data
+    pub const LEN: usize = 8 + 1 + 8;
+
+    /// Tag written at the start of the fee vault
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"feevault";
+    /// Anchor's `sha256("account:FeeVault")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [192, 178, 69, 232, 58, 149, 157, 132];
+
+    /// Whether `data` is the data of a fee vault
+    pub fn is_vault(data: &[u8]) -> bool {
+        data.len() == Self::LEN && data[..8] == Self::DISCRIMINATOR
+    }
+}
+
+/// Address of the SPL Token program, one of the two programs a fee may be
+/// transferred with
+pub(crate) const TOKEN_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+/// Address of the SPL Token-2022 program
+pub(crate) const TOKEN_2022_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Offset of the `u64` supply in an SPL Token mint, which Token-2022 mints share
+pub(crate) const MINT_SUPPLY_OFFSET: usize = 36;
+
+/// Offset of the `decimals` byte in an SPL Token mint
+pub(crate) const MINT_DECIMALS_OFFSET: usize = 44;
+
+/// The SPL Governance `GovernanceAccountType`s of governance accounts, the
+/// first byte of their data: `GovernanceV1`, `ProgramGovernanceV1`,
+/// `MintGovernanceV1`, `TokenGovernanceV1` and their `V2` counterparts
+pub(crate) const GOVERNANCE_ACCOUNT_TYPES: [u8; 8] = [3, 4, 9, 10, 18, 19, 20, 21];
+
+/// Seed prefix of a governance's native treasury, derived by the governance
+/// program from the governance's key
+pub const NATIVE_TREASURY_SEED: &[u8] = b"native-treasury";
+
+/// Find the address of `governance`'s native treasury, the account
+/// `SetGovernanceAuthority` hands authority to with `treasury` set
+///
+/// The governance program derives it at `["native-treasury", governance]`, so
+/// `governance_program` must be the deployment owning `governance`.
+pub fn find_native_treasury_address(
+    governance_program: &Pubkey,
+    governance: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[NATIVE_TREASURY_SEED, governance.as_ref()],
+        governance_program,
+    )
+}
+
+/// A pair of tallies for like/dislike style voting, stored in one account
+///
+/// Both sides only ever grow; [`VoteCounter::net`] gives the balance.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct VoteCounter {
+    pub discriminator: [u8; 8], // Always VoteCounter::DISCRIMINATOR
+    pub up: u64,                // Total upvotes
+    pub down: u64,              // Total downvotes
+}
+
+impl VoteCounter {
+    /// Number of bytes a serialized VoteCounter occupies in account data
+    pub const LEN: usize = 8 + 8 + 8;
+
+    /// Tag written at the start of every vote account
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"votes\0\0\0";
+    /// Anchor's `sha256("account:VoteCounter")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [130, 160, 71, 216, 34, 169, 97, 199];
+
+    /// A vote account with no votes on either side
+    pub fn new() -> Self {
+        Self {
+            discriminator: Self::DISCRIMINATOR,
+            up: 0,
+            down: 0,
+        }
+    }
+
+    /// Upvotes minus downvotes
+    pub fn net(&self) -> i128 {
+        i128::from(self.up) - i128::from(self.down)
+    }
+}
+
+/// One named counter inside a [`CounterMap`]
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct MapEntry {
+    pub name: [u8; 32], // Name of the metric, typically zero-padded UTF-8
+    pub count: u64,     // Current value
+}
+
+/// A set of named counters kept in a single account
+///
+/// The account is resized whenever an entry is added or removed, so its data
+/// is always exactly the Borsh encoding of the map.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CounterMap {
+    pub discriminator: [u8; 8], // Always CounterMap::DISCRIMINATOR
+    pub authority: Pubkey,      // The key allowed to add, remove and decrement entries
+    pub entries: Vec<MapEntry>, // The named counters, in insertion order
+}
+
+impl CounterMap {
+    /// Number of bytes an empty map occupies in account data
+    pub const BASE_LEN: usize = 8 + 32 + 4;
+
+    /// Number of bytes each entry adds
+    pub const ENTRY_LEN: usize = 32 + 8;
+
+    /// Tag written at the start of every map account
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"countmap";
+    /// Anchor's `sha256("account:CounterMap")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [24, 70, 42, 192, 154, 67, 209, 158];
+
+    /// An empty map administered by `authority`
+    pub fn new(authority: Pubkey) -> Self {
+        Self {
+            discriminator: Self::DISCRIMINATOR,
+            authority,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Number of bytes the map occupies in account data
+    pub fn len(&self) -> usize {
+        Self::BASE_LEN + self.entries.len() * Self::ENTRY_LEN
+    }
+
+    /// Position of the entry called `name`
+    pub fn position(&self, name: &[u8; 32]) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.name == *name)
+    }
+
+    /// The entry called `name`
+    pub fn entry_mut(&mut self, name: &[u8; 32]) -> Result<&mut MapEntry, CounterError> {
+        self.entries
+            .iter_mut()
+            .find(|entry| entry.name == *name)
+            .ok_or(CounterError::EntryNotFound)
+    }
+}
+
+/// Largest number of keys a [`Multisig`] can hold
+pub(crate) const MAX_SIGNERS: usize = 11;
+
+/// An M-of-N multisig that can act as a counter authority
+///
+/// Modelled on SPL token multisigs: a counter whose authority is the address
+/// of a `Multisig` account accepts a privileged instruction once at least `m`
+/// of the `n` listed keys have signed it.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct Multisig {
+    pub discriminator: [u8; 8],         // Always Multisig::DISCRIMINATOR
+    pub m: u8,                          // Number of signatures required
+    pub n: u8,                          // Number of valid entries in `signers`
+    pub signers: [Pubkey; MAX_SIGNERS], // Signer keys; entries past `n` are unused
+}
+
+impl Multisig {
+    /// Number of bytes a serialized Multisig occupies in account data
+    pub const LEN: usize = 8 + 1 + 1 + 32 * MAX_SIGNERS;
+
+    /// Tag written at the start of every multisig account
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"multisig";
+    /// Anchor's `sha256("account:Multisig")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [224, 116, 121, 186, 68, 161, 79, 236];
+
+    /// A multisig requiring `m` of `signers`, which must hold between `m` and
+    /// [`MAX_SIGNERS`] keys, with `m` at least one
+    pub fn new(m: u8, keys: &[Pubkey]) -> Result<Self, CounterError> {
+        if m == 0 || usize::from(m) > keys.len() || keys.len() > MAX_SIGNERS {
+            return Err(CounterError::InvalidMultisig);
+        }
+        let mut signers = [Pubkey::default(); MAX_SIGNERS];
+        signers[..keys.len()].copy_from_slice(keys);
+        Ok(Self {
+            discriminator: Self::DISCRIMINATOR,
+            m,
+            n: keys.len() as u8,
+            signers,
+        })
+    }
+
+    /// The valid signer keys
+    pub fn signers(&self) -> &[Pubkey] {
+        &self.signers[..usize::from(self.n)]
+    }
+}
+
+/// Seed prefix of history PDAs
+pub const HISTORY_SEED: &[u8] = b"history";
+
+/// Find the address of the history account of `counter`
+///
+/// Each counter has at most one history, at `["history", counter]`.
+pub fn find_history_address(program_id: &Pubkey, counter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[HISTORY_SEED, counter.as_ref()], program_id)
+}
+
+/// Kind of change recorded in a [`HistoryEntry`]
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug)]
+pub(crate) enum HistoryOp {
+    /// `Increment`, `IncrementByOne` or an increment in a batch
+    Increment,
+    /// `Decrement` or a decrement in a batch
+    Decrement,
+    /// `Multiply`
+    Multiply,
+    /// `Divide`
+    Divide,
+    /// A successful `CompareAndSwap`, recording the value written
+    CompareAndSwap,
+    /// `Reset`
+    Reset,
+    /// `Set` or a set in a batch
+    Set,
+    /// `Aggregate`, recording the total drained from the shards
+    Aggregate,
+    /// Either side of a `Merge`, recording the source's value as it was stored
+    Merge,
+}
+
+/// One change to a counter, as kept in its [`CounterHistory`]
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy)]
+pub(crate) struct HistoryEntry {
+    pub op: HistoryOp, // What the instruction did
+    pub amount: u64,   // Its argument: the amount, factor or divisor, or the value written
+    pub actor: Pubkey, // The authority or delegate, or for increments the optional actor signer
+    pub slot: u64,     // Slot the change was made in
+}
+
+/// The last few changes to a counter, kept as a ring buffer in a companion PDA
+///
+/// The account is allocated for `capacity` entries up front. Entries are
+/// appended until it is full, after which each new entry overwrites the
+/// oldest one, at `next`; reading from `next` to the end and then from the
+/// start gives the entries oldest first.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CounterHistory {
+    pub discriminator: [u8; 8],     // Always CounterHistory::DISCRIMINATOR
+    pub counter: Pubkey,            // The counter whose changes are recorded
+    pub capacity: u16,              // Number of entries kept
+    pub next: u16,                  // Position the next entry is written to
+    pub entries: Vec<HistoryEntry>, // At most `capacity` entries
+}
+
+impl CounterHistory {
+    /// Number of bytes a history without entries occupies in account data
+    pub const BASE_LEN: usize = 8 + 32 + 2 + 2 + 4;
+
+    /// Number of bytes each entry adds
+    pub const ENTRY_LEN: usize = 1 + 8 + 32 + 8;
+
+    /// Largest number of entries a history can keep
+    pub const MAX_CAPACITY: u16 = 64;
+
+    /// Tag written at the start of every history account
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"history\0";
+    /// Anchor's `sha256("account:CounterHistory")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [43, 21, 146, 74, 250, 212, 105, 134];
+
+    /// An empty history of `counter` keeping the last `capacity` entries
+    pub fn new(counter: Pubkey, capacity: u16) -> Self {
+        Self {
+            discriminator: Self::DISCRIMINATOR,
+            counter,
+            capacity,
+            next: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Number of bytes the account needs once every entry is in use
+    pub fn len(capacity: u16) -> usize {
+        Self::BASE_LEN + usize::from(capacity) * Self::ENTRY_LEN
+    }
+
+    /// Record `entry`, overwriting the oldest entry once the history is full
+    pub fn push(&mut self, entry: HistoryEntry) {
+        let next = usize::from(self.next);
+        if next < self.entries.len() {
+            self.entries[next] = entry;
+        } else {
+            self.entries.push(entry);
+        }
+        self.next = (self.next + 1) % self.capacity;
+    }
+}
+
+/// Prefix of the seeds used to derive shard PDAs
+pub const SHARD_SEED: &[u8] = b"shard";
+
+/// Find the address of shard `index` of `counter`
+///
+/// Shards live at `["shard", counter, index]`, with the index as two
+/// little-endian bytes.
+pub fn find_shard_address(program_id: &Pubkey, counter: &Pubkey, index: u16) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[SHARD_SEED, counter.as_ref(), &index.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Part of a counter's increments, kept in an account of its own
+///
+/// A hot counter is a single writable account, so every transaction touching
+/// it is processed one after the other. Spreading increments over several
+/// shards lets them run in parallel; `Aggregate` later drains the shards into
+/// the counter.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CounterShard {
+    pub discriminator: [u8; 8], // Always CounterShard::DISCRIMINATOR
+    pub counter: Pubkey,        // The counter this shard is drained into
+    pub index: u16,             // Position of the shard among the counter's shards
+    pub count: u64,             // Sum of the increments since the last aggregation
+}
+
+impl CounterShard {
+    /// Number of bytes a serialized CounterShard occupies in account data
+    pub const LEN: usize = 8 + 32 + 2 + 8;
+
+    /// Tag written at the start of every shard account
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"shard\0\0\0";
+    /// Anchor's `sha256("account:CounterShard")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [159, 207, 243, 96, 242, 32, 1, 236];
+
+    /// An empty shard `index` of `counter`
+    pub fn new(counter: Pubkey, index: u16) -> Self {
+        Self {
+            discriminator: Self::DISCRIMINATOR,
+            counter,
+            index,
+            count: 0,
+        }
+    }
+
+    /// Whether `data` is the data of a shard
+    pub fn is_shard(data: &[u8]) -> bool {
+        data.len() == Self::LEN && data[..8] == Self::DISCRIMINATOR
+    }
+}
+
+/// Seed prefix of callback PDAs
+pub const CALLBACK_SEED: &[u8] = b"callback";
+
+/// Find the address of the callback account of `counter`
+///
+/// Each counter has at most one callback, at `["callback", counter]`. The
+/// address signs the callback's invocation, see [`crate::cpi::OnTargetReached`].
+pub fn find_callback_address(program_id: &Pubkey, counter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CALLBACK_SEED, counter.as_ref()], program_id)
+}
+
+/// Most accounts `SetCallback` may register with a counter's callback
+pub const MAX_CALLBACK_ACCOUNTS: usize = CounterCallback::MAX_ACCOUNTS;
+
+/// An account a counter's callback is invoked with, as registered by `SetCallback`
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CallbackAccount {
+    pub address: Pubkey,
+    /// Whether the callback is passed the account as writable
+    pub writable: bool,
+}
+
+/// The program a counter invokes once its count reaches a target, kept in a
+/// companion PDA
+///
+/// The account is allocated for [`CounterCallback::MAX_ACCOUNTS`] accounts up
+/// front, so `SetCallback` can replace the callback in place. The counter's
+/// `Callback` flag says whether it is armed: set by `SetCallback`, and
+/// cleared when the callback fires or by `ClearCallback`.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CounterCallback {
+    pub discriminator: [u8; 8], // Always CounterCallback::DISCRIMINATOR
+    pub counter: Pubkey,        // The counter whose changes are watched
+    pub bump: u8,               // Bump seed of the account's address, for signing
+    pub target: i128,           // Value whose reaching fires the callback
+    pub program: Pubkey,        // Program invoked when it fires
+    pub accounts: Vec<CallbackAccount>, // Accounts passed after the callback and counter
+}
+
+impl CounterCallback {
+    /// Number of bytes a callback without accounts occupies in account data
+    pub const BASE_LEN: usize = 8 + 32 + 1 + 16 + 32 + 4;
+
+    /// Number of bytes each registered account adds
+    pub const ACCOUNT_LEN: usize = 32 + 1;
+
+    /// Most accounts a callback can be invoked with, besides the callback and
+    /// counter accounts themselves
+    pub const MAX_ACCOUNTS: usize = 8;
+
+    /// Number of bytes the account is allocated with
+    pub const LEN: usize = Self::BASE_LEN + Self::MAX_ACCOUNTS * Self::ACCOUNT_LEN;
+
+    /// Tag written at the start of every callback account
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"callback";
+    /// Anchor's `sha256("account:CounterCallback")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [193, 119, 42, 83, 55, 146, 194, 60];
+
+    /// Number of accounts registered in the callback account holding `data`,
+    /// or `None` if `data` is not one
+    pub fn account_count(data: &[u8]) -> Option<usize> {
+        if data.get(..8)? != Self::DISCRIMINATOR {
+            return None;
+        }
+        let len = data.get(Self::BASE_LEN - 4..Self::BASE_LEN)?;
+        Some(u32::from_le_bytes(len.try_into().ok()?) as usize)
+    }
+
+    /// Whether a change from `old_value` to `new_value` reaches the target,
+    /// from either side
+    pub fn is_reached(&self, old_value: i128, new_value: i128) -> bool {
+        (old_value < self.target && self.target <= new_value)
+            || (old_value > self.target && self.target >= new_value)
+    }
+}
+
+/// Seed prefix of operator list PDAs
+pub const OPERATORS_SEED: &[u8] = b"operators";
+
+/// Find the address of the operator list of `counter`
+///
+/// Each counter has at most one, at `["operators", counter]`.
+pub fn find_operators_address(program_id: &Pubkey, counter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[OPERATORS_SEED, counter.as_ref()], program_id)
+}
+
+/// Most operators a counter can have at once
+pub const MAX_OPERATORS: usize = CounterOperators::MAX_OPERATORS;
+
+/// A key allowed to decrement a counter, as added by `AddOperator`
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Operator {
+    pub key: Pubkey,
+    /// How much the operator may still decrement the counter by in total
+    pub allowance: u64,
+    /// First slot the operator may no longer act in; 0 never expires
+    pub expiry_slot: u64,
+}
+
+impl Operator {
+    /// Whether the operator may still act in `slot`
+    pub fn is_active(&self, slot: u64) -> bool {
+        self.expiry_slot == 0 || slot < self.expiry_slot
+    }
+}
+
+/// The operators of a counter, kept in a companion PDA
+///
+/// Operators are delegates with an expiry, several at a time, so keys can be
+/// rotated without a gap. The account is allocated for
+/// [`CounterOperators::MAX_OPERATORS`] up front and stays open once empty.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CounterOperators {
+    pub discriminator: [u8; 8], // Always CounterOperators::DISCRIMINATOR
+    pub counter: Pubkey,        // The counter the operators may decrement
+    pub bump: u8,               // Bump seed of the account's address
+    pub operators: Vec<Operator>,
+}
+
+impl CounterOperators {
+    /// Number of bytes an empty operator list occupies in account data
+    pub const BASE_LEN: usize = 8 + 32 + 1 + 4;
+
+    /// Number of bytes each operator adds
+    pub const OPERATOR_LEN: usize = 32 + 8 + 8;
+
+    /// Most operators the account holds
+    pub const MAX_OPERATORS: usize = 8;
+
+    /// Number of bytes the account is allocated with
+    pub const LEN: usize = Self::BASE_LEN + Self::MAX_OPERATORS * Self::OPERATOR_LEN;
+
+    /// Tag written at the start of every operator list
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"operator";
+    /// Anchor's `sha256("account:CounterOperators")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [170, 228, 230, 50, 79, 182, 80, 42];
+}
+
+/// Seed prefix of hooks PDAs
+pub const HOOKS_SEED: &[u8] = b"hooks";
+
+/// Find the address of the hooks account of `counter`
+///
+/// Each counter has at most one, at `["hooks", counter]`. The address signs
+/// the hooks' invocations, see [`crate::cpi::OnCounterChange`].
+pub fn find_hooks_address(program_id: &Pubkey, counter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[HOOKS_SEED, counter.as_ref()], program_id)
+}
+
+/// The programs a counter invokes around each change, kept in a companion PDA
+///
+/// The counter's `Hooks` flag is set while the account exists, so a change
+/// cannot skip the hooks by leaving their accounts out.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CounterHooks {
+    pub discriminator: [u8; 8], // Always CounterHooks::DISCRIMINATOR
+    pub counter: Pubkey,        // The counter whose changes are hooked
+    pub bump: u8,               // Bump seed of the account's address, for signing
+    pub pre: Option<Pubkey>,    // Program invoked before each change
+    pub post: Option<Pubkey>,   // Program invoked after each change
+}
+
+impl CounterHooks {
+    /// Number of bytes the account is allocated with, enough for both hooks
+    pub const LEN: usize = 8 + 32 + 1 + (1 + 32) + (1 + 32);
+
+    /// Tag written at the start of every hooks account
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"hooks\0\0\0";
+    /// Anchor's `sha256("account:CounterHooks")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [122, 120, 26, 92, 210, 187, 75, 87];
+
+    /// Number of hook programs set in the hooks account holding `data`, or
+    /// `None` if `data` is not one
+    pub fn program_count(data: &[u8]) -> Option<usize> {
+        if data.len() != Self::LEN || data[..8] != Self::DISCRIMINATOR {
+            return None;
+        }
+        // Each hook is an `Option<Pubkey>`: a tag byte, then the key if set
+        let pre = data[41] != 0;
+        let post = data[if pre { 74 } else { 42 }] != 0;
+        Some(usize::from(pre) + usize::from(post))
+    }
+
+    /// The hook programs, as they follow the hooks account in a change's
+    /// accounts: the pre hook, then the post hook
+    pub fn programs(&self) -> impl Iterator<Item = Pubkey> {
+        self.pre.into_iter().chain(self.post)
+    }
+}
+
+/// Seed prefix of token gate PDAs
+pub const TOKEN_GATE_SEED: &[u8] = b"gate";
+
+/// Find the address of the token gate of `counter`
+///
+/// Each counter has at most one, at `["gate", counter]`.
+pub fn find_token_gate_address(program_id: &Pubkey, counter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TOKEN_GATE_SEED, counter.as_ref()], program_id)
+}
+
+/// The tokens an increment's actor must hold, kept in a companion PDA
+///
+/// The counter's `TokenGate` flag is set while the account exists, so an
+/// increment cannot skip the check by leaving the gate out.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CounterGate {
+    pub discriminator: [u8; 8], // Always CounterGate::DISCRIMINATOR
+    pub counter: Pubkey,        // The counter whose increments are gated
+    pub mint: Pubkey,           // Mint of the tokens the actor must hold
+    pub min_balance: u64,       // Smallest balance, in base units, that may increment
+}
+
+impl CounterGate {
+    /// Number of bytes a serialized CounterGate occupies in account data
+    pub const LEN: usize = 8 + 32 + 32 + 8;
+
+    /// Tag written at the start of every token gate
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"gate\0\0\0\0";
+    /// Anchor's `sha256("account:CounterGate")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [54, 62, 54, 121, 14, 59, 104, 76];
+
+    /// Whether `data` is the data of a token gate
+    pub fn is_gate(data: &[u8]) -> bool {
+        data.len() == Self::LEN && data[..8] == Self::DISCRIMINATOR
+    }
+}
+
+/// Seed prefix of burn account PDAs
+pub const BURN_SEED: &[u8] = b"burn";
+
+/// Find the address of the burn account of `counter`
+///
+/// Each counter has at most one, at `["burn", counter]`.
+pub fn find_burn_address(program_id: &Pubkey, counter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[BURN_SEED, counter.as_ref()], program_id)
+}
+
+/// The mint a burn-backed counter's increments burn, kept in a companion PDA
+///
+/// The counter's `Burn` flag is set for good once the account exists, so an
+/// increment cannot skip the burn by leaving the account out.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CounterBurn {
+    pub discriminator: [u8; 8], // Always CounterBurn::DISCRIMINATOR
+    pub counter: Pubkey,        // The counter whose increments burn tokens
+    pub mint: Pubkey,           // Mint of the tokens burned
+    pub burned: u64,            // Base units burned so far, which the count equals
+}
+
+impl CounterBurn {
+    /// Number of bytes a serialized CounterBurn occupies in account data
+    pub const LEN: usize = 8 + 32 + 32 + 8;
+
+    /// Tag written at the start of every burn account
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"burn\0\0\0\0";
+    /// Anchor's `sha256("account:CounterBurn")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [211, 108, 225, 137, 31, 166, 207, 44];
+
+    /// Whether `data` is the data of a burn account
+    pub fn is_burn(data: &[u8]) -> bool {
+        data.len() == Self::LEN && data[..8] == Self::DISCRIMINATOR
+    }
+}
+
+/// Seed prefix of rewards PDAs
+pub const REWARDS_SEED: &[u8] = b"rewards";
+
+/// Find the address of the rewards account of `counter`
+///
+/// Each counter has at most one, at `["rewards", counter]`. The address is
+/// the mint authority of the reward mint and signs for the minting.
+pub fn find_rewards_address(program_id: &Pubkey, counter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REWARDS_SEED, counter.as_ref()], program_id)
+}
+
+/// Most milestones a counter rewards
+pub const MAX_MILESTONES: usize = 8;
+
+/// The milestones a counter rewards and the tokens it mints for them, kept
+/// in a companion PDA
+///
+/// The account is allocated for [`MAX_MILESTONES`] up front, so rewards can
+/// be replaced in place.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CounterRewards {
+    pub discriminator: [u8; 8], // Always CounterRewards::DISCRIMINATOR
+    pub counter: Pubkey,        // The counter whose changes are rewarded
+    pub bump: u8,               // Bump seed of the account's address, which signs as mint authority
+    pub mint: Pubkey,           // Mint of the reward tokens
+    pub amount: u64,            // Base units minted for each milestone
+    pub reached: u8,            // Number of `milestones`, from the first, already reached
+    pub milestones: Vec<i128>,  // Counts rewarded, strictly ascending
+}
+
+impl CounterRewards {
+    /// Number of bytes the account is allocated with
+    pub const LEN: usize = 8 + 32 + 1 + 32 + 8 + 1 + 4 + 16 * MAX_MILESTONES;
+
+    /// Tag written at the start of every rewards account
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"rewards\0";
+    /// Anchor's `sha256("account:CounterRewards")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [163, 236, 62, 198, 50, 87, 78, 81];
+
+    /// Whether `data` is the data of a rewards account
+    pub fn is_rewards(data: &[u8]) -> bool {
+        data.len() == Self::LEN && data[..8] == Self::DISCRIMINATOR
+    }
+
+    /// The milestones not reached yet that `value` is at or above
+    pub fn due(&self, value: i128) -> usize {
+        self.milestones
+            .iter()
+            .skip(self.reached.into())
+            .take_while(|milestone| **milestone <= value)
+            .count()
+    }
+
+    /// The milestones not reached yet that a change from `old_value` to
+    /// `value` crossed, going from below them to at or above them
+    pub fn crossed(&self, old_value: i128, value: i128) -> usize {
+        self.milestones
+            .iter()
+            .skip(self.reached.into())
+            .take_while(|milestone| **milestone <= value)
+            .filter(|milestone| **milestone > old_value)
+            .count()
+    }
+}
+
+/// Seed prefix of escrow PDAs
+pub const ESCROW_SEED: &[u8] = b"escrow";
+
+/// Find the address of the escrow of `counter`
+///
+/// Each counter has at most one, at `["escrow", counter]`.
+pub fn find_escrow_address(program_id: &Pubkey, counter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ESCROW_SEED, counter.as_ref()], program_id)
+}
+
+/// Seed prefix of escrow deposit record PDAs
+pub const ESCROW_DEPOSIT_SEED: &[u8] = b"deposit";
+
+/// Find the address of the record of `depositor`'s deposits into `escrow`
+///
+/// Each depositor has one per escrow, at `["deposit", escrow, depositor]`.
+pub fn find_escrow_deposit_address(
+    program_id: &Pubkey,
+    escrow: &Pubkey,
+    depositor: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ESCROW_DEPOSIT_SEED, escrow.as_ref(), depositor.as_ref()],
+        program_id,
+    )
+}
+
+/// Lamports held for a beneficiary until a counter reaches a target, in a
+/// companion PDA whose balance above its rent is the deposits
+///
+/// The account stays open once settled, so no new escrow can reuse the
+/// address while old deposit records still point at it.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CounterEscrow {
+    pub discriminator: [u8; 8], // Always CounterEscrow::DISCRIMINATOR
+    pub counter: Pubkey,        // The counter whose count releases the escrow
+    pub beneficiary: Pubkey,    // Who receives the deposits once the target is reached
+    pub target: i128,           // Count the counter's high water mark must reach
+    pub deadline_slot: u64,     // Slot after which an unreached target refunds the deposits
+    pub deposited: u64,         // Lamports deposited and neither released nor refunded
+    pub settled: bool,          // Whether the deposits went to the beneficiary
+}
+
+impl CounterEscrow {
+    /// Number of bytes a serialized CounterEscrow occupies in account data
+    pub const LEN: usize = 8 + 32 + 32 + 16 + 8 + 8 + 1;
+
+    /// Tag written at the start of every escrow
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"escrow\0\0";
+    /// Anchor's `sha256("account:CounterEscrow")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [36, 162, 144, 220, 181, 235, 15, 3];
+}
+
+/// The lamports one depositor put into an escrow, for refunding them
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct EscrowDeposit {
+    pub discriminator: [u8; 8], // Always EscrowDeposit::DISCRIMINATOR
+    pub escrow: Pubkey,         // The escrow deposited into
+    pub depositor: Pubkey,      // Who deposited, and receives any refund
+    pub lamports: u64,          // Lamports deposited so far
+}
+
+impl EscrowDeposit {
+    /// Number of bytes a serialized EscrowDeposit occupies in account data
+    pub const LEN: usize = 8 + 32 + 32 + 8;
+
+    /// Tag written at the start of every deposit record
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"deposit\0";
+    /// Anchor's `sha256("account:EscrowDeposit")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [56, 152, 208, 160, 159, 83, 6, 17];
+}
+
+/// Seed prefix of permit nonce PDAs
+pub const PERMITS_SEED: &[u8] = b"permits";
+
+/// Find the address of the permit nonce account of `counter`
+///
+/// Each counter has at most one, at `["permits", counter]`.
+pub fn find_permits_address(program_id: &Pubkey, counter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PERMITS_SEED, counter.as_ref()], program_id)
+}
+
+/// Number of bytes of the message a permit signs, see [`permit_message`]
+pub const PERMIT_MESSAGE_LEN: usize = 32 + 8 + 8 + 8;
+
+/// The message the authority of `counter` signs to allow one increment by
+/// `amount`, until `expiry_slot` and only while `nonce` is unused
+///
+/// The counter's key, then `amount`, `nonce` and `expiry_slot` as
+/// little-endian `u64`s. The signature goes in an Ed25519 program
+/// instruction of the increment's transaction.
+///
+/// A permit is only accepted with a nonce above that of every permit used
+/// before it, so using one voids all unused permits with lower nonces. Give
+/// out nonces in the order the permits will be used.
+pub fn permit_message(
+    counter: &Pubkey,
+    amount: u64,
+    nonce: u64,
+    expiry_slot: u64,
+) -> [u8; PERMIT_MESSAGE_LEN] {
+    let mut message = [0; PERMIT_MESSAGE_LEN];
+    message[..32].copy_from_slice(counter.as_ref());
+    message[32..40].copy_from_slice(&amount.to_le_bytes());
+    message[40..48].copy_from_slice(&nonce.to_le_bytes());
+    message[48..].copy_from_slice(&expiry_slot.to_le_bytes());
+    message
+}
+
+/// The permit nonces a counter has used up, kept in a companion PDA
+///
+/// Nonces only go up: a permit is accepted if its nonce is at least
+/// `next_nonce`, which then moves past it, so every permit works once and
+/// issuing them in order keeps them all usable. Permits with lower nonces
+/// that were not used by then never can be.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CounterPermits {
+    pub discriminator: [u8; 8], // Always CounterPermits::DISCRIMINATOR
+    pub counter: Pubkey,        // The counter whose increments need permits
+    pub next_nonce: u64,        // Smallest nonce a permit may still carry
+}
+
+impl CounterPermits {
+    /// Number of bytes a serialized CounterPermits occupies in account data
+    pub const LEN: usize = 8 + 32 + 8;
+
+    /// Tag written at the start of every permit nonce account
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"permits\0";
+    /// Anchor's `sha256("account:CounterPermits")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [108, 29, 27, 221, 5, 57, 240, 174];
+
+    /// Whether `data` is the data of a permit nonce account
+    pub fn is_permits(data: &[u8]) -> bool {
+        data.len() == Self::LEN && data[..8] == Self::DISCRIMINATOR
+    }
+}
+
+/// Seed prefix of cooldown PDAs
+pub const COOLDOWN_SEED: &[u8] = b"cooldown";
+
+/// Find the address of the cooldown of `counter`
+///
+/// Each counter has at most one, at `["cooldown", counter]`.
+pub fn find_cooldown_address(program_id: &Pubkey, counter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[COOLDOWN_SEED, counter.as_ref()], program_id)
+}
+
+/// Seed prefix of cooldown stamp PDAs
+pub const COOLDOWN_STAMP_SEED: &[u8] = b"stamp";
+
+/// Find the address of the stamp recording when `actor` last incremented
+/// `counter`
+///
+/// Each actor has at most one per counter, at `["stamp", counter, actor]`.
+pub fn find_cooldown_stamp_address(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    actor: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[COOLDOWN_STAMP_SEED, counter.as_ref(), actor.as_ref()],
+        program_id,
+    )
+}
+
+/// The slots each actor must wait between increments and the most they may
+/// add per day, kept in a companion PDA
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CounterCooldown {
+    pub discriminator: [u8; 8], // Always CounterCooldown::DISCRIMINATOR
+    pub counter: Pubkey,        // The counter whose increments are throttled
+    pub slots: u64,             // Slots an actor's increment must follow their last by, or 0
+    pub daily_cap: u64,         // Most an actor may add per day, or 0 for no cap
+}
+
+impl CounterCooldown {
+    /// Number of bytes a serialized CounterCooldown occupies in account data
+    pub const LEN: usize = 8 + 32 + 8 + 8;
+
+    /// Tag written at the start of every cooldown
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"cooldown";
+    /// Anchor's `sha256("account:CounterCooldown")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [130, 7, 51, 167, 210, 27, 119, 231];
+
+    /// Whether `data` is the data of a cooldown
+    pub fn is_cooldown(data: &[u8]) -> bool {
+        data.len() == Self::LEN && data[..8] == Self::DISCRIMINATOR
+    }
+}
+
+/// When one actor last incremented a counter with a cooldown, and how much
+/// they added that day
+///
+/// Created, at the actor's expense, by their first increment, which is what
+/// makes every extra identity cost rent. Stamps are never closed, so they
+/// still count if the cooldown is cleared and set again.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CooldownStamp {
+    pub discriminator: [u8; 8], // Always CooldownStamp::DISCRIMINATOR
+    pub counter: Pubkey,        // The counter incremented
+    pub actor: Pubkey,          // Who incremented it
+    pub last_slot: u64,         // Slot of the actor's last increment
+    pub day: i64,               // Day of the actor's last increment, see `CooldownStamp::day_of`
+    pub contributed: u64,       // What the actor added to the count that day
+}
+
+impl CooldownStamp {
+    /// Number of bytes a serialized CooldownStamp occupies in account data
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 8 + 8;
+
+    /// Tag written at the start of every cooldown stamp
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"stamp\0\0\0";
+    /// Anchor's `sha256("account:CooldownStamp")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [181, 250, 157, 21, 141, 205, 31, 159];
+
+    /// The UTC day, counted from the unix epoch, that `unix_timestamp` falls on
+    pub fn day_of(unix_timestamp: i64) -> i64 {
+        unix_timestamp.div_euclid(24 * 60 * 60)
+    }
+}
+
+/// The average value of a counter between two readings of its
+/// [`Counter::cumulative_value_at`], each paired with its unix timestamp
+///
+/// Averaging over a window keeps a few blocks of manipulated values from
+/// moving the result much, which the spot value offers no protection
+/// against. `None` unless `end` is later than `start`.
+pub fn time_weighted_average(start: (i128, i64), end: (i128, i64)) -> Option<i128> {
+    let elapsed = end.1.checked_sub(start.1).filter(|&elapsed| elapsed > 0)?;
+    Some(end.0.wrapping_sub(start.0) / i128::from(elapsed))
+}
+
+/// Seed prefix of decay PDAs
+pub const DECAY_SEED: &[u8] = b"decay";
+
+/// Find the address of the decay of `counter`
+///
+/// Each counter has at most one, at `["decay", counter]`.
+pub fn find_decay_address(program_id: &Pubkey, counter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[DECAY_SEED, counter.as_ref()], program_id)
+}
+
+/// What the rate of a counter's decay is measured per
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(
+    BorshSerialize, BorshDeserialize, BorshSchema, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+#[repr(u8)]
+pub enum DecayUnit {
+    /// Per slot since the counter last changed
+    #[default]
+    Slot,
+    /// Per second of the cluster's unix timestamp since the counter last changed
+    Second,
+}
+
+/// How fast a counter's value fades towards zero, kept in a companion PDA
+///
+/// Decay is applied lazily, by the next change, for the time since the later
+/// of the counter's last change and the decay being set.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CounterDecay {
+    pub discriminator: [u8; 8], // Always CounterDecay::DISCRIMINATOR
+    pub counter: Pubkey,        // The counter whose value decays
+    pub rate: u64,              // How far the value moves towards zero per `unit`
+    pub unit: DecayUnit,        // Whether `rate` is per slot or per second
+    pub since_slot: u64,        // Slot the decay was set in
+    pub since_ts: i64,          // Unix timestamp the decay was set at
+}
+
+impl CounterDecay {
+    /// Number of bytes a serialized CounterDecay occupies in account data
+    pub const LEN: usize = 8 + 32 + 8 + 1 + 8 + 8;
+
+    /// Tag written at the start of every decay
+    #[cfg(not(feature = "anchor"))]
+    pub const DISCRIMINATOR: [u8; 8] = *b"decay\0\0\0";
+    /// Anchor's `sha256("account:CounterDecay")[..8]`, used with the `anchor` feature
+    #[cfg(feature = "anchor")]
+    pub const DISCRIMINATOR: [u8; 8] = [194, 126, 225, 78, 43, 16, 174, 248];
+
+    /// Whether `data` is the data of a decay
+    pub fn is_decay(data: &[u8]) -> bool {
+        data.len() == Self::LEN && data[..8] == Self::DISCRIMINATOR
+    }
+
+    /// How far `counter` has decayed by `slot` and `unix_timestamp` since it
+    /// last changed, saturating
+    pub fn owed(&self, counter: &Counter, slot: u64, unix_timestamp: i64) -> u64 {
+        let elapsed = match self.unit {
+            DecayUnit::Slot => slot.saturating_sub(counter.last_updated_slot.max(self.since_slot)),
+            DecayUnit::Second => {
+                let since = counter.last_updated_ts.max(self.since_ts);
+                u64::try_from(unix_timestamp.saturating_sub(since)).unwrap_or(0)
+            }
+        };
+        self.rate.saturating_mul(elapsed)
+    }
+}
+
+/// Address of the SPL Associated Token Account program
+pub(crate) const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+/// The associated token account of `wallet` for `mint`, under `token_program`
+pub(crate) fn associated_token_address(
+    wallet: &Pubkey,
+    token_program: &Pubkey,
+    mint: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[wallet.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
+
+/// Offset of the mint in an SPL Token account, which Token-2022 accounts share
+pub(crate) const TOKEN_ACCOUNT_MINT_OFFSET: usize = 0;
+
+/// Offset of the owner in an SPL Token account
+pub(crate) const TOKEN_ACCOUNT_OWNER_OFFSET: usize = 32;
+
+/// Offset of the `u64` balance in an SPL Token account
+pub(crate) const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
+
+/// The kinds of account this program creates, told apart by the
+/// discriminator each of them starts with
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AccountType {
+    Counter,
+    Config,
+    VoteCounter,
+    CounterMap,
+    Multisig,
+    CounterHistory,
+    CounterShard,
+    CounterCallback,
+    CounterOperators,
+    CounterHooks,
+    FeeVault,
+    CounterGate,
+    CounterBurn,
+    CounterRewards,
+    CounterEscrow,
+    EscrowDeposit,
+    CounterPermits,
+    CounterCooldown,
+    CooldownStamp,
+    CounterDecay,
+}
+
+impl AccountType {
+    /// Every account kind, in no particular order
+    pub const ALL: [AccountType; 20] = [
+        AccountType::Counter,
+        AccountType::Config,
+        AccountType::VoteCounter,
+        AccountType::CounterMap,
+        AccountType::Multisig,
+        AccountType::CounterHistory,
+        AccountType::CounterShard,
+        AccountType::CounterCallback,
+        AccountType::CounterOperators,
+        AccountType::CounterHooks,
+        AccountType::FeeVault,
+        AccountType::CounterGate,
+        AccountType::CounterBurn,
+        AccountType::CounterRewards,
+        AccountType::CounterEscrow,
+        AccountType::EscrowDeposit,
+        AccountType::CounterPermits,
+        AccountType::CounterCooldown,
+        AccountType::CooldownStamp,
+        AccountType::CounterDecay,
+    ];
+
+    /// Tag written at the start of accounts of this kind
+    pub fn discriminator(self) -> [u8; 8] {
+        match self {
+            AccountType::Counter => Counter::DISCRIMINATOR,
+            AccountType::Config => Config::DISCRIMINATOR,
+            AccountType::VoteCounter => VoteCounter::DISCRIMINATOR,
+            AccountType::CounterMap => CounterMap::DISCRIMINATOR,
+            AccountType::Multisig => Multisig::DISCRIMINATOR,
+            AccountType::CounterHistory => CounterHistory::DISCRIMINATOR,
+            AccountType::CounterShard => CounterShard::DISCRIMINATOR,
+            AccountType::CounterCallback => CounterCallback::DISCRIMINATOR,
+            AccountType::CounterOperators => CounterOperators::DISCRIMINATOR,
+            AccountType::CounterHooks => CounterHooks::DISCRIMINATOR,
+            AccountType::FeeVault => FeeVault::DISCRIMINATOR,
+            AccountType::CounterGate => CounterGate::DISCRIMINATOR,
+            AccountType::CounterBurn => CounterBurn::DISCRIMINATOR,
+            AccountType::CounterRewards => CounterRewards::DISCRIMINATOR,
+            AccountType::CounterEscrow => CounterEscrow::DISCRIMINATOR,
+            AccountType::EscrowDeposit => EscrowDeposit::DISCRIMINATOR,
+            AccountType::CounterPermits => CounterPermits::DISCRIMINATOR,
+            AccountType::CounterCooldown => CounterCooldown::DISCRIMINATOR,
+            AccountType::CooldownStamp => CooldownStamp::DISCRIMINATOR,
+            AccountType::CounterDecay => CounterDecay::DISCRIMINATOR,
+        }
+    }
+
+    /// The kind of account whose data starts with `data`, if it is one of ours
+    pub fn of(data: &[u8]) -> Option<Self> {
+        let tag = data.get(..8)?;
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.discriminator() == tag)
+    }
+
+    /// How log messages refer to accounts of this kind
+    pub fn name(self) -> &'static str {
+        match self {
+            AccountType::Counter => "counter",
+            AccountType::Config => "config",
+            AccountType::VoteCounter => "vote account",
+            AccountType::CounterMap => "counter map",
+            AccountType::Multisig => "multisig",
+            AccountType::CounterHistory => "history account",
+            AccountType::CounterShard => "shard",
+            AccountType::CounterCallback => "callback account",
+            AccountType::CounterOperators => "operator list",
+            AccountType::CounterHooks => "hooks account",
+            AccountType::FeeVault => "fee vault",
+            AccountType::CounterGate => "token gate",
+            AccountType::CounterBurn => "burn account",
+            AccountType::CounterRewards => "rewards account",
+            AccountType::CounterEscrow => "escrow",
+            AccountType::EscrowDeposit => "deposit record",
+            AccountType::CounterPermits => "permit nonce account",
+            AccountType::CounterCooldown => "cooldown",
+            AccountType::CooldownStamp => "cooldown stamp",
+            AccountType::CounterDecay => "decay",
+        }
+    }
+}
+
+/// Counter layout version 13, from before the modes were packed into
+/// `flags`
+///
+/// Identical to the current layout with one byte per mode in place of
+/// `flags`, and no reserved block.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct CounterV13 {
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub overflow_mode: u8,
+    pub kind: u8,
+    pub paused: u8,
+    pub step: u32,
+    pub authority: Pubkey,
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub pending_authority: Pubkey,
+    pub delegate: Pubkey,
+    pub allowance: u64,
+    pub modulus: u64,
+    pub total_increments: u64,
+    pub total_decrements: u64,
+    pub last_updated_slot: u64,
+    pub high_water: u64,
+    pub low_water: u64,
+    pub last_updated_ts: i64,
+    pub thresholds: [u64; Counter::MAX_THRESHOLDS],
+    pub epoch: u64,
+    pub epoch_change: u64,
+    pub cumulative: [u8; 16],
+    pub locked_until: u64,
+    pub expires_at: i64,
+    pub ema: [u8; 16],
+    pub ema_alpha: u64,
+    pub next_crank_epoch: u64,
+    pub history: u8,
+    pub clamp_decrements: u8,
+    pub exactly_once: u8,
+    pub freeze_authority: Pubkey,
+    pub threshold_count: u8,
+    pub callback: u8,
+    pub hooks: u8,
+    pub token_gate: u8,
+    pub nft_authority: u8,
+    pub burn: u8,
+    pub rewards: u8,
+    pub permits: u8,
+    pub cooldown: u8,
+    pub epoch_reset: u8,
+    pub decay: u8,
+    pub fee_paid: u8,
+    pub escrow: u8,
+}
+
+impl CounterV13 {
+    /// Number of bytes a version 13 counter occupies in account data
+    pub const LEN: usize = core::mem::size_of::<Self>();
+}
+
+impl From<CounterV13> for Counter {
+    fn from(v13: CounterV13) -> Self {
+        let mut counter = Self {
+            discriminator: v13.discriminator,
+            version: Counter::VERSION,
+            overflow_mode: v13.overflow_mode,
+            kind: v13.kind,
+            paused: v13.paused,
+            step: v13.step,
+            authority: v13.authority,
+            count: v13.count,
+            min: v13.min,
+            max: v13.max,
+            pending_authority: v13.pending_authority,
+            delegate: v13.delegate,
+            allowance: v13.allowance,
+            modulus: v13.modulus,
+            total_increments: v13.total_increments,
+            total_decrements: v13.total_decrements,
+            last_updated_slot: v13.last_updated_slot,
+            high_water: v13.high_water,
+            low_water: v13.low_water,
+            last_updated_ts: v13.last_updated_ts,
+            thresholds: v13.thresholds,
+            epoch: v13.epoch,
+            epoch_change: v13.epoch_change,
+            cumulative: v13.cumulative,
+            locked_until: v13.locked_until,
+            expires_at: v13.expires_at,
+            ema: v13.ema,
+            ema_alpha: v13.ema_alpha,
+            next_crank_epoch: v13.next_crank_epoch,
+            flags: 0,
+            freeze_authority: v13.freeze_authority,
+            threshold_count: v13.threshold_count,
+            reserved: [0; Counter::RESERVED_LEN],
+        };
+        let modes = [
+            (CounterFlag::History, v13.history),
+            (CounterFlag::ClampDecrements, v13.clamp_decrements),
+            (CounterFlag::ExactlyOnce, v13.exactly_once),
+            (CounterFlag::Callback, v13.callback),
+            (CounterFlag::Hooks, v13.hooks),
+            (CounterFlag::TokenGate, v13.token_gate),
+            (CounterFlag::NftAuthority, v13.nft_authority),
+            (CounterFlag::Burn, v13.burn),
+            (CounterFlag::Rewards, v13.rewards),
+            (CounterFlag::Permits, v13.permits),
+            (CounterFlag::Cooldown, v13.cooldown),
+            (CounterFlag::EpochReset, v13.epoch_reset),
+            (CounterFlag::Decay, v13.decay),
+            (CounterFlag::FeePaid, v13.fee_paid),
+            (CounterFlag::Escrow, v13.escrow),
+        ];
+        for (flag, byte) in modes {
+            counter.set_flag(flag, byte != 0);
+        }
+        counter
+    }
+}
+
+/// Counter layout version 12, from before crank rewards were limited to
+/// fee-paying counters
+///
+/// Identical to version 13 without `next_crank_epoch`, `fee_paid`
+/// and `escrow`.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct CounterV12 {
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub overflow_mode: u8,
+    pub kind: u8,
+    pub paused: u8,
+    pub step: u32,
+    pub authority: Pubkey,
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub pending_authority: Pubkey,
+    pub delegate: Pubkey,
+    pub allowance: u64,
+    pub modulus: u64,
+    pub total_increments: u64,
+    pub total_decrements: u64,
+    pub last_updated_slot: u64,
+    pub high_water: u64,
+    pub low_water: u64,
+    pub last_updated_ts: i64,
+    pub thresholds: [u64; Counter::MAX_THRESHOLDS],
+    pub epoch: u64,
+    pub epoch_change: u64,
+    pub cumulative: [u8; 16],
+    pub locked_until: u64,
+    pub expires_at: i64,
+    pub ema: [u8; 16],
+    pub ema_alpha: u64,
+    pub history: u8,
+    pub clamp_decrements: u8,
+    pub exactly_once: u8,
+    pub freeze_authority: Pubkey,
+    pub threshold_count: u8,
+    pub callback: u8,
+    pub hooks: u8,
+    pub token_gate: u8,
+    pub nft_authority: u8,
+    pub burn: u8,
+    pub rewards: u8,
+    pub permits: u8,
+    pub cooldown: u8,
+    pub epoch_reset: u8,
+    pub decay: u8,
+    pub reserved: [u8; 2],
+}
+
+impl CounterV12 {
+    /// Number of bytes a version 12 counter occupies in account data
+    pub const LEN: usize = core::mem::size_of::<Self>();
+}
+
+impl From<CounterV12> for CounterV13 {
+    fn from(v12: CounterV12) -> Self {
+        Self {
+            discriminator: v12.discriminator,
+            version: 13,
+            overflow_mode: v12.overflow_mode,
+            kind: v12.kind,
+            paused: v12.paused,
+            step: v12.step,
+            authority: v12.authority,
+            count: v12.count,
+            min: v12.min,
+            max: v12.max,
+            pending_authority: v12.pending_authority,
+            delegate: v12.delegate,
+            allowance: v12.allowance,
+            modulus: v12.modulus,
+            total_increments: v12.total_increments,
+            total_decrements: v12.total_decrements,
+            last_updated_slot: v12.last_updated_slot,
+            high_water: v12.high_water,
+            low_water: v12.low_water,
+            last_updated_ts: v12.last_updated_ts,
+            thresholds: v12.thresholds,
+            epoch: v12.epoch,
+            epoch_change: v12.epoch_change,
+            cumulative: v12.cumulative,
+            locked_until: v12.locked_until,
+            expires_at: v12.expires_at,
+            ema: v12.ema,
+            ema_alpha: v12.ema_alpha,
+            next_crank_epoch: 0,
+            history: v12.history,
+            clamp_decrements: v12.clamp_decrements,
+            exactly_once: v12.exactly_once,
+            freeze_authority: v12.freeze_authority,
+            threshold_count: v12.threshold_count,
+            callback: v12.callback,
+            hooks: v12.hooks,
+            token_gate: v12.token_gate,
+            nft_authority: v12.nft_authority,
+            burn: v12.burn,
+            rewards: v12.rewards,
+            permits: v12.permits,
+            cooldown: v12.cooldown,
+            epoch_reset: v12.epoch_reset,
+            decay: v12.decay,
+            fee_paid: 0,
+            escrow: 0,
+        }
+    }
+}
+
+/// Counter layout version 11, from before the moving average
+///
+/// Identical to version 12 without `ema` and `ema_alpha`.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct CounterV11 {
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub overflow_mode: u8,
+    pub kind: u8,
+    pub paused: u8,
+    pub step: u32,
+    pub authority: Pubkey,
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub pending_authority: Pubkey,
+    pub delegate: Pubkey,
+    pub allowance: u64,
+    pub modulus: u64,
+    pub total_increments: u64,
+    pub total_decrements: u64,
+    pub last_updated_slot: u64,
+    pub high_water: u64,
+    pub low_water: u64,
+    pub last_updated_ts: i64,
+    pub thresholds: [u64; Counter::MAX_THRESHOLDS],
+    pub epoch: u64,
+    pub epoch_change: u64,
+    pub cumulative: [u8; 16],
+    pub locked_until: u64,
+    pub expires_at: i64,
+    pub history: u8,
+    pub clamp_decrements: u8,
+    pub exactly_once: u8,
+    pub freeze_authority: Pubkey,
+    pub threshold_count: u8,
+    pub callback: u8,
+    pub hooks: u8,
+    pub token_gate: u8,
+    pub nft_authority: u8,
+    pub burn: u8,
+    pub rewards: u8,
+    pub permits: u8,
+    pub cooldown: u8,
+    pub epoch_reset: u8,
+    pub decay: u8,
+    pub reserved: [u8; 2],
+}
+
+impl CounterV11 {
+    /// Number of bytes a version 11 counter occupies in account data
+    pub const LEN: usize = core::mem::size_of::<Self>();
+}
+
+impl From<CounterV11> for CounterV13 {
+    fn from(v11: CounterV11) -> Self {
+        Self {
+            discriminator: v11.discriminator,
+            version: 13,
+            overflow_mode: v11.overflow_mode,
+            kind: v11.kind,
+            paused: v11.paused,
+            step: v11.step,
+            authority: v11.authority,
+            count: v11.count,
+            min: v11.min,
+            max: v11.max,
+            pending_authority: v11.pending_authority,
+            delegate: v11.delegate,
+            allowance: v11.allowance,
+            modulus: v11.modulus,
+            total_increments: v11.total_increments,
+            total_decrements: v11.total_decrements,
+            last_updated_slot: v11.last_updated_slot,
+            high_water: v11.high_water,
+            low_water: v11.low_water,
+            last_updated_ts: v11.last_updated_ts,
+            thresholds: v11.thresholds,
+            epoch: v11.epoch,
+            epoch_change: v11.epoch_change,
+            cumulative: v11.cumulative,
+            locked_until: v11.locked_until,
+            expires_at: v11.expires_at,
+            ema: [0; 16],
+            ema_alpha: 0,
+            next_crank_epoch: 0,
+            history: v11.history,
+            clamp_decrements: v11.clamp_decrements,
+            exactly_once: v11.exactly_once,
+            freeze_authority: v11.freeze_authority,
+            threshold_count: v11.threshold_count,
+            callback: v11.callback,
+            hooks: v11.hooks,
+            token_gate: v11.token_gate,
+            nft_authority: v11.nft_authority,
+            burn: v11.burn,
+            rewards: v11.rewards,
+            permits: v11.permits,
+            cooldown: v11.cooldown,
+            epoch_reset: v11.epoch_reset,
+            decay: v11.decay,
+            fee_paid: 0,
+            escrow: 0,
+        }
+    }
+}
+
+/// Counter layout version 10, from before expiry
+///
+/// Identical to version 11 without `expires_at`.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct CounterV10 {
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub overflow_mode: u8,
+    pub kind: u8,
+    pub paused: u8,
+    pub step: u32,
+    pub authority: Pubkey,
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub pending_authority: Pubkey,
+    pub delegate: Pubkey,
+    pub allowance: u64,
+    pub modulus: u64,
+    pub total_increments: u64,
+    pub total_decrements: u64,
+    pub last_updated_slot: u64,
+    pub high_water: u64,
+    pub low_water: u64,
+    pub last_updated_ts: i64,
+    pub thresholds: [u64; Counter::MAX_THRESHOLDS],
+    pub epoch: u64,
+    pub epoch_change: u64,
+    pub cumulative: [u8; 16],
+    pub locked_until: u64,
+    pub history: u8,
+    pub clamp_decrements: u8,
+    pub exactly_once: u8,
+    pub freeze_authority: Pubkey,
+    pub threshold_count: u8,
+    pub callback: u8,
+    pub hooks: u8,
+    pub token_gate: u8,
+    pub nft_authority: u8,
+    pub burn: u8,
+    pub rewards: u8,
+    pub permits: u8,
+    pub cooldown: u8,
+    pub epoch_reset: u8,
+    pub decay: u8,
+    pub reserved: [u8; 2],
+}
+
+impl CounterV10 {
+    /// Number of bytes a version 10 counter occupies in account data
+    pub const LEN: usize = core::mem::size_of::<Self>();
+}
+
+impl From<CounterV10> for CounterV13 {
+    fn from(v10: CounterV10) -> Self {
+        Self {
+            discriminator: v10.discriminator,
+            version: 13,
+            overflow_mode: v10.overflow_mode,
+            kind: v10.kind,
+            paused: v10.paused,
+            step: v10.step,
+            authority: v10.authority,
+            count: v10.count,
+            min: v10.min,
+            max: v10.max,
+            pending_authority: v10.pending_authority,
+            delegate: v10.delegate,
+            allowance: v10.allowance,
+            modulus: v10.modulus,
+            total_increments: v10.total_increments,
+            total_decrements: v10.total_decrements,
+            last_updated_slot: v10.last_updated_slot,
+            high_water: v10.high_water,
+            low_water: v10.low_water,
+            last_updated_ts: v10.last_updated_ts,
+            thresholds: v10.thresholds,
+            epoch: v10.epoch,
+            epoch_change: v10.epoch_change,
+            cumulative: v10.cumulative,
+            locked_until: v10.locked_until,
+            expires_at: 0,
+            ema: [0; 16],
+            ema_alpha: 0,
+            next_crank_epoch: 0,
+            history: v10.history,
+            clamp_decrements: v10.clamp_decrements,
+            exactly_once: v10.exactly_once,
+            freeze_authority: v10.freeze_authority,
+            threshold_count: v10.threshold_count,
+            callback: v10.callback,
+            hooks: v10.hooks,
+            token_gate: v10.token_gate,
+            nft_authority: v10.nft_authority,
+            burn: v10.burn,
+            rewards: v10.rewards,
+            permits: v10.permits,
+            cooldown: v10.cooldown,
+            epoch_reset: v10.epoch_reset,
+            decay: v10.decay,
+            fee_paid: 0,
+            escrow: 0,
+        }
+    }
+}
+
+/// Counter layout version 9, from before timelocks
+///
+/// Identical to version 10 without `locked_until`.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct CounterV9 {
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub overflow_mode: u8,
+    pub kind: u8,
+    pub paused: u8,
+    pub step: u32,
+    pub authority: Pubkey,
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub pending_authority: Pubkey,
+    pub delegate: Pubkey,
+    pub allowance: u64,
+    pub modulus: u64,
+    pub total_increments: u64,
+    pub total_decrements: u64,
+    pub last_updated_slot: u64,
+    pub high_water: u64,
+    pub low_water: u64,
+    pub last_updated_ts: i64,
+    pub thresholds: [u64; Counter::MAX_THRESHOLDS],
+    pub epoch: u64,
+    pub epoch_change: u64,
+    pub cumulative: [u8; 16],
+    pub history: u8,
+    pub clamp_decrements: u8,
+    pub exactly_once: u8,
+    pub freeze_authority: Pubkey,
+    pub threshold_count: u8,
+    pub callback: u8,
+    pub hooks: u8,
+    pub token_gate: u8,
+    pub nft_authority: u8,
+    pub burn: u8,
+    pub rewards: u8,
+    pub permits: u8,
+    pub cooldown: u8,
+    pub epoch_reset: u8,
+    pub decay: u8,
+    pub reserved: [u8; 2],
+}
+
+impl CounterV9 {
+    /// Number of bytes a version 9 counter occupies in account data
+    pub const LEN: usize = core::mem::size_of::<Self>();
+}
+
+impl From<CounterV9> for CounterV13 {
+    fn from(v9: CounterV9) -> Self {
+        Self {
+            discriminator: v9.discriminator,
+            version: 13,
+            overflow_mode: v9.overflow_mode,
+            kind: v9.kind,
+            paused: v9.paused,
+            step: v9.step,
+            authority: v9.authority,
+            count: v9.count,
+            min: v9.min,
+            max: v9.max,
+            pending_authority: v9.pending_authority,
+            delegate: v9.delegate,
+            allowance: v9.allowance,
+            modulus: v9.modulus,
+            total_increments: v9.total_increments,
+            total_decrements: v9.total_decrements,
+            last_updated_slot: v9.last_updated_slot,
+            high_water: v9.high_water,
+            low_water: v9.low_water,
+            last_updated_ts: v9.last_updated_ts,
+            thresholds: v9.thresholds,
+            epoch: v9.epoch,
+            epoch_change: v9.epoch_change,
+            cumulative: v9.cumulative,
+            locked_until: 0,
+            expires_at: 0,
+            ema: [0; 16],
+            ema_alpha: 0,
+            next_crank_epoch: 0,
+            history: v9.history,
+            clamp_decrements: v9.clamp_decrements,
+            exactly_once: v9.exactly_once,
+            freeze_authority: v9.freeze_authority,
+            threshold_count: v9.threshold_count,
+            callback: v9.callback,
+            hooks: v9.hooks,
+            token_gate: v9.token_gate,
+            nft_authority: v9.nft_authority,
+            burn: v9.burn,
+            rewards: v9.rewards,
+            permits: v9.permits,
+            cooldown: v9.cooldown,
+            epoch_reset: v9.epoch_reset,
+            decay: v9.decay,
+            fee_paid: 0,
+            escrow: 0,
+        }
+    }
+}
+
+/// Counter layout version 8, from before the time-weighted accumulator
+///
+/// Identical to version 9 without `cumulative`.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct CounterV8 {
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub overflow_mode: u8,
+    pub kind: u8,
+    pub paused: u8,
+    pub step: u32,
+    pub authority: Pubkey,
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub pending_authority: Pubkey,
+    pub delegate: Pubkey,
+    pub allowance: u64,
+    pub modulus: u64,
+    pub total_increments: u64,
+    pub total_decrements: u64,
+    pub last_updated_slot: u64,
+    pub high_water: u64,
+    pub low_water: u64,
+    pub last_updated_ts: i64,
+    pub thresholds: [u64; Counter::MAX_THRESHOLDS],
+    pub epoch: u64,
+    pub epoch_change: u64,
+    pub history: u8,
+    pub clamp_decrements: u8,
+    pub exactly_once: u8,
+    pub freeze_authority: Pubkey,
+    pub threshold_count: u8,
+    pub callback: u8,
+    pub hooks: u8,
+    pub token_gate: u8,
+    pub nft_authority: u8,
+    pub burn: u8,
+    pub rewards: u8,
+    pub permits: u8,
+    pub cooldown: u8,
+    pub epoch_reset: u8,
+    pub decay: u8,
+    pub reserved: [u8; 2],
+}
+
+impl CounterV8 {
+    /// Number of bytes a version 8 counter occupies in account data
+    pub const LEN: usize = core::mem::size_of::<Self>();
+}
+
+impl From<CounterV8> for CounterV13 {
+    fn from(v8: CounterV8) -> Self {
+        Self {
+            discriminator: v8.discriminator,
+            version: 13,
+            overflow_mode: v8.overflow_mode,
+            kind: v8.kind,
+            paused: v8.paused,
+            step: v8.step,
+            authority: v8.authority,
+            count: v8.count,
+            min: v8.min,
+            max: v8.max,
+            pending_authority: v8.pending_authority,
+            delegate: v8.delegate,
+            allowance: v8.allowance,
+            modulus: v8.modulus,
+            total_increments: v8.total_increments,
+            total_decrements: v8.total_decrements,
+            last_updated_slot: v8.last_updated_slot,
+            high_water: v8.high_water,
+            low_water: v8.low_water,
+            last_updated_ts: v8.last_updated_ts,
+            thresholds: v8.thresholds,
+            epoch: v8.epoch,
+            epoch_change: v8.epoch_change,
+            cumulative: [0; 16],
+            locked_until: 0,
+            expires_at: 0,
+            ema: [0; 16],
+            ema_alpha: 0,
+            next_crank_epoch: 0,
+            history: v8.history,
+            clamp_decrements: v8.clamp_decrements,
+            exactly_once: v8.exactly_once,
+            freeze_authority: v8.freeze_authority,
+            threshold_count: v8.threshold_count,
+            callback: v8.callback,
+            hooks: v8.hooks,
+            token_gate: v8.token_gate,
+            nft_authority: v8.nft_authority,
+            burn: v8.burn,
+            rewards: v8.rewards,
+            permits: v8.permits,
+            cooldown: v8.cooldown,
+            epoch_reset: v8.epoch_reset,
+            decay: v8.decay,
+            fee_paid: 0,
+            escrow: 0,
+        }
+    }
+}
+
+/// Counter layout version 7, from before the epoch change tally
+///
+/// Identical to version 8 without `epoch` and `epoch_change`, and with
+/// every byte after `cooldown` reserved.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct CounterV7 {
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub overflow_mode: u8,
+    pub kind: u8,
+    pub paused: u8,
+    pub step: u32,
+    pub authority: Pubkey,
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub pending_authority: Pubkey,
+    pub delegate: Pubkey,
+    pub allowance: u64,
+    pub modulus: u64,
+    pub total_increments: u64,
+    pub total_decrements: u64,
+    pub last_updated_slot: u64,
+    pub high_water: u64,
+    pub low_water: u64,
+    pub last_updated_ts: i64,
+    pub thresholds: [u64; Counter::MAX_THRESHOLDS],
+    pub history: u8,
+    pub clamp_decrements: u8,
+    pub exactly_once: u8,
+    pub freeze_authority: Pubkey,
+    pub threshold_count: u8,
+    pub callback: u8,
+    pub hooks: u8,
+    pub token_gate: u8,
+    pub nft_authority: u8,
+    pub burn: u8,
+    pub rewards: u8,
+    pub permits: u8,
+    pub cooldown: u8,
+    pub reserved: [u8; 4],
+}
+
+impl CounterV7 {
+    /// Number of bytes a version 7 counter occupies in account data
+    pub const LEN: usize = core::mem::size_of::<Self>();
+}
+
+impl From<CounterV7> for CounterV13 {
+    fn from(v7: CounterV7) -> Self {
+        Self {
+            discriminator: v7.discriminator,
+            version: 13,
+            overflow_mode: v7.overflow_mode,
+            kind: v7.kind,
+            paused: v7.paused,
+            step: v7.step,
+            authority: v7.authority,
+            count: v7.count,
+            min: v7.min,
+            max: v7.max,
+            pending_authority: v7.pending_authority,
+            delegate: v7.delegate,
+            allowance: v7.allowance,
+            modulus: v7.modulus,
+            total_increments: v7.total_increments,
+            total_decrements: v7.total_decrements,
+            last_updated_slot: v7.last_updated_slot,
+            high_water: v7.high_water,
+            low_water: v7.low_water,
+            last_updated_ts: v7.last_updated_ts,
+            thresholds: v7.thresholds,
+            epoch: 0,
+            epoch_change: 0,
+            cumulative: [0; 16],
+            locked_until: 0,
+            expires_at: 0,
+            ema: [0; 16],
+            ema_alpha: 0,
+            next_crank_epoch: 0,
+            history: v7.history,
+            clamp_decrements: v7.clamp_decrements,
+            exactly_once: v7.exactly_once,
+            freeze_authority: v7.freeze_authority,
+            threshold_count: v7.threshold_count,
+            callback: v7.callback,
+            hooks: v7.hooks,
+            token_gate: v7.token_gate,
+            nft_authority: v7.nft_authority,
+            burn: v7.burn,
+            rewards: v7.rewards,
+            permits: v7.permits,
+            cooldown: v7.cooldown,
+            epoch_reset: 0,
+            decay: 0,
+            fee_paid: 0,
+            escrow: 0,
+        }
+    }
+}
+
+/// Counter layout version 6, from before thresholds
+///
+/// Identical to version 7 without `thresholds` and
+/// `threshold_count`, and with one more reserved byte.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct CounterV6 {
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub overflow_mode: u8,
+    pub kind: u8,
+    pub paused: u8,
+    pub step: u32,
+    pub authority: Pubkey,
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub pending_authority: Pubkey,
+    pub delegate: Pubkey,
+    pub allowance: u64,
+    pub modulus: u64,
+    pub total_increments: u64,
+    pub total_decrements: u64,
+    pub last_updated_slot: u64,
+    pub high_water: u64,
+    pub low_water: u64,
+    pub last_updated_ts: i64,
+    pub history: u8,
+    pub clamp_decrements: u8,
+    pub exactly_once: u8,
+    pub freeze_authority: Pubkey,
+    pub reserved: [u8; 13],
+}
+
+impl CounterV6 {
+    /// Number of bytes a version 6 counter occupies in account data
+    pub const LEN: usize = core::mem::size_of::<Self>();
+}
+
+impl From<CounterV6> for CounterV13 {
+    fn from(v6: CounterV6) -> Self {
+        Self {
+            discriminator: v6.discriminator,
+            version: 13,
+            overflow_mode: v6.overflow_mode,
+            kind: v6.kind,
+            paused: v6.paused,
+            step: v6.step,
+            authority: v6.authority,
+            count: v6.count,
+            min: v6.min,
+            max: v6.max,
+            pending_authority: v6.pending_authority,
+            delegate: v6.delegate,
+            allowance: v6.allowance,
+            modulus: v6.modulus,
+            total_increments: v6.total_increments,
+            total_decrements: v6.total_decrements,
+            last_updated_slot: v6.last_updated_slot,
+            high_water: v6.high_water,
+            low_water: v6.low_water,
+            last_updated_ts: v6.last_updated_ts,
+            thresholds: [0; Counter::MAX_THRESHOLDS],
+            epoch: 0,
+            epoch_change: 0,
+            cumulative: [0; 16],
+            locked_until: 0,
+            expires_at: 0,
+            ema: [0; 16],
+            ema_alpha: 0,
+            next_crank_epoch: 0,
+            history: v6.history,
+            clamp_decrements: v6.clamp_decrements,
+            exactly_once: v6.exactly_once,
+            freeze_authority: v6.freeze_authority,
+            threshold_count: 0,
+            callback: 0,
+            hooks: 0,
+            token_gate: 0,
+            nft_authority: 0,
+            burn: 0,
+            rewards: 0,
+            permits: 0,
+            cooldown: 0,
+            epoch_reset: 0,
+            decay: 0,
+            fee_paid: 0,
+            escrow: 0,
+        }
+    }
+}
+
+/// Counter layout version 5, from before the freeze authority
+///
+/// Identical to the current layout up to `exactly_once`, which the freeze
+/// authority follows. Anything stored after the counter, such as its
+/// metadata, moves along when `Migrate` upgrades it.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct CounterV5 {
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub overflow_mode: u8,
+    pub kind: u8,
+    pub paused: u8,
+    pub step: u32,
+    pub authority: Pubkey,
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub pending_authority: Pubkey,
+    pub delegate: Pubkey,
+    pub allowance: u64,
+    pub modulus: u64,
+    pub total_increments: u64,
+    pub total_decrements: u64,
+    pub last_updated_slot: u64,
+    pub high_water: u64,
+    pub low_water: u64,
+    pub last_updated_ts: i64,
+    pub history: u8,
+    pub clamp_decrements: u8,
+    pub exactly_once: u8,
+    pub reserved: [u8; 13],
+}
+
+impl CounterV5 {
+    /// Number of bytes a version 5 counter occupies in account data
+    pub const LEN: usize = core::mem::size_of::<Self>();
+}
+
+impl From<CounterV5> for CounterV13 {
+    fn from(v5: CounterV5) -> Self {
+        Self {
+            discriminator: v5.discriminator,
+            version: 13,
+            overflow_mode: v5.overflow_mode,
+            kind: v5.kind,
+            paused: v5.paused,
+            step: v5.step,
+            authority: v5.authority,
+            count: v5.count,
+            min: v5.min,
+            max: v5.max,
+            pending_authority: v5.pending_authority,
+            delegate: v5.delegate,
+            allowance: v5.allowance,
+            modulus: v5.modulus,
+            total_increments: v5.total_increments,
+            total_decrements: v5.total_decrements,
+            last_updated_slot: v5.last_updated_slot,
+            high_water: v5.high_water,
+            low_water: v5.low_water,
+            last_updated_ts: v5.last_updated_ts,
+            thresholds: [0; Counter::MAX_THRESHOLDS],
+            epoch: 0,
+            epoch_change: 0,
+            cumulative: [0; 16],
+            locked_until: 0,
+            expires_at: 0,
+            ema: [0; 16],
+            ema_alpha: 0,
+            next_crank_epoch: 0,
+            history: v5.history,
+            clamp_decrements: v5.clamp_decrements,
+            exactly_once: v5.exactly_once,
+            freeze_authority: Pubkey::default(),
+            threshold_count: 0,
+            callback: 0,
+            hooks: 0,
+            token_gate: 0,
+            nft_authority: 0,
+            burn: 0,
+            rewards: 0,
+            permits: 0,
+            cooldown: 0,
+            epoch_reset: 0,
+            decay: 0,
+            fee_paid: 0,
+            escrow: 0,
+        }
+    }
+}
+
+/// Counter layout versions 3 and 4, from before counters were stored as `Pod`
+///
+/// Version 4 is this layout followed by a 64-byte reserved block, and
+/// version 3 is the same without it. Both keep their settings in a different
+/// order than the current layout, and predate the lifetime statistics, which
+/// start from the migrated value. Accounts in either are rejected by every
+/// instruction except `Migrate`.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CounterV4 {
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub count: u64,
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub overflow_mode: OverflowMode,
+    pub kind: CounterKind,
+    pub min: u64,
+    pub max: u64,
+    pub step: u32,
+    pub paused: bool,
+    pub delegate: Pubkey,
+    pub allowance: u64,
+    pub modulus: u64,
+}
+
+impl CounterV4 {
+    /// Number of bytes a version 3 counter occupies in account data
+    pub const V3_LEN: usize = 8 + 1 + 8 + 32 + 32 + 1 + 1 + 8 + 8 + 4 + 1 + 32 + 8 + 8;
+
+    /// Number of bytes a version 4 counter occupies in account data
+    pub const LEN: usize = Self::V3_LEN + 64;
+}
+
+impl From<CounterV4> for Counter {
+    fn from(v4: CounterV4) -> Self {
+        Self {
+            count: v4.count,
+            high_water: v4.count,
+            low_water: v4.count,
+            pending_authority: v4.pending_authority,
+            min: v4.min,
+            max: v4.max,
+            step: v4.step,
+            paused: v4.paused.into(),
+            delegate: v4.delegate,
+            allowance: v4.allowance,
+            modulus: v4.modulus,
+            ..Counter::new(v4.authority, v4.overflow_mode, v4.kind)
+        }
+    }
+}
+
+/// Counter layout version 2, from before bounds, step, pausing, delegates and moduli
+///
+/// Version 3 added those settings, so upgrading keeps every field and starts
+/// the new ones at their defaults. Accounts in this layout are
+/// rejected by every instruction except `Migrate`.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CounterV2 {
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub count: u64,
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub overflow_mode: OverflowMode,
+    pub kind: CounterKind,
+}
+
+impl CounterV2 {
+    /// Number of bytes a version 2 counter occupies in account data
+    pub const LEN: usize = 8 + 1 + 8 + 32 + 32 + 1 + 1;
+}
+
+impl From<CounterV2> for Counter {
+    fn from(v2: CounterV2) -> Self {
+        Self {
+            count: v2.count,
+            high_water: v2.count,
+            low_water: v2.count,
+            pending_authority: v2.pending_authority,
+            ..Counter::new(v2.authority, v2.overflow_mode, v2.kind)
+        }
+    }
+}
+
+/// Counter layout version 1, used before the version byte was introduced
+///
+/// It only differs from version 2 by lacking the version byte, so its
+/// `count` bits carry over unchanged. Accounts in this layout are rejected by
+/// every instruction except `Migrate`.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct CounterV1 {
+    pub discriminator: [u8; 8],
+    pub count: i64,
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub overflow_mode: OverflowMode,
+    pub kind: CounterKind,
+}
+
+impl CounterV1 {
+    /// Number of bytes a version 1 counter occupies in account data
+    pub const LEN: usize = 8 + 8 + 32 + 32 + 1 + 1;
+}
+
+impl From<CounterV1> for Counter {
+    fn from(v1: CounterV1) -> Self {
+        Self {
+            count: v1.count as u64,
+            high_water: v1.count as u64,
+            low_water: v1.count as u64,
+            pending_authority: v1.pending_authority,
+            ..Counter::new(v1.authority, v1.overflow_mode, v1.kind)
+        }
+    }
+}
+
+/// Counter layout used before the discriminator was introduced
+///
+/// Accounts in this layout are rejected by every instruction except
+/// `Migrate` and `MigrateLegacy`, which rewrite them as a [`Counter`].
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct LegacyCounter {
+    pub count: u32,
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+}
+
+impl LegacyCounter {
+    /// Number of bytes a legacy counter occupies in account data
+    pub const LEN: usize = 4 + 32 + 32;
+}
+
+impl From<LegacyCounter> for Counter {
+    fn from(legacy: LegacyCounter) -> Self {
+        Self {
+            count: legacy.count.into(),
+            high_water: legacy.count.into(),
+            low_water: legacy.count.into(),
+            pending_authority: legacy.pending_authority,
+            ..Counter::new(
+                legacy.authority,
+                OverflowMode::Checked,
+                CounterKind::Unsigned,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `find_*_address` helper for a counter's companion
+    type FindCompanion = fn(&Pubkey, &Pubkey) -> (Pubkey, u8);
+
+    /// The address the program signs for with `seeds` and `bump`, as its
+    /// handlers pass them to `invoke_signed`
+    fn signed(seeds: &[&[u8]], bump: u8, program_id: &Pubkey) -> Pubkey {
+        let mut seeds = seeds.to_vec();
+        let bump = [bump];
+        seeds.push(&bump);
+        Pubkey::create_program_address(&seeds, program_id).unwrap()
+    }
+
+    #[test]
+    fn seeds_match_the_published_conventions() {
+        // The strings every client derives the addresses from, the
+        // TypeScript helpers included
+        let seeds: [(&[u8], &str); 18] = [
+            (COUNTER_SEED, "counter"),
+            (CONFIG_SEED, "config"),
+            (FEE_VAULT_SEED, "fee_vault"),
+            (HISTORY_SEED, "history"),
+            (SHARD_SEED, "shard"),
+            (CALLBACK_SEED, "callback"),
+            (OPERATORS_SEED, "operators"),
+            (HOOKS_SEED, "hooks"),
+            (TOKEN_GATE_SEED, "gate"),
+            (BURN_SEED, "burn"),
+            (REWARDS_SEED, "rewards"),
+            (ESCROW_SEED, "escrow"),
+            (ESCROW_DEPOSIT_SEED, "deposit"),
+            (PERMITS_SEED, "permits"),
+            (COOLDOWN_SEED, "cooldown"),
+            (COOLDOWN_STAMP_SEED, "stamp"),
+            (DECAY_SEED, "decay"),
+            (NATIVE_TREASURY_SEED, "native-treasury"),
+        ];
+        for (seed, expected) in seeds {
+            assert_eq!(seed, expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn create_helpers_match_find_helpers() {
+        let authority = Pubkey::new_unique();
+
+        let (counter, bump) = find_counter_address(&crate::ID, &authority, "visits");
+        assert_eq!(
+            create_counter_address(&crate::ID, &authority, "visits", bump),
+            Ok(counter)
+        );
+        assert_eq!(
+            signed(
+                &[COUNTER_SEED, authority.as_ref(), b"visits"],
+                bump,
+                &crate::ID
+            ),
+            counter
+        );
+        // Any other bump misses the address, if it gives one at all
+        assert_ne!(
+            create_counter_address(&crate::ID, &authority, "visits", bump.wrapping_sub(1)),
+            Ok(counter)
+        );
+
+        let (config, bump) = find_config_address(&crate::ID);
+        assert_eq!(create_config_address(&crate::ID, bump), Ok(config));
+        assert_eq!(signed(&[CONFIG_SEED], bump, &crate::ID), config);
+    }
+
+    #[test]
+    fn find_helpers_match_the_program_derivations() {
+        let program_id = crate::ID;
+        let counter = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+
+        let (address, bump) = find_fee_vault_address(&program_id);
+        assert_eq!(signed(&[FEE_VAULT_SEED], bump, &program_id), address);
+
+        // Each companion of a counter, at `[seed, counter]`
+        let companions: [(&[u8], FindCompanion); 11] = [
+            (HISTORY_SEED, find_history_address),
+            (CALLBACK_SEED, find_callback_address),
+            (OPERATORS_SEED, find_operators_address),
+            (HOOKS_SEED, find_hooks_address),
+            (TOKEN_GATE_SEED, find_token_gate_address),
+            (BURN_SEED, find_burn_address),
+            (REWARDS_SEED, find_rewards_address),
+            (ESCROW_SEED, find_escrow_address),
+            (PERMITS_SEED, find_permits_address),
+            (COOLDOWN_SEED, find_cooldown_address),
+            (DECAY_SEED, find_decay_address),
+        ];
+        for (seed, find) in companions {
+            let (address, bump) = find(&program_id, &counter);
+            assert_eq!(
+                signed(&[seed, counter.as_ref()], bump, &program_id),
+                address
+            );
+        }
+
+        let (address, bump) = find_shard_address(&program_id, &counter, 258);
+        assert_eq!(
+            signed(&[SHARD_SEED, counter.as_ref(), &[2, 1]], bump, &program_id),
+            address
+        );
+        let (address, bump) = find_escrow_deposit_address(&program_id, &counter, &other);
+        assert_eq!(
+            signed(
+                &[ESCROW_DEPOSIT_SEED, counter.as_ref(), other.as_ref()],
+                bump,
+                &program_id
+            ),
+            address
+        );
+        let (address, bump) = find_cooldown_stamp_address(&program_id, &counter, &other);
+        assert_eq!(
+            signed(
+                &[COOLDOWN_STAMP_SEED, counter.as_ref(), other.as_ref()],
+                bump,
+                &program_id
+            ),
+            address
+        );
+    }
+
+    #[test]
+    fn native_treasuries_are_derived_under_the_governance_program() {
+        // SPL Governance's mainnet deployment
+        let governance_program =
+            solana_program::pubkey!("GovER5Lthms3bLBqWub97yVrMmEogzX7xNjdXpPPCVZw");
+        let governance = Pubkey::new_unique();
+
+        let (treasury, bump) = find_native_treasury_address(&governance_program, &governance);
+        assert_eq!(
+            signed(
+                &[NATIVE_TREASURY_SEED, governance.as_ref()],
+                bump,
+                &governance_program
+            ),
+            treasury
+        );
+        // Not an address of this program's
+        assert_ne!(
+            find_native_treasury_address(&crate::ID, &governance).0,
+            treasury
+        );
+    }
+
+    #[test]
+    fn migrating_version_13_packs_each_mode_into_its_flag() {
+        let v13 = CounterV13 {
+            discriminator: Counter::DISCRIMINATOR,
+            version: 13,
+            count: 7,
+            token_gate: 1,
+            nft_authority: 1,
+            fee_paid: 1,
+            ..Zeroable::zeroed()
+        };
+        let counter = Counter::from(v13);
+
+        assert_eq!(Counter::LEN, CounterV13::LEN);
+        assert_eq!(counter.version, Counter::VERSION);
+        assert_eq!(counter.count, 7);
+        assert_eq!(
+            counter.flags,
+            CounterFlag::TokenGate as u16
+                | CounterFlag::NftAuthority as u16
+                | CounterFlag::FeePaid as u16
+        );
+        assert!(!counter.has_flag(CounterFlag::History));
+        assert_eq!(counter.reserved, [0; Counter::RESERVED_LEN]);
+    }
+
+    #[test]
+    fn set_flag_leaves_the_other_flags_alone() {
+        let mut counter = Counter::new(
+            Pubkey::new_unique(),
+            OverflowMode::Checked,
+            CounterKind::Unsigned,
+        );
+        counter.set_flag(CounterFlag::Decay, true);
+        counter.set_flag(CounterFlag::Escrow, true);
+        counter.set_flag(CounterFlag::Decay, false);
+
+        assert!(!counter.has_flag(CounterFlag::Decay));
+        assert!(counter.has_flag(CounterFlag::Escrow));
+        assert_eq!(counter.flags, CounterFlag::Escrow as u16);
+    }
+}
@@ -0,0 +1,1107 @@
+use crate::{
+    error::CounterError,
+    instruction::{
+        CounterInstruction, CounterOp, MAX_AGGREGATE_SHARDS, MAX_MEMO_LEN, MEMO_PROGRAM_ID,
+    },
+    state::{
+        CounterBurn, CounterCallback, CounterCooldown, CounterDecay, CounterGate, CounterHooks,
+        CounterPermits, CounterRewards, CounterShard, FeeVault, MAX_SIGNERS, TOKEN_2022_PROGRAM_ID,
+        TOKEN_PROGRAM_ID,
+    },
+};
+use solana_program::{
+    account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, system_program,
+};
+
+/// Description of one account an instruction expects at a given position
+pub(crate) struct AccountSpec {
+    /// Role of the account, used in log messages
+    pub name: &'static str,
+    /// Whether the account must have signed the transaction
+    pub signer: bool,
+    /// Whether the account must be passed as writable
+    pub writable: bool,
+    /// Address the account must have, for well-known programs
+    pub address: Option<Pubkey>,
+    /// Whether the account is an authority that may be a multisig, whose
+    /// approval is checked by the handler instead of by its signer flag
+    pub authority: bool,
+}
+
+impl AccountSpec {
+    /// An account the instruction only reads
+    const fn readonly(name: &'static str) -> Self {
+        Self {
+            name,
+            signer: false,
+            writable: false,
+            address: None,
+            authority: false,
+        }
+    }
+
+    /// An account the instruction modifies
+    const fn writable(name: &'static str) -> Self {
+        Self {
+            writable: true,
+            ..Self::readonly(name)
+        }
+    }
+
+    /// An account whose signature authorizes the instruction
+    const fn signer(name: &'static str) -> Self {
+        Self {
+            signer: true,
+            ..Self::readonly(name)
+        }
+    }
+
+    /// An authority that either signs itself or is a multisig approved by
+    /// trailing signer accounts
+    const fn authority(name: &'static str) -> Self {
+        Self {
+            authority: true,
+            ..Self::readonly(name)
+        }
+    }
+
+    /// An account that both signs and is modified, such as a fee payer
+    const fn writable_signer(name: &'static str) -> Self {
+        Self {
+            signer: true,
+            writable: true,
+            ..Self::readonly(name)
+        }
+    }
+
+    /// The flags the account must carry, written like the "Accounts expected"
+    /// lists on `CounterInstruction`
+    fn flags(&self) -> &'static str {
+        match (self.writable, self.signer || self.authority) {
+            (true, true) => "[writable, signer]",
+            (true, false) => "[writable]",
+            (false, true) => "[signer]",
+            (false, false) => "[]",
+        }
+    }
+
+    /// A program that is invoked through CPI and must have a fixed address
+    const fn program(name: &'static str, address: Pubkey) -> Self {
+        Self {
+            address: Some(address),
+            ..Self::readonly(name)
+        }
+    }
+}
+
+/// Accounts expected by `CounterInstruction::Increment`
+const INCREMENT_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::readonly("config"),
+];
+
+/// Accounts expected by instructions that change a counter's value on behalf of its authority
+const AUTHORITY_MUTATION_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::readonly("config"),
+];
+
+/// Accounts expected by instructions that change a counter's settings on behalf of its authority
+const AUTHORITY_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+];
+
+/// Accounts expected by `CounterInstruction::Initialize`
+const INITIALIZE_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable_signer("counter"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::AcceptAuthority`
+const ACCEPT_AUTHORITY_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("pending_authority"),
+];
+
+/// Accounts expected by `CounterInstruction::Close`
+const CLOSE_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("destination"),
+];
+
+/// Accounts expected by `CounterInstruction::MigrateLegacy`
+const MIGRATE_LEGACY_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::InitializePda`
+const INITIALIZE_PDA_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::Migrate` and `CounterInstruction::MigrateV1ToV2`
+const MIGRATE_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::InitializeConfig`
+const INITIALIZE_CONFIG_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("config"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::UpdateConfig`, `EmergencyHalt` and `EmergencyResume`
+const UPDATE_CONFIG_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("config"),
+    AccountSpec::signer("admin"),
+];
+
+/// Accounts expected by `CounterInstruction::InitializeMultisig`
+const INITIALIZE_MULTISIG_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable_signer("multisig"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::Merge`
+const MERGE_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("destination"),
+    AccountSpec::authority("destination_authority"),
+    AccountSpec::writable("source"),
+    AccountSpec::authority("source_authority"),
+    AccountSpec::readonly("config"),
+];
+
+/// Accounts expected by `CounterInstruction::Clone` into a keypair account
+const CLONE_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::readonly("source"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable_signer("counter"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::Clone` into a PDA
+const CLONE_PDA_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::readonly("source"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("counter"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::InitializeVotes`
+const INITIALIZE_VOTES_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable_signer("votes"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::VoteUp` and `CounterInstruction::VoteDown`
+const VOTE_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("votes"),
+    AccountSpec::readonly("config"),
+];
+
+/// Accounts expected by `CounterInstruction::InitializeMap`
+const INITIALIZE_MAP_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable_signer("map"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::AddMapEntry`
+const ADD_MAP_ENTRY_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("map"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::RemoveMapEntry`
+const REMOVE_MAP_ENTRY_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("map"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("destination"),
+];
+
+/// Accounts expected by `CounterInstruction::Resize` and `CounterInstruction::SetMetadata`
+const RESIZE_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::InitializeShard`
+const INITIALIZE_SHARD_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::readonly("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("shard"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::IncrementShard`
+const INCREMENT_SHARD_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("shard"),
+    AccountSpec::readonly("counter"),
+    AccountSpec::readonly("config"),
+];
+
+/// Accounts expected by `CounterInstruction::Aggregate`, before any further shards
+const AGGREGATE_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::readonly("config"),
+    AccountSpec::writable("shard"),
+];
+
+/// Accounts expected by `CounterInstruction::InitializeHistory`
+const INITIALIZE_HISTORY_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("history"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::SetCallback`
+const SET_CALLBACK_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("callback"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::ClearCallback`
+const CLEAR_CALLBACK_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("callback"),
+    AccountSpec::writable("destination"),
+];
+
+/// Accounts expected by `CounterInstruction::AddOperator`
+const ADD_OPERATOR_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::readonly("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("operators"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::RemoveOperator`
+const REMOVE_OPERATOR_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::readonly("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("operators"),
+];
+
+/// Accounts expected by `CounterInstruction::SetHooks`
+const SET_HOOKS_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("hooks"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::ClearHooks`
+const CLEAR_HOOKS_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("hooks"),
+    AccountSpec::writable("destination"),
+];
+
+/// Accounts expected by `CounterInstruction::View`
+const VIEW_ACCOUNTS: &[AccountSpec] = &[AccountSpec::readonly("counter")];
+
+/// Accounts expected by `CounterInstruction::SetCallerGuard`
+const SET_CALLER_GUARD_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("config"),
+    AccountSpec::signer("admin"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::SetFee`
+const SET_FEE_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("config"),
+    AccountSpec::signer("admin"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::SetLamportFee`
+const SET_LAMPORT_FEE_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("config"),
+    AccountSpec::signer("admin"),
+    AccountSpec::writable("fee_vault"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::WithdrawFees`
+const WITHDRAW_FEES_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::readonly("config"),
+    AccountSpec::signer("admin"),
+    AccountSpec::writable("fee_vault"),
+    AccountSpec::writable("destination"),
+];
+
+/// Accounts expected by `CounterInstruction::SetTokenGate`
+const SET_TOKEN_GATE_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("token_gate"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::ClearTokenGate`
+const CLEAR_TOKEN_GATE_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("token_gate"),
+    AccountSpec::writable("destination"),
+];
+
+/// Accounts expected by `CounterInstruction::SetNftAuthority`
+const SET_NFT_AUTHORITY_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::readonly("mint"),
+];
+
+/// Accounts expected by `CounterInstruction::SetGovernanceAuthority`
+const SET_GOVERNANCE_AUTHORITY_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter or config"),
+    AccountSpec::authority("authority"),
+    AccountSpec::readonly("governance"),
+];
+
+/// Accounts expected by `CounterInstruction::SetBurnMint`
+const SET_BURN_MINT_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("burn_account"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::SetRewards`
+const SET_REWARDS_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("rewards"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::ClearRewards`
+const CLEAR_REWARDS_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("rewards"),
+    AccountSpec::writable("destination"),
+];
+
+/// Accounts expected by `CounterInstruction::InitializeEscrow`
+const INITIALIZE_ESCROW_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("escrow"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::DepositEscrow`
+const DEPOSIT_ESCROW_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::readonly("counter"),
+    AccountSpec::writable("escrow"),
+    AccountSpec::writable("deposit"),
+    AccountSpec::writable_signer("depositor"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::Settle`; a refund also passes
+/// the deposit record
+const SETTLE_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::writable("escrow"),
+    AccountSpec::writable("recipient"),
+];
+
+/// Accounts expected by `CounterInstruction::RequirePermits`
+const REQUIRE_PERMITS_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("permits"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::ClearPermits`
+const CLEAR_PERMITS_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("permits"),
+    AccountSpec::writable("destination"),
+];
+
+/// Accounts expected by `CounterInstruction::SetCooldown`
+const SET_COOLDOWN_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("cooldown"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::ClearCooldown`
+const CLEAR_COOLDOWN_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("cooldown"),
+    AccountSpec::writable("destination"),
+];
+
+/// Accounts expected by `CounterInstruction::SetEpochCap`
+const SET_EPOCH_CAP_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("config"),
+    AccountSpec::signer("admin"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::SetDecay`
+const SET_DECAY_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("decay"),
+    AccountSpec::writable_signer("payer"),
+    AccountSpec::program("system_program", system_program::ID),
+];
+
+/// Accounts expected by `CounterInstruction::ClearDecay`
+const CLEAR_DECAY_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::authority("authority"),
+    AccountSpec::writable("decay"),
+    AccountSpec::writable("destination"),
+];
+
+/// Accounts expected by `CounterInstruction::Crank`
+const CRANK_ACCOUNTS: &[AccountSpec] = &[
+    AccountSpec::writable("counter"),
+    AccountSpec::writable("config"),
+    AccountSpec::writable_signer("cranker"),
+    AccountSpec::writable("fee_vault"),
+];
+
+/// History account optionally passed right after the listed accounts, see
+/// [`CounterInstruction::changes_count`]
+const HISTORY: AccountSpec = AccountSpec::writable("history");
+
+/// Signer recorded as the actor of an increment, after the listed accounts
+const ACTOR: AccountSpec = AccountSpec::signer("actor");
+
+/// Signer made the authority of a new counter instead of the payer, after
+/// the listed accounts
+const NEW_AUTHORITY: AccountSpec = AccountSpec::signer("authority");
+
+/// Signer approving on behalf of a multisig authority, after the listed accounts
+const MULTISIG_SIGNER: AccountSpec = AccountSpec::signer("multisig_signer");
+
+/// Shard drained by `CounterInstruction::Aggregate`, after the listed accounts
+const TRAILING_SHARD: AccountSpec = AccountSpec::writable("shard");
+
+/// Memo program invoked by `CounterInstruction::WithMemo`, after the accounts
+/// of the instruction it wraps
+const MEMO_PROGRAM: AccountSpec = AccountSpec::program("memo_program", MEMO_PROGRAM_ID);
+
+/// Number of accounts `instruction` lists, before any it may pass after them
+pub(crate) fn listed_accounts(instruction: &CounterInstruction) -> usize {
+    expected_accounts(instruction).len()
+}
+
+/// The ordered list of accounts `instruction` expects
+fn expected_accounts(instruction: &CounterInstruction) -> &'static [AccountSpec] {
+    match instruction {
+        CounterInstruction::Increment(_) | CounterInstruction::IncrementByOne => INCREMENT_ACCOUNTS,
+        CounterInstruction::Decrement(_)
+        | CounterInstruction::Reset
+        | CounterInstruction::Set(_)
+        | CounterInstruction::Multiply(_)
+        | CounterInstruction::Divide(_)
+        | CounterInstruction::CompareAndSwap { .. } => AUTHORITY_MUTATION_ACCOUNTS,
+        CounterInstruction::TransferAuthority { .. }
+        | CounterInstruction::Pause
+        | CounterInstruction::Unpause
+        | CounterInstruction::Approve { .. }
+        | CounterInstruction::Revoke
+        | CounterInstruction::SetExactlyOnce { .. }
+        | CounterInstruction::SetEpochReset { .. }
+        | CounterInstruction::Lock { .. }
+        | CounterInstruction::SetExpiry { .. }
+        | CounterInstruction::SetEma { .. }
+        | CounterInstruction::SetThresholds { .. }
+        | CounterInstruction::SetFreezeAuthority { .. } => AUTHORITY_ACCOUNTS,
+        CounterInstruction::Initialize(_) => INITIALIZE_ACCOUNTS,
+        CounterInstruction::AcceptAuthority => ACCEPT_AUTHORITY_ACCOUNTS,
+        CounterInstruction::Close => CLOSE_ACCOUNTS,
+        CounterInstruction::MigrateLegacy => MIGRATE_LEGACY_ACCOUNTS,
+        CounterInstruction::Migrate | CounterInstruction::MigrateV1ToV2 => MIGRATE_ACCOUNTS,
+        CounterInstruction::Resize { .. } | CounterInstruction::SetMetadata { .. } => {
+            RESIZE_ACCOUNTS
+        }
+        CounterInstruction::InitializePda { .. } => INITIALIZE_PDA_ACCOUNTS,
+        CounterInstruction::InitializeConfig { .. } => INITIALIZE_CONFIG_ACCOUNTS,
+        CounterInstruction::UpdateConfig { .. }
+        | CounterInstruction::EmergencyHalt
+        | CounterInstruction::EmergencyResume => UPDATE_CONFIG_ACCOUNTS,
+        CounterInstruction::InitializeMultisig { .. } => INITIALIZE_MULTISIG_ACCOUNTS,
+        CounterInstruction::Merge => MERGE_ACCOUNTS,
+        CounterInstruction::Clone { seed: None } => CLONE_ACCOUNTS,
+        CounterInstruction::Clone { seed: Some(_) } => CLONE_PDA_ACCOUNTS,
+        CounterInstruction::InitializeVotes => INITIALIZE_VOTES_ACCOUNTS,
+        CounterInstruction::VoteUp(_) | CounterInstruction::VoteDown(_) => VOTE_ACCOUNTS,
+        CounterInstruction::InitializeMap => INITIALIZE_MAP_ACCOUNTS,
+        CounterInstruction::AddMapEntry { .. } => ADD_MAP_ENTRY_ACCOUNTS,
+        CounterInstruction::RemoveMapEntry { .. } => REMOVE_MAP_ENTRY_ACCOUNTS,
+        // A map takes the same accounts as a single counter
+        CounterInstruction::IncrementMapEntry { .. } => INCREMENT_ACCOUNTS,
+        CounterInstruction::DecrementMapEntry { .. } => AUTHORITY_MUTATION_ACCOUNTS,
+        CounterInstruction::Batch(ops) => {
+            if ops.iter().any(CounterOp::requires_authority) {
+                AUTHORITY_MUTATION_ACCOUNTS
+            } else {
+                INCREMENT_ACCOUNTS
+            }
+        }
+        CounterInstruction::InitializeShard { .. } => INITIALIZE_SHARD_ACCOUNTS,
+        CounterInstruction::IncrementShard(_) => INCREMENT_SHARD_ACCOUNTS,
+        CounterInstruction::Aggregate => AGGREGATE_ACCOUNTS,
+        CounterInstruction::InitializeHistory { .. } => INITIALIZE_HISTORY_ACCOUNTS,
+        CounterInstruction::SetCallerGuard { .. } => SET_CALLER_GUARD_ACCOUNTS,
+        CounterInstruction::View => VIEW_ACCOUNTS,
+        CounterInstruction::SetCallback { .. } => SET_CALLBACK_ACCOUNTS,
+        CounterInstruction::ClearCallback => CLEAR_CALLBACK_ACCOUNTS,
+        CounterInstruction::AddOperator { .. } => ADD_OPERATOR_ACCOUNTS,
+        CounterInstruction::RemoveOperator { .. } => REMOVE_OPERATOR_ACCOUNTS,
+        CounterInstruction::SetHooks { .. } => SET_HOOKS_ACCOUNTS,
+        CounterInstruction::ClearHooks => CLEAR_HOOKS_ACCOUNTS,
+        CounterInstruction::SetFee { .. } => SET_FEE_ACCOUNTS,
+        CounterInstruction::SetLamportFee { .. } => SET_LAMPORT_FEE_ACCOUNTS,
+        CounterInstruction::WithdrawFees { .. } => WITHDRAW_FEES_ACCOUNTS,
+        CounterInstruction::SetTokenGate { .. } => SET_TOKEN_GATE_ACCOUNTS,
+        CounterInstruction::ClearTokenGate => CLEAR_TOKEN_GATE_ACCOUNTS,
+        CounterInstruction::SetNftAuthority { .. } => SET_NFT_AUTHORITY_ACCOUNTS,
+        CounterInstruction::SetGovernanceAuthority { .. } => SET_GOVERNANCE_AUTHORITY_ACCOUNTS,
+        CounterInstruction::SetBurnMint { .. } => SET_BURN_MINT_ACCOUNTS,
+        CounterInstruction::SetRewards { .. } => SET_REWARDS_ACCOUNTS,
+        CounterInstruction::ClearRewards => CLEAR_REWARDS_ACCOUNTS,
+        CounterInstruction::InitializeEscrow { .. } => INITIALIZE_ESCROW_ACCOUNTS,
+        CounterInstruction::DepositEscrow { .. } => DEPOSIT_ESCROW_ACCOUNTS,
+        CounterInstruction::Settle => SETTLE_ACCOUNTS,
+        CounterInstruction::RequirePermits => REQUIRE_PERMITS_ACCOUNTS,
+        CounterInstruction::ClearPermits => CLEAR_PERMITS_ACCOUNTS,
+        CounterInstruction::SetCooldown { .. } => SET_COOLDOWN_ACCOUNTS,
+        CounterInstruction::ClearCooldown => CLEAR_COOLDOWN_ACCOUNTS,
+        CounterInstruction::SetEpochCap { .. } | CounterInstruction::SetCrankReward { .. } => {
+            SET_EPOCH_CAP_ACCOUNTS
+        }
+        CounterInstruction::SetDecay { .. } => SET_DECAY_ACCOUNTS,
+        CounterInstruction::ClearDecay => CLEAR_DECAY_ACCOUNTS,
+        CounterInstruction::Crank => CRANK_ACCOUNTS,
+        // Unwrapped before validation, see `validate_memo` and `validate_op_id`
+        CounterInstruction::WithMemo { .. } | CounterInstruction::WithOpId { .. } => &[],
+    }
+}
+
+/// Whether `instruction` takes the program config among its own accounts
+///
+/// Every change to a counter's value does, and so does a `WithMemo` or
+/// `WithOpId` wrapping one.
+pub(crate) fn takes_config(instruction: &CounterInstruction) -> bool {
+    match instruction {
+        CounterInstruction::WithMemo { .. } | CounterInstruction::WithOpId { .. } => true,
+        instruction => expected_accounts(instruction)
+            .iter()
+            .any(|spec| spec.name == "config"),
+    }
+}
+
+/// How many accounts `instruction` accepts after those in `specs`, and the
+/// flags each of them must carry
+fn trailing_accounts(
+    instruction: &CounterInstruction,
+    specs: &[AccountSpec],
+) -> (usize, &'static AccountSpec) {
+    if let CounterInstruction::Aggregate = instruction {
+        (0, &TRAILING_SHARD)
+    } else if let CounterInstruction::Initialize(_) | CounterInstruction::InitializePda { .. } =
+        instruction
+    {
+        (1, &NEW_AUTHORITY)
+    } else if specs.iter().any(|spec| spec.authority) {
+        (MAX_SIGNERS, &MULTISIG_SIGNER)
+    } else if instruction.changes_count() {
+        (1, &ACTOR)
+    } else {
+        (0, &MULTISIG_SIGNER)
+    }
+}
+
+/// Check `accounts` against the account list `instruction` expects
+///
+/// The number of accounts must match exactly and every account must carry the
+/// signer and writable flags (and, for programs, the address) listed for its
+/// position, so accounts passed in the wrong order are caught here instead of
+/// being misinterpreted by the handler. The exceptions are instructions
+/// taking an authority, which may be followed by up to [`MAX_SIGNERS`] signer
+/// accounts approving on behalf of a multisig authority, `Aggregate`,
+/// which takes up to [`MAX_AGGREGATE_SHARDS`] shards and then the counter's
+/// history and callback accounts like a change, the initializers, which
+/// may take the new counter's authority, and instructions that
+/// record their change, which may first take the counter's history account
+/// and then its callback's accounts, checked when the callback fires, as
+/// `Merge` does for the destination and then the source, and
+/// `Crank`, which may take the counter's decay and then its callback's.
+/// A trailing Instructions sysvar has already been set aside by the caller.
+pub(crate) fn validate_accounts(
+    instruction: &CounterInstruction,
+    accounts: &[AccountInfo],
+) -> Result<(), ProgramError> {
+    let name = instruction.name();
+    let specs = expected_accounts(instruction);
+    // A merge passes the destination's reward accounts last, as a change does
+    // after its signers
+    let accounts = match instruction {
+        CounterInstruction::Merge => split_rewards(accounts).0,
+        _ => accounts,
+    };
+
+    if accounts.len() < specs.len() {
+        let missing: Vec<String> = specs
+            .iter()
+            .enumerate()
+            .skip(accounts.len())
+            .map(|(index, spec)| format!("{} ({}) {}", index, spec.name, spec.flags()))
+            .collect();
+        msg!(
+            "{}: expected {} accounts, got {}; missing account {}",
+            name,
+            specs.len(),
+            accounts.len(),
+            missing.join(", ")
+        );
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    // A history account never signs, which tells it apart from the signers,
+    // and a callback account says how many accounts follow it
+    let mut fixed = specs.len();
+    if let CounterInstruction::Aggregate = instruction {
+        // The further shards are told apart by their data, and checked here
+        // as the trailing accounts are not theirs
+        let shards = accounts[fixed..]
+            .iter()
+            .take_while(|shard| {
+                shard
+                    .try_borrow_data()
+                    .is_ok_and(|data| CounterShard::is_shard(&data))
+            })
+            .count();
+        if shards > MAX_AGGREGATE_SHARDS - 1 {
+            msg!(
+                "{}: expected at most {} shards, got {}",
+                name,
+                MAX_AGGREGATE_SHARDS,
+                shards + 1
+            );
+            return Err(CounterError::UnexpectedAccounts.into());
+        }
+        for (index, shard) in accounts.iter().enumerate().skip(fixed).take(shards) {
+            check_account(name, index, &TRAILING_SHARD, shard)?;
+        }
+        fixed += shards;
+    }
+    // A merge passes the history and callback accounts of both its counters
+    let recorded = match instruction {
+        CounterInstruction::Merge => 2,
+        CounterInstruction::Aggregate => 1,
+        instruction => usize::from(instruction.changes_count()),
+    };
+    for _ in 0..recorded {
+        if let Some(history) = accounts.get(fixed)
+            && !history.is_signer
+            && callback_group_len(history).is_none()
+        {
+            check_account(name, fixed, &HISTORY, history)?;
+            fixed += 1;
+        }
+        if let Some(callback) = accounts.get(fixed)
+            && !callback.is_signer
+            && let Some(len) = callback_group_len(callback)
+        {
+            fixed = accounts.len().min(fixed + len);
+        }
+    }
+    if let CounterInstruction::Crank = instruction {
+        // The decay is told apart by its data
+        if accounts.get(fixed).is_some_and(|decay| {
+            decay
+                .try_borrow_data()
+                .is_ok_and(|data| CounterDecay::is_decay(&data))
+        }) {
+            fixed += 1;
+        }
+        if let Some(callback) = accounts.get(fixed)
+            && let Some(len) = callback_group_len(callback)
+        {
+            fixed = accounts.len().min(fixed + len);
+        }
+    }
+    let (max_trailing, trailing) = trailing_accounts(instruction, specs);
+    if accounts.len() > fixed + max_trailing {
+        msg!(
+            "{}: expected {} accounts, got {}",
+            name,
+            specs.len(),
+            accounts.len()
+        );
+        return Err(CounterError::UnexpectedAccounts.into());
+    }
+    for (index, account) in accounts.iter().enumerate().skip(fixed) {
+        check_account(name, index, trailing, account)?;
+    }
+
+    for (index, (spec, account)) in specs.iter().zip(accounts).enumerate() {
+        check_account(name, index, spec, account)?;
+    }
+
+    Ok(())
+}
+
+/// Check that the account at `index` carries the flags and address `spec` lists
+fn check_account(
+    name: &str,
+    index: usize,
+    spec: &AccountSpec,
+    account: &AccountInfo,
+) -> Result<(), ProgramError> {
+    if spec.signer && !account.is_signer {
+        msg!(
+            "{}: account {} ({}) {} must sign, expected {}",
+            name,
+            index,
+            spec.name,
+            account.key,
+            spec.flags()
+        );
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if spec.writable && !account.is_writable {
+        msg!(
+            "{}: account {} ({}) {} must be writable, expected {}",
+            name,
+            index,
+            spec.name,
+            account.key,
+            spec.flags()
+        );
+        return Err(CounterError::AccountNotWritable.into());
+    }
+    if let Some(address) = spec.address
+        && *account.key != address
+    {
+        msg!(
+            "{}: account {} ({}) must be {}, got {}",
+            name,
+            index,
+            spec.name,
+            address,
+            account.key
+        );
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+/// Number of accounts in the callback group starting at `account`: the
+/// callback account itself, the callback program and the accounts registered
+/// with it, or `None` if `account` is not a callback account
+///
+/// Only the data is looked at; the handler checks the rest once the callback fires.
+pub(crate) fn callback_group_len(account: &AccountInfo) -> Option<usize> {
+    let data = account.try_borrow_data().ok()?;
+    Some(2 + CounterCallback::account_count(&data)?)
+}
+
+/// Split the hooks account and the hook programs after it off the end of a
+/// change's accounts, if they are there
+///
+/// The hooks account says how many programs follow it, at most two, so only
+/// the last three positions need looking at. Only the data is looked at; the
+/// handler checks the rest.
+pub(crate) fn split_hooks<'a, 'b>(
+    accounts: &'b [AccountInfo<'a>],
+) -> (&'b [AccountInfo<'a>], &'b [AccountInfo<'a>]) {
+    for programs in 0..=2 {
+        // The counter comes first, so it cannot be the hooks account
+        let Some(start) = accounts
+            .len()
+            .checked_sub(programs + 1)
+            .filter(|&start| start > 0)
+        else {
+            break;
+        };
+        let hooks = &accounts[start];
+        let count = hooks
+            .try_borrow_data()
+            .ok()
+            .and_then(|data| CounterHooks::program_count(&data));
+        if !hooks.is_signer && count == Some(programs) {
+            return accounts.split_at(start);
+        }
+    }
+    (accounts, &[])
+}
+
+/// Number of accounts paying a change's fee, see `CounterInstruction::SetFee`
+const FEE_ACCOUNT_COUNT: usize = 5;
+
+/// Split the accounts paying the fee off the end of a change's accounts, once
+/// the hooks are split off, if they are there
+///
+/// They end with a token program, which none of a change's other accounts
+/// can be. The handler checks the rest.
+pub(crate) fn split_fee<'a, 'b>(
+    accounts: &'b [AccountInfo<'a>],
+) -> (&'b [AccountInfo<'a>], &'b [AccountInfo<'a>]) {
+    match accounts.last() {
+        Some(last)
+            if [TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID].contains(last.key)
+                && accounts.len() > FEE_ACCOUNT_COUNT =>
+        {
+            accounts.split_at(accounts.len() - FEE_ACCOUNT_COUNT)
+        }
+        _ => (accounts, &[]),
+    }
+}
+
+/// Split the payer, the fee vault and the system program paying a change's
+/// lamport fee off the end of its accounts, once the hooks are split off, if
+/// they are there
+///
+/// Only the vault's data is looked at; the handler checks the rest.
+pub(crate) fn split_fee_vault<'a, 'b>(
+    accounts: &'b [AccountInfo<'a>],
+) -> (&'b [AccountInfo<'a>], &'b [AccountInfo<'a>]) {
+    match accounts {
+        // The counter and config come first
+        [rest @ .., _, vault, system]
+            if rest.len() >= 2
+                && *system.key == system_program::ID
+                && vault
+                    .try_borrow_data()
+                    .is_ok_and(|data| FeeVault::is_vault(&data)) =>
+        {
+            accounts.split_at(rest.len())
+        }
+        _ => (accounts, &[]),
+    }
+}
+
+/// Split the token gate and the actor's token account off the end of a
+/// change's accounts, once the fee accounts are split off, if they are there
+///
+/// Only the gate's data is looked at; the handler checks the rest.
+pub(crate) fn split_token_gate<'a, 'b>(
+    accounts: &'b [AccountInfo<'a>],
+) -> (&'b [AccountInfo<'a>], &'b [AccountInfo<'a>]) {
+    match accounts {
+        // The counter and config come first
+        [rest @ .., gate, _]
+            if rest.len() >= 2
+                && gate
+                    .try_borrow_data()
+                    .is_ok_and(|data| CounterGate::is_gate(&data)) =>
+        {
+            accounts.split_at(rest.len())
+        }
+        _ => (accounts, &[]),
+    }
+}
+
+/// Number of accounts burning an increment's tokens, see
+/// `CounterInstruction::SetBurnMint`
+const BURN_ACCOUNT_COUNT: usize = 4;
+
+/// Split the burn account, the token program, the actor's token account and
+/// the mint off the end of a change's accounts, once the token gate is split
+/// off, if they are there
+///
+/// Only the burn account's data is looked at; the handler checks the rest.
+pub(crate) fn split_burn<'a, 'b>(
+    accounts: &'b [AccountInfo<'a>],
+) -> (&'b [AccountInfo<'a>], &'b [AccountInfo<'a>]) {
+    // The counter and config come first
+    match accounts.len().checked_sub(BURN_ACCOUNT_COUNT) {
+        Some(start)
+            if start >= 2
+                && accounts[start]
+                    .try_borrow_data()
+                    .is_ok_and(|data| CounterBurn::is_burn(&data)) =>
+        {
+            accounts.split_at(start)
+        }
+        _ => (accounts, &[]),
+    }
+}
+
+/// Split the rewards account, the token program, the mint and the actor's
+/// token account off the end of a change's accounts, once the burn accounts
+/// are split off, if they are there
+///
+/// Only the rewards account's data is looked at; the handler checks the rest.
+pub(crate) fn split_rewards<'a, 'b>(
+    accounts: &'b [AccountInfo<'a>],
+) -> (&'b [AccountInfo<'a>], &'b [AccountInfo<'a>]) {
+    match accounts {
+        // The counter and config come first
+        [rest @ .., rewards, _, _, _]
+            if rest.len() >= 2
+                && rewards
+                    .try_borrow_data()
+                    .is_ok_and(|data| CounterRewards::is_rewards(&data)) =>
+        {
+            accounts.split_at(rest.len())
+        }
+        _ => (accounts, &[]),
+    }
+}
+
+/// Split the permit nonce account off the end of a change's accounts, once
+/// the reward accounts are split off, if it is there
+///
+/// Only its data is looked at; the handler checks the rest.
+pub(crate) fn split_permits<'a, 'b>(
+    accounts: &'b [AccountInfo<'a>],
+) -> (&'b [AccountInfo<'a>], &'b [AccountInfo<'a>]) {
+    match accounts {
+        // The counter and config come first
+        [rest @ .., permits]
+            if rest.len() >= 2
+                && permits
+                    .try_borrow_data()
+                    .is_ok_and(|data| CounterPermits::is_permits(&data)) =>
+        {
+            accounts.split_at(rest.len())
+        }
+        _ => (accounts, &[]),
+    }
+}
+
+/// Split the cooldown, the actor's stamp and the system program off the end
+/// of a change's accounts, once the permit nonce account is split off, if
+/// they are there
+///
+/// Only the cooldown's data is looked at; the handler checks the rest.
+pub(crate) fn split_cooldown<'a, 'b>(
+    accounts: &'b [AccountInfo<'a>],
+) -> (&'b [AccountInfo<'a>], &'b [AccountInfo<'a>]) {
+    match accounts {
+        // The counter and config come first
+        [rest @ .., cooldown, _, system]
+            if rest.len() >= 2
+                && *system.key == system_program::ID
+                && cooldown
+                    .try_borrow_data()
+                    .is_ok_and(|data| CounterCooldown::is_cooldown(&data)) =>
+        {
+            accounts.split_at(rest.len())
+        }
+        _ => (accounts, &[]),
+    }
+}
+
+/// Split the decay off the end of a change's accounts, once the cooldown
+/// accounts are split off, if it is there
+///
+/// Only its data is looked at; the handler checks the rest.
+pub(crate) fn split_decay<'a, 'b>(
+    accounts: &'b [AccountInfo<'a>],
+) -> (&'b [AccountInfo<'a>], &'b [AccountInfo<'a>]) {
+    match accounts {
+        // The counter and config come first
+        [rest @ .., decay]
+            if rest.len() >= 2
+                && decay
+                    .try_borrow_data()
+                    .is_ok_and(|data| CounterDecay::is_decay(&data)) =>
+        {
+            accounts.split_at(rest.len())
+        }
+        _ => (accounts, &[]),
+    }
+}
+
+/// Check the parts of a `CounterInstruction::WithMemo` around `instruction`
+/// that are not the wrapped instruction's own, returning its accounts and the
+/// Memo program passed after them
+///
+/// Only an instruction that changes the count may be wrapped, and a memo
+/// cannot wrap another memo.
+pub(crate) fn validate_memo<'a, 'b>(
+    instruction: &CounterInstruction,
+    memo: &str,
+    accounts: &'b [AccountInfo<'a>],
+) -> Result<(&'b [AccountInfo<'a>], &'b AccountInfo<'a>), ProgramError> {
+    if matches!(instruction, CounterInstruction::WithMemo { .. }) || !instruction.changes_count() {
+        msg!("WithMemo cannot wrap {}", instruction.name());
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    if memo.len() > MAX_MEMO_LEN {
+        msg!(
+            "Memo is {} bytes long, at most {} are allowed",
+            memo.len(),
+            MAX_MEMO_LEN
+        );
+        return Err(CounterError::MemoTooLong.into());
+    }
+    let Some((memo_program, accounts)) = accounts.split_last() else {
+        msg!("WithMemo: missing the memo_program account");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    check_account("WithMemo", accounts.len(), &MEMO_PROGRAM, memo_program)?;
+    Ok((accounts, memo_program))
+}
+
+/// Check that `instruction`, wrapped in a `CounterInstruction::WithOpId`,
+/// changes the count itself rather than wrapping a memo or another op id
+pub(crate) fn validate_op_id(instruction: &CounterInstruction) -> Result<(), ProgramError> {
+    if matches!(
+        instruction,
+        CounterInstruction::WithMemo { .. } | CounterInstruction::WithOpId { .. }
+    ) || !instruction.changes_count()
+    {
+        msg!("WithOpId cannot wrap {}", instruction.name());
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    Ok(())
+}
@@ -1,34 +1,113 @@
 // Import testing utilities and blockchain interaction libraries
 import { expect, test } from "bun:test";
+import { readFileSync } from "fs";
 import * as borsh from "borsh";  // Library for binary serialization (same as used in Rust program)
 import { 
     Connection, 
     Keypair, 
     LAMPORTS_PER_SOL, 
     PublicKey, 
+    Ed25519Program,
     SystemProgram, 
+    SYSVAR_INSTRUCTIONS_PUBKEY,
     Transaction, 
     TransactionInstruction 
 } from "@solana/web3.js";
 
-// TODO: Uncomment when instruction helpers are implemented
-// import { CounterInstruction, CounterInstructionSchema, CounterInstructionType, createIncrementInstructionData, createDecrementInstructionData } from "./instruction";
+import { createIncrementInstructionData, createDecrementInstructionData, createInitializeInstructionData, createResetInstructionData, createSetInstructionData, createTransferAuthorityInstructionData, createAcceptAuthorityInstructionData, createCloseInstructionData, createInitializePdaInstructionData, findCounterAddress, createMultiplyInstructionData, createDivideInstructionData, createCompareAndSwapInstructionData, createBatchInstructionData, createIncrementByOneInstructionData, createPauseInstructionData, createUnpauseInstructionData, createInitializeConfigInstructionData, createUpdateConfigInstructionData, findConfigAddress, createInitializeMultisigInstructionData, createApproveInstructionData, createRevokeInstructionData, createMergeInstructionData, createCloneInstructionData, createInitializeVotesInstructionData, createVoteUpInstructionData, createVoteDownInstructionData, createInitializeMapInstructionData, createAddMapEntryInstructionData, createRemoveMapEntryInstructionData, createIncrementMapEntryInstructionData, createDecrementMapEntryInstructionData, createMigrateInstructionData, createResizeInstructionData, createSetMetadataInstructionData, createInitializeShardInstructionData, createIncrementShardInstructionData, createAggregateInstructionData, findShardAddress, createInitializeHistoryInstructionData, findHistoryAddress, createSetCallerGuardInstructionData, createSetExactlyOnceInstructionData, createSetFreezeAuthorityInstructionData, createEmergencyHaltInstructionData, createEmergencyResumeInstructionData, createSetThresholdsInstructionData, createWithMemoInstructionData, createWithOpIdInstructionData, MEMO_PROGRAM_ID, decodeReturnData, createViewInstructionData, createSetCallbackInstructionData, createClearCallbackInstructionData, findCallbackAddress, createAddOperatorInstructionData, createRemoveOperatorInstructionData, findOperatorsAddress, createSetHooksInstructionData, createClearHooksInstructionData, findHooksAddress, createSetFeeInstructionData, TOKEN_PROGRAM_ID, createSetLamportFeeInstructionData, createWithdrawFeesInstructionData, findFeeVaultAddress, createSetTokenGateInstructionData, createClearTokenGateInstructionData, findTokenGateAddress, createSetNftAuthorityInstructionData, createSetGovernanceAuthorityInstructionData, createSetBurnMintInstructionData, findBurnAddress, createSetRewardsInstructionData, createClearRewardsInstructionData, findRewardsAddress, findAssociatedTokenAddress, ASSOCIATED_TOKEN_PROGRAM_ID, createInitializeEscrowInstructionData, createDepositEscrowInstructionData, createSettleInstructionData, findEscrowAddress, findEscrowDepositAddress, createRequirePermitsInstructionData, createClearPermitsInstructionData, createPermitMessage, findPermitsAddress, createSetCooldownInstructionData, createClearCooldownInstructionData, createSetEpochCapInstructionData, createSetEpochResetInstructionData, createSetDecayInstructionData, createClearDecayInstructionData, findDecayAddress, createSetCrankRewardInstructionData, createCrankInstructionData, createLockInstructionData, createSetExpiryInstructionData, createSetEmaInstructionData, EMA_SCALE, timeWeightedAverage, findCooldownAddress, findCooldownStampAddress, counterViewSchema, eventsOf, decodeEvent, EVENT_SCHEMA_VERSION, toAnchorEvent, anchorEventDiscriminator, Operation, Direction, counterAuthorityFilters, counterErrorOf, CounterError, OverflowMode, CounterKind, CounterFlag, hasFlag, DecayUnit, LockStatus } from "./instruction";
 
 /**
  * TypeScript representation of the Counter account data structure
  * This mirrors the Counter struct in our Rust program
  */
 class CounterAccount {
-    count = 0;
+    discriminator = new Uint8Array(8);
+    version = 14;
+    overflow_mode = OverflowMode.Checked;
+    kind = CounterKind.Unsigned;
+    paused = false;
+    step = 0;
+    authority = new Uint8Array(32);
+    count: bigint | number = 0; // u64 on chain; borsh decodes it as a bigint
+    min: bigint | number = 0; // Raw bounds, stored like count
+    max: bigint | number = 2n ** 64n - 1n;
+    pending_authority = new Uint8Array(32);
+    delegate = new Uint8Array(32);
+    allowance: bigint | number = 0;
+    modulus: bigint | number = 0;
+    total_increments: bigint | number = 0;
+    total_decrements: bigint | number = 0;
+    last_updated_slot: bigint | number = 0;
+    high_water: bigint | number = 0; // Raw extremes, stored like count
+    low_water: bigint | number = 0;
+    last_updated_ts: bigint | number = 0; // i64 unix timestamp
+    thresholds: (bigint | number)[] = [0, 0, 0, 0]; // Raw, stored like count; the first threshold_count are set
+    epoch: bigint | number = 0; // Epoch epoch_change was tallied in
+    epoch_change: bigint | number = 0; // How far changes moved the count in epoch
+    cumulative: bigint | number = 0; // i128 sum of each value times the seconds it was held
+    locked_until: bigint | number = 0; // Changes fail before this slot
+    expires_at: bigint | number = 0; // Unix timestamp from which changes fail for good
+    ema: bigint | number = 0; // i128 moving average of the value, scaled by EMA_SCALE
+    ema_alpha: bigint | number = 0; // Weight of each new value in the average, in basis points
+    next_crank_epoch: bigint | number = 0; // First epoch in which a crank may be rewarded again
+    flags = 0; // Modes switched on for the counter, one bit per CounterFlag
+    freeze_authority = new Uint8Array(32);
+    threshold_count = 0;
+    reserved = new Uint8Array(13);
 
-    constructor({count}: {count: number}) {
+    constructor({count, authority}: {count: bigint | number, authority?: Uint8Array}) {
         this.count = count;
+        if (authority) {
+            this.authority = authority;
+        }
     }
 }
 
 // Define the Borsh schema for serializing/deserializing counter data
-// This must match the Rust Counter struct exactly
-const schema: borsh.Schema = { struct: { count: 'u32'}};
+// This must match the Rust Counter struct exactly; its fields are laid out
+// without padding, so the Borsh encoding is the raw account data
+const schema: borsh.Schema = {
+    struct: {
+        discriminator: { array: { type: 'u8', len: 8 } },
+        version: 'u8',
+        overflow_mode: 'u8',
+        kind: 'u8',
+        paused: 'bool',
+        step: 'u32',
+        authority: { array: { type: 'u8', len: 32 } },
+        count: 'u64',
+        min: 'u64',
+        max: 'u64',
+        pending_authority: { array: { type: 'u8', len: 32 } },
+        delegate: { array: { type: 'u8', len: 32 } },
+        allowance: 'u64',
+        modulus: 'u64',
+        total_increments: 'u64',
+        total_decrements: 'u64',
+        last_updated_slot: 'u64',
+        high_water: 'u64',
+        low_water: 'u64',
+        last_updated_ts: 'i64',
+        thresholds: { array: { type: 'u64', len: 4 } },
+        epoch: 'u64',
+        epoch_change: 'u64',
+        cumulative: 'i128',
+        locked_until: 'u64',
+        expires_at: 'i64',
+        ema: 'i128',
+        ema_alpha: 'u64',
+        next_crank_epoch: 'u64',
+        flags: 'u16',
+        freeze_authority: { array: { type: 'u8', len: 32 } },
+        threshold_count: 'u8',
+        reserved: { array: { type: 'u8', len: 13 } },
+    },
+};
+
+// Metadata stored after the counter by SetMetadata
+const metadataSchema: borsh.Schema = {
+    struct: { name: 'string', description: 'string' },
+};
 
 // Calculate the size needed for a counter account by serializing a sample
 // This ensures we allocate enough space when creating the account on Solana
@@ -44,6 +123,10 @@ console.log(borsh.serialize(schema, counter));
 // The deployed program's public key - this is where our counter program lives on Solana
 const programId = new PublicKey("CQQP6NYLvbwwZd9hssQM5p45tumGQGGc5m7wGBiXHpgU");
 
+// Every instruction that changes a counter's value reads the program config
+const [configAddress] = findConfigAddress(programId);
+const configKey = { pubkey: configAddress, isSigner: false, isWritable: false };
+
 // Generate keypairs for testing
 let adminAccount = Keypair.generate();  // Account that will fund operations
 let dataAccount = Keypair.generate();   // Account that will store counter data
@@ -52,6 +135,44 @@ let dataAccount = Keypair.generate();   // Account that will store counter data
 // The RPC endpoint should match your local solana-test-validator
 const connection = new Connection("http://127.0.0.1:8899", "confirmed");
 
+type AccountKey = { pubkey: PublicKey, isSigner: boolean, isWritable: boolean };
+
+// Account metas shared by the tests; the admin signs as the authority of the
+// counters it creates and pays for them
+const readonly = (pubkey: PublicKey): AccountKey => ({ pubkey, isSigner: false, isWritable: false });
+const writable = (pubkey: PublicKey): AccountKey => ({ pubkey, isSigner: false, isWritable: true });
+const authorityKey: AccountKey = { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false };
+const payerKey: AccountKey = { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true };
+const systemKey = readonly(SystemProgram.programId);
+
+// Send `instructions` in one transaction, by default signed and paid for by the admin
+async function sendInstructions(instructions: TransactionInstruction[], signers: Keypair[] = [adminAccount]) {
+    const txHash = await connection.sendTransaction(new Transaction().add(...instructions), signers);
+    await connection.confirmTransaction(txHash);
+    return txHash;
+}
+
+// Send one instruction of the counter program
+const send = (data: Buffer, keys: AccountKey[], signers: Keypair[] = [adminAccount]) =>
+    sendInstructions([new TransactionInstruction({ programId, keys, data })], signers);
+
+// The log messages of a confirmed transaction
+async function logsOf(txHash: string) {
+    const tx = await connection.getTransaction(txHash, { commitment: "confirmed", maxSupportedTransactionVersion: 0 });
+    return tx!.meta!.logMessages!;
+}
+
+const stateOf = async (address: PublicKey) =>
+    borsh.deserialize(schema, (await connection.getAccountInfo(address))!.data) as CounterAccount;
+
+// Create the admin's counter under `seed`
+async function createAdminCounter(seed: string) {
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const counterKey = writable(counterAddress);
+    await send(createInitializePdaInstructionData(seed), [counterKey, payerKey, systemKey]);
+    return { counterAddress, counterKey };
+}
+
 /**
  * Test to set up accounts needed for counter program testing
  * This test demonstrates the basic Solana account creation workflow
@@ -70,22 +191,21 @@ test("account setup", async () => {
     }
     console.log("Airdrop completed", data?.lamports / LAMPORTS_PER_SOL, "SOL");
 
-    // Calculate minimum lamports needed for rent exemption
-    // Solana requires accounts to maintain a minimum balance to avoid being deleted
-    const lamports = await connection.getMinimumBalanceForRentExemption(GREETING_SIZE);
-
-    // Create instruction to create a new account for storing counter data
-    // This account will be owned by our counter program
-    const createCounterAccountIx = SystemProgram.createAccount({
-        fromPubkey: adminAccount.publicKey,    // Account paying for creation
-        lamports,                              // Rent-exempt balance
-        newAccountPubkey: dataAccount.publicKey, // New account's address
-        programId: programId,                  // Program that will own this account
-        space: GREETING_SIZE,                  // Space needed to store counter data
+    // Ask the counter program to create the account for us
+    // The program allocates the space, funds rent from the admin account via a
+    // CPI to the system program and writes an initial count of zero
+    const initializeIx = new TransactionInstruction({
+        programId,
+        keys: [
+            { pubkey: dataAccount.publicKey, isSigner: true, isWritable: true },  // New counter account
+            { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true }, // Payer for rent
+            { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        ],
+        data: createInitializeInstructionData(),
     });
 
-    // Create and send transaction to create the counter account
-    const tx = new Transaction().add(createCounterAccountIx);
+    // Create and send transaction to initialize the counter account
+    const tx = new Transaction().add(initializeIx);
     const txHash = await connection.sendTransaction(tx, [adminAccount, dataAccount]);
     await connection.confirmTransaction(txHash);
     console.log("Counter account created with transaction hash:", txHash, dataAccount.publicKey.toBase58());
@@ -99,5 +219,2738 @@ test("account setup", async () => {
         throw new Error("Failed to deserialize counter account");
     }
     console.log("Counter account initialized with count:", counter.count);
-    expect(counter.count).toBe(0); // Initial count should be 0
-});
\ No newline at end of file
+    expect(Buffer.from(counter.discriminator).toString()).toBe("counter\0"); // Tag identifying counter accounts
+    expect(Number(counter.count)).toBe(0); // Initial count should be 0
+    // The payer becomes the counter's authority
+    expect(new PublicKey(counter.authority).equals(adminAccount.publicKey)).toBe(true);
+});
+/**
+ * Helper to send a single counter instruction and return the decoded count afterwards
+ */
+async function sendCounterInstruction(data: Buffer, signedByAuthority = false): Promise<number> {
+    const keys = [{ pubkey: dataAccount.publicKey, isSigner: false, isWritable: true }];
+    if (signedByAuthority) {
+        // Decrements must be signed by the counter authority
+        keys.push({ pubkey: adminAccount.publicKey, isSigner: true, isWritable: false });
+    }
+    keys.push(configKey);
+    const ix = new TransactionInstruction({ programId, keys, data });
+    const txHash = await connection.sendTransaction(new Transaction().add(ix), [adminAccount]);
+    await connection.confirmTransaction(txHash);
+
+    const info = await connection.getAccountInfo(dataAccount.publicKey);
+    if (!info) {
+        throw new Error("Failed to get counter account info");
+    }
+    return Number((borsh.deserialize(schema, info.data) as CounterAccount).count);
+}
+
+test("increment and decrement", async () => {
+    expect(await sendCounterInstruction(createIncrementInstructionData(5))).toBe(5);
+    expect(await sendCounterInstruction(createDecrementInstructionData(2), true)).toBe(3);
+});
+
+test("decrement below zero is rejected", async () => {
+    // The counter holds 3 after the previous test, so this would underflow
+    // and must fail with CounterError::Underflow (custom error 1)
+    await expect(sendCounterInstruction(createDecrementInstructionData(10), true)).rejects.toThrow();
+});
+
+/**
+ * Build an authority-gated instruction (Reset, Set, ...) signed by the given authority
+ */
+function authorityInstruction(authority: PublicKey, data: Buffer) {
+    return new TransactionInstruction({
+        programId,
+        keys: [
+            { pubkey: dataAccount.publicKey, isSigner: false, isWritable: true },
+            { pubkey: authority, isSigner: true, isWritable: false },
+        ],
+        data,
+    });
+}
+
+/**
+ * Build an authority-gated instruction that changes the count (Reset, Set, ...),
+ * which also takes the program config
+ */
+function mutationInstruction(authority: PublicKey, data: Buffer) {
+    const ix = authorityInstruction(authority, data);
+    ix.keys.push(configKey);
+    return ix;
+}
+
+function resetInstruction(authority: PublicKey) {
+    return mutationInstruction(authority, createResetInstructionData());
+}
+
+test("reset requires the counter authority", async () => {
+    // A random keypair is not the authority, so the program must refuse
+    const stranger = Keypair.generate();
+    const tx = new Transaction().add(resetInstruction(stranger.publicKey));
+    tx.feePayer = adminAccount.publicKey;
+    await expect(connection.sendTransaction(tx, [adminAccount, stranger])).rejects.toThrow();
+
+    // The admin created the counter and is allowed to reset it
+    const txHash = await connection.sendTransaction(
+        new Transaction().add(resetInstruction(adminAccount.publicKey)),
+        [adminAccount],
+    );
+    await connection.confirmTransaction(txHash);
+    const info = await connection.getAccountInfo(dataAccount.publicKey);
+    expect(Number((borsh.deserialize(schema, info!.data) as CounterAccount).count)).toBe(0);
+});
+
+test("set overwrites the count", async () => {
+    const txHash = await connection.sendTransaction(
+        new Transaction().add(mutationInstruction(adminAccount.publicKey, createSetInstructionData(42))),
+        [adminAccount],
+    );
+    await connection.confirmTransaction(txHash);
+    const info = await connection.getAccountInfo(dataAccount.publicKey);
+    expect(Number((borsh.deserialize(schema, info!.data) as CounterAccount).count)).toBe(42);
+});
+
+test("decrement requires the counter authority", async () => {
+    const stranger = Keypair.generate();
+    const ix = new TransactionInstruction({
+        programId,
+        keys: [
+            { pubkey: dataAccount.publicKey, isSigner: false, isWritable: true },
+            { pubkey: stranger.publicKey, isSigner: true, isWritable: false },
+            configKey,
+        ],
+        data: createDecrementInstructionData(1),
+    });
+    const tx = new Transaction().add(ix);
+    tx.feePayer = adminAccount.publicKey;
+    await expect(connection.sendTransaction(tx, [adminAccount, stranger])).rejects.toThrow();
+});
+
+test("authority transfer is two-step", async () => {
+    const newAuthority = Keypair.generate();
+
+    // Propose the transfer; the admin remains the authority until it is accepted
+    let txHash = await connection.sendTransaction(
+        new Transaction().add(authorityInstruction(adminAccount.publicKey, createTransferAuthorityInstructionData(newAuthority.publicKey))),
+        [adminAccount],
+    );
+    await connection.confirmTransaction(txHash);
+
+    // The proposed key accepts
+    const acceptTx = new Transaction().add(authorityInstruction(newAuthority.publicKey, createAcceptAuthorityInstructionData()));
+    acceptTx.feePayer = adminAccount.publicKey;
+    txHash = await connection.sendTransaction(acceptTx, [adminAccount, newAuthority]);
+    await connection.confirmTransaction(txHash);
+
+    let info = await connection.getAccountInfo(dataAccount.publicKey);
+    let state = borsh.deserialize(schema, info!.data) as CounterAccount;
+    expect(new PublicKey(state.authority).equals(newAuthority.publicKey)).toBe(true);
+
+    // Hand the counter back so later tests can keep using the admin key
+    const backTx = new Transaction().add(authorityInstruction(newAuthority.publicKey, createTransferAuthorityInstructionData(adminAccount.publicKey)));
+    backTx.feePayer = adminAccount.publicKey;
+    txHash = await connection.sendTransaction(backTx, [adminAccount, newAuthority]);
+    await connection.confirmTransaction(txHash);
+    txHash = await connection.sendTransaction(
+        new Transaction().add(authorityInstruction(adminAccount.publicKey, createAcceptAuthorityInstructionData())),
+        [adminAccount],
+    );
+    await connection.confirmTransaction(txHash);
+
+    info = await connection.getAccountInfo(dataAccount.publicKey);
+    state = borsh.deserialize(schema, info!.data) as CounterAccount;
+    expect(new PublicKey(state.authority).equals(adminAccount.publicKey)).toBe(true);
+});
+
+test("close returns rent to the destination", async () => {
+    // Closing is destructive, so use a dedicated counter for it
+    const closable = Keypair.generate();
+    let txHash = await connection.sendTransaction(
+        new Transaction().add(new TransactionInstruction({
+            programId,
+            keys: [
+                { pubkey: closable.publicKey, isSigner: true, isWritable: true },
+                { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+                { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+            ],
+            data: createInitializeInstructionData(),
+        })),
+        [adminAccount, closable],
+    );
+    await connection.confirmTransaction(txHash);
+
+    const destination = Keypair.generate();
+    txHash = await connection.sendTransaction(
+        new Transaction().add(new TransactionInstruction({
+            programId,
+            keys: [
+                { pubkey: closable.publicKey, isSigner: false, isWritable: true },
+                { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+                { pubkey: destination.publicKey, isSigner: false, isWritable: true },
+            ],
+            data: createCloseInstructionData(),
+        })),
+        [adminAccount],
+    );
+    await connection.confirmTransaction(txHash);
+
+    expect(await connection.getAccountInfo(closable.publicKey)).toBeNull();
+    expect(await connection.getBalance(destination.publicKey)).toBeGreaterThan(0);
+});
+
+test("accounts not owned by the program are rejected", async () => {
+    // The admin wallet is owned by the system program, not the counter program
+    const ix = new TransactionInstruction({
+        programId,
+        keys: [{ pubkey: adminAccount.publicKey, isSigner: false, isWritable: true }, configKey],
+        data: createIncrementInstructionData(1),
+    });
+    await expect(connection.sendTransaction(new Transaction().add(ix), [adminAccount])).rejects.toThrow();
+});
+
+test("surplus accounts are rejected", async () => {
+    // Increment only takes the counter and config accounts, and optionally a
+    // writable history account
+    const ix = new TransactionInstruction({
+        programId,
+        keys: [
+            { pubkey: dataAccount.publicKey, isSigner: false, isWritable: true },
+            configKey,
+            { pubkey: adminAccount.publicKey, isSigner: false, isWritable: false },
+        ],
+        data: createIncrementInstructionData(1),
+    });
+    await expect(connection.sendTransaction(new Transaction().add(ix), [adminAccount])).rejects.toThrow();
+});
+
+test("PDA counters are derived from the authority and a seed", async () => {
+    const seed = "page-views";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+
+    // No keypair for the counter is needed; the program signs for the PDA
+    const txHash = await connection.sendTransaction(
+        new Transaction().add(new TransactionInstruction({
+            programId,
+            keys: [
+                { pubkey: counterAddress, isSigner: false, isWritable: true },
+                { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+                { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+            ],
+            data: createInitializePdaInstructionData(seed),
+        })),
+        [adminAccount],
+    );
+    await connection.confirmTransaction(txHash);
+
+    const info = await connection.getAccountInfo(counterAddress);
+    expect(info?.owner.equals(programId)).toBe(true);
+    const state = borsh.deserialize(schema, info!.data) as CounterAccount;
+    expect(new PublicKey(state.authority).equals(adminAccount.publicKey)).toBe(true);
+});
+
+test("multiply and divide", async () => {
+    await sendCounterInstruction(createSetInstructionData(6), true);
+    expect(await sendCounterInstruction(createMultiplyInstructionData(7), true)).toBe(42);
+    expect(await sendCounterInstruction(createDivideInstructionData(4), true)).toBe(10);
+    // Dividing by zero fails with CounterError::DivisionByZero instead of panicking
+    await expect(sendCounterInstruction(createDivideInstructionData(0), true)).rejects.toThrow();
+});
+
+test("compare-and-swap only writes on a match", async () => {
+    await sendCounterInstruction(createSetInstructionData(7), true);
+    // A stale expectation must be rejected with CounterError::ValueMismatch
+    await expect(sendCounterInstruction(createCompareAndSwapInstructionData(8, 100), true)).rejects.toThrow();
+    expect(await sendCounterInstruction(createCompareAndSwapInstructionData(7, 100), true)).toBe(100);
+});
+
+test("batch applies every operation or none", async () => {
+    await sendCounterInstruction(createSetInstructionData(0), true);
+    expect(await sendCounterInstruction(createBatchInstructionData([{ Increment: 10 }, { Decrement: 3 }, { Increment: 1 }]), true)).toBe(8);
+
+    // The second step underflows, so the first one must not be applied either
+    await expect(sendCounterInstruction(createBatchInstructionData([{ Increment: 1 }, { Decrement: 100 }]), true)).rejects.toThrow();
+    expect(await sendCounterInstruction(createBatchInstructionData([{ Increment: 1 }]))).toBe(9);
+});
+
+test("one-byte increment", async () => {
+    await sendCounterInstruction(createSetInstructionData(41), true);
+    expect(createIncrementByOneInstructionData().length).toBe(1);
+    expect(await sendCounterInstruction(createIncrementByOneInstructionData())).toBe(42);
+});
+
+test("saturating counters clamp instead of failing", async () => {
+    const seed = "saturating";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const counterKeys = [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+        configKey,
+    ];
+
+    await send(createInitializePdaInstructionData(seed, OverflowMode.Saturating), [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+    await send(createDecrementInstructionData(5), counterKeys);
+
+    const info = await connection.getAccountInfo(counterAddress);
+    const state = borsh.deserialize(schema, info!.data) as CounterAccount;
+    expect(state.overflow_mode).toBe(OverflowMode.Saturating);
+    expect(Number(state.count)).toBe(0);
+});
+
+test("signed counters go below zero", async () => {
+    const seed = "balance";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    let txHash = await connection.sendTransaction(
+        new Transaction().add(new TransactionInstruction({
+            programId,
+            keys: [
+                { pubkey: counterAddress, isSigner: false, isWritable: true },
+                { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+                { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+            ],
+            data: createInitializePdaInstructionData(seed, OverflowMode.Checked, CounterKind.Signed),
+        })),
+        [adminAccount],
+    );
+    await connection.confirmTransaction(txHash);
+
+    txHash = await connection.sendTransaction(
+        new Transaction().add(new TransactionInstruction({
+            programId,
+            keys: [
+                { pubkey: counterAddress, isSigner: false, isWritable: true },
+                { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+                configKey,
+            ],
+            data: createDecrementInstructionData(25),
+        })),
+        [adminAccount],
+    );
+    await connection.confirmTransaction(txHash);
+
+    const info = await connection.getAccountInfo(counterAddress);
+    const state = borsh.deserialize(schema, info!.data) as CounterAccount;
+    expect(state.kind).toBe(CounterKind.Signed);
+    // Signed counters store their value as two's complement in the u64 field
+    expect(BigInt.asIntN(64, BigInt(state.count))).toBe(-25n);
+});
+
+test("counters hold values beyond u32", async () => {
+    const big = 2n ** 40n;
+    expect(BigInt(await sendCounterInstruction(createSetInstructionData(big), true))).toBe(big);
+    expect(BigInt(await sendCounterInstruction(createIncrementInstructionData(1), false))).toBe(big + 1n);
+});
+
+test("bounded counters reject values outside their range", async () => {
+    const seed = "bounded";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const counterKeys = [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+        configKey,
+    ];
+
+    await send(createInitializePdaInstructionData(seed, OverflowMode.Checked, CounterKind.Signed, { min: -10, max: 10 }), [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+    await send(createIncrementInstructionData(10), counterKeys);
+    await expect(send(createIncrementInstructionData(1), counterKeys)).rejects.toThrow();
+    await expect(send(createDecrementInstructionData(21), counterKeys)).rejects.toThrow();
+
+    const info = await connection.getAccountInfo(counterAddress);
+    const state = borsh.deserialize(schema, info!.data) as CounterAccount;
+    expect(Number(state.count)).toBe(10);
+    expect(BigInt.asIntN(64, BigInt(state.min))).toBe(-10n);
+    expect(Number(state.max)).toBe(10);
+});
+
+test("stepped counters only accept multiples of the step", async () => {
+    const seed = "tickets";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const counterKeys = [{ pubkey: counterAddress, isSigner: false, isWritable: true }, configKey];
+
+    await send(createInitializePdaInstructionData(seed, OverflowMode.Checked, CounterKind.Unsigned, { step: 10 }), [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+    await send(createIncrementInstructionData(30), counterKeys);
+    await expect(send(createIncrementInstructionData(5), counterKeys)).rejects.toThrow();
+
+    const info = await connection.getAccountInfo(counterAddress);
+    const state = borsh.deserialize(schema, info!.data) as CounterAccount;
+    expect(state.step).toBe(10);
+    expect(Number(state.count)).toBe(30);
+});
+
+test("paused counters reject changes until unpaused", async () => {
+    const setPaused = async (data: Buffer) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(authorityInstruction(adminAccount.publicKey, data)),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+
+    const before = await sendCounterInstruction(createIncrementInstructionData(1), false);
+    await setPaused(createPauseInstructionData());
+    await expect(sendCounterInstruction(createIncrementInstructionData(1), false)).rejects.toThrow();
+    await expect(sendCounterInstruction(createSetInstructionData(7), true)).rejects.toThrow();
+
+    await setPaused(createUnpauseInstructionData());
+    expect(await sendCounterInstruction(createIncrementInstructionData(1), false)).toBe(before + 1);
+});
+
+test("the config admin can pause the whole program and cap amounts", async () => {
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const configAdminKeys = [
+        { pubkey: configAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+    ];
+
+    await send(createInitializeConfigInstructionData(100), [
+        { pubkey: configAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+    // Amounts above the configured limit fail with CounterError::AmountExceedsLimit
+    await expect(sendCounterInstruction(createIncrementInstructionData(101))).rejects.toThrow();
+    const before = await sendCounterInstruction(createIncrementInstructionData(100));
+
+    await send(createUpdateConfigInstructionData(adminAccount.publicKey, true), configAdminKeys);
+    await expect(sendCounterInstruction(createIncrementInstructionData(1))).rejects.toThrow();
+
+    await send(createUpdateConfigInstructionData(adminAccount.publicKey, false), configAdminKeys);
+    expect(await sendCounterInstruction(createIncrementInstructionData(1))).toBe(before + 1);
+});
+
+test("multisig authorities need m of n signatures", async () => {
+    const multisig = Keypair.generate();
+    const members = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+    const signerKeys = (signers: Keypair[]) =>
+        signers.map((signer) => ({ pubkey: signer.publicKey, isSigner: true, isWritable: false }));
+    const send = async (ix: TransactionInstruction, signers: Keypair[]) => {
+        const txHash = await connection.sendTransaction(new Transaction().add(ix), [adminAccount, ...signers]);
+        await connection.confirmTransaction(txHash);
+    };
+
+    await send(new TransactionInstruction({
+        programId,
+        keys: [
+            { pubkey: multisig.publicKey, isSigner: true, isWritable: true },
+            { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+            { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        ],
+        data: createInitializeMultisigInstructionData(2, members.map((member) => member.publicKey)),
+    }), [multisig]);
+
+    // Hand the counter to the multisig, which accepts with two of its members
+    await send(authorityInstruction(adminAccount.publicKey, createTransferAuthorityInstructionData(multisig.publicKey)), []);
+    const accept = authorityInstruction(multisig.publicKey, createAcceptAuthorityInstructionData());
+    accept.keys[1].isSigner = false;
+    accept.keys.push(...signerKeys(members.slice(0, 2)));
+    await send(accept, members.slice(0, 2));
+
+    // One member alone cannot set the counter; two can
+    const set = (signers: Keypair[]) => {
+        const ix = mutationInstruction(multisig.publicKey, createSetInstructionData(5));
+        ix.keys[1].isSigner = false;
+        ix.keys.push(...signerKeys(signers));
+        return ix;
+    };
+    await expect(send(set(members.slice(0, 1)), members.slice(0, 1))).rejects.toThrow();
+    await send(set(members.slice(1)), members.slice(1));
+    const info = await connection.getAccountInfo(dataAccount.publicKey);
+    expect(Number((borsh.deserialize(schema, info!.data) as CounterAccount).count)).toBe(5);
+
+    // Give the counter back to the admin for any later tests
+    const back = authorityInstruction(multisig.publicKey, createTransferAuthorityInstructionData(adminAccount.publicKey));
+    back.keys[1].isSigner = false;
+    back.keys.push(...signerKeys(members.slice(0, 2)));
+    await send(back, members.slice(0, 2));
+    await send(authorityInstruction(adminAccount.publicKey, createAcceptAuthorityInstructionData()), []);
+});
+
+test("delegates decrement within their allowance", async () => {
+    const bot = Keypair.generate();
+    const send = async (ix: TransactionInstruction, signers: Keypair[] = []) => {
+        const txHash = await connection.sendTransaction(new Transaction().add(ix), [adminAccount, ...signers]);
+        await connection.confirmTransaction(txHash);
+    };
+
+    await sendCounterInstruction(createSetInstructionData(100), true);
+    await send(authorityInstruction(adminAccount.publicKey, createApproveInstructionData(bot.publicKey, 10)));
+
+    await send(mutationInstruction(bot.publicKey, createDecrementInstructionData(6)), [bot]);
+    // Only 4 of the allowance is left
+    await expect(send(mutationInstruction(bot.publicKey, createDecrementInstructionData(5)), [bot])).rejects.toThrow();
+
+    let info = await connection.getAccountInfo(dataAccount.publicKey);
+    let state = borsh.deserialize(schema, info!.data) as CounterAccount;
+    expect(Number(state.count)).toBe(94);
+    expect(Number(state.allowance)).toBe(4);
+
+    await send(authorityInstruction(adminAccount.publicKey, createRevokeInstructionData()));
+    await expect(send(mutationInstruction(bot.publicKey, createDecrementInstructionData(1)), [bot])).rejects.toThrow();
+    info = await connection.getAccountInfo(dataAccount.publicKey);
+    state = borsh.deserialize(schema, info!.data) as CounterAccount;
+    expect(new PublicKey(state.delegate).equals(PublicKey.default)).toBe(true);
+});
+
+test("merge moves a counter's value into another", async () => {
+    const seed = "campaign-a";
+    const [source] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const admin = { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false };
+
+    await send(createInitializePdaInstructionData(seed), [
+        { pubkey: source, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+    await send(createIncrementInstructionData(25), [{ pubkey: source, isSigner: false, isWritable: true }, configKey]);
+    const before = await sendCounterInstruction(createSetInstructionData(10), true);
+
+    await send(createMergeInstructionData(), [
+        { pubkey: dataAccount.publicKey, isSigner: false, isWritable: true },
+        admin,
+        { pubkey: source, isSigner: false, isWritable: true },
+        admin,
+        configKey,
+    ]);
+
+    const destinationInfo = await connection.getAccountInfo(dataAccount.publicKey);
+    expect(Number((borsh.deserialize(schema, destinationInfo!.data) as CounterAccount).count)).toBe(before + 25);
+    const sourceInfo = await connection.getAccountInfo(source);
+    expect(Number((borsh.deserialize(schema, sourceInfo!.data) as CounterAccount).count)).toBe(0);
+});
+
+test("clone snapshots a counter into a new account", async () => {
+    const snapshot = Keypair.generate();
+    const value = await sendCounterInstruction(createSetInstructionData(1234), true);
+
+    const txHash = await connection.sendTransaction(
+        new Transaction().add(new TransactionInstruction({
+            programId,
+            keys: [
+                { pubkey: dataAccount.publicKey, isSigner: false, isWritable: false },
+                { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+                { pubkey: snapshot.publicKey, isSigner: true, isWritable: true },
+                { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+                { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+            ],
+            data: createCloneInstructionData(),
+        })),
+        [adminAccount, snapshot],
+    );
+    await connection.confirmTransaction(txHash);
+
+    // Later changes to the live counter leave the snapshot alone
+    await sendCounterInstruction(createIncrementInstructionData(1));
+    const info = await connection.getAccountInfo(snapshot.publicKey);
+    const state = borsh.deserialize(schema, info!.data) as CounterAccount;
+    expect(Number(state.count)).toBe(value);
+    expect(new PublicKey(state.authority).equals(adminAccount.publicKey)).toBe(true);
+});
+
+// Mirrors the Rust VoteCounter struct
+const votesSchema: borsh.Schema = {
+    struct: {
+        discriminator: { array: { type: 'u8', len: 8 } },
+        up: 'u64',
+        down: 'u64',
+    },
+};
+
+test("vote accounts tally up and down votes", async () => {
+    const votes = Keypair.generate();
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[], signers: Keypair[] = []) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount, ...signers],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const voteKeys = [{ pubkey: votes.publicKey, isSigner: false, isWritable: true }, configKey];
+
+    await send(createInitializeVotesInstructionData(), [
+        { pubkey: votes.publicKey, isSigner: true, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ], [votes]);
+    await send(createVoteUpInstructionData(3), voteKeys);
+    await send(createVoteDownInstructionData(5), voteKeys);
+
+    const info = await connection.getAccountInfo(votes.publicKey);
+    const tally = borsh.deserialize(votesSchema, info!.data) as { up: bigint, down: bigint };
+    expect(Number(tally.up)).toBe(3);
+    expect(Number(tally.down)).toBe(5);
+    expect(Number(tally.up - tally.down)).toBe(-2);
+});
+
+// Mirrors the Rust CounterMap struct
+const mapSchema: borsh.Schema = {
+    struct: {
+        discriminator: { array: { type: 'u8', len: 8 } },
+        authority: { array: { type: 'u8', len: 32 } },
+        entries: { array: { type: { struct: { name: { array: { type: 'u8', len: 32 } }, count: 'u64' } } } },
+    },
+};
+
+test("counter maps hold named counters in one account", async () => {
+    const map = Keypair.generate();
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[], signers: Keypair[] = []) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount, ...signers],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const mapKey = { pubkey: map.publicKey, isSigner: false, isWritable: true };
+    const admin = { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false };
+    const payer = { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true };
+    const system = { pubkey: SystemProgram.programId, isSigner: false, isWritable: false };
+
+    await send(createInitializeMapInstructionData(), [{ ...mapKey, isSigner: true }, payer, system], [map]);
+    await send(createAddMapEntryInstructionData("signups"), [mapKey, admin, payer, system]);
+    await send(createAddMapEntryInstructionData("logins"), [mapKey, admin, payer, system]);
+    // Names are unique within a map
+    await expect(send(createAddMapEntryInstructionData("logins"), [mapKey, admin, payer, system])).rejects.toThrow();
+
+    await send(createIncrementMapEntryInstructionData("logins", 7), [mapKey, configKey]);
+    await send(createDecrementMapEntryInstructionData("logins", 2), [mapKey, admin, configKey]);
+    await send(createRemoveMapEntryInstructionData("signups"), [mapKey, admin, payer]);
+
+    const info = await connection.getAccountInfo(map.publicKey);
+    const state = borsh.deserialize(mapSchema, info!.data) as { entries: { name: Uint8Array, count: bigint }[] };
+    expect(state.entries.length).toBe(1);
+    expect(Buffer.from(state.entries[0].name).toString().replace(/\0+$/, "")).toBe("logins");
+    expect(Number(state.entries[0].count)).toBe(5);
+});
+
+test("malformed instruction data is rejected with specific errors", async () => {
+    const send = (data: Buffer) => sendCounterInstruction(data);
+    // CounterError::UnknownInstruction
+    await expect(send(Buffer.from([200]))).rejects.toThrow("custom program error: 0x1d");
+    // Trailing bytes, even after the single-byte IncrementByOne
+    await expect(send(Buffer.concat([createIncrementInstructionData(1), Buffer.from([0xff])]))).rejects.toThrow("invalid instruction data");
+    await expect(send(Buffer.concat([createIncrementByOneInstructionData(), Buffer.from([0xff])]))).rejects.toThrow("invalid instruction data");
+    // CounterError::TruncatedInstruction
+    await expect(send(createIncrementInstructionData(1).subarray(0, 3))).rejects.toThrow("custom program error: 0x1f");
+});
+
+test("modular counters wrap at their modulus", async () => {
+    const seed = "minutes";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const count = async () => {
+        const info = await connection.getAccountInfo(counterAddress);
+        return Number((borsh.deserialize(schema, info!.data) as CounterAccount).count);
+    };
+    const openKeys = [{ pubkey: counterAddress, isSigner: false, isWritable: true }, configKey];
+    const authorityKeys = [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+        configKey,
+    ];
+
+    await send(createInitializePdaInstructionData(seed, OverflowMode.Checked, CounterKind.Unsigned, { modulus: 60 }), [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+
+    // 0 - 1 wraps to the top of the range
+    await send(createDecrementInstructionData(1), authorityKeys);
+    expect(await count()).toBe(59);
+    // 59 + 1 wraps back to zero
+    await send(createIncrementInstructionData(1), openKeys);
+    expect(await count()).toBe(0);
+    // Amounts larger than the modulus wrap as often as needed
+    await send(createIncrementInstructionData(125), openKeys);
+    expect(await count()).toBe(5);
+});
+
+test("migrating a current counter leaves it unchanged", async () => {
+    const before = await connection.getAccountInfo(dataAccount.publicKey);
+    const txHash = await connection.sendTransaction(
+        new Transaction().add(new TransactionInstruction({
+            programId,
+            keys: [
+                { pubkey: dataAccount.publicKey, isSigner: false, isWritable: true },
+                { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+                { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+            ],
+            data: createMigrateInstructionData(),
+        })),
+        [adminAccount],
+    );
+    await connection.confirmTransaction(txHash);
+
+    const after = await connection.getAccountInfo(dataAccount.publicKey);
+    expect(after!.data.equals(before!.data)).toBe(true);
+    expect((borsh.deserialize(schema, after!.data) as CounterAccount).version).toBe(13);
+});
+
+test("resizing a counter grows it and keeps its state", async () => {
+    const seed = "resizable";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const payerKeys = [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ];
+    const resizeKeys = [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ];
+
+    await send(createInitializePdaInstructionData(seed), payerKeys);
+    await send(createIncrementInstructionData(7), [{ pubkey: counterAddress, isSigner: false, isWritable: true }, configKey]);
+    await send(createResizeInstructionData(GREETING_SIZE + 100), resizeKeys);
+
+    const info = await connection.getAccountInfo(counterAddress);
+    expect(info!.data.length).toBe(GREETING_SIZE + 100);
+    // The counter stays in front, and the new space starts zeroed
+    const state = borsh.deserialize(schema, info!.data.subarray(0, GREETING_SIZE)) as CounterAccount;
+    expect(Number(state.count)).toBe(7);
+    expect(info!.data.subarray(GREETING_SIZE).every((byte) => byte === 0)).toBe(true);
+    expect(info!.lamports).toBe(await connection.getMinimumBalanceForRentExemption(GREETING_SIZE + 100));
+
+    // Shrinking is refused with CounterError::InvalidAccountLength
+    await expect(send(createResizeInstructionData(GREETING_SIZE), resizeKeys)).rejects.toThrow("custom program error: 0x6");
+});
+
+test("counters can be labelled with a name and description", async () => {
+    const seed = "labelled";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const metadataKeys = [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ];
+    const metadata = async () => {
+        const info = await connection.getAccountInfo(counterAddress);
+        const region = info!.data.subarray(GREETING_SIZE);
+        const length = 8 + region.readUInt32LE(0) + region.readUInt32LE(4 + region.readUInt32LE(0));
+        return borsh.deserialize(metadataSchema, region.subarray(0, length)) as { name: string, description: string };
+    };
+
+    await send(createInitializePdaInstructionData(seed), [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+
+    await send(createSetMetadataInstructionData("Page views", "Views of the landing page"), metadataKeys);
+    expect(await metadata()).toEqual({ name: "Page views", description: "Views of the landing page" });
+
+    // Shorter labels replace the old ones without leftovers
+    await send(createSetMetadataInstructionData("Views", ""), metadataKeys);
+    expect(await metadata()).toEqual({ name: "Views", description: "" });
+
+    // CounterError::MetadataTooLong
+    await expect(send(createSetMetadataInstructionData("x".repeat(33), ""), metadataKeys)).rejects.toThrow("custom program error: 0x20");
+});
+
+test("lifetime statistics track increments, decrements and the last update", async () => {
+    const seed = "statistics";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const state = async () => {
+        const info = await connection.getAccountInfo(counterAddress);
+        return borsh.deserialize(schema, info!.data) as CounterAccount;
+    };
+    const openKeys = [{ pubkey: counterAddress, isSigner: false, isWritable: true }, configKey];
+    const authorityKeys = [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+        configKey,
+    ];
+
+    await send(createInitializePdaInstructionData(seed), [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+    expect(Number((await state()).last_updated_slot)).toBe(0);
+
+    await send(createIncrementInstructionData(10), openKeys);
+    await send(createIncrementInstructionData(5), openKeys);
+    await send(createDecrementInstructionData(3), authorityKeys);
+    // A reset changes the value but is neither an increment nor a decrement
+    await send(createResetInstructionData(), authorityKeys);
+
+    const after = await state();
+    expect(Number(after.count)).toBe(0);
+    expect(Number(after.total_increments)).toBe(15);
+    expect(Number(after.total_decrements)).toBe(3);
+    expect(Number(after.last_updated_slot)).toBeGreaterThan(0);
+    expect(Number(after.last_updated_slot)).toBeLessThanOrEqual(await connection.getSlot());
+    // The validator clock may lag wall time a little, but not by a day
+    expect(Math.abs(Number(after.last_updated_ts) - Date.now() / 1000)).toBeLessThan(24 * 60 * 60);
+});
+
+test("high and low water marks record the extremes a counter reached", async () => {
+    const seed = "water-marks";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const openKeys = [{ pubkey: counterAddress, isSigner: false, isWritable: true }, configKey];
+    const authorityKeys = [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+        configKey,
+    ];
+
+    await send(createInitializePdaInstructionData(seed, OverflowMode.Checked, CounterKind.Signed), [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+    await send(createIncrementInstructionData(30), openKeys);
+    await send(createDecrementInstructionData(50), authorityKeys);
+    await send(createIncrementInstructionData(5), openKeys);
+
+    const info = await connection.getAccountInfo(counterAddress);
+    const state = borsh.deserialize(schema, info!.data) as CounterAccount;
+    // Signed counters store their raw bits as two's complement
+    expect(BigInt.asIntN(64, BigInt(state.count))).toBe(-15n);
+    expect(BigInt.asIntN(64, BigInt(state.high_water))).toBe(30n);
+    expect(BigInt.asIntN(64, BigInt(state.low_water))).toBe(-20n);
+});
+
+test("a wallet's counters can be listed with memcmp filters", async () => {
+    const accounts = await connection.getProgramAccounts(programId, {
+        filters: counterAuthorityFilters(adminAccount.publicKey),
+    });
+    const addresses = accounts.map(({ pubkey }) => pubkey.toBase58());
+
+    expect(addresses).toContain(dataAccount.publicKey.toBase58());
+    expect(addresses).toContain(findCounterAddress(programId, adminAccount.publicKey, "labelled")[0].toBase58());
+    for (const { account } of accounts) {
+        const state = borsh.deserialize(schema, account.data.subarray(0, GREETING_SIZE)) as CounterAccount;
+        expect(new PublicKey(state.authority).equals(adminAccount.publicKey)).toBe(true);
+    }
+    // Accounts of other wallets are filtered out on the node
+    expect(await connection.getProgramAccounts(programId, {
+        filters: counterAuthorityFilters(Keypair.generate().publicKey),
+    })).toHaveLength(0);
+});
+
+test("instruction data stays within the published maximum sizes", async () => {
+    // Mirrors max_instruction_len for the variable-length instructions at their limits
+    expect(createSetMetadataInstructionData("n".repeat(32), "d".repeat(200)).length).toBe(241);
+    expect(createBatchInstructionData(Array(32).fill({ Set: 1 })).length).toBe(1 + 4 + 32 * 9);
+    expect(createInitializePdaInstructionData("s".repeat(32), OverflowMode.Checked, CounterKind.Signed, { min: -1, max: 1 }).length).toBe(1 + 4 + 32 + 49);
+
+    // A counter account is funded with exactly the rent-exempt minimum for its size
+    const info = await connection.getAccountInfo(dataAccount.publicKey);
+    expect(info!.lamports).toBe(await connection.getMinimumBalanceForRentExemption(GREETING_SIZE));
+
+    // CounterError::BatchTooLarge, for more than MAX_BATCH_OPS operations
+    await expect(sendCounterInstruction(createBatchInstructionData(Array(33).fill({ Increment: 1 })))).rejects.toThrow("custom program error: 0x21");
+});
+
+// Mirrors the Rust `CounterShard` struct
+const shardSchema: borsh.Schema = {
+    struct: { discriminator: { array: { type: 'u8', len: 8 } }, counter: { array: { type: 'u8', len: 32 } }, index: 'u16', count: 'u64' },
+};
+
+test("sharded increments are aggregated into the counter", async () => {
+    const seed = "sharded";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const shards = [0, 1].map((index) => findShardAddress(programId, counterAddress, index)[0]);
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const shardCount = async (shard: PublicKey) => {
+        const info = await connection.getAccountInfo(shard);
+        return Number((borsh.deserialize(shardSchema, info!.data) as { count: bigint }).count);
+    };
+
+    await send(createInitializePdaInstructionData(seed), [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+    for (const [index, shard] of shards.entries()) {
+        await send(createInitializeShardInstructionData(index), [
+            { pubkey: counterAddress, isSigner: false, isWritable: false },
+            { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+            { pubkey: shard, isSigner: false, isWritable: true },
+            { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+            { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        ]);
+    }
+
+    // Writers only lock their shard; the counter is passed read-only
+    const incrementShard = (shard: PublicKey, amount: number) => send(createIncrementShardInstructionData(amount), [
+        { pubkey: shard, isSigner: false, isWritable: true },
+        { pubkey: counterAddress, isSigner: false, isWritable: false },
+        configKey,
+    ]);
+    await incrementShard(shards[0], 3);
+    await incrementShard(shards[1], 4);
+    await incrementShard(shards[0], 5);
+    expect(await shardCount(shards[0])).toBe(8);
+
+    // Anyone can drain the shards into the counter
+    await send(createAggregateInstructionData(), [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        configKey,
+        ...shards.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })),
+    ]);
+    const info = await connection.getAccountInfo(counterAddress);
+    const state = borsh.deserialize(schema, info!.data) as CounterAccount;
+    expect(Number(state.count)).toBe(12);
+    expect(Number(state.total_increments)).toBe(12);
+    expect(await shardCount(shards[0])).toBe(0);
+    expect(await shardCount(shards[1])).toBe(0);
+
+    // CounterError::InvalidShard, for a shard of another counter
+    await expect(send(createAggregateInstructionData(), [
+        { pubkey: dataAccount.publicKey, isSigner: false, isWritable: true },
+        configKey,
+        { pubkey: shards[0], isSigner: false, isWritable: true },
+    ])).rejects.toThrow("custom program error: 0x22");
+});
+
+// Mirrors the Rust `CounterHistory` struct; entries have an op byte in the
+// order of `HistoryOp`: Increment, Decrement, Multiply, Divide,
+// CompareAndSwap, Reset, Set, Aggregate, Merge
+const historySchema: borsh.Schema = {
+    struct: {
+        discriminator: { array: { type: 'u8', len: 8 } },
+        counter: { array: { type: 'u8', len: 32 } },
+        capacity: 'u16',
+        next: 'u16',
+        entries: { array: { type: { struct: { op: 'u8', amount: 'u64', actor: { array: { type: 'u8', len: 32 } }, slot: 'u64' } } } },
+    },
+};
+
+type HistoryEntry = { op: number, amount: bigint, actor: Uint8Array, slot: bigint };
+
+test("a counter's history keeps its last changes and who made them", async () => {
+    const seed = "audited";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const [historyAddress] = findHistoryAddress(programId, counterAddress);
+    const historyKey = { pubkey: historyAddress, isSigner: false, isWritable: true };
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const authorityKeys = [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+        configKey,
+    ];
+
+    await send(createInitializePdaInstructionData(seed), [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+    await send(createInitializeHistoryInstructionData(3), [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+        historyKey,
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+
+    // An increment may name its actor with a signer after the history account
+    await send(createIncrementInstructionData(5), [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        configKey,
+        historyKey,
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+    ]);
+    await send(createDecrementInstructionData(2), [...authorityKeys, historyKey]);
+    await send(createSetInstructionData(10), [...authorityKeys, historyKey]);
+    // The fourth change overwrites the oldest entry
+    await send(createMultiplyInstructionData(3), [...authorityKeys, historyKey]);
+
+    const info = await connection.getAccountInfo(historyAddress);
+    const history = borsh.deserialize(historySchema, info!.data) as { next: number, entries: HistoryEntry[] };
+    const oldestFirst = [...history.entries.slice(history.next), ...history.entries.slice(0, history.next)];
+    expect(oldestFirst.map(({ op, amount }) => [op, Number(amount)])).toEqual([[1, 2], [6, 10], [2, 3]]);
+    for (const entry of oldestFirst) {
+        expect(new PublicKey(entry.actor).equals(adminAccount.publicKey)).toBe(true);
+        expect(Number(entry.slot)).toBeGreaterThan(0);
+    }
+
+    // A merge records the value it moved, here from a counter without a history
+    const [source] = findCounterAddress(programId, adminAccount.publicKey, "audited-source");
+    await send(createInitializePdaInstructionData("audited-source"), [writable(source), payerKey, systemKey]);
+    await send(createIncrementInstructionData(4), [writable(source), configKey]);
+    await send(createMergeInstructionData(), [authorityKeys[0], authorityKey, writable(source), authorityKey, configKey, historyKey]);
+    const merged = borsh.deserialize(historySchema, (await connection.getAccountInfo(historyAddress))!.data) as { next: number, entries: HistoryEntry[] };
+    const newest = merged.entries[(merged.next + merged.entries.length - 1) % merged.entries.length];
+    expect([newest.op, Number(newest.amount)]).toEqual([8, 4]);
+
+    // CounterError::MissingHistory, once the counter keeps a history
+    await expect(send(createIncrementInstructionData(1), [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        configKey,
+    ])).rejects.toThrow("custom program error: 0x23");
+});
+
+test("custom error codes map back to CounterError variants", async () => {
+    const error = await sendCounterInstruction(createDivideInstructionData(0), true).catch((error) => error);
+    expect(counterErrorOf(error)).toBe(CounterError.DivisionByZero);
+    expect(CounterError[counterErrorOf(error)!]).toBe("DivisionByZero");
+    // The program also logs the error's description
+    expect(String(error.logs ?? error)).toContain("Error: Division by zero");
+});
+
+test("every account the program creates is rent-exempt", async () => {
+    const counterAddress = findCounterAddress(programId, adminAccount.publicKey, "audited")[0];
+    const addresses = [
+        dataAccount.publicKey,
+        counterAddress,
+        findHistoryAddress(programId, counterAddress)[0],
+        findShardAddress(programId, findCounterAddress(programId, adminAccount.publicKey, "sharded")[0], 0)[0],
+    ];
+    for (const address of addresses) {
+        const info = await connection.getAccountInfo(address);
+        expect(info!.lamports).toBeGreaterThanOrEqual(await connection.getMinimumBalanceForRentExemption(info!.data.length));
+    }
+});
+
+test("initializing an existing counter again is rejected", async () => {
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, "audited");
+    const before = await connection.getAccountInfo(counterAddress);
+    const ix = new TransactionInstruction({
+        programId,
+        keys: [
+            { pubkey: counterAddress, isSigner: false, isWritable: true },
+            { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+            { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        ],
+        data: createInitializePdaInstructionData("audited"),
+    });
+    // CounterError::AlreadyInitialized
+    await expect(connection.sendTransaction(new Transaction().add(ix), [adminAccount])).rejects.toThrow("custom program error: 0x26");
+
+    const after = await connection.getAccountInfo(counterAddress);
+    expect(after!.data.equals(before!.data)).toBe(true);
+});
+
+test("account errors name the expected account and its flags", async () => {
+    const logsOf = async (keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const ix = new TransactionInstruction({ programId, keys, data: createDecrementInstructionData(1) });
+        const error = await connection.sendTransaction(new Transaction().add(ix), [adminAccount]).catch((error) => error);
+        return String(error.logs);
+    };
+    const counterKey = { pubkey: dataAccount.publicKey, isSigner: false, isWritable: true };
+
+    expect(await logsOf([counterKey])).toContain("missing account 1 (authority) [signer], 2 (config) []");
+    expect(await logsOf([
+        { ...counterKey, isWritable: false },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+        configKey,
+    ])).toContain("account 0 (counter) " + dataAccount.publicKey.toBase58() + " must be writable, expected [writable]");
+});
+
+test("decrements past zero clamp or fail as chosen at initialization", async () => {
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const create = async (seed: string, clampDecrements: boolean) => {
+        const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+        await send(createInitializePdaInstructionData(seed, OverflowMode.Checked, CounterKind.Unsigned, { clampDecrements }), [
+            { pubkey: counterAddress, isSigner: false, isWritable: true },
+            { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+            { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        ]);
+        await send(createIncrementInstructionData(3), [{ pubkey: counterAddress, isSigner: false, isWritable: true }, configKey]);
+        return counterAddress;
+    };
+    const decrement = (counterAddress: PublicKey, amount: number) => send(createDecrementInstructionData(amount), [
+        { pubkey: counterAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+        configKey,
+    ]);
+
+    const clamped = await create("floor-clamp", true);
+    await decrement(clamped, 10);
+    const info = await connection.getAccountInfo(clamped);
+    expect(Number((borsh.deserialize(schema, info!.data) as CounterAccount).count)).toBe(0);
+
+    // CounterError::Underflow
+    const strict = await create("floor-error", false);
+    await expect(decrement(strict, 10)).rejects.toThrow(/custom program error: 0x1\b/);
+});
+
+// Mirrors the Rust `CallerGuard` struct stored after the config
+const callerGuardSchema: borsh.Schema = {
+    struct: {
+        enabled: 'bool',
+        allowed_programs: { array: { type: { array: { type: 'u8', len: 32 } } } },
+        forbidden_programs: { array: { type: { array: { type: 'u8', len: 32 } } } },
+    },
+};
+
+test("the caller guard allowlists CPI callers without affecting direct calls", async () => {
+    const setGuard = async (enabled: boolean, allowedPrograms: PublicKey[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({
+                programId,
+                keys: [
+                    { pubkey: configAddress, isSigner: false, isWritable: true },
+                    { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+                    { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+                    { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+                ],
+                data: createSetCallerGuardInstructionData(enabled, allowedPrograms),
+            })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const integrator = Keypair.generate().publicKey;
+
+    await setGuard(true, [integrator]);
+    const info = await connection.getAccountInfo(configAddress);
+    // The guard follows the 45-byte config
+    const guard = borsh.deserialize(callerGuardSchema, info!.data.subarray(45, 45 + 1 + 4 + 32 + 4)) as { enabled: boolean, allowed_programs: Uint8Array[] };
+    expect(guard.enabled).toBe(true);
+    expect(new PublicKey(guard.allowed_programs[0]).equals(integrator)).toBe(true);
+
+    // Instructions sent directly are never guarded, with or without the sysvar
+    const before = await sendCounterInstruction(createIncrementInstructionData(1));
+    const ix = new TransactionInstruction({
+        programId,
+        keys: [
+            { pubkey: dataAccount.publicKey, isSigner: false, isWritable: true },
+            configKey,
+            { pubkey: SYSVAR_INSTRUCTIONS_PUBKEY, isSigner: false, isWritable: false },
+        ],
+        data: createIncrementInstructionData(1),
+    });
+    await connection.confirmTransaction(await connection.sendTransaction(new Transaction().add(ix), [adminAccount]));
+    const after = await connection.getAccountInfo(dataAccount.publicKey);
+    expect(Number((borsh.deserialize(schema, after!.data) as CounterAccount).count)).toBe(before + 1);
+
+    // CounterError::CallerNotAllowed for a CPI by any other program, even of
+    // an instruction that does not take the config
+    const ownerProgram = ownerProgramId();
+    const [ownerAddress] = PublicKey.findProgramAddressSync([Buffer.from("owner")], ownerProgram);
+    const [guardedAddress] = findCounterAddress(programId, ownerAddress, "guarded");
+    const create = await connection.sendTransaction(
+        new Transaction().add(new TransactionInstruction({
+            programId: ownerProgram,
+            keys: [
+                { pubkey: guardedAddress, isSigner: false, isWritable: true },
+                { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+                { pubkey: ownerAddress, isSigner: false, isWritable: false },
+                { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+                configKey,
+                { pubkey: programId, isSigner: false, isWritable: false },
+            ],
+            // OwnerInstruction::Create { seed: "guarded" }
+            data: Buffer.from(borsh.serialize({ enum: [{ struct: { Create: { struct: { seed: 'string' } } } }] }, { Create: { seed: "guarded" } })),
+        })),
+        [adminAccount],
+    ).catch((error) => error);
+    expect(counterErrorOf(create)).toBe(CounterError.CallerNotAllowed);
+    expect(await connection.getAccountInfo(guardedAddress)).toBeNull();
+
+    await setGuard(false, []);
+});
+
+test("exactly-once counters and forbidden programs are checked against the whole transaction", async () => {
+    const seed = "raffle";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const counterKey = { pubkey: counterAddress, isSigner: false, isWritable: true };
+    const sysvarKey = { pubkey: SYSVAR_INSTRUCTIONS_PUBKEY, isSigner: false, isWritable: false };
+    const increment = (keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) =>
+        new TransactionInstruction({ programId, keys, data: createIncrementInstructionData(1) });
+    const send = async (...instructions: TransactionInstruction[]) =>
+        connection.confirmTransaction(await connection.sendTransaction(new Transaction().add(...instructions), [adminAccount]));
+
+    await send(
+        new TransactionInstruction({
+            programId,
+            keys: [
+                counterKey,
+                { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+                { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+            ],
+            data: createInitializePdaInstructionData(seed),
+        }),
+        new TransactionInstruction({
+            programId,
+            keys: [counterKey, { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false }],
+            data: createSetExactlyOnceInstructionData(true),
+        }),
+    );
+
+    // CounterError::DuplicateMutation, also when the sysvar is left out
+    await expect(send(increment([counterKey, configKey]))).rejects.toThrow("custom program error: 0x28");
+    await expect(send(
+        increment([counterKey, configKey, sysvarKey]),
+        increment([counterKey, configKey, sysvarKey]),
+    )).rejects.toThrow("custom program error: 0x28");
+    // Other counters in the same transaction do not count
+    await send(
+        increment([counterKey, configKey, sysvarKey]),
+        increment([{ pubkey: dataAccount.publicKey, isSigner: false, isWritable: true }, configKey]),
+    );
+    const info = await connection.getAccountInfo(counterAddress);
+    expect(Number((borsh.deserialize(schema, info!.data) as CounterAccount).count)).toBe(1);
+
+    // CounterError::ForbiddenProgram
+    const setGuard = (forbiddenPrograms: PublicKey[]) => new TransactionInstruction({
+        programId,
+        keys: [
+            { pubkey: configAddress, isSigner: false, isWritable: true },
+            { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+            { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+            { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        ],
+        data: createSetCallerGuardInstructionData(false, [], forbiddenPrograms),
+    });
+    await send(setGuard([SystemProgram.programId]));
+    await expect(send(
+        SystemProgram.transfer({ fromPubkey: adminAccount.publicKey, toPubkey: counterAddress, lamports: 1 }),
+        increment([counterKey, configKey, sysvarKey]),
+    )).rejects.toThrow("custom program error: 0x29");
+    await send(setGuard([]));
+});
+
+test("short and oversized accounts are rejected with their length before being decoded", async () => {
+    const voteWith = async (space: number) => {
+        const account = Keypair.generate();
+        const txHash = await connection.sendTransaction(new Transaction().add(SystemProgram.createAccount({
+            fromPubkey: adminAccount.publicKey,
+            newAccountPubkey: account.publicKey,
+            lamports: await connection.getMinimumBalanceForRentExemption(space),
+            space,
+            programId,
+        })), [adminAccount, account]);
+        await connection.confirmTransaction(txHash);
+        const ix = new TransactionInstruction({
+            programId,
+            keys: [{ pubkey: account.publicKey, isSigner: false, isWritable: true }, configKey],
+            data: createVoteUpInstructionData(1),
+        });
+        const error = await connection.sendTransaction(new Transaction().add(ix), [adminAccount]).catch((error) => error);
+        return String(error.logs);
+    };
+
+    // The vote account is 24 bytes; CounterError::InvalidAccountLength
+    const short = await voteWith(10);
+    expect(short).toContain("holds 10 bytes, expected 24");
+    expect(short).toContain("custom program error: 0x6");
+    expect(await voteWith(32)).toContain("holds 32 bytes, expected 24");
+});
+
+test("accounts of the wrong kind are named as such", async () => {
+    const logsOf = async (key: PublicKey, data: Buffer) => {
+        const ix = new TransactionInstruction({
+            programId,
+            keys: [{ pubkey: key, isSigner: false, isWritable: true }, configKey],
+            data,
+        });
+        const error = await connection.sendTransaction(new Transaction().add(ix), [adminAccount]).catch((error) => error);
+        expect(counterErrorOf(error)).toBe(CounterError.WrongAccountType);
+        return String(error.logs);
+    };
+
+    expect(await logsOf(dataAccount.publicKey, createVoteUpInstructionData(1))).toContain("is a counter, expected a vote account");
+    expect(await logsOf(configAddress, createIncrementInstructionData(1))).toContain("is a config, expected a counter");
+});
+
+test("the built program embeds its security contact", () => {
+    const binary = readFileSync(new URL("../target/deploy/solana_counter_program.so", import.meta.url));
+    // The NUL-separated fields sit between the markers
+    const begin = binary.indexOf("=======BEGIN SECURITY.TXT V1=======\0");
+    const end = binary.indexOf("=======END SECURITY.TXT V1=======\0");
+    expect(begin).toBeGreaterThanOrEqual(0);
+    expect(end).toBeGreaterThan(begin);
+    const fields = binary.subarray(begin, end).toString().split("\0").slice(1, -1);
+    for (const key of ["name", "project_url", "contacts", "policy"]) {
+        expect(fields).toContain(key);
+    }
+    expect(fields[fields.indexOf("contacts") + 1]).toStartWith("link:");
+});
+
+test("arithmetic edge cases have fixed outcomes", async () => {
+    const seed = "arithmetic";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const counterKey = { pubkey: counterAddress, isSigner: false, isWritable: true };
+    const authorityKey = { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false };
+    const send = async (data: Buffer, keys = [counterKey, authorityKey, configKey]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const count = async () => {
+        const info = await connection.getAccountInfo(counterAddress);
+        return BigInt((borsh.deserialize(schema, info!.data) as CounterAccount).count);
+    };
+    await send(createInitializePdaInstructionData(seed), [
+        counterKey,
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+
+    // Zero amounts are errors rather than no-ops
+    expect(counterErrorOf(await send(createIncrementInstructionData(0), [counterKey, configKey]).catch((error) => error))).toBe(CounterError.ZeroAmount);
+    expect(counterErrorOf(await send(createDecrementInstructionData(0)).catch((error) => error))).toBe(CounterError.ZeroAmount);
+
+    // u32::MAX is an ordinary amount; a u64 counter holds several
+    await send(createIncrementInstructionData(0xffffffff), [counterKey, configKey]);
+    await send(createIncrementInstructionData(0xffffffff), [counterKey, configKey]);
+    expect(await count()).toBe(2n * 0xffffffffn);
+
+    // Decrementing by exactly the count reaches zero; one more underflows
+    await send(createDecrementInstructionData(0xffffffff));
+    await send(createDecrementInstructionData(0xffffffff));
+    expect(await count()).toBe(0n);
+    expect(counterErrorOf(await send(createDecrementInstructionData(1)).catch((error) => error))).toBe(CounterError.Underflow);
+
+    // Multiplying by zero clears the count, dividing by one keeps it
+    await send(createIncrementInstructionData(7), [counterKey, configKey]);
+    await send(createDivideInstructionData(1));
+    expect(await count()).toBe(7n);
+    await send(createMultiplyInstructionData(0));
+    expect(await count()).toBe(0n);
+});
+
+test("a freeze authority pauses the counter while the authority keeps its other powers", async () => {
+    const seed = "frozen";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const counterKey = { pubkey: counterAddress, isSigner: false, isWritable: true };
+    const operations = Keypair.generate();
+    const send = async (data: Buffer, signer: Keypair, keys = [counterKey, { pubkey: signer.publicKey, isSigner: true, isWritable: false }]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction({ feePayer: adminAccount.publicKey }).add(new TransactionInstruction({ programId, keys, data })),
+            signer === adminAccount ? [adminAccount] : [adminAccount, signer],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    await send(createInitializePdaInstructionData(seed), adminAccount, [
+        counterKey,
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+    await send(createSetFreezeAuthorityInstructionData(operations.publicKey), adminAccount);
+
+    // CounterError::Unauthorized: only the freeze authority pauses now
+    expect(counterErrorOf(await send(createPauseInstructionData(), adminAccount).catch((error) => error))).toBe(CounterError.Unauthorized);
+    await send(createPauseInstructionData(), operations);
+    const info = await connection.getAccountInfo(counterAddress);
+    const state = borsh.deserialize(schema, info!.data) as CounterAccount;
+    expect(state.paused).toBe(true);
+    expect(new PublicKey(state.freeze_authority).equals(operations.publicKey)).toBe(true);
+
+    // ...but gains none of the authority's powers
+    const set = createSetInstructionData(1);
+    expect(counterErrorOf(await send(set, operations, [counterKey, { pubkey: operations.publicKey, isSigner: true, isWritable: false }, configKey]).catch((error) => error))).toBe(CounterError.Unauthorized);
+    await send(createUnpauseInstructionData(), operations);
+
+    // Clearing it hands pausing back to the authority
+    await send(createSetFreezeAuthorityInstructionData(PublicKey.default), adminAccount);
+    await send(createPauseInstructionData(), adminAccount);
+});
+
+test("the config admin can halt and resume every change, emitting events", async () => {
+    const keys = [
+        { pubkey: configAddress, isSigner: false, isWritable: true },
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false },
+    ];
+    const send = async (data: Buffer, signer = adminAccount) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction({ feePayer: adminAccount.publicKey }).add(new TransactionInstruction({ programId, keys: [keys[0], { ...keys[1], pubkey: signer.publicKey }], data })),
+            signer === adminAccount ? [adminAccount] : [adminAccount, signer],
+        );
+        await connection.confirmTransaction(txHash);
+        const tx = await connection.getTransaction(txHash, { commitment: "confirmed", maxSupportedTransactionVersion: 0 });
+        return eventsOf(tx!.meta!.logMessages!);
+    };
+
+    // CounterError::Unauthorized: nobody but the admin pulls the lever
+    expect(counterErrorOf(await send(createEmergencyHaltInstructionData(), Keypair.generate()).catch((error) => error))).toBe(CounterError.Unauthorized);
+
+    const [halt] = await send(createEmergencyHaltInstructionData());
+    expect(halt.EmergencyHalt).toBeDefined();
+    expect(new PublicKey(halt.EmergencyHalt.admin).equals(adminAccount.publicKey)).toBe(true);
+    expect(counterErrorOf(await sendCounterInstruction(createIncrementInstructionData(1)).catch((error) => error))).toBe(CounterError.ProgramPaused);
+    // Draining shards into a counter is a change like any other
+    const [shardAddress] = findShardAddress(programId, dataAccount.publicKey, 0);
+    const aggregate = await connection.sendTransaction(
+        new Transaction().add(new TransactionInstruction({
+            programId,
+            keys: [
+                { pubkey: dataAccount.publicKey, isSigner: false, isWritable: true },
+                configKey,
+                { pubkey: shardAddress, isSigner: false, isWritable: true },
+            ],
+            data: createAggregateInstructionData(),
+        })),
+        [adminAccount],
+    ).catch((error) => error);
+    expect(counterErrorOf(aggregate)).toBe(CounterError.ProgramPaused);
+
+    const [resume] = await send(createEmergencyResumeInstructionData());
+    expect(resume.EmergencyResume).toBeDefined();
+    expect(BigInt(resume.EmergencyResume.slot)).toBeGreaterThanOrEqual(BigInt(halt.EmergencyHalt.slot));
+    const before = await sendCounterInstruction(createIncrementInstructionData(1));
+    expect(await sendCounterInstruction(createIncrementInstructionData(1))).toBe(before + 1);
+});
+
+test("every change to a counter is logged as a structured event", async () => {
+    const logsOf = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+        const tx = await connection.getTransaction(txHash, { commitment: "confirmed", maxSupportedTransactionVersion: 0 });
+        return eventsOf(tx!.meta!.logMessages!);
+    };
+    const counterKey = { pubkey: dataAccount.publicKey, isSigner: false, isWritable: true };
+    const authorityKey = { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false };
+
+    const [increment] = await logsOf(createIncrementInstructionData(3), [counterKey, configKey]);
+    const updated = increment.CounterUpdated;
+    expect(new PublicKey(updated.counter).equals(dataAccount.publicKey)).toBe(true);
+    expect(updated.operation).toBe(Operation.Increment);
+    expect(BigInt(updated.new_value) - BigInt(updated.old_value)).toBe(3n);
+    // Unsigned increments carry no actor
+    expect(new PublicKey(updated.actor).equals(PublicKey.default)).toBe(true);
+
+    const [set] = await logsOf(createSetInstructionData(7), [counterKey, authorityKey, configKey]);
+    expect(set.CounterUpdated.operation).toBe(Operation.Set);
+    expect(BigInt(set.CounterUpdated.old_value)).toBe(BigInt(updated.new_value));
+    expect(BigInt(set.CounterUpdated.new_value)).toBe(7n);
+    expect(new PublicKey(set.CounterUpdated.actor).equals(adminAccount.publicKey)).toBe(true);
+    expect(BigInt(set.CounterUpdated.slot)).toBeGreaterThan(0n);
+});
+
+test("events decode the same in the Anchor event encoding", async () => {
+    const txHash = await connection.sendTransaction(
+        new Transaction().add(new TransactionInstruction({
+            programId,
+            keys: [{ pubkey: dataAccount.publicKey, isSigner: false, isWritable: true }, configKey],
+            data: createIncrementInstructionData(1),
+        })),
+        [adminAccount],
+    );
+    await connection.confirmTransaction(txHash);
+    const tx = await connection.getTransaction(txHash, { commitment: "confirmed", maxSupportedTransactionVersion: 0 });
+    const line = tx!.meta!.logMessages!.find((log) => log.startsWith("Program data: "))!;
+    const data = Buffer.from(line.slice("Program data: ".length), "base64");
+
+    // Anchor's `emit!` layout: 8-byte discriminator, then the fields
+    const anchor = toAnchorEvent(data);
+    expect(anchor.subarray(0, 8)).toEqual(anchorEventDiscriminator("CounterUpdated"));
+    expect(Buffer.from(anchor.subarray(0, 8)).toJSON().data).toEqual([56, 210, 136, 13, 88, 67, 151, 167]);
+    expect(anchor.length).toBe(data.length + 7);
+    expect(decodeEvent(anchor)).toEqual(decodeEvent(data));
+});
+
+test("crossing a threshold emits a ThresholdCrossed event with its direction", async () => {
+    const seed = "thresholds";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const counterKey = { pubkey: counterAddress, isSigner: false, isWritable: true };
+    const authorityKey = { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false };
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+        const tx = await connection.getTransaction(txHash, { commitment: "confirmed", maxSupportedTransactionVersion: 0 });
+        return eventsOf(tx!.meta!.logMessages!).filter((event) => event.ThresholdCrossed).map((event) => event.ThresholdCrossed);
+    };
+    await send(createInitializePdaInstructionData(seed), [
+        counterKey,
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+
+    // CounterError::InvalidThresholds: at most four, within the counter's range
+    expect(counterErrorOf(await send(createSetThresholdsInstructionData([1, 2, 3, 4, 5]), [counterKey, authorityKey]).catch((error) => error))).toBe(CounterError.InvalidThresholds);
+    expect(counterErrorOf(await send(createSetThresholdsInstructionData([-1]), [counterKey, authorityKey]).catch((error) => error))).toBe(CounterError.InvalidThresholds);
+    await send(createSetThresholdsInstructionData([10, 20]), [counterKey, authorityKey]);
+    const state = borsh.deserialize(schema, (await connection.getAccountInfo(counterAddress))!.data) as CounterAccount;
+    expect(state.threshold_count).toBe(2);
+    expect(state.thresholds.map(Number)).toEqual([10, 20, 0, 0]);
+
+    expect(await send(createIncrementInstructionData(9), [counterKey, configKey])).toEqual([]);
+    // Reaching a threshold exactly counts as crossing it
+    const [up] = await send(createIncrementInstructionData(1), [counterKey, configKey]);
+    expect(new PublicKey(up.counter).equals(counterAddress)).toBe(true);
+    expect(Number(up.threshold)).toBe(10);
+    expect(up.direction).toBe(Direction.Up);
+
+    // One change can cross several thresholds, each reported once
+    const crossed = await send(createIncrementInstructionData(15), [counterKey, configKey]);
+    expect(crossed.map((event) => Number(event.threshold))).toEqual([20]);
+    const down = await send(createSetInstructionData(5), [counterKey, authorityKey, configKey]);
+    expect(down.map((event) => [Number(event.threshold), event.direction])).toEqual([[10, Direction.Down], [20, Direction.Down]]);
+    expect(Number(down[0].old_value)).toBe(25);
+    expect(Number(down[0].new_value)).toBe(5);
+});
+
+test("mutations can carry a memo forwarded to the Memo program", async () => {
+    const memoKey = { pubkey: MEMO_PROGRAM_ID, isSigner: false, isWritable: false };
+    const counterKey = { pubkey: dataAccount.publicKey, isSigner: false, isWritable: true };
+    const send = async (data: Buffer, keys = [counterKey, configKey, memoKey]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+        const tx = await connection.getTransaction(txHash, { commitment: "confirmed", maxSupportedTransactionVersion: 0 });
+        return tx!.meta!.logMessages!;
+    };
+    const valueOf = async () => {
+        const info = await connection.getAccountInfo(dataAccount.publicKey);
+        return Number((borsh.deserialize(schema, info!.data) as CounterAccount).count);
+    };
+
+    const before = await valueOf();
+    const logs = await send(createWithMemoInstructionData("refund batch #42", createIncrementInstructionData(2)));
+    expect(logs.some((log) => log.startsWith(`Program ${MEMO_PROGRAM_ID.toBase58()} invoke`))).toBe(true);
+    expect(logs.join("\n")).toContain("refund batch #42");
+    expect(await valueOf()).toBe(before + 2);
+    // The wrapped change still reports its own event
+    expect(eventsOf(logs)[0].CounterUpdated.operation).toBe(Operation.Increment);
+
+    // CounterError::MemoTooLong past 256 bytes
+    expect(counterErrorOf(await send(createWithMemoInstructionData("x".repeat(257), createIncrementInstructionData(1))).catch((error) => error))).toBe(CounterError.MemoTooLong);
+    // Only changes to a count can carry a memo, and it must go to the real Memo program
+    await expect(send(createWithMemoInstructionData("pause", createPauseInstructionData()))).rejects.toThrow("invalid instruction data");
+    await expect(send(createWithMemoInstructionData("spoofed", createIncrementInstructionData(1)), [counterKey, configKey, { ...memoKey, pubkey: Keypair.generate().publicKey }])).rejects.toThrow();
+    expect(await valueOf()).toBe(before + 2);
+});
+
+test("changes return the new value to callers", async () => {
+    const counterKey = { pubkey: dataAccount.publicKey, isSigner: false, isWritable: true };
+    // The return data a CPI caller would read with get_return_data
+    const returned = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const tx = new Transaction().add(new TransactionInstruction({ programId, keys, data }));
+        tx.feePayer = adminAccount.publicKey;
+        const { value } = await connection.simulateTransaction(tx, [adminAccount]);
+        expect(value.err).toBeNull();
+        expect(value.returnData!.programId).toBe(programId.toBase58());
+        return decodeReturnData(Buffer.from(value.returnData!.data[0], "base64"));
+    };
+    const info = await connection.getAccountInfo(dataAccount.publicKey);
+    const count = BigInt((borsh.deserialize(schema, info!.data) as CounterAccount).count);
+
+    expect(await returned(createIncrementInstructionData(4), [counterKey, configKey])).toBe(count + 4n);
+    expect(await returned(createSetInstructionData(9), [counterKey, { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false }, configKey])).toBe(9n);
+    // Instructions that leave the count alone return nothing
+    const tx = new Transaction().add(new TransactionInstruction({ programId, keys: [counterKey, { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false }], data: createSetThresholdsInstructionData([]) }));
+    tx.feePayer = adminAccount.publicKey;
+    expect((await connection.simulateTransaction(tx, [adminAccount])).value.returnData ?? null).toBeNull();
+});
+
+test("View reports the full state without writing", async () => {
+    const tx = new Transaction().add(new TransactionInstruction({
+        programId,
+        keys: [{ pubkey: dataAccount.publicKey, isSigner: false, isWritable: false }],
+        data: createViewInstructionData(),
+    }));
+    const txHash = await connection.sendTransaction(tx, [adminAccount]);
+    await connection.confirmTransaction(txHash);
+    const confirmed = await connection.getTransaction(txHash, { commitment: "confirmed", maxSupportedTransactionVersion: 0 });
+    const [viewed] = eventsOf(confirmed!.meta!.logMessages!);
+
+    const info = await connection.getAccountInfo(dataAccount.publicKey);
+    const state = borsh.deserialize(schema, info!.data) as CounterAccount;
+    const view = viewed.CounterViewed;
+    expect(new PublicKey(view.counter).equals(dataAccount.publicKey)).toBe(true);
+    expect(view.version).toBe(state.version);
+    expect(BigInt(view.value)).toBe(BigInt(state.count));
+    expect(new PublicKey(view.authority).equals(new PublicKey(state.authority))).toBe(true);
+    expect(view.paused).toBe(state.paused);
+    expect(BigInt(view.total_increments)).toBe(BigInt(state.total_increments));
+    expect(Number(view.last_updated_slot)).toBe(Number(state.last_updated_slot));
+
+    // CPI callers get the same bytes as return data
+    const { value } = await connection.simulateTransaction(new Transaction({ feePayer: adminAccount.publicKey }).add(tx.instructions[0]), [adminAccount]);
+    const returned = borsh.deserialize(counterViewSchema, Buffer.from(value.returnData!.data[0], "base64")) as any;
+    expect(BigInt(returned.value)).toBe(BigInt(view.value));
+});
+
+test("op ids are echoed in the change's event", async () => {
+    const counterKey = { pubkey: dataAccount.publicKey, isSigner: false, isWritable: true };
+    const memoKey = { pubkey: MEMO_PROGRAM_ID, isSigner: false, isWritable: false };
+    const send = async (data: Buffer, keys = [counterKey, configKey]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+        const tx = await connection.getTransaction(txHash, { commitment: "confirmed", maxSupportedTransactionVersion: 0 });
+        return eventsOf(tx!.meta!.logMessages!)[0].CounterUpdated;
+    };
+    const opId = Uint8Array.from({ length: 16 }, (_, i) => i + 1);
+
+    const tagged = await send(createWithOpIdInstructionData(opId, createIncrementInstructionData(1)));
+    expect(Buffer.from(tagged.op_id)).toEqual(Buffer.from(opId));
+    // Changes sent on their own carry none
+    expect((await send(createIncrementInstructionData(1))).op_id).toBeNull();
+    // A memo goes around the op id, not inside it
+    const both = await send(createWithMemoInstructionData("job 7", createWithOpIdInstructionData(opId, createIncrementInstructionData(1))), [counterKey, configKey, memoKey]);
+    expect(Buffer.from(both.op_id)).toEqual(Buffer.from(opId));
+    await expect(send(createWithOpIdInstructionData(opId, createWithMemoInstructionData("job 7", createIncrementInstructionData(1))), [counterKey, configKey, memoKey])).rejects.toThrow("invalid instruction data");
+    await expect(send(createWithOpIdInstructionData(opId, createPauseInstructionData()))).rejects.toThrow("invalid instruction data");
+});
+
+test("every event leads with the schema version", async () => {
+    const txHash = await connection.sendTransaction(
+        new Transaction().add(new TransactionInstruction({
+            programId,
+            keys: [{ pubkey: dataAccount.publicKey, isSigner: false, isWritable: true }, configKey],
+            data: createIncrementInstructionData(1),
+        })),
+        [adminAccount],
+    );
+    await connection.confirmTransaction(txHash);
+    const tx = await connection.getTransaction(txHash, { commitment: "confirmed", maxSupportedTransactionVersion: 0 });
+    const payloads = tx!.meta!.logMessages!
+        .filter((log) => log.startsWith("Program data: "))
+        .map((log) => Buffer.from(log.slice("Program data: ".length), "base64"));
+    expect(payloads.length).toBeGreaterThan(0);
+    for (const data of payloads) {
+        expect(data[0]).toBe(EVENT_SCHEMA_VERSION);
+        // In Anchor's encoding the version follows the discriminator
+        expect(toAnchorEvent(data)[8]).toBe(EVENT_SCHEMA_VERSION);
+    }
+    // A version the decoders do not know is refused rather than misread
+    const future = Buffer.from(payloads[0]);
+    future[0] = EVENT_SCHEMA_VERSION + 1;
+    expect(() => decodeEvent(future)).toThrow("Unknown event schema version");
+});
+
+test("a callback is invoked once a change reaches its target", async () => {
+    const seed = "callback";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const [callbackAddress] = findCallbackAddress(programId, counterAddress);
+    const counterKey = { pubkey: counterAddress, isSigner: false, isWritable: true };
+    const authorityKey = { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false };
+    const callbackKey = { pubkey: callbackAddress, isSigner: false, isWritable: true };
+    const memoKey = { pubkey: MEMO_PROGRAM_ID, isSigner: false, isWritable: false };
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const stateOf = async () => {
+        const info = await connection.getAccountInfo(counterAddress);
+        return borsh.deserialize(schema, info!.data) as CounterAccount;
+    };
+
+    await send(createInitializePdaInstructionData(seed), [
+        counterKey,
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+    // The Memo program stands in for a callback; it rejects the call, which
+    // shows both that it was invoked and that a failing callback fails the change
+    await send(createSetCallbackInstructionData(3, MEMO_PROGRAM_ID), [
+        counterKey,
+        authorityKey,
+        callbackKey,
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+    expect(hasFlag(await stateOf(), CounterFlag.Callback)).toBe(true);
+
+    // CounterError::MissingCallback, once a callback is armed
+    expect(counterErrorOf(await send(createIncrementInstructionData(1), [counterKey, configKey]).catch((error) => error))).toBe(CounterError.MissingCallback);
+    // Short of the target the callback is not invoked
+    await send(createIncrementInstructionData(1), [counterKey, configKey, callbackKey, memoKey]);
+    expect(Number((await stateOf()).count)).toBe(1);
+    const error = await send(createIncrementInstructionData(2), [counterKey, configKey, callbackKey, memoKey]).catch((error) => error);
+    expect(String(error.logs ?? error)).toContain(`Program ${MEMO_PROGRAM_ID.toBase58()} invoke`);
+    expect(Number((await stateOf()).count)).toBe(1);
+    // CounterError::InvalidCallback for any other program
+    expect(counterErrorOf(await send(createIncrementInstructionData(2), [counterKey, configKey, callbackKey, { ...memoKey, pubkey: SystemProgram.programId }]).catch((error) => error))).toBe(CounterError.InvalidCallback);
+
+    await send(createClearCallbackInstructionData(), [
+        counterKey,
+        authorityKey,
+        callbackKey,
+        { pubkey: adminAccount.publicKey, isSigner: false, isWritable: true },
+    ]);
+    expect(await connection.getAccountInfo(callbackAddress)).toBeNull();
+    expect(hasFlag(await stateOf(), CounterFlag.Callback)).toBe(false);
+    await send(createIncrementInstructionData(2), [counterKey, configKey]);
+    expect(Number((await stateOf()).count)).toBe(3);
+});
+
+// The sample program in examples/counter-owner, deployed with the keypair
+// cargo build-sbf generated for it
+const ownerProgramId = () => Keypair.fromSecretKey(Uint8Array.from(JSON.parse(
+    readFileSync(new URL("../target/deploy/counter_owner-keypair.json", import.meta.url), "utf8"),
+))).publicKey;
+
+// OwnerInstruction in examples/counter-owner
+const ownerInstructionSchema: borsh.Schema = {
+    enum: [
+        { struct: { Create: { struct: { seed: 'string' } } } },
+        { struct: { Decrement: 'u32' } },
+        { struct: { Reset: { struct: {} } } },
+        { struct: { Increment: 'u32' } },
+    ],
+};
+
+test("a program can own a counter through its PDA", async () => {
+    const ownerProgram = ownerProgramId();
+    const [ownerAddress] = PublicKey.findProgramAddressSync([Buffer.from("owner")], ownerProgram);
+    const [counterAddress] = findCounterAddress(programId, ownerAddress, "owned");
+    const counterKey = { pubkey: counterAddress, isSigner: false, isWritable: true };
+    const ownerKey = { pubkey: ownerAddress, isSigner: false, isWritable: false };
+    const send = async (ix: TransactionInstruction) => {
+        const txHash = await connection.sendTransaction(new Transaction().add(ix), [adminAccount]);
+        await connection.confirmTransaction(txHash);
+    };
+    const sendOwner = (data: object, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[]) =>
+        send(new TransactionInstruction({ programId: ownerProgram, keys, data: Buffer.from(borsh.serialize(ownerInstructionSchema, data)) }));
+    const stateOf = async () => {
+        const info = await connection.getAccountInfo(counterAddress);
+        return borsh.deserialize(schema, info!.data) as CounterAccount;
+    };
+    const programKeys = [ownerKey, configKey, { pubkey: programId, isSigner: false, isWritable: false }];
+
+    // The admin pays for the counter, the PDA signs as its authority
+    await sendOwner({ Create: { seed: "owned" } }, [
+        counterKey,
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        ownerKey,
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        configKey,
+        { pubkey: programId, isSigner: false, isWritable: false },
+    ]);
+    expect(new PublicKey((await stateOf()).authority).equals(ownerAddress)).toBe(true);
+
+    await send(new TransactionInstruction({ programId, keys: [counterKey, configKey], data: createIncrementInstructionData(5) }));
+    await sendOwner({ Decrement: 2 }, [counterKey, ...programKeys]);
+    expect(Number((await stateOf()).count)).toBe(3);
+    // Only the program can sign for its PDA
+    const direct = await send(new TransactionInstruction({
+        programId,
+        keys: [counterKey, { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false }, configKey],
+        data: createResetInstructionData(),
+    })).catch((error) => error);
+    expect(counterErrorOf(direct)).toBe(CounterError.Unauthorized);
+    await sendOwner({ Reset: {} }, [counterKey, ...programKeys]);
+    expect(Number((await stateOf()).count)).toBe(0);
+});
+
+test("a calling program reads the new count from the return data", async () => {
+    const seed = "returned";
+    const [counterAddress] = findCounterAddress(programId, adminAccount.publicKey, seed);
+    const counterKey = { pubkey: counterAddress, isSigner: false, isWritable: true };
+    const programKey = (pubkey: PublicKey) => ({ pubkey, isSigner: false, isWritable: false });
+    const send = async (ix: TransactionInstruction) => {
+        const txHash = await connection.sendTransaction(new Transaction().add(ix), [adminAccount]);
+        await connection.confirmTransaction(txHash);
+        const tx = await connection.getTransaction(txHash, { commitment: "confirmed", maxSupportedTransactionVersion: 0 });
+        return tx!.meta!.logMessages!;
+    };
+    await send(new TransactionInstruction({
+        programId,
+        keys: [
+            counterKey,
+            { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+            programKey(SystemProgram.programId),
+        ],
+        data: createInitializePdaInstructionData(seed),
+    }));
+
+    // The sample program increments through a CPI, then logs what get_return_data gave it
+    const increment = (amount: number) => send(new TransactionInstruction({
+        programId: ownerProgramId(),
+        keys: [counterKey, configKey, programKey(programId)],
+        data: Buffer.from(borsh.serialize(ownerInstructionSchema, { Increment: amount })),
+    }));
+    expect(await increment(7)).toContain(`Program log: Counter ${counterAddress.toBase58()} is now 7`);
+    expect(await increment(3)).toContain(`Program log: Counter ${counterAddress.toBase58()} is now 10`);
+});
+
+test("operators decrement within their allowance until they expire", async () => {
+    const [weekOne, weekTwo, retired, stranger] = [Keypair.generate(), Keypair.generate(), Keypair.generate(), Keypair.generate()];
+    const [operatorsAddress] = findOperatorsAddress(programId, dataAccount.publicKey);
+    const counterKey = { pubkey: dataAccount.publicKey, isSigner: false, isWritable: true };
+    const authorityKey = { pubkey: adminAccount.publicKey, isSigner: true, isWritable: false };
+    const operatorsKey = { pubkey: operatorsAddress, isSigner: false, isWritable: true };
+    const send = async (data: Buffer, keys: { pubkey: PublicKey, isSigner: boolean, isWritable: boolean }[], signers: Keypair[] = []) => {
+        const txHash = await connection.sendTransaction(
+            new Transaction().add(new TransactionInstruction({ programId, keys, data })),
+            [adminAccount, ...signers],
+        );
+        await connection.confirmTransaction(txHash);
+    };
+    const add = (operator: PublicKey, allowance: number, expirySlot = 0) => send(createAddOperatorInstructionData(operator, allowance, expirySlot), [
+        { ...counterKey, isWritable: false },
+        authorityKey,
+        operatorsKey,
+        { pubkey: adminAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ]);
+    // The operator list takes the authority's place, and the operator signs last
+    const decrement = (operator: Keypair, amount: number) =>
+        send(createDecrementInstructionData(amount), [counterKey, operatorsKey, configKey, { pubkey: operator.publicKey, isSigner: true, isWritable: false }], [operator]);
+    const errorOf = async (operation: Promise<unknown>) => counterErrorOf(await operation.catch((error) => error));
+
+    await sendCounterInstruction(createSetInstructionData(100), true);
+    const slot = await connection.getSlot();
+    // Rotating keys: next week's operator is added before this week's is removed
+    await add(weekOne.publicKey, 10, slot + 10_000);
+    await add(weekTwo.publicKey, 5);
+    await add(retired.publicKey, 5, 1);
+
+    await decrement(weekOne, 6);
+    await decrement(weekTwo, 5);
+    expect(await errorOf(decrement(weekOne, 5))).toBe(CounterError.AllowanceExceeded);
+    expect(await errorOf(decrement(retired, 1))).toBe(CounterError.OperatorExpired);
+    expect(await errorOf(decrement(stranger, 1))).toBe(CounterError.UnknownOperator);
+
+    const info = await connection.getAccountInfo(operatorsAddress);
+    const list = borsh.deserialize({
+        struct: {
+            discriminator: { array: { type: 'u8', len: 8 } },
+            counter: { array: { type: 'u8', len: 32 } },
+            bump: 'u8',
+            operators: { array: { type: { struct: { key: { array: { type: 'u8', len: 32 } }, allowance: 'u64', expiry_slot: 'u64' } } } },
+        },
+    }, info!.data) as { operators: { key: Uint8Array, allowance: bigint }[] };
+    expect(list.operators.map(({ allowance }) => Number(allowance))).toEqual([4, 0, 5]);
+
+    await send(createRemoveOperatorInstructionData(weekOne.publicKey), [{ ...counterKey, isWritable: false }, authorityKey, operatorsKey]);
+    expect(await errorOf(decrement(weekOne, 1))).toBe(CounterError.UnknownOperator);
+    const counterInfo = await connection.getAccountInfo(dataAccount.publicKey);
+    expect(Number((borsh.deserialize(schema, counterInfo!.data) as CounterAccount).count)).toBe(89);
+});
+
+test("hook programs are invoked around every change", async () => {
+    const seed = "hooked";
+    const hookProgram = ownerProgramId();
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+    const [hooksAddress] = findHooksAddress(programId, counterAddress);
+    const hooksKey = readonly(hooksAddress);
+    const setHooks = (pre: PublicKey | null, post: PublicKey | null) => send(createSetHooksInstructionData(pre, post), [
+        counterKey,
+        authorityKey,
+        { ...hooksKey, isWritable: true },
+        payerKey,
+        systemKey,
+    ]);
+
+    await setHooks(hookProgram, hookProgram);
+    expect(hasFlag(await stateOf(counterAddress), CounterFlag.Hooks)).toBe(true);
+
+    // The hooks account and the pre and post hook programs come last
+    const logs = await logsOf(await send(createIncrementInstructionData(2), [counterKey, configKey, hooksKey, readonly(hookProgram), readonly(hookProgram)]));
+    const hookLogs = logs.filter((log) => log.includes(" hook of counter "));
+    expect(hookLogs).toEqual([
+        `Program log: Pre hook of counter ${counterAddress.toBase58()}: 0 -> None`,
+        `Program log: Post hook of counter ${counterAddress.toBase58()}: 0 -> Some(2)`,
+    ]);
+    expect(Number((await stateOf(counterAddress)).count)).toBe(2);
+
+    // CounterError::MissingHooks and InvalidHooks
+    expect(counterErrorOf(await send(createIncrementInstructionData(1), [counterKey, configKey]).catch((error) => error))).toBe(CounterError.MissingHooks);
+    expect(counterErrorOf(await send(createIncrementInstructionData(1), [counterKey, configKey, hooksKey, readonly(hookProgram), readonly(MEMO_PROGRAM_ID)]).catch((error) => error))).toBe(CounterError.InvalidHooks);
+
+    // A failing pre hook rejects the change; the Memo program rejects the call
+    await setHooks(MEMO_PROGRAM_ID, null);
+    await expect(send(createIncrementInstructionData(1), [counterKey, configKey, hooksKey, readonly(MEMO_PROGRAM_ID)])).rejects.toThrow();
+    expect(Number((await stateOf(counterAddress)).count)).toBe(2);
+
+    await send(createClearHooksInstructionData(), [counterKey, authorityKey, { ...hooksKey, isWritable: true }, writable(adminAccount.publicKey)]);
+    expect(await connection.getAccountInfo(hooksAddress)).toBeNull();
+    await send(createIncrementInstructionData(1), [counterKey, configKey]);
+    expect(Number((await stateOf(counterAddress)).count)).toBe(3);
+});
+
+// Mints and token accounts are set up with raw SPL Token instructions,
+// all with the admin as mint authority and owner
+async function createTokenProgramAccount(account: Keypair, space: number, data: Buffer, keys: PublicKey[]) {
+    const ix = new TransactionInstruction({
+        programId: TOKEN_PROGRAM_ID,
+        keys: keys.map((pubkey, index) => ({ pubkey, isSigner: false, isWritable: index == 0 })),
+        data,
+    });
+    const tx = new Transaction().add(SystemProgram.createAccount({
+        fromPubkey: adminAccount.publicKey,
+        newAccountPubkey: account.publicKey,
+        lamports: await connection.getMinimumBalanceForRentExemption(space),
+        space,
+        programId: TOKEN_PROGRAM_ID,
+    }), ix);
+    await connection.confirmTransaction(await connection.sendTransaction(tx, [adminAccount, account]));
+}
+
+// InitializeMint2 with no freeze authority
+async function createTestMint(decimals = 2) {
+    const mint = Keypair.generate();
+    await createTokenProgramAccount(mint, 82, Buffer.concat([Buffer.from([20, decimals]), adminAccount.publicKey.toBuffer(), Buffer.from([0])]), [mint.publicKey]);
+    return mint.publicKey;
+}
+
+// InitializeAccount3, then MintTo for `amount` tokens
+async function createTestTokenAccount(mint: PublicKey, owner: PublicKey, amount = 0n) {
+    const account = Keypair.generate();
+    await createTokenProgramAccount(account, 165, Buffer.concat([Buffer.from([18]), owner.toBuffer()]), [account.publicKey, mint]);
+    if (amount > 0n) {
+        const mintTo = Buffer.alloc(9);
+        mintTo.writeUInt8(7);
+        mintTo.writeBigUInt64LE(amount, 1);
+        await connection.confirmTransaction(await connection.sendTransaction(new Transaction().add(new TransactionInstruction({
+            programId: TOKEN_PROGRAM_ID,
+            keys: [
+                writable(mint),
+                writable(account.publicKey),
+                authorityKey,
+            ],
+            data: mintTo,
+        })), [adminAccount]));
+    }
+    return account.publicKey;
+}
+
+// A token account's balance is the u64 at offset 64
+const tokenBalanceOf = async (account: PublicKey) => (await connection.getAccountInfo(account))!.data.readBigUInt64LE(64);
+
+test("the config can charge a token fee for every change", async () => {
+    const mint = await createTestMint();
+    const source = await createTestTokenAccount(mint, adminAccount.publicKey, 1000n);
+    const treasury = await createTestTokenAccount(mint, adminAccount.publicKey);
+
+    const setFee = async (fee: { mint: PublicKey, amount: number, treasury: PublicKey } | null) => {
+        const txHash = await connection.sendTransaction(new Transaction().add(new TransactionInstruction({
+            programId,
+            keys: [
+                writable(configAddress),
+                authorityKey,
+                payerKey,
+                systemKey,
+            ],
+            data: createSetFeeInstructionData(fee),
+        })), [adminAccount]);
+        await connection.confirmTransaction(txHash);
+    };
+    const increment = async (feeKeys: PublicKey[]) => {
+        const ix = new TransactionInstruction({
+            programId,
+            keys: [
+                writable(dataAccount.publicKey),
+                configKey,
+                ...feeKeys.map((pubkey, index) => ({ pubkey, isSigner: index == 1, isWritable: index == 0 || index == 2 })),
+            ],
+            data: createIncrementInstructionData(1),
+        });
+        await connection.confirmTransaction(await connection.sendTransaction(new Transaction().add(ix), [adminAccount]));
+    };
+    const feeKeys = [source, adminAccount.publicKey, treasury, mint, TOKEN_PROGRAM_ID];
+
+    await setFee({ mint, amount: 25, treasury });
+    const countOf = async () => Number((await stateOf(dataAccount.publicKey)).count);
+    const before = await countOf();
+    await increment(feeKeys);
+    expect(await countOf()).toBe(before + 1);
+    expect(await tokenBalanceOf(source)).toBe(975n);
+    expect(await tokenBalanceOf(treasury)).toBe(25n);
+
+    // CounterError::MissingFee and InvalidFeeAccount
+    expect(counterErrorOf(await increment([]).catch((error) => error))).toBe(CounterError.MissingFee);
+    expect(counterErrorOf(await increment([source, adminAccount.publicKey, source, mint, TOKEN_PROGRAM_ID]).catch((error) => error))).toBe(CounterError.InvalidFeeAccount);
+    expect(await tokenBalanceOf(source)).toBe(975n);
+
+    // Without a fee, the accounts are not needed and ignored if passed
+    await setFee(null);
+    await increment([]);
+    await increment(feeKeys);
+    expect(await tokenBalanceOf(source)).toBe(975n);
+    expect(await countOf()).toBe(before + 3);
+});
+
+test("a lamport fee per change is collected in the fee vault and withdrawn by the admin", async () => {
+    const [vaultAddress] = findFeeVaultAddress(programId);
+    const adminKey = (isWritable: boolean) => ({ pubkey: adminAccount.publicKey, isSigner: true, isWritable });
+    const setLamportFee = (lamports: number) => send(createSetLamportFeeInstructionData(lamports), [
+        writable(configAddress),
+        adminKey(false),
+        writable(vaultAddress),
+        adminKey(true),
+        systemKey,
+    ]);
+    const vaultKeys = [
+        adminKey(true),
+        writable(vaultAddress),
+        systemKey,
+    ];
+    const counterKey = writable(dataAccount.publicKey);
+    const balance = async () => (await connection.getAccountInfo(vaultAddress))!.lamports;
+
+    await setLamportFee(5000);
+    const rent = await balance();
+    await send(createIncrementInstructionData(1), [counterKey, configKey, ...vaultKeys]);
+    await send(createIncrementByOneInstructionData(), [counterKey, configKey, ...vaultKeys]);
+    expect(await balance()).toBe(rent + 10000);
+
+    // CounterError::MissingFee
+    expect(counterErrorOf(await send(createIncrementInstructionData(1), [counterKey, configKey]).catch((error) => error))).toBe(CounterError.MissingFee);
+
+    // Only what is above the vault's rent-exempt minimum can be withdrawn
+    const withdraw = (amount: number) => send(createWithdrawFeesInstructionData(amount), [
+        configKey,
+        adminKey(false),
+        writable(vaultAddress),
+        writable(adminAccount.publicKey),
+    ]);
+    await withdraw(10000);
+    expect(await balance()).toBe(rent);
+    await expect(withdraw(1)).rejects.toThrow(/insufficient funds/);
+
+    await setLamportFee(0);
+    await send(createIncrementInstructionData(1), [counterKey, configKey]);
+    expect(await balance()).toBe(rent);
+});
+
+test("a token-gated counter only lets holders of its mint increment", async () => {
+    const seed = "holders-only";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+    const [gateAddress] = findTokenGateAddress(programId, counterAddress);
+    const mint = await createTestMint();
+    const holding = await createTestTokenAccount(mint, adminAccount.publicKey, 10n);
+    const short = await createTestTokenAccount(mint, adminAccount.publicKey, 9n);
+    const gateKey = readonly(gateAddress);
+    const countOf = async () => Number((await stateOf(counterAddress)).count);
+
+    await send(createSetTokenGateInstructionData(mint, 10), [
+        counterKey,
+        authorityKey,
+        { ...gateKey, isWritable: true },
+        payerKey,
+        systemKey,
+    ]);
+    const info = await connection.getAccountInfo(counterAddress);
+    expect(hasFlag(borsh.deserialize(schema, info!.data) as CounterAccount, CounterFlag.TokenGate)).toBe(true);
+
+    // The actor signs, then come the gate and the actor's token account
+    await send(createIncrementInstructionData(1), [counterKey, configKey, authorityKey, gateKey, readonly(holding)]);
+    await send(createIncrementByOneInstructionData(), [counterKey, configKey, authorityKey, gateKey, readonly(holding)]);
+    expect(await countOf()).toBe(2);
+
+    // CounterError::MissingTokenGate and InsufficientTokens
+    expect(counterErrorOf(await send(createIncrementInstructionData(1), [counterKey, configKey, authorityKey]).catch((error) => error))).toBe(CounterError.MissingTokenGate);
+    expect(counterErrorOf(await send(createIncrementInstructionData(1), [counterKey, configKey, authorityKey, gateKey, readonly(short)]).catch((error) => error))).toBe(CounterError.InsufficientTokens);
+    // A Batch of increments is gated like the increments it holds
+    const batch = createBatchInstructionData([{ Increment: 1 }, { Increment: 1 }]);
+    expect(counterErrorOf(await send(batch, [counterKey, configKey, authorityKey, gateKey, readonly(short)]).catch((error) => error))).toBe(CounterError.InsufficientTokens);
+    expect(counterErrorOf(await send(batch, [counterKey, configKey, authorityKey]).catch((error) => error))).toBe(CounterError.MissingTokenGate);
+    await send(batch, [counterKey, configKey, authorityKey, gateKey, readonly(holding)]);
+    expect(await countOf()).toBe(4);
+
+    // CounterError::NotShardable: increments of a shard would skip the gate
+    const [shardAddress] = findShardAddress(programId, counterAddress, 0);
+    expect(counterErrorOf(await send(createInitializeShardInstructionData(0), [readonly(counterAddress), authorityKey, writable(shardAddress), payerKey, systemKey]).catch((error) => error))).toBe(CounterError.NotShardable);
+
+    // Changes that need the authority are not gated
+    await send(createResetInstructionData(), [counterKey, authorityKey, configKey]);
+    expect(await countOf()).toBe(0);
+
+    await send(createClearTokenGateInstructionData(), [counterKey, authorityKey, { ...gateKey, isWritable: true }, writable(adminAccount.publicKey)]);
+    expect(await connection.getAccountInfo(gateAddress)).toBeNull();
+    await send(createIncrementInstructionData(1), [counterKey, configKey]);
+    expect(await countOf()).toBe(1);
+});
+
+test("whoever holds a counter's NFT is its authority", async () => {
+    const seed = "nft-owned";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+    const nft = await createTestMint(0);
+    const holding = await createTestTokenAccount(nft, adminAccount.publicKey, 1n);
+    const empty = await createTestTokenAccount(nft, adminAccount.publicKey);
+
+    await send(createIncrementInstructionData(3), [counterKey, configKey]);
+
+    // A mint with more than one token or with decimals is not an NFT
+    const fungible = await createTestMint();
+    await expect(send(createSetNftAuthorityInstructionData(fungible), [counterKey, authorityKey, readonly(fungible)])).rejects.toThrow();
+
+    await send(createSetNftAuthorityInstructionData(nft), [counterKey, authorityKey, readonly(nft)]);
+    const counter = await stateOf(counterAddress);
+    expect(hasFlag(counter, CounterFlag.NftAuthority)).toBe(true);
+    expect(new PublicKey(counter.authority).equals(nft)).toBe(true);
+
+    // CounterError::Unauthorized for a token account without the NFT
+    expect(counterErrorOf(await send(createResetInstructionData(), [counterKey, readonly(empty), configKey, authorityKey]).catch((error) => error))).toBe(CounterError.Unauthorized);
+    // The holder has to sign
+    await expect(send(createResetInstructionData(), [counterKey, readonly(holding), configKey])).rejects.toThrow(/missing required signature/);
+
+    await send(createResetInstructionData(), [counterKey, readonly(holding), configKey, authorityKey]);
+    expect(Number((await stateOf(counterAddress)).count)).toBe(0);
+});
+
+test("a clone of a fully featured counter drops the features tied to the source", async () => {
+    const seed = "featured";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+    const snapshot = Keypair.generate();
+    const nft = await createTestMint(0);
+    const holding = await createTestTokenAccount(nft, adminAccount.publicKey, 1n);
+    const mint = await createTestMint();
+
+    await send(createIncrementInstructionData(2), [counterKey, configKey]);
+    const featureKeys = (address: PublicKey) => [counterKey, authorityKey, writable(address), payerKey, systemKey];
+    await send(createInitializeHistoryInstructionData(3), featureKeys(findHistoryAddress(programId, counterAddress)[0]));
+    await send(createSetCallbackInstructionData(5, MEMO_PROGRAM_ID), featureKeys(findCallbackAddress(programId, counterAddress)[0]));
+    await send(createSetHooksInstructionData(ownerProgramId(), null), featureKeys(findHooksAddress(programId, counterAddress)[0]));
+    await send(createSetTokenGateInstructionData(mint, 1), featureKeys(findTokenGateAddress(programId, counterAddress)[0]));
+    await send(createSetDecayInstructionData(1, DecayUnit.Slot), featureKeys(findDecayAddress(programId, counterAddress)[0]));
+    await send(createSetNftAuthorityInstructionData(nft), [counterKey, authorityKey, readonly(nft)]);
+    const source = await stateOf(counterAddress);
+    const features = [CounterFlag.History, CounterFlag.Callback, CounterFlag.Hooks, CounterFlag.TokenGate, CounterFlag.Decay, CounterFlag.NftAuthority];
+    expect(features.map((flag) => hasFlag(source, flag))).toEqual([true, true, true, true, true, true]);
+
+    // The NFT's token account stands in for the authority, its holder signs
+    await send(createCloneInstructionData(), [
+        readonly(counterAddress),
+        readonly(holding),
+        { pubkey: snapshot.publicKey, isSigner: true, isWritable: true },
+        payerKey,
+        systemKey,
+        authorityKey,
+    ], [adminAccount, snapshot]);
+    const copy = await stateOf(snapshot.publicKey);
+    expect(Number(copy.count)).toBe(2);
+    expect(features.map((flag) => hasFlag(copy, flag))).toEqual([false, false, false, false, false, false]);
+    expect(new PublicKey(copy.authority).equals(adminAccount.publicKey)).toBe(true);
+
+    // None of the source's accounts are needed to change the copy
+    await send(createIncrementInstructionData(1), [writable(snapshot.publicKey), configKey]);
+    expect(Number((await stateOf(snapshot.publicKey)).count)).toBe(3);
+    await send(createResetInstructionData(), [writable(snapshot.publicKey), authorityKey, configKey]);
+    expect(Number((await stateOf(snapshot.publicKey)).count)).toBe(0);
+});
+
+test("only a governance account can be made a counter's governance authority", async () => {
+    const seed = "dao-owned";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+
+    // The test validator runs no governance program; a mint, a wallet or an
+    // account named differently from the instruction's `governance` is refused
+    const mint = await createTestMint();
+    await expect(send(createSetGovernanceAuthorityInstructionData(mint), [counterKey, authorityKey, readonly(mint)])).rejects.toThrow(/invalid program argument/);
+    await expect(send(createSetGovernanceAuthorityInstructionData(adminAccount.publicKey, true), [counterKey, authorityKey, readonly(adminAccount.publicKey)])).rejects.toThrow(/invalid program argument/);
+    await expect(send(createSetGovernanceAuthorityInstructionData(mint), [counterKey, authorityKey, readonly(configAddress)])).rejects.toThrow(/invalid program argument/);
+
+    const info = await connection.getAccountInfo(counterAddress);
+    expect(new PublicKey((borsh.deserialize(schema, info!.data) as CounterAccount).authority).equals(adminAccount.publicKey)).toBe(true);
+});
+
+test("a burn-backed counter counts exactly the tokens its increments burn", async () => {
+    const seed = "redemptions";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+    const [burnAddress] = findBurnAddress(programId, counterAddress);
+    const mint = await createTestMint();
+    const holding = await createTestTokenAccount(mint, adminAccount.publicKey, 100n);
+    const burnKeys = [writable(burnAddress), readonly(TOKEN_PROGRAM_ID), writable(holding), writable(mint)];
+    const countOf = async () => Number((await stateOf(counterAddress)).count);
+
+    await send(createSetBurnMintInstructionData(mint), [
+        counterKey,
+        authorityKey,
+        writable(burnAddress),
+        payerKey,
+        systemKey,
+    ]);
+
+    // The actor signs, then come the burn account, the token program, the
+    // actor's token account and the mint
+    await send(createIncrementInstructionData(30), [counterKey, configKey, authorityKey, ...burnKeys]);
+    await send(createIncrementByOneInstructionData(), [counterKey, configKey, authorityKey, ...burnKeys]);
+    expect(await countOf()).toBe(31);
+    expect(await tokenBalanceOf(holding)).toBe(69n);
+
+    // CounterError::MissingBurn and BurnBacked
+    expect(counterErrorOf(await send(createIncrementInstructionData(1), [counterKey, configKey, authorityKey]).catch((error) => error))).toBe(CounterError.MissingBurn);
+    expect(counterErrorOf(await send(createResetInstructionData(), [counterKey, authorityKey, configKey]).catch((error) => error))).toBe(CounterError.BurnBacked);
+    // Nor can it fade, as that would unburn tokens
+    const [decayAddress] = findDecayAddress(programId, counterAddress);
+    expect(counterErrorOf(await send(createSetDecayInstructionData(1, DecayUnit.Slot), [
+        counterKey,
+        authorityKey,
+        writable(decayAddress),
+        payerKey,
+        systemKey,
+    ]).catch((error) => error))).toBe(CounterError.BurnBacked);
+    // Or start over each epoch
+    expect(counterErrorOf(await send(createSetEpochResetInstructionData(true), [counterKey, authorityKey]).catch((error) => error))).toBe(CounterError.BurnBacked);
+    // Burning more than the account holds fails the increment with it
+    await expect(send(createIncrementInstructionData(70), [counterKey, configKey, authorityKey, ...burnKeys])).rejects.toThrow();
+    expect(await countOf()).toBe(31);
+});
+
+test("increments reaching a milestone mint rewards to the actor's associated token account", async () => {
+    const seed = "milestones";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+    const [rewardsAddress] = findRewardsAddress(programId, counterAddress);
+    const mint = await createTestMint();
+    const ata = findAssociatedTokenAddress(adminAccount.publicKey, mint);
+    const rewardKeys = [writable(rewardsAddress), readonly(TOKEN_PROGRAM_ID), writable(mint), writable(ata)];
+
+    // SPL Token's SetAuthority makes the rewards account the mint authority
+    await sendInstructions([new TransactionInstruction({
+        programId: TOKEN_PROGRAM_ID,
+        keys: [writable(mint), authorityKey],
+        data: Buffer.concat([Buffer.from([6, 0, 1]), rewardsAddress.toBuffer()]),
+    })]);
+    // The ATA program's CreateIdempotent
+    await sendInstructions([new TransactionInstruction({
+        programId: ASSOCIATED_TOKEN_PROGRAM_ID,
+        keys: [
+            payerKey,
+            writable(ata),
+            readonly(adminAccount.publicKey),
+            readonly(mint),
+            systemKey,
+            readonly(TOKEN_PROGRAM_ID),
+        ],
+        data: Buffer.from([1]),
+    })]);
+    await send(createSetRewardsInstructionData(mint, 500, [5, 10]), [
+        counterKey,
+        authorityKey,
+        writable(rewardsAddress),
+        payerKey,
+        systemKey,
+    ]);
+
+    // The actor signs, then come the rewards account, the token program, the
+    // mint and the actor's associated token account
+    await send(createIncrementInstructionData(4), [counterKey, configKey, authorityKey, ...rewardKeys]);
+    expect(await tokenBalanceOf(ata)).toBe(0n);
+    await send(createIncrementByOneInstructionData(), [counterKey, configKey, authorityKey, ...rewardKeys]);
+    expect(await tokenBalanceOf(ata)).toBe(500n);
+
+    // CounterError::MissingRewards and InvalidRewardAccount
+    expect(counterErrorOf(await send(createIncrementInstructionData(1), [counterKey, configKey, authorityKey]).catch((error) => error))).toBe(CounterError.MissingRewards);
+    const other = await createTestTokenAccount(mint, adminAccount.publicKey);
+    expect(counterErrorOf(await send(createIncrementInstructionData(5), [counterKey, configKey, authorityKey, ...rewardKeys.slice(0, 3), writable(other)]).catch((error) => error))).toBe(CounterError.InvalidRewardAccount);
+
+    // Each milestone pays once: going back below and up again mints nothing
+    await send(createIncrementInstructionData(5), [counterKey, configKey, authorityKey, ...rewardKeys]);
+    expect(await tokenBalanceOf(ata)).toBe(1000n);
+    await send(createResetInstructionData(), [counterKey, authorityKey, configKey]);
+    await send(createIncrementInstructionData(10), [counterKey, configKey, authorityKey, ...rewardKeys]);
+    expect(await tokenBalanceOf(ata)).toBe(1000n);
+
+    await send(createClearRewardsInstructionData(), [counterKey, authorityKey, writable(rewardsAddress), writable(adminAccount.publicKey)]);
+    expect(await connection.getAccountInfo(rewardsAddress)).toBeNull();
+    await send(createIncrementInstructionData(1), [counterKey, configKey]);
+});
+
+test("an escrow pays its beneficiary once the counter reaches the target", async () => {
+    const seed = "escrowed";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+    const [escrowAddress] = findEscrowAddress(programId, counterAddress);
+    const [depositAddress] = findEscrowDepositAddress(programId, escrowAddress, adminAccount.publicKey);
+    const beneficiary = Keypair.generate().publicKey;
+    const deposit = (lamports: number) => send(createDepositEscrowInstructionData(lamports), [
+        readonly(counterAddress),
+        writable(escrowAddress),
+        writable(depositAddress),
+        payerKey,
+        systemKey,
+    ]);
+
+    await send(createInitializeEscrowInstructionData(beneficiary, 3, (await connection.getSlot()) + 1_000_000), [
+        counterKey,
+        authorityKey,
+        writable(escrowAddress),
+        payerKey,
+        systemKey,
+    ]);
+    await deposit(LAMPORTS_PER_SOL / 10);
+    await deposit(LAMPORTS_PER_SOL / 10);
+
+    // CounterError::EscrowLocked until the target, and for refunds before the deadline
+    const release = [counterKey, writable(escrowAddress), writable(beneficiary)];
+    expect(counterErrorOf(await send(createSettleInstructionData(), release).catch((error) => error))).toBe(CounterError.EscrowLocked);
+    const refund = [counterKey, writable(escrowAddress), writable(adminAccount.publicKey), writable(depositAddress)];
+    expect(counterErrorOf(await send(createSettleInstructionData(), refund).catch((error) => error))).toBe(CounterError.EscrowLocked);
+    // CounterError::EscrowOpen: the counter stays open for its depositors
+    const close = () => send(createCloseInstructionData(), [counterKey, authorityKey, writable(adminAccount.publicKey)]);
+    expect(counterErrorOf(await close().catch((error) => error))).toBe(CounterError.EscrowOpen);
+
+    await send(createIncrementInstructionData(3), [counterKey, configKey]);
+    await send(createSettleInstructionData(), release);
+    expect(await connection.getBalance(beneficiary)).toBe(LAMPORTS_PER_SOL / 5);
+
+    // Once settled a deposit record only hands back its rent, and deposits stop
+    await send(createSettleInstructionData(), refund);
+    expect(await connection.getAccountInfo(depositAddress)).toBeNull();
+    expect(counterErrorOf(await deposit(1).catch((error) => error))).toBe(CounterError.EscrowClosed);
+
+    // Released, the escrow no longer holds the counter open
+    await close();
+    expect(await connection.getAccountInfo(counterAddress)).toBeNull();
+});
+
+test("a permit-only counter takes each increment's permit from an Ed25519 instruction", async () => {
+    const seed = "permits";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+    const [permitsAddress] = findPermitsAddress(programId, counterAddress);
+    // The authority signs permits offline; anyone may send them, here a fresh fee payer
+    const relayer = Keypair.generate();
+    await connection.confirmTransaction(await connection.requestAirdrop(relayer.publicKey, LAMPORTS_PER_SOL));
+    const sendAs = (signer: Keypair, ...instructions: TransactionInstruction[]) => sendInstructions(instructions, [signer]);
+    const increment = (amount: number) => new TransactionInstruction({
+        programId,
+        keys: [counterKey, configKey, writable(permitsAddress), readonly(SYSVAR_INSTRUCTIONS_PUBKEY)],
+        data: createIncrementInstructionData(amount),
+    });
+    const permit = (amount: number, nonce: number, expirySlot: number) => Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: adminAccount.secretKey,
+        message: createPermitMessage(counterAddress, amount, nonce, expirySlot),
+    });
+    const countOf = async () => Number((await stateOf(counterAddress)).count);
+
+    await send(createRequirePermitsInstructionData(), [counterKey, authorityKey, writable(permitsAddress), payerKey, systemKey]);
+
+    // CounterError::MissingPermit without the nonce account, InvalidPermit without a signed permit
+    const bare = new TransactionInstruction({ programId, keys: [counterKey, configKey], data: createIncrementInstructionData(2) });
+    expect(counterErrorOf(await sendAs(relayer, bare).catch((error) => error))).toBe(CounterError.MissingPermit);
+    expect(counterErrorOf(await sendAs(relayer, increment(2)).catch((error) => error))).toBe(CounterError.InvalidPermit);
+
+    const expiry = (await connection.getSlot()) + 1_000;
+    await sendAs(relayer, permit(2, 0, expiry), increment(2));
+    expect(await countOf()).toBe(2);
+    // A permit works once, for its own amount, until its expiry slot
+    expect(counterErrorOf(await sendAs(relayer, permit(2, 0, expiry), increment(2)).catch((error) => error))).toBe(CounterError.InvalidPermit);
+    expect(counterErrorOf(await sendAs(relayer, permit(2, 1, expiry), increment(3)).catch((error) => error))).toBe(CounterError.InvalidPermit);
+    expect(counterErrorOf(await sendAs(relayer, permit(2, 1, 1), increment(2)).catch((error) => error))).toBe(CounterError.InvalidPermit);
+    await sendAs(relayer, permit(3, 1, expiry), increment(3));
+    expect(await countOf()).toBe(5);
+
+    await send(createClearPermitsInstructionData(), [counterKey, authorityKey, writable(permitsAddress), writable(adminAccount.publicKey)]);
+    expect(await connection.getAccountInfo(permitsAddress)).toBeNull();
+    await sendAs(relayer, bare);
+    expect(await countOf()).toBe(7);
+});
+
+test("a cooldown makes each actor wait between increments", async () => {
+    const seed = "cooldown";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+    const [cooldownAddress] = findCooldownAddress(programId, counterAddress);
+    // The actor signs, pays for their stamp on their first increment, then
+    // come the cooldown, the stamp and the system program
+    const tap = (actor: Keypair) => send(createIncrementByOneInstructionData(), [
+        counterKey,
+        configKey,
+        { pubkey: actor.publicKey, isSigner: true, isWritable: true },
+        readonly(cooldownAddress),
+        writable(findCooldownStampAddress(programId, counterAddress, actor.publicKey)[0]),
+        systemKey,
+    ], [actor]);
+    const [alice, bob] = [Keypair.generate(), Keypair.generate()];
+    for (const actor of [alice, bob]) {
+        await connection.confirmTransaction(await connection.requestAirdrop(actor.publicKey, LAMPORTS_PER_SOL));
+    }
+    const countOf = async () => Number((await stateOf(counterAddress)).count);
+
+    await send(createSetCooldownInstructionData(1_000), [counterKey, authorityKey, writable(cooldownAddress), payerKey, systemKey]);
+
+    await tap(alice);
+    // CounterError::CooldownActive for the same actor, while another may go
+    expect(counterErrorOf(await tap(alice).catch((error) => error))).toBe(CounterError.CooldownActive);
+    await tap(bob);
+    expect(await countOf()).toBe(2);
+    // CounterError::MissingCooldown without the cooldown accounts
+    expect(counterErrorOf(await send(createIncrementInstructionData(1), [counterKey, configKey]).catch((error) => error))).toBe(CounterError.MissingCooldown);
+    // The authority's changes are not throttled
+    await send(createSetInstructionData(10), [counterKey, authorityKey, configKey]);
+
+    await send(createClearCooldownInstructionData(), [counterKey, authorityKey, writable(cooldownAddress), writable(adminAccount.publicKey)]);
+    expect(await connection.getAccountInfo(cooldownAddress)).toBeNull();
+    await send(createIncrementInstructionData(1), [counterKey, configKey]);
+    expect(await countOf()).toBe(11);
+});
+
+test("a daily cap limits what each actor adds per day", async () => {
+    const seed = "daily-cap";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+    const [cooldownAddress] = findCooldownAddress(programId, counterAddress);
+    const contribute = (amount: number) => send(createIncrementInstructionData(amount), [
+        counterKey,
+        configKey,
+        payerKey,
+        readonly(cooldownAddress),
+        writable(findCooldownStampAddress(programId, counterAddress, adminAccount.publicKey)[0]),
+        systemKey,
+    ]);
+
+    // No slots to wait, only the cap
+    await send(createSetCooldownInstructionData(0, 5), [
+        counterKey,
+        authorityKey,
+        writable(cooldownAddress),
+        payerKey,
+        systemKey,
+    ]);
+
+    await contribute(3);
+    // CounterError::DailyCapExceeded once the day's total would pass 5
+    expect(counterErrorOf(await contribute(3).catch((error) => error))).toBe(CounterError.DailyCapExceeded);
+    await contribute(2);
+    expect(counterErrorOf(await contribute(1).catch((error) => error))).toBe(CounterError.DailyCapExceeded);
+    expect(Number((await stateOf(counterAddress)).count)).toBe(5);
+});
+
+test("an epoch cap limits how far changes move a counter per epoch", async () => {
+    const seed = "epoch-cap";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+    const setEpochCap = (cap: number) => send(createSetEpochCapInstructionData(cap), [
+        writable(configAddress),
+        authorityKey,
+        payerKey,
+        systemKey,
+    ]);
+
+    await setEpochCap(5);
+    await send(createIncrementInstructionData(3), [counterKey, configKey]);
+    // Decrements count towards the cap as much as increments
+    await send(createDecrementInstructionData(1), [counterKey, authorityKey, configKey]);
+    // CounterError::EpochCapExceeded once the epoch's tally would pass 5
+    expect(counterErrorOf(await send(createIncrementInstructionData(2), [counterKey, configKey]).catch((error) => error))).toBe(CounterError.EpochCapExceeded);
+    await send(createIncrementInstructionData(1), [counterKey, configKey]);
+    const state = await stateOf(counterAddress);
+    expect(Number(state.count)).toBe(3);
+    expect(Number(state.epoch_change)).toBe(5);
+    expect(Number(state.epoch)).toBe((await connection.getEpochInfo()).epoch);
+
+    // Lifting the cap lets changes through, and the tally goes on
+    await setEpochCap(0);
+    await send(createIncrementInstructionData(2), [counterKey, configKey]);
+    expect(Number((await stateOf(counterAddress)).epoch_change)).toBe(7);
+});
+
+test("an epoch-reset counter keeps counting within its epoch", async () => {
+    const seed = "per-epoch";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+
+    await send(createIncrementInstructionData(4), [counterKey, configKey]);
+    await send(createSetEpochResetInstructionData(true), [counterKey, authorityKey]);
+    const state = await stateOf(counterAddress);
+    expect(hasFlag(state, CounterFlag.EpochReset)).toBe(true);
+    // Enabling it counts as a change in the current epoch, so nothing resets yet
+    const epoch = state.epoch;
+    const events = eventsOf(await logsOf(await send(createIncrementInstructionData(1), [counterKey, configKey])));
+    if ((await stateOf(counterAddress)).epoch === epoch) {
+        expect(events.some((event) => event.EpochReset)).toBe(false);
+        expect(Number((await stateOf(counterAddress)).count)).toBe(5);
+    } else {
+        // The epoch advanced in between, so the increment started from zero
+        expect(Number(events.find((event) => event.EpochReset).EpochReset.final_value)).toBe(4);
+        expect(Number((await stateOf(counterAddress)).count)).toBe(1);
+    }
+
+    await send(createSetEpochResetInstructionData(false), [counterKey, authorityKey]);
+    expect(hasFlag(await stateOf(counterAddress), CounterFlag.EpochReset)).toBe(false);
+});
+
+test("a decaying counter fades towards zero between changes", async () => {
+    const seed = "fading";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+    const [decayAddress] = findDecayAddress(programId, counterAddress);
+    const decayKey = readonly(decayAddress);
+    const countOf = async () => Number((await stateOf(counterAddress)).count);
+
+    await send(createSetDecayInstructionData(1, DecayUnit.Slot), [counterKey, authorityKey, { ...decayKey, isWritable: true }, payerKey, systemKey]);
+    await send(createIncrementInstructionData(1000), [counterKey, configKey, decayKey]);
+    expect(await countOf()).toBe(1000);
+
+    // Every slot since the last change takes one off before the next applies
+    const slot = await connection.getSlot();
+    while (await connection.getSlot() < slot + 5) {
+        await new Promise((resolve) => setTimeout(resolve, 100));
+    }
+    await send(createIncrementInstructionData(1), [counterKey, configKey, decayKey]);
+    expect(await countOf()).toBeLessThanOrEqual(996);
+    // CounterError::MissingDecay without the decay account
+    expect(counterErrorOf(await send(createIncrementInstructionData(1), [counterKey, configKey]).catch((error) => error))).toBe(CounterError.MissingDecay);
+
+    await send(createClearDecayInstructionData(), [counterKey, authorityKey, { ...decayKey, isWritable: true }, writable(adminAccount.publicKey)]);
+    expect(await connection.getAccountInfo(decayAddress)).toBeNull();
+    const settled = await countOf();
+    await send(createIncrementInstructionData(1), [counterKey, configKey]);
+    expect(await countOf()).toBe(settled + 1);
+});
+
+test("the cumulative value gives the time-weighted average between two readings", async () => {
+    const seed = "time-weighted";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+
+    // Nothing is counted before the first change
+    await send(createIncrementInstructionData(10), [counterKey, configKey]);
+    const start = await stateOf(counterAddress);
+    expect(BigInt(start.cumulative)).toBe(0n);
+
+    await new Promise((resolve) => setTimeout(resolve, 3000));
+    await send(createIncrementInstructionData(20), [counterKey, configKey]);
+    const end = await stateOf(counterAddress);
+    const elapsed = BigInt(end.last_updated_ts) - BigInt(start.last_updated_ts);
+    expect(elapsed).toBeGreaterThan(0n);
+    // The counter held 10 the whole time in between
+    expect(BigInt(end.cumulative)).toBe(10n * elapsed);
+    expect(timeWeightedAverage([BigInt(start.cumulative), start.last_updated_ts], [BigInt(end.cumulative), end.last_updated_ts])).toBe(10n);
+    expect(timeWeightedAverage([BigInt(end.cumulative), end.last_updated_ts], [BigInt(end.cumulative), end.last_updated_ts])).toBeNull();
+});
+
+test("a crank applies a counter's decay and pays the cranker once per fee paid", async () => {
+    const [vaultAddress] = findFeeVaultAddress(programId);
+    const vaultKey = writable(vaultAddress);
+    const writableConfigKey = { ...configKey, isWritable: true };
+    const setCrankReward = (lamports: number, epochBudget: number) => send(createSetCrankRewardInstructionData(lamports, epochBudget), [
+        writableConfigKey,
+        authorityKey,
+        payerKey,
+        systemKey,
+    ]);
+    const setLamportFee = (lamports: number) => send(createSetLamportFeeInstructionData(lamports), [
+        writableConfigKey,
+        authorityKey,
+        vaultKey,
+        payerKey,
+        systemKey,
+    ]);
+    const waitSlots = async (slots: number) => {
+        const slot = await connection.getSlot();
+        while (await connection.getSlot() < slot + slots) {
+            await new Promise((resolve) => setTimeout(resolve, 100));
+        }
+    };
+    // A decaying counter whose first increment paid the lamport fee
+    const decayingCounter = async (seed: string) => {
+        const { counterAddress, counterKey } = await createAdminCounter(seed);
+        const [decayAddress] = findDecayAddress(programId, counterAddress);
+        const decayKey = readonly(decayAddress);
+        await send(createSetDecayInstructionData(1, DecayUnit.Slot), [counterKey, authorityKey, { ...decayKey, isWritable: true }, payerKey, systemKey]);
+        await setLamportFee(1_000);
+        await send(createIncrementInstructionData(1000), [counterKey, configKey, decayKey, payerKey, vaultKey, systemKey]);
+        await setLamportFee(0);
+        return {
+            counterKey,
+            decayKey,
+            crank: () => send(createCrankInstructionData(), [counterKey, writableConfigKey, payerKey, vaultKey, decayKey]),
+            stateOf: () => stateOf(counterAddress),
+            clearDecay: () => send(createClearDecayInstructionData(), [counterKey, authorityKey, { ...decayKey, isWritable: true }, writable(adminAccount.publicKey)]),
+        };
+    };
+
+    const first = await decayingCounter("cranked");
+    expect(hasFlag(await first.stateOf(), CounterFlag.FeePaid)).toBe(true);
+    // Fund the vault so it can pay the rewards
+    const fund = new Transaction().add(SystemProgram.transfer({ fromPubkey: adminAccount.publicKey, toPubkey: vaultAddress, lamports: 10_000 }));
+    await connection.confirmTransaction(await connection.sendTransaction(fund, [adminAccount]));
+    await setCrankReward(1_000, 1_500);
+
+    // Nothing changes the counter but the decay it owes
+    await waitSlots(3);
+    let before = await connection.getBalance(vaultAddress);
+    const txHash = await first.crank();
+    let state = await first.stateOf();
+    expect(Number(state.count)).toBeLessThanOrEqual(997);
+    expect(await connection.getBalance(vaultAddress)).toBe(before - 1_000);
+    expect(hasFlag(state, CounterFlag.FeePaid)).toBe(false);
+    const epoch = (await connection.getEpochInfo()).epoch;
+    expect(Number(state.next_crank_epoch)).toBe(epoch + 1);
+    const update = eventsOf(await logsOf(txHash)).find((event) => event.CounterUpdated).CounterUpdated;
+    expect(update.operation).toBe(Operation.Decay);
+    expect(Number(update.old_value)).toBe(1000);
+
+    // Cranking it again still applies the decay, but pays nothing until it pays another fee
+    await waitSlots(3);
+    before = await connection.getBalance(vaultAddress);
+    const count = Number(state.count);
+    await first.crank();
+    expect(Number((await first.stateOf()).count)).toBeLessThan(count);
+    expect(await connection.getBalance(vaultAddress)).toBe(before);
+
+    // Another fee-paying counter only gets what is left of the epoch's budget
+    const second = await decayingCounter("cranked-too");
+    await waitSlots(3);
+    before = await connection.getBalance(vaultAddress);
+    await second.crank();
+    expect(await connection.getBalance(vaultAddress)).toBe(before - 500);
+
+    // CounterError::MissingDecay without the decay account
+    expect(counterErrorOf(await send(createCrankInstructionData(), [first.counterKey, writableConfigKey, payerKey, vaultKey]).catch((error) => error))).toBe(CounterError.MissingDecay);
+    await setCrankReward(0, 0);
+    await first.clearDecay();
+    await second.clearDecay();
+});
+
+test("a locked counter rejects changes until its slot", async () => {
+    const seed = "judging";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+    const viewOf = async () => {
+        const txHash = await send(createViewInstructionData(), [{ ...counterKey, isWritable: false }]);
+        return eventsOf(await logsOf(txHash))[0].CounterViewed;
+    };
+
+    await send(createIncrementInstructionData(7), [counterKey, configKey]);
+    const untilSlot = (await connection.getSlot()) + 20;
+    await send(createLockInstructionData(untilSlot), [counterKey, authorityKey]);
+    const view = await viewOf();
+    expect(view.lock_status).toBe(LockStatus.Locked);
+    expect(Number(view.locked_until)).toBe(untilSlot);
+
+    // CounterError::CounterLocked for any change before the slot
+    expect(counterErrorOf(await send(createIncrementInstructionData(1), [counterKey, configKey]).catch((error) => error))).toBe(CounterError.CounterLocked);
+    expect(counterErrorOf(await send(createResetInstructionData(), [counterKey, authorityKey, configKey]).catch((error) => error))).toBe(CounterError.CounterLocked);
+    // CounterError::InvalidLock for a lock ending sooner
+    expect(counterErrorOf(await send(createLockInstructionData(untilSlot - 1), [counterKey, authorityKey]).catch((error) => error))).toBe(CounterError.InvalidLock);
+
+    while (await connection.getSlot() < untilSlot) {
+        await new Promise((resolve) => setTimeout(resolve, 100));
+    }
+    await send(createIncrementInstructionData(1), [counterKey, configKey]);
+    expect((await viewOf()).lock_status).toBe(LockStatus.Unlocked);
+    expect(Number((await stateOf(counterAddress)).count)).toBe(8);
+});
+
+test("an expired counter only takes a close", async () => {
+    const seed = "campaign";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+    const clusterTime = async () => (await connection.getBlockTime(await connection.getSlot()))!;
+
+    // CounterError::InvalidExpiry for a deadline already behind us
+    expect(counterErrorOf(await send(createSetExpiryInstructionData(1), [counterKey, authorityKey]).catch((error) => error))).toBe(CounterError.InvalidExpiry);
+    const deadline = (await clusterTime()) + 3;
+    await send(createSetExpiryInstructionData(deadline), [counterKey, authorityKey]);
+    await send(createIncrementInstructionData(1), [counterKey, configKey]);
+    expect(Number((await stateOf(counterAddress)).expires_at)).toBe(deadline);
+
+    while (await clusterTime() < deadline + 1) {
+        await new Promise((resolve) => setTimeout(resolve, 500));
+    }
+    // CounterError::CounterExpired for changes and for moving the deadline
+    expect(counterErrorOf(await send(createIncrementInstructionData(1), [counterKey, configKey]).catch((error) => error))).toBe(CounterError.CounterExpired);
+    expect(counterErrorOf(await send(createSetExpiryInstructionData(0), [counterKey, authorityKey]).catch((error) => error))).toBe(CounterError.CounterExpired);
+
+    await send(createCloseInstructionData(), [counterKey, authorityKey, writable(adminAccount.publicKey)]);
+    expect(await connection.getAccountInfo(counterAddress)).toBeNull();
+});
+
+test("a moving average follows the counter's changes", async () => {
+    const seed = "smoothed";
+    const { counterAddress, counterKey } = await createAdminCounter(seed);
+
+    // CounterError::InvalidSmoothing past 10,000 basis points
+    expect(counterErrorOf(await send(createSetEmaInstructionData(10_001), [counterKey, authorityKey]).catch((error) => error))).toBe(CounterError.InvalidSmoothing);
+    await send(createSetEmaInstructionData(5_000), [counterKey, authorityKey]);
+    expect(Number((await stateOf(counterAddress)).ema_alpha)).toBe(5_000);
+
+    // Each change moves the average half way to the new value
+    await send(createIncrementInstructionData(100), [counterKey, configKey]);
+    expect(BigInt((await stateOf(counterAddress)).ema)).toBe(50n * EMA_SCALE);
+    await send(createIncrementInstructionData(100), [counterKey, configKey]);
+    expect(BigInt((await stateOf(counterAddress)).ema)).toBe(125n * EMA_SCALE);
+
+    await send(createSetEmaInstructionData(0), [counterKey, authorityKey]);
+    const state = await stateOf(counterAddress);
+    expect(Number(state.ema_alpha)).toBe(0);
+    expect(BigInt(state.ema)).toBe(0n);
+});
@@ -1,8 +1,181 @@
 import * as borsh from "borsh";
+import { PublicKey } from "@solana/web3.js";
+import { createHash } from "crypto";
 
+// Variant order must match the Rust `CounterInstruction` enum, since Borsh
+// encodes the variant index as the leading byte
 enum CounterInstructionType {
   Increment = 0,
   Decrement = 1,
+  Initialize = 2,
+  Reset = 3,
+  Set = 4,
+  TransferAuthority = 5,
+  AcceptAuthority = 6,
+  Close = 7,
+  MigrateLegacy = 8,
+  InitializePda = 9,
+  Multiply = 10,
+  Divide = 11,
+  CompareAndSwap = 12,
+  Batch = 13,
+  IncrementByOne = 14,
+  MigrateV1ToV2 = 15,
+  Pause = 16,
+  Unpause = 17,
+  InitializeConfig = 18,
+  UpdateConfig = 19,
+  InitializeMultisig = 20,
+  Approve = 21,
+  Revoke = 22,
+  Merge = 23,
+  Clone = 24,
+  InitializeVotes = 25,
+  VoteUp = 26,
+  VoteDown = 27,
+  InitializeMap = 28,
+  AddMapEntry = 29,
+  RemoveMapEntry = 30,
+  IncrementMapEntry = 31,
+  DecrementMapEntry = 32,
+  Migrate = 33,
+  Resize = 34,
+  SetMetadata = 35,
+  InitializeShard = 36,
+  IncrementShard = 37,
+  Aggregate = 38,
+  InitializeHistory = 39,
+  SetCallerGuard = 40,
+  SetExactlyOnce = 41,
+  SetFreezeAuthority = 42,
+  EmergencyHalt = 43,
+  EmergencyResume = 44,
+  SetThresholds = 45,
+  WithMemo = 46,
+  View = 47,
+  WithOpId = 48,
+  SetCallback = 49,
+  ClearCallback = 50,
+  AddOperator = 51,
+  RemoveOperator = 52,
+  SetHooks = 53,
+  ClearHooks = 54,
+  SetFee = 55,
+  SetLamportFee = 56,
+  WithdrawFees = 57,
+  SetTokenGate = 58,
+  ClearTokenGate = 59,
+  SetNftAuthority = 60,
+  SetGovernanceAuthority = 61,
+  SetBurnMint = 62,
+  SetRewards = 63,
+  ClearRewards = 64,
+  InitializeEscrow = 65,
+  DepositEscrow = 66,
+  Settle = 67,
+  RequirePermits = 68,
+  ClearPermits = 69,
+  SetCooldown = 70,
+  ClearCooldown = 71,
+  SetEpochCap = 72,
+  SetEpochReset = 73,
+  SetDecay = 74,
+  ClearDecay = 75,
+  SetCrankReward = 76,
+  Crank = 77,
+  Lock = 78,
+  SetExpiry = 79,
+  SetEma = 80,
+}
+
+// Mirrors the Rust `CounterError` enum: a failed instruction reports
+// `custom program error: 0x<code>`, and the codes never change
+enum CounterError {
+  Overflow = 0,
+  Underflow = 1,
+  Unauthorized = 2,
+  NoPendingAuthority = 3,
+  IncorrectOwner = 4,
+  AccountNotWritable = 5,
+  InvalidAccountLength = 6,
+  UnexpectedAccounts = 7,
+  UninitializedAccount = 8,
+  InvalidDiscriminator = 9,
+  InvalidSeed = 10,
+  InvalidCounterAddress = 11,
+  DivisionByZero = 12,
+  ValueMismatch = 13,
+  EmptyBatch = 14,
+  UnsupportedVersion = 15,
+  BelowMinimum = 16,
+  AboveMaximum = 17,
+  InvalidBounds = 18,
+  InvalidStep = 19,
+  CounterPaused = 20,
+  InvalidConfigAddress = 21,
+  ProgramPaused = 22,
+  AmountExceedsLimit = 23,
+  InvalidMultisig = 24,
+  NotEnoughSigners = 25,
+  AllowanceExceeded = 26,
+  EntryExists = 27,
+  EntryNotFound = 28,
+  UnknownInstruction = 29,
+  TrailingBytes = 30,
+  TruncatedInstruction = 31,
+  MetadataTooLong = 32,
+  BatchTooLarge = 33,
+  InvalidShard = 34,
+  MissingHistory = 35,
+  InvalidHistory = 36,
+  NotRentExempt = 37,
+  AlreadyInitialized = 38,
+  CallerNotAllowed = 39,
+  DuplicateMutation = 40,
+  ForbiddenProgram = 41,
+  WrongAccountType = 42,
+  ZeroAmount = 43,
+  InvalidThresholds = 44,
+  MemoTooLong = 45,
+  MissingCallback = 46,
+  InvalidCallback = 47,
+  UnknownOperator = 48,
+  OperatorExpired = 49,
+  TooManyOperators = 50,
+  MissingHooks = 51,
+  InvalidHooks = 52,
+  MissingFee = 53,
+  InvalidFeeAccount = 54,
+  MissingTokenGate = 55,
+  InsufficientTokens = 56,
+  MissingBurn = 57,
+  BurnBacked = 58,
+  MissingRewards = 59,
+  InvalidRewardAccount = 60,
+  EscrowLocked = 61,
+  EscrowClosed = 62,
+  MissingPermit = 63,
+  InvalidPermit = 64,
+  MissingCooldown = 65,
+  CooldownActive = 66,
+  DailyCapExceeded = 67,
+  EpochCapExceeded = 68,
+  MissingDecay = 69,
+  CounterLocked = 70,
+  InvalidLock = 71,
+  CounterExpired = 72,
+  InvalidExpiry = 73,
+  InvalidSmoothing = 74,
+  EscrowOpen = 75,
+  NotShardable = 76,
+}
+
+/**
+ * The `CounterError` a failed transaction reported, if any
+ */
+export function counterErrorOf(error: unknown): CounterError | undefined {
+  const match = /custom program error: 0x([0-9a-f]+)/.exec(String(error));
+  return match ? parseInt(match[1], 16) as CounterError : undefined;
 }
 
 class CounterInstruction {
@@ -15,29 +188,1046 @@ class CounterInstruction {
   }
 }
 
-const CounterInstructionSchema = new Map([
-  [
-    CounterInstructionType,
-    {
-      kind: 'enum',
-      field: 'variant',
-      values: [
-        ['Increment', { kind: 'struct', fields: [['value', 'u32']] }],
-        ['Decrement', { kind: 'struct', fields: [['value', 'u32']] }],
-      ],
-    },
+// Mirrors the Rust `OverflowMode` enum, encoded as a single byte
+enum OverflowMode {
+  Checked = 0,
+  Saturating = 1,
+  Wrapping = 2,
+}
+
+// Mirrors the Rust `CounterKind` enum, encoded as a single byte
+enum CounterKind {
+  Unsigned = 0,
+  Signed = 1,
+}
+
+// Mirrors the Rust `DecayUnit` enum, encoded as a single byte
+enum DecayUnit {
+  Slot = 0,
+  Second = 1,
+}
+
+// Mirrors the Rust `LockStatus` enum, encoded as a single byte
+enum LockStatus {
+  Unlocked = 0,
+  Locked = 1,
+}
+
+// Mirrors the Rust `CounterFlag` enum, one bit each of a counter's `flags`
+enum CounterFlag {
+  History = 1 << 0,
+  ClampDecrements = 1 << 1,
+  ExactlyOnce = 1 << 2,
+  Callback = 1 << 3,
+  Hooks = 1 << 4,
+  TokenGate = 1 << 5,
+  NftAuthority = 1 << 6,
+  Burn = 1 << 7,
+  Rewards = 1 << 8,
+  Permits = 1 << 9,
+  Cooldown = 1 << 10,
+  EpochReset = 1 << 11,
+  Decay = 1 << 12,
+  FeePaid = 1 << 13,
+  Escrow = 1 << 14,
+}
+
+/**
+ * Whether `flag` is switched on in a counter's decoded `flags`
+ */
+export function hasFlag(state: { flags: number }, flag: CounterFlag) {
+  return (state.flags & flag) !== 0;
+}
+
+// Mirrors the Rust `InitializeArgs` struct
+const InitializeArgsSchema: borsh.Schema = {
+  struct: { overflow_mode: 'u8', kind: 'u8', min: { option: 'i128' }, max: { option: 'i128' }, step: 'u32', modulus: 'u64', clamp_decrements: 'bool' },
+};
+
+// Optional constraints on a counter: bounds narrowing the range it may take,
+// defaulting to the full range of its kind, a step that increments and
+// decrements must be multiples of, defaulting to any amount, a modulus every
+// result wraps at, defaulting to none, and whether decrements past the lower
+// bound stop at it instead of failing, defaulting to failing
+type CounterOptions = { min?: number | bigint; max?: number | bigint; step?: number; modulus?: number | bigint; clampDecrements?: boolean };
+
+function initializeArgs(overflowMode: OverflowMode, kind: CounterKind, options: CounterOptions) {
+  return {
+    overflow_mode: overflowMode,
+    kind,
+    min: options.min ?? null,
+    max: options.max ?? null,
+    step: options.step ?? 0,
+    modulus: options.modulus ?? 0,
+    clamp_decrements: options.clampDecrements ?? false,
+  };
+}
+
+// Mirrors the Rust `CounterOp` enum used by Batch
+const CounterOpSchema: borsh.Schema = {
+  enum: [
+    { struct: { Increment: 'u32' } },
+    { struct: { Decrement: 'u32' } },
+    { struct: { Set: 'u64' } },
   ],
-]);
+};
+
+type CounterOp = { Increment: number } | { Decrement: number } | { Set: number | bigint };
+
+const CounterInstructionSchema: borsh.Schema = {
+  enum: [
+    { struct: { Increment: 'u32' } },
+    { struct: { Decrement: 'u32' } },
+    { struct: { Initialize: InitializeArgsSchema } },
+    { struct: { Reset: { struct: {} } } },
+    { struct: { Set: 'u64' } },
+    { struct: { TransferAuthority: { struct: { new_authority: { array: { type: 'u8', len: 32 } } } } } },
+    { struct: { AcceptAuthority: { struct: {} } } },
+    { struct: { Close: { struct: {} } } },
+    { struct: { MigrateLegacy: { struct: {} } } },
+    { struct: { InitializePda: { struct: { seed: 'string', args: InitializeArgsSchema } } } },
+    { struct: { Multiply: 'u32' } },
+    { struct: { Divide: 'u32' } },
+    { struct: { CompareAndSwap: { struct: { expected: 'u64', new: 'u64' } } } },
+    { struct: { Batch: { array: { type: CounterOpSchema } } } },
+    { struct: { IncrementByOne: { struct: {} } } },
+    { struct: { MigrateV1ToV2: { struct: {} } } },
+    { struct: { Pause: { struct: {} } } },
+    { struct: { Unpause: { struct: {} } } },
+    { struct: { InitializeConfig: { struct: { max_amount: 'u32' } } } },
+    { struct: { UpdateConfig: { struct: { admin: { array: { type: 'u8', len: 32 } }, paused: 'bool', max_amount: 'u32' } } } },
+    { struct: { InitializeMultisig: { struct: { m: 'u8', signers: { array: { type: { array: { type: 'u8', len: 32 } } } } } } } },
+    { struct: { Approve: { struct: { delegate: { array: { type: 'u8', len: 32 } }, allowance: 'u64' } } } },
+    { struct: { Revoke: { struct: {} } } },
+    { struct: { Merge: { struct: {} } } },
+    { struct: { Clone: { struct: { seed: { option: 'string' } } } } },
+    { struct: { InitializeVotes: { struct: {} } } },
+    { struct: { VoteUp: 'u32' } },
+    { struct: { VoteDown: 'u32' } },
+    { struct: { InitializeMap: { struct: {} } } },
+    { struct: { AddMapEntry: { struct: { name: { array: { type: 'u8', len: 32 } } } } } },
+    { struct: { RemoveMapEntry: { struct: { name: { array: { type: 'u8', len: 32 } } } } } },
+    { struct: { IncrementMapEntry: { struct: { name: { array: { type: 'u8', len: 32 } }, amount: 'u32' } } } },
+    { struct: { DecrementMapEntry: { struct: { name: { array: { type: 'u8', len: 32 } }, amount: 'u32' } } } },
+    { struct: { Migrate: { struct: {} } } },
+    { struct: { Resize: { struct: { len: 'u32' } } } },
+    { struct: { SetMetadata: { struct: { name: 'string', description: 'string' } } } },
+    { struct: { InitializeShard: { struct: { index: 'u16' } } } },
+    { struct: { IncrementShard: 'u32' } },
+    { struct: { Aggregate: { struct: {} } } },
+    { struct: { InitializeHistory: { struct: { capacity: 'u16' } } } },
+    { struct: { SetCallerGuard: { struct: { enabled: 'bool', allowed_programs: { array: { type: { array: { type: 'u8', len: 32 } } } }, forbidden_programs: { array: { type: { array: { type: 'u8', len: 32 } } } } } } } },
+    { struct: { SetExactlyOnce: { struct: { enabled: 'bool' } } } },
+    { struct: { SetFreezeAuthority: { struct: { freeze_authority: { array: { type: 'u8', len: 32 } } } } } },
+    { struct: { EmergencyHalt: { struct: {} } } },
+    { struct: { EmergencyResume: { struct: {} } } },
+    { struct: { SetThresholds: { struct: { thresholds: { array: { type: 'i128' } } } } } },
+    // WithMemo nests a whole instruction, see createWithMemoInstructionData
+    { struct: { WithMemo: { struct: { memo: 'string' } } } },
+    { struct: { View: { struct: {} } } },
+    // Nests a whole instruction too, see createWithOpIdInstructionData
+    { struct: { WithOpId: { struct: { op_id: { array: { type: 'u8', len: 16 } } } } } },
+    { struct: { SetCallback: { struct: { target: 'i128', program: { array: { type: 'u8', len: 32 } }, accounts: { array: { type: { struct: { address: { array: { type: 'u8', len: 32 } }, writable: 'bool' } } } } } } } },
+    { struct: { ClearCallback: { struct: {} } } },
+    { struct: { AddOperator: { struct: { operator: { array: { type: 'u8', len: 32 } }, allowance: 'u64', expiry_slot: 'u64' } } } },
+    { struct: { RemoveOperator: { struct: { operator: { array: { type: 'u8', len: 32 } } } } } },
+    { struct: { SetHooks: { struct: { pre: { option: { array: { type: 'u8', len: 32 } } }, post: { option: { array: { type: 'u8', len: 32 } } } } } } },
+    { struct: { ClearHooks: { struct: {} } } },
+    { struct: { SetFee: { struct: { fee: { option: { struct: { mint: { array: { type: 'u8', len: 32 } }, amount: 'u64', treasury: { array: { type: 'u8', len: 32 } } } } } } } } },
+    { struct: { SetLamportFee: { struct: { lamports: 'u64' } } } },
+    { struct: { WithdrawFees: { struct: { amount: 'u64' } } } },
+    { struct: { SetTokenGate: { struct: { mint: { array: { type: 'u8', len: 32 } }, min_balance: 'u64' } } } },
+    { struct: { ClearTokenGate: { struct: {} } } },
+    { struct: { SetNftAuthority: { struct: { mint: { array: { type: 'u8', len: 32 } } } } } },
+    { struct: { SetGovernanceAuthority: { struct: { governance: { array: { type: 'u8', len: 32 } }, treasury: 'bool' } } } },
+    { struct: { SetBurnMint: { struct: { mint: { array: { type: 'u8', len: 32 } } } } } },
+    { struct: { SetRewards: { struct: { mint: { array: { type: 'u8', len: 32 } }, amount: 'u64', milestones: { array: { type: 'i128' } } } } } },
+    { struct: { ClearRewards: { struct: {} } } },
+    { struct: { InitializeEscrow: { struct: { beneficiary: { array: { type: 'u8', len: 32 } }, target: 'i128', deadline_slot: 'u64' } } } },
+    { struct: { DepositEscrow: { struct: { lamports: 'u64' } } } },
+    { struct: { Settle: { struct: {} } } },
+    { struct: { RequirePermits: { struct: {} } } },
+    { struct: { ClearPermits: { struct: {} } } },
+    { struct: { SetCooldown: { struct: { slots: 'u64', daily_cap: 'u64' } } } },
+    { struct: { ClearCooldown: { struct: {} } } },
+    { struct: { SetEpochCap: { struct: { cap: 'u64' } } } },
+    { struct: { SetEpochReset: { struct: { enabled: 'bool' } } } },
+    { struct: { SetDecay: { struct: { rate: 'u64', unit: 'u8' } } } },
+    { struct: { ClearDecay: { struct: {} } } },
+    { struct: { SetCrankReward: { struct: { lamports: 'u64', epoch_budget: 'u64' } } } },
+    { struct: { Crank: { struct: {} } } },
+    { struct: { Lock: { struct: { until_slot: 'u64' } } } },
+    { struct: { SetExpiry: { struct: { expires_at: 'i64' } } } },
+    { struct: { SetEma: { struct: { alpha_bps: 'u16' } } } },
+  ],
+};
+
+function encode(instruction: object) {
+  return Buffer.from(borsh.serialize(CounterInstructionSchema, instruction));
+}
 
 export function createIncrementInstructionData(value: number) {
-  const instruction = new CounterInstruction(CounterInstructionType.Increment, value);
-  return Buffer.from(borsh.serialize(CounterInstructionSchema as unknown as borsh.Schema, instruction));
+  return encode({ Increment: value });
 }
 
 export function createDecrementInstructionData(value: number) {
-  const instruction = new CounterInstruction(CounterInstructionType.Decrement, value);
-  return Buffer.from(borsh.serialize(CounterInstructionSchema as unknown as borsh.Schema, instruction));
+  return encode({ Decrement: value });
+}
+
+export function createInitializeInstructionData(overflowMode = OverflowMode.Checked, kind = CounterKind.Unsigned, options: CounterOptions = {}) {
+  return encode({ Initialize: initializeArgs(overflowMode, kind, options) });
+}
+
+export function createResetInstructionData() {
+  return encode({ Reset: {} });
+}
+
+export function createSetInstructionData(value: number | bigint) {
+  return encode({ Set: value });
+}
+
+export function createTransferAuthorityInstructionData(newAuthority: PublicKey) {
+  return encode({ TransferAuthority: { new_authority: newAuthority.toBytes() } });
+}
+
+export function createAcceptAuthorityInstructionData() {
+  return encode({ AcceptAuthority: {} });
+}
+
+export function createCloseInstructionData() {
+  return encode({ Close: {} });
+}
+
+export function createMigrateLegacyInstructionData() {
+  return encode({ MigrateLegacy: {} });
+}
+
+export function createInitializePdaInstructionData(seed: string, overflowMode = OverflowMode.Checked, kind = CounterKind.Unsigned, options: CounterOptions = {}) {
+  return encode({ InitializePda: { seed, args: initializeArgs(overflowMode, kind, options) } });
+}
+
+export function createMultiplyInstructionData(factor: number) {
+  return encode({ Multiply: factor });
+}
+
+export function createDivideInstructionData(divisor: number) {
+  return encode({ Divide: divisor });
+}
+
+export function createCompareAndSwapInstructionData(expected: number | bigint, value: number | bigint) {
+  return encode({ CompareAndSwap: { expected, new: value } });
+}
+
+export function createBatchInstructionData(ops: CounterOp[]) {
+  return encode({ Batch: ops });
+}
+
+/**
+ * IncrementByOne is a single byte holding its variant index and no payload,
+ * which the program handles without Borsh decoding
+ */
+export function createIncrementByOneInstructionData() {
+  return Buffer.from([CounterInstructionType.IncrementByOne]);
+}
+
+export function createMigrateV1ToV2InstructionData() {
+  return encode({ MigrateV1ToV2: {} });
+}
+
+export function createPauseInstructionData() {
+  return encode({ Pause: {} });
+}
+
+export function createUnpauseInstructionData() {
+  return encode({ Unpause: {} });
+}
+
+export function createInitializeConfigInstructionData(maxAmount = 0) {
+  return encode({ InitializeConfig: { max_amount: maxAmount } });
+}
+
+export function createUpdateConfigInstructionData(admin: PublicKey, paused: boolean, maxAmount = 0) {
+  return encode({ UpdateConfig: { admin: admin.toBytes(), paused, max_amount: maxAmount } });
+}
+
+export function createInitializeMultisigInstructionData(m: number, signers: PublicKey[]) {
+  return encode({ InitializeMultisig: { m, signers: signers.map((signer) => signer.toBytes()) } });
+}
+
+export function createApproveInstructionData(delegate: PublicKey, allowance: number | bigint) {
+  return encode({ Approve: { delegate: delegate.toBytes(), allowance } });
+}
+
+export function createRevokeInstructionData() {
+  return encode({ Revoke: {} });
+}
+
+export function createMergeInstructionData() {
+  return encode({ Merge: {} });
+}
+
+/**
+ * Snapshot a counter into a fresh keypair account, or into the PDA its
+ * authority owns under `seed`
+ */
+export function createCloneInstructionData(seed?: string) {
+  return encode({ Clone: { seed: seed ?? null } });
+}
+
+export function createInitializeVotesInstructionData() {
+  return encode({ InitializeVotes: {} });
+}
+
+export function createVoteUpInstructionData(amount: number) {
+  return encode({ VoteUp: amount });
+}
+
+export function createVoteDownInstructionData(amount: number) {
+  return encode({ VoteDown: amount });
+}
+
+export function createMigrateInstructionData() {
+  return encode({ Migrate: {} });
+}
+
+/**
+ * Grow a counter account to `len` bytes, making room for optional data after the counter
+ */
+export function createResizeInstructionData(len: number) {
+  return encode({ Resize: { len } });
+}
+
+/**
+ * Label a counter with a name of at most 32 bytes and a description of at most 200
+ */
+export function createSetMetadataInstructionData(name: string, description: string) {
+  return encode({ SetMetadata: { name, description } });
+}
+
+export function createInitializeShardInstructionData(index: number) {
+  return encode({ InitializeShard: { index } });
+}
+
+export function createIncrementShardInstructionData(amount: number) {
+  return encode({ IncrementShard: amount });
+}
+
+/**
+ * Drain the shards passed after the counter and the config into it
+ */
+export function createAggregateInstructionData() {
+  return encode({ Aggregate: {} });
+}
+
+/**
+ * Create a counter's history account, keeping its last `capacity` changes
+ */
+export function createInitializeHistoryInstructionData(capacity: number) {
+  return encode({ InitializeHistory: { capacity } });
+}
+
+/**
+ * Turn the caller guard on or off, allowing CPIs only from `allowedPrograms` while on,
+ * and reject counter changes in any transaction that includes one of `forbiddenPrograms`
+ */
+export function createSetCallerGuardInstructionData(enabled: boolean, allowedPrograms: PublicKey[], forbiddenPrograms: PublicKey[] = []) {
+  return encode({
+    SetCallerGuard: {
+      enabled,
+      allowed_programs: allowedPrograms.map((program) => program.toBytes()),
+      forbidden_programs: forbiddenPrograms.map((program) => program.toBytes()),
+    },
+  });
+}
+
+/**
+ * Let a transaction change the counter with at most one instruction, or lift that limit
+ */
+export function createSetExactlyOnceInstructionData(enabled: boolean) {
+  return encode({ SetExactlyOnce: { enabled } });
+}
+
+/**
+ * Let `freezeAuthority` pause and unpause the counter instead of its authority;
+ * the default key hands those powers back
+ */
+export function createSetFreezeAuthorityInstructionData(freezeAuthority: PublicKey) {
+  return encode({ SetFreezeAuthority: { freeze_authority: freezeAuthority.toBytes() } });
+}
+
+/**
+ * Halt every counter change program-wide; only the config admin may send it
+ */
+export function createEmergencyHaltInstructionData() {
+  return encode({ EmergencyHalt: {} });
+}
+
+export function createEmergencyResumeInstructionData() {
+  return encode({ EmergencyResume: {} });
+}
+
+/**
+ * Watch up to four values of the counter, each crossing emitting a `ThresholdCrossed` event
+ */
+export function createSetThresholdsInstructionData(thresholds: (bigint | number)[]) {
+  return encode({ SetThresholds: { thresholds } });
+}
+
+/**
+ * Report the counter's full state, as a `CounterView`, without changing anything
+ */
+export function createViewInstructionData() {
+  return encode({ View: {} });
+}
+
+/**
+ * Invoke `program` with `accounts` once a change moves the count onto or past `target`
+ */
+export function createSetCallbackInstructionData(target: bigint | number, program: PublicKey, accounts: { address: PublicKey, writable: boolean }[] = []) {
+  return encode({
+    SetCallback: {
+      target: BigInt(target),
+      program: program.toBytes(),
+      accounts: accounts.map(({ address, writable }) => ({ address: address.toBytes(), writable })),
+    },
+  });
+}
+
+/**
+ * Disarm the counter's callback and close its callback account
+ */
+export function createClearCallbackInstructionData() {
+  return encode({ ClearCallback: {} });
+}
+
+/**
+ * Let `operator` decrement by up to `allowance` until `expirySlot`, or for good if it is 0
+ */
+export function createAddOperatorInstructionData(operator: PublicKey, allowance: bigint | number, expirySlot: bigint | number = 0) {
+  return encode({ AddOperator: { operator: operator.toBytes(), allowance: BigInt(allowance), expiry_slot: BigInt(expirySlot) } });
+}
+
+/**
+ * Remove `operator` from the counter's operator list
+ */
+export function createRemoveOperatorInstructionData(operator: PublicKey) {
+  return encode({ RemoveOperator: { operator: operator.toBytes() } });
+}
+
+/**
+ * Invoke `pre` before and `post` after every change; at least one must be set
+ */
+export function createSetHooksInstructionData(pre: PublicKey | null, post: PublicKey | null) {
+  return encode({ SetHooks: { pre: pre?.toBytes() ?? null, post: post?.toBytes() ?? null } });
+}
+
+/**
+ * Remove the counter's hooks and close its hooks account
+ */
+export function createClearHooksInstructionData() {
+  return encode({ ClearHooks: {} });
+}
+
+/**
+ * Charge `amount` of `mint`, paid to the `treasury` token account, for every
+ * change; `null` stops charging
+ */
+export function createSetFeeInstructionData(fee: { mint: PublicKey, amount: number | bigint, treasury: PublicKey } | null) {
+  return encode({ SetFee: { fee: fee && { mint: fee.mint.toBytes(), amount: BigInt(fee.amount), treasury: fee.treasury.toBytes() } } });
+}
+
+/**
+ * Charge `lamports`, collected in the fee vault, for every change; 0 stops charging
+ */
+export function createSetLamportFeeInstructionData(lamports: number | bigint) {
+  return encode({ SetLamportFee: { lamports: BigInt(lamports) } });
+}
+
+/**
+ * Move `amount` collected lamports out of the fee vault
+ */
+export function createWithdrawFeesInstructionData(amount: number | bigint) {
+  return encode({ WithdrawFees: { amount: BigInt(amount) } });
+}
+
+/**
+ * Let only holders of at least `minBalance` tokens of `mint` increment the counter
+ */
+export function createSetTokenGateInstructionData(mint: PublicKey, minBalance: number | bigint) {
+  return encode({ SetTokenGate: { mint: mint.toBytes(), min_balance: BigInt(minBalance) } });
+}
+
+/**
+ * Open the counter's increments to anyone again and close its token gate
+ */
+export function createClearTokenGateInstructionData() {
+  return encode({ ClearTokenGate: {} });
 }
 
-export { CounterInstruction, CounterInstructionType, CounterInstructionSchema };
-  
\ No newline at end of file
+/**
+ * Hand the counter to whoever holds the NFT `mint`, whose token account then
+ * goes where the authority does, with the holder signing after every other account
+ */
+export function createSetNftAuthorityInstructionData(mint: PublicKey) {
+  return encode({ SetNftAuthority: { mint: mint.toBytes() } });
+}
+
+/**
+ * Hand a counter, or the program config, to an SPL Governance governance or
+ * its native treasury, which then signs for it only in passed proposals
+ */
+export function createSetGovernanceAuthorityInstructionData(governance: PublicKey, treasury = false) {
+  return encode({ SetGovernanceAuthority: { governance: governance.toBytes(), treasury } });
+}
+
+/**
+ * Make every increment of the counter burn as many base units of `mint` as it adds
+ */
+export function createSetBurnMintInstructionData(mint: PublicKey) {
+  return encode({ SetBurnMint: { mint: mint.toBytes() } });
+}
+
+/**
+ * Mint `amount` base units of `mint` to the actor whose increment takes the
+ * counter to each of `milestones`, strictly ascending
+ */
+export function createSetRewardsInstructionData(mint: PublicKey, amount: number | bigint, milestones: (number | bigint)[]) {
+  return encode({ SetRewards: { mint: mint.toBytes(), amount: BigInt(amount), milestones: milestones.map(BigInt) } });
+}
+
+/**
+ * Stop rewarding milestones and close the counter's rewards account
+ */
+export function createClearRewardsInstructionData() {
+  return encode({ ClearRewards: {} });
+}
+
+/**
+ * Open an escrow that pays `beneficiary` once the counter reaches `target`,
+ * and refunds depositors once `deadlineSlot` passes without it
+ */
+export function createInitializeEscrowInstructionData(beneficiary: PublicKey, target: number | bigint, deadlineSlot: number | bigint) {
+  return encode({ InitializeEscrow: { beneficiary: beneficiary.toBytes(), target: BigInt(target), deadline_slot: BigInt(deadlineSlot) } });
+}
+
+/**
+ * Deposit `lamports` into a counter's escrow
+ */
+export function createDepositEscrowInstructionData(lamports: number | bigint) {
+  return encode({ DepositEscrow: { lamports: BigInt(lamports) } });
+}
+
+/**
+ * Release an escrow to its beneficiary, or refund a deposit record
+ */
+export function createSettleInstructionData() {
+  return encode({ Settle: {} });
+}
+
+/**
+ * Require every increment to carry a permit signed by the counter's authority
+ */
+export function createRequirePermitsInstructionData() {
+  return encode({ RequirePermits: {} });
+}
+
+/**
+ * Let increments go without permits and close the counter's permit nonce account
+ */
+export function createClearPermitsInstructionData() {
+  return encode({ ClearPermits: {} });
+}
+
+/**
+ * Make each actor wait `slots` slots between increments and add at most
+ * `dailyCap` per day, either of them 0 to leave it off
+ */
+export function createSetCooldownInstructionData(slots: number | bigint, dailyCap: number | bigint = 0) {
+  return encode({ SetCooldown: { slots: BigInt(slots), daily_cap: BigInt(dailyCap) } });
+}
+
+/**
+ * Stop throttling increments and close the counter's cooldown
+ */
+export function createClearCooldownInstructionData() {
+  return encode({ ClearCooldown: {} });
+}
+
+/**
+ * Cap how far changes may move any one counter per epoch; 0 lifts the cap
+ */
+export function createSetEpochCapInstructionData(cap: number | bigint) {
+  return encode({ SetEpochCap: { cap: BigInt(cap) } });
+}
+
+/**
+ * Make the first change in each new epoch start the counter over from zero
+ */
+export function createSetEpochResetInstructionData(enabled: boolean) {
+  return encode({ SetEpochReset: { enabled } });
+}
+
+/**
+ * Make the counter's value fade towards zero by `rate` per slot or per second
+ */
+export function createSetDecayInstructionData(rate: number | bigint, unit = DecayUnit.Slot) {
+  return encode({ SetDecay: { rate: BigInt(rate), unit } });
+}
+
+/**
+ * Stop the counter's value from decaying and close its decay
+ */
+export function createClearDecayInstructionData() {
+  return encode({ ClearDecay: {} });
+}
+
+/**
+ * Pay each crank with work to do `lamports` out of the fee vault, or stop with 0,
+ * and no more than `epochBudget` in total per epoch
+ */
+export function createSetCrankRewardInstructionData(lamports: number | bigint, epochBudget: number | bigint) {
+  return encode({ SetCrankReward: { lamports: BigInt(lamports), epoch_budget: BigInt(epochBudget) } });
+}
+
+/**
+ * Bring a counter's decay, epoch and callback up to date, which anyone may do
+ */
+export function createCrankInstructionData() {
+  return encode({ Crank: {} });
+}
+
+/**
+ * Lock the counter against changes until `untilSlot`
+ */
+export function createLockInstructionData(untilSlot: number | bigint) {
+  return encode({ Lock: { until_slot: BigInt(untilSlot) } });
+}
+
+/**
+ * Make the counter read-only from the unix timestamp `expiresAt`, or never with 0
+ */
+export function createSetExpiryInstructionData(expiresAt: number | bigint) {
+  return encode({ SetExpiry: { expires_at: BigInt(expiresAt) } });
+}
+
+/**
+ * Keep a moving average of the counter's value, moved `alphaBps` basis points
+ * of the way to each new value, or stop keeping one with 0
+ */
+export function createSetEmaInstructionData(alphaBps: number) {
+  return encode({ SetEma: { alpha_bps: alphaBps } });
+}
+
+// Mirrors `Counter::EMA_SCALE`: the moving average keeps nine decimals
+export const EMA_SCALE = 1_000_000_000n;
+
+/**
+ * The message the authority signs to allow one increment of `counter` by `amount`
+ * Mirrors `permit_message` in the Rust program
+ *
+ * Using a permit voids every unused one with a lower nonce, so nonces should be
+ * given out in the order the permits will be used.
+ */
+export function createPermitMessage(counter: PublicKey, amount: number | bigint, nonce: number | bigint, expirySlot: number | bigint) {
+  const message = Buffer.alloc(56);
+  counter.toBuffer().copy(message);
+  message.writeBigUInt64LE(BigInt(amount), 32);
+  message.writeBigUInt64LE(BigInt(nonce), 40);
+  message.writeBigUInt64LE(BigInt(expirySlot), 48);
+  return message;
+}
+
+// Address of the SPL Token program, which fees are transferred with
+export const TOKEN_PROGRAM_ID = new PublicKey("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+// Address of the SPL Memo program, passed after the accounts of a `WithMemo`'s instruction
+export const MEMO_PROGRAM_ID = new PublicKey("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+/**
+ * Wrap the encoded `instruction`, which must change a count, so `memo` is
+ * forwarded to the SPL Memo program
+ *
+ * Written by hand because the variant nests a whole instruction: the tag,
+ * the memo as a Borsh string, then the wrapped data unchanged.
+ */
+export function createWithMemoInstructionData(memo: string, instruction: Buffer) {
+  const text = Buffer.from(memo, "utf8");
+  const length = Buffer.alloc(4);
+  length.writeUInt32LE(text.length);
+  return Buffer.concat([Buffer.from([CounterInstructionType.WithMemo]), length, text, instruction]);
+}
+
+/**
+ * Wrap the encoded `instruction`, which must change a count, so the 16-byte
+ * `opId` is echoed in its `CounterUpdated` event. Pass the result to
+ * createWithMemoInstructionData to add a memo as well
+ */
+export function createWithOpIdInstructionData(opId: Uint8Array, instruction: Buffer) {
+  return Buffer.concat([Buffer.from([CounterInstructionType.WithOpId]), Buffer.from(opId), instruction]);
+}
+
+// Mirrors the Rust `CounterView`, which View logs and returns
+export const counterViewSchema: borsh.Schema = {
+  struct: {
+    counter: { array: { type: 'u8', len: 32 } },
+    version: 'u8',
+    value: 'i128',
+    kind: 'u8',
+    overflow_mode: 'u8',
+    min: 'i128',
+    max: 'i128',
+    step: 'u32',
+    modulus: 'u64',
+    authority: { array: { type: 'u8', len: 32 } },
+    pending_authority: { array: { type: 'u8', len: 32 } },
+    delegate: { array: { type: 'u8', len: 32 } },
+    allowance: 'u64',
+    freeze_authority: { array: { type: 'u8', len: 32 } },
+    paused: 'bool',
+    clamp_decrements: 'bool',
+    exactly_once: 'bool',
+    has_history: 'bool',
+    thresholds: { array: { type: 'i128' } },
+    total_increments: 'u64',
+    total_decrements: 'u64',
+    high_water: 'i128',
+    low_water: 'i128',
+    last_updated_slot: 'u64',
+    last_updated_ts: 'i64',
+    has_callback: 'bool',
+    has_hooks: 'bool',
+    token_gated: 'bool',
+    nft_authority: 'bool',
+    burn_backed: 'bool',
+    has_rewards: 'bool',
+    permit_only: 'bool',
+    has_cooldown: 'bool',
+    epoch_change: 'u64',
+    epoch: 'u64',
+    epoch_reset: 'bool',
+    decays: 'bool',
+    cumulative_value: 'i128',
+    lock_status: 'u8',
+    locked_until: 'u64',
+    expires_at: 'i64',
+    expired: 'bool',
+    ema: { option: 'i128' },
+  },
+};
+
+// Mirrors the Rust `EVENT_SCHEMA_VERSION`, the byte each logged event starts with
+export const EVENT_SCHEMA_VERSION = 16;
+
+// Mirrors the Rust `Event` enum, logged after the schema version as `Program data: <base64>`
+export const eventSchema: borsh.Schema = {
+  enum: [
+    { struct: { EmergencyHalt: { struct: { admin: { array: { type: 'u8', len: 32 } }, slot: 'u64' } } } },
+    { struct: { EmergencyResume: { struct: { admin: { array: { type: 'u8', len: 32 } }, slot: 'u64' } } } },
+    { struct: { CounterUpdated: { struct: { counter: { array: { type: 'u8', len: 32 } }, operation: 'u8', old_value: 'i128', new_value: 'i128', actor: { array: { type: 'u8', len: 32 } }, slot: 'u64', op_id: { option: { array: { type: 'u8', len: 16 } } } } } } },
+    { struct: { ThresholdCrossed: { struct: { counter: { array: { type: 'u8', len: 32 } }, threshold: 'i128', direction: 'u8', old_value: 'i128', new_value: 'i128', slot: 'u64' } } } },
+    { struct: { CounterViewed: counterViewSchema } },
+    { struct: { TargetReached: { struct: { counter: { array: { type: 'u8', len: 32 } }, target: 'i128', program: { array: { type: 'u8', len: 32 } }, old_value: 'i128', new_value: 'i128', slot: 'u64' } } } },
+    { struct: { EpochReset: { struct: { counter: { array: { type: 'u8', len: 32 } }, epoch: 'u64', final_value: 'i128', slot: 'u64' } } } },
+  ],
+};
+
+// Mirrors the Rust `Direction` enum reported in `ThresholdCrossed` events
+export enum Direction {
+  Up = 0,
+  Down = 1,
+}
+
+// Mirrors the Rust `Operation` enum reported in `CounterUpdated` events
+export enum Operation {
+  Increment = 0,
+  Decrement = 1,
+  Multiply = 2,
+  Divide = 3,
+  CompareAndSwap = 4,
+  Reset = 5,
+  Set = 6,
+  Batch = 7,
+  Merge = 8,
+  Aggregate = 9,
+  Crank = 10,
+  Decay = 11,
+}
+
+/**
+ * Anchor's `sha256("event:<name>")[..8]` discriminator for an event
+ */
+export function anchorEventDiscriminator(name: string) {
+  return createHash("sha256").update(`event:${name}`).digest().subarray(0, 8);
+}
+
+/**
+ * Rewrite an event as a build with the `anchor` feature logs it: the
+ * discriminator, then the schema version and the same fields
+ */
+export function toAnchorEvent(data: Buffer) {
+  const variants = (eventSchema as { enum: { struct: object }[] }).enum;
+  const name = Object.keys(variants[data[1]].struct)[0];
+  return Buffer.concat([anchorEventDiscriminator(name), data.subarray(0, 1), data.subarray(2)]);
+}
+
+/**
+ * Decode one `Program data:` payload in either encoding, as `{ <Name>: fields }`,
+ * refusing a schema version these decoders were not written for
+ */
+export function decodeEvent(data: Buffer): any {
+  const variants = (eventSchema as { enum: { struct: object }[] }).enum;
+  const index = variants.findIndex(({ struct }) =>
+    data.length >= 8 && anchorEventDiscriminator(Object.keys(struct)[0]).equals(data.subarray(0, 8)));
+  const [version, event] = index < 0
+    ? [data[0], data.subarray(1)]
+    : [data[8], Buffer.concat([Buffer.from([index]), data.subarray(9)])];
+  if (version !== EVENT_SCHEMA_VERSION) {
+    throw new Error(`Unknown event schema version ${version}`);
+  }
+  return borsh.deserialize(eventSchema, event);
+}
+
+/**
+ * Decode every event in a transaction's log messages
+ */
+export function eventsOf(logs: string[]) {
+  return logs
+    .filter((line) => line.startsWith("Program data: "))
+    .map((line) => decodeEvent(Buffer.from(line.slice("Program data: ".length), "base64")));
+}
+
+/**
+ * Encode a counter map entry name as the zero-padded 32 bytes the program expects
+ */
+export function mapEntryName(name: string) {
+  const bytes = new Uint8Array(32);
+  bytes.set(Buffer.from(name).subarray(0, 32));
+  return bytes;
+}
+
+export function createInitializeMapInstructionData() {
+  return encode({ InitializeMap: {} });
+}
+
+export function createAddMapEntryInstructionData(name: string) {
+  return encode({ AddMapEntry: { name: mapEntryName(name) } });
+}
+
+export function createRemoveMapEntryInstructionData(name: string) {
+  return encode({ RemoveMapEntry: { name: mapEntryName(name) } });
+}
+
+export function createIncrementMapEntryInstructionData(name: string, amount: number) {
+  return encode({ IncrementMapEntry: { name: mapEntryName(name), amount } });
+}
+
+export function createDecrementMapEntryInstructionData(name: string, amount: number) {
+  return encode({ DecrementMapEntry: { name: mapEntryName(name), amount } });
+}
+
+// Fixed byte offsets within a counter account, for getProgramAccounts memcmp
+// filters. Mirror COUNTER_DISCRIMINATOR_OFFSET, COUNTER_VERSION_OFFSET and
+// COUNTER_AUTHORITY_OFFSET in the Rust program
+export const COUNTER_DISCRIMINATOR = Buffer.from("counter\0");
+export const COUNTER_DISCRIMINATOR_OFFSET = 0;
+export const COUNTER_VERSION_OFFSET = 8;
+export const COUNTER_AUTHORITY_OFFSET = 16;
+
+/**
+ * Filters selecting the counters `authority` owns in getProgramAccounts
+ */
+export function counterAuthorityFilters(authority: PublicKey) {
+  return [
+    { memcmp: { offset: COUNTER_DISCRIMINATOR_OFFSET, bytes: COUNTER_DISCRIMINATOR.toString("base64"), encoding: "base64" as const } },
+    { memcmp: { offset: COUNTER_AUTHORITY_OFFSET, bytes: authority.toBase58() } },
+  ];
+}
+
+/**
+ * Derive the address of the program config, which every instruction that
+ * changes a counter's value expects. Mirrors `find_config_address`
+ */
+export function findConfigAddress(programId: PublicKey) {
+  return PublicKey.findProgramAddressSync([Buffer.from("config")], programId);
+}
+
+/**
+ * Derive the address of the PDA counter `authority` owns under `seed`
+ * Mirrors `find_counter_address` in the Rust program
+ */
+export function findCounterAddress(programId: PublicKey, authority: PublicKey, seed: string) {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("counter"), authority.toBuffer(), Buffer.from(seed)],
+    programId,
+  );
+}
+
+/**
+ * Derive the address of the history account of `counter`
+ * Mirrors `find_history_address` in the Rust program
+ */
+export function findHistoryAddress(programId: PublicKey, counter: PublicKey) {
+  return PublicKey.findProgramAddressSync([Buffer.from("history"), counter.toBuffer()], programId);
+}
+
+/**
+ * Derive the address of the callback account of `counter`
+ * Mirrors `find_callback_address` in the Rust program
+ */
+export function findCallbackAddress(programId: PublicKey, counter: PublicKey) {
+  return PublicKey.findProgramAddressSync([Buffer.from("callback"), counter.toBuffer()], programId);
+}
+
+/**
+ * Derive the address of the operator list of `counter`
+ * Mirrors `find_operators_address` in the Rust program
+ */
+export function findOperatorsAddress(programId: PublicKey, counter: PublicKey) {
+  return PublicKey.findProgramAddressSync([Buffer.from("operators"), counter.toBuffer()], programId);
+}
+
+/**
+ * Derive the address of the hooks account of `counter`
+ * Mirrors `find_hooks_address` in the Rust program
+ */
+export function findHooksAddress(programId: PublicKey, counter: PublicKey) {
+  return PublicKey.findProgramAddressSync([Buffer.from("hooks"), counter.toBuffer()], programId);
+}
+
+/**
+ * Derive the address of the program's fee vault
+ * Mirrors `find_fee_vault_address` in the Rust program
+ */
+export function findFeeVaultAddress(programId: PublicKey) {
+  return PublicKey.findProgramAddressSync([Buffer.from("fee_vault")], programId);
+}
+
+/**
+ * Derive the address of the token gate of `counter`
+ * Mirrors `find_token_gate_address` in the Rust program
+ */
+export function findTokenGateAddress(programId: PublicKey, counter: PublicKey) {
+  return PublicKey.findProgramAddressSync([Buffer.from("gate"), counter.toBuffer()], programId);
+}
+
+/**
+ * Derive the address of the burn account of `counter`
+ * Mirrors `find_burn_address` in the Rust program
+ */
+export function findBurnAddress(programId: PublicKey, counter: PublicKey) {
+  return PublicKey.findProgramAddressSync([Buffer.from("burn"), counter.toBuffer()], programId);
+}
+
+/**
+ * Derive the address of the rewards account of `counter`, the authority of its reward mint
+ * Mirrors `find_rewards_address` in the Rust program
+ */
+export function findRewardsAddress(programId: PublicKey, counter: PublicKey) {
+  return PublicKey.findProgramAddressSync([Buffer.from("rewards"), counter.toBuffer()], programId);
+}
+
+/**
+ * Derive the address of the escrow of `counter`
+ * Mirrors `find_escrow_address` in the Rust program
+ */
+export function findEscrowAddress(programId: PublicKey, counter: PublicKey) {
+  return PublicKey.findProgramAddressSync([Buffer.from("escrow"), counter.toBuffer()], programId);
+}
+
+/**
+ * Derive the address of the record of what `depositor` put into `escrow`
+ * Mirrors `find_escrow_deposit_address` in the Rust program
+ */
+export function findEscrowDepositAddress(programId: PublicKey, escrow: PublicKey, depositor: PublicKey) {
+  return PublicKey.findProgramAddressSync([Buffer.from("deposit"), escrow.toBuffer(), depositor.toBuffer()], programId);
+}
+
+/**
+ * Derive the address of the permit nonce account of `counter`
+ * Mirrors `find_permits_address` in the Rust program
+ */
+export function findPermitsAddress(programId: PublicKey, counter: PublicKey) {
+  return PublicKey.findProgramAddressSync([Buffer.from("permits"), counter.toBuffer()], programId);
+}
+
+/**
+ * Derive the address of the cooldown of `counter`
+ * Mirrors `find_cooldown_address` in the Rust program
+ */
+export function findCooldownAddress(programId: PublicKey, counter: PublicKey) {
+  return PublicKey.findProgramAddressSync([Buffer.from("cooldown"), counter.toBuffer()], programId);
+}
+
+/**
+ * Derive the address of the stamp recording when `actor` last incremented `counter`
+ * Mirrors `find_cooldown_stamp_address` in the Rust program
+ */
+export function findCooldownStampAddress(programId: PublicKey, counter: PublicKey, actor: PublicKey) {
+  return PublicKey.findProgramAddressSync([Buffer.from("stamp"), counter.toBuffer(), actor.toBuffer()], programId);
+}
+
+/**
+ * The average value of a counter between two readings of its cumulative
+ * value, each paired with its unix timestamp
+ * Mirrors `time_weighted_average` in the Rust program
+ */
+export function timeWeightedAverage(start: [bigint, number | bigint], end: [bigint, number | bigint]) {
+  const elapsed = BigInt(end[1]) - BigInt(start[1]);
+  if (elapsed <= 0n) {
+    return null;
+  }
+  // The accumulator wraps as an i128
+  const difference = BigInt.asIntN(128, end[0] - start[0]);
+  const average = difference / elapsed;
+  return average;
+}
+
+/**
+ * Derive the address of the decay of `counter`
+ * Mirrors `find_decay_address` in the Rust program
+ */
+export function findDecayAddress(programId: PublicKey, counter: PublicKey) {
+  return PublicKey.findProgramAddressSync([Buffer.from("decay"), counter.toBuffer()], programId);
+}
+
+// Address of the SPL Associated Token Account program, whose accounts receive milestone rewards
+export const ASSOCIATED_TOKEN_PROGRAM_ID = new PublicKey("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+/**
+ * Derive the associated token account of `wallet` for `mint`
+ */
+export function findAssociatedTokenAddress(wallet: PublicKey, mint: PublicKey, tokenProgramId = TOKEN_PROGRAM_ID) {
+  return PublicKey.findProgramAddressSync([wallet.toBuffer(), tokenProgramId.toBuffer(), mint.toBuffer()], ASSOCIATED_TOKEN_PROGRAM_ID)[0];
+}
+
+/**
+ * Derive the native treasury of an SPL Governance `governance`, the authority
+ * `SetGovernanceAuthority` sets with `treasury`
+ */
+export function findNativeTreasuryAddress(governanceProgramId: PublicKey, governance: PublicKey) {
+  return PublicKey.findProgramAddressSync([Buffer.from("native-treasury"), governance.toBuffer()], governanceProgramId);
+}
+
+/**
+ * Derive the address of shard `index` of `counter`
+ * Mirrors `find_shard_address` in the Rust program
+ */
+export function findShardAddress(programId: PublicKey, counter: PublicKey, index: number) {
+  const indexBytes = Buffer.alloc(2);
+  indexBytes.writeUInt16LE(index);
+  return PublicKey.findProgramAddressSync([Buffer.from("shard"), counter.toBuffer(), indexBytes], programId);
+}
+
+export { CounterError, CounterInstruction, CounterInstructionType, CounterInstructionSchema, OverflowMode, CounterKind, CounterFlag, DecayUnit, LockStatus };
+export type { CounterOp, CounterOptions };
+
+/**
+ * The new count in the return data of an instruction that changed one: a
+ * little-endian i128
+ */
+export function decodeReturnData(data: Buffer) {
+  if (data.length !== 16) {
+    throw new Error(`Expected 16 bytes of return data, got ${data.length}`);
+  }
+  return data.readBigInt64LE(8) * 2n ** 64n + data.readBigUInt64LE(0);
+}
@@ -0,0 +1,333 @@
+//! Milestone rewards minted through the SPL Token program, which
+//! `solana-program-test` loads alongside the program
+
+use solana_counter_program::{
+    CounterInstruction, CounterOp, ID, REWARDS_SEED, builders::*, find_config_address,
+    find_counter_address, find_rewards_address, process_instruction,
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+};
+use solana_program_test::{BanksClient, ProgramTest, processor, tokio};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey = pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+/// An account of the token program holding `data`
+fn token_account(data: Vec<u8>) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: TOKEN_PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// An SPL Token mint with no supply yet, minted by `authority`
+fn mint(authority: &Pubkey) -> Account {
+    let mut data = vec![0; 82];
+    data[..4].copy_from_slice(&1u32.to_le_bytes());
+    data[4..36].copy_from_slice(authority.as_ref());
+    data[44] = 2; // decimals
+    data[45] = 1; // initialized
+    token_account(data)
+}
+
+/// An empty SPL Token account of `owner` for `mint`
+fn wallet(mint: &Pubkey, owner: &Pubkey) -> Account {
+    let mut data = vec![0; 165];
+    data[..32].copy_from_slice(mint.as_ref());
+    data[32..64].copy_from_slice(owner.as_ref());
+    data[108] = 1; // initialized
+    token_account(data)
+}
+
+/// The associated token account of `owner` for `mint`
+fn associated_token_address(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[owner.as_ref(), TOKEN_PROGRAM_ID.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
+
+/// The amount a token account holds, the u64 at offset 64
+async fn balance(banks_client: &mut BanksClient, account: &Pubkey) -> u64 {
+    let account = banks_client.get_account(*account).await.unwrap().unwrap();
+    u64::from_le_bytes(account.data[64..72].try_into().unwrap())
+}
+
+/// A counter paying 500 base units at 5 and at 10, and the accounts an
+/// increment of it passes for the reward
+struct Rewarded {
+    counter: Pubkey,
+    reward_accounts: Vec<AccountMeta>,
+}
+
+/// Start the program with a mint whose authority is `mint_authority` of the
+/// counter, and the payer's associated token account for it, then create
+/// the payer's counter under `seed` with its rewards
+async fn start(
+    mint_authority: impl FnOnce(&Pubkey) -> Pubkey,
+    seed: &str,
+) -> (BanksClient, Keypair, Rewarded) {
+    let mut program_test = ProgramTest::new(
+        "solana_counter_program",
+        ID,
+        processor!(process_instruction),
+    );
+    let payer = Keypair::new();
+    program_test.add_account(
+        payer.pubkey(),
+        Account::new(10_000_000_000, 0, &system_program::ID),
+    );
+    let (counter, _) = find_counter_address(&ID, &payer.pubkey(), seed);
+    let (rewards, _) = find_rewards_address(&ID, &counter);
+    let mint_address = Pubkey::new_unique();
+    let destination = associated_token_address(&payer.pubkey(), &mint_address);
+    program_test.add_account(mint_address, mint(&mint_authority(&counter)));
+    program_test.add_account(destination, wallet(&mint_address, &payer.pubkey()));
+    let (mut banks_client, _, _) = program_test.start().await;
+
+    let set_rewards = Instruction::new_with_borsh(
+        ID,
+        &CounterInstruction::SetRewards {
+            mint: mint_address,
+            amount: 500,
+            milestones: vec![5, 10],
+        },
+        vec![
+            AccountMeta::new(counter, false),
+            AccountMeta::new_readonly(payer.pubkey(), true),
+            AccountMeta::new(rewards, false),
+            AccountMeta::new(payer.pubkey(), true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    );
+    send(
+        &mut banks_client,
+        &payer,
+        vec![
+            initialize_pda_ix(&ID, &payer.pubkey(), seed, Default::default()),
+            set_rewards,
+        ],
+    )
+    .await
+    .unwrap();
+
+    let rewarded = Rewarded {
+        counter,
+        reward_accounts: vec![
+            AccountMeta::new(rewards, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new(mint_address, false),
+            AccountMeta::new(destination, false),
+        ],
+    };
+    (banks_client, payer, rewarded)
+}
+
+/// Send `instructions` in a transaction `payer` signs and pays for
+async fn send(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    instructions: Vec<Instruction>,
+) -> Result<(), TransactionError> {
+    let blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &[payer],
+        blockhash,
+    );
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|error| error.unwrap())
+}
+
+/// An increment by `amount` that `actor` signs, passing the reward accounts
+fn rewarded_increment(rewarded: &Rewarded, actor: &Pubkey, amount: u32) -> Instruction {
+    let mut instruction = increment_ix(&ID, &rewarded.counter, amount);
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(*actor, true));
+    instruction
+        .accounts
+        .extend(rewarded.reward_accounts.iter().cloned());
+    instruction
+}
+
+#[test]
+fn the_rewards_account_is_the_mint_authority_pda() {
+    let counter = Pubkey::new_unique();
+    let (address, bump) = find_rewards_address(&ID, &counter);
+    assert_eq!(
+        Pubkey::create_program_address(&[REWARDS_SEED, counter.as_ref(), &[bump]], &ID),
+        Ok(address)
+    );
+    assert!(!address.is_on_curve());
+}
+
+#[tokio::test]
+async fn milestones_mint_the_reward_to_the_actor() {
+    let (mut banks_client, payer, rewarded) =
+        start(|counter| find_rewards_address(&ID, counter).0, "milestones").await;
+    let actor = payer.pubkey();
+    let destination = rewarded.reward_accounts[3].pubkey;
+
+    // Short of the first milestone nothing is minted
+    let increment = rewarded_increment(&rewarded, &actor, 4);
+    send(&mut banks_client, &payer, vec![increment])
+        .await
+        .unwrap();
+    assert_eq!(balance(&mut banks_client, &destination).await, 0);
+
+    let increment = rewarded_increment(&rewarded, &actor, 1);
+    send(&mut banks_client, &payer, vec![increment])
+        .await
+        .unwrap();
+    assert_eq!(balance(&mut banks_client, &destination).await, 500);
+
+    // Each milestone pays once, and past the last nothing more is minted
+    let increment = rewarded_increment(&rewarded, &actor, 6);
+    send(&mut banks_client, &payer, vec![increment])
+        .await
+        .unwrap();
+    assert_eq!(balance(&mut banks_client, &destination).await, 1000);
+    let increment = rewarded_increment(&rewarded, &actor, 2);
+    send(&mut banks_client, &payer, vec![increment])
+        .await
+        .unwrap();
+    assert_eq!(balance(&mut banks_client, &destination).await, 1000);
+
+    let mint = rewarded.reward_accounts[2].pubkey;
+    let mint = banks_client.get_account(mint).await.unwrap().unwrap();
+    assert_eq!(
+        u64::from_le_bytes(mint.data[36..44].try_into().unwrap()),
+        1000
+    );
+}
+
+#[tokio::test]
+async fn a_mint_with_another_authority_pays_nothing() {
+    let (mut banks_client, payer, rewarded) = start(|_| Pubkey::new_unique(), "foreign").await;
+    let actor = payer.pubkey();
+    let destination = rewarded.reward_accounts[3].pubkey;
+
+    // The token program refuses the rewards account's signature with OwnerMismatch
+    let increment = rewarded_increment(&rewarded, &actor, 5);
+    assert_eq!(
+        send(&mut banks_client, &payer, vec![increment]).await,
+        Err(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(4)
+        ))
+    );
+    assert_eq!(balance(&mut banks_client, &destination).await, 0);
+}
+
+#[tokio::test]
+async fn a_batch_crossing_a_milestone_mints_the_reward() {
+    let (mut banks_client, payer, rewarded) =
+        start(|counter| find_rewards_address(&ID, counter).0, "batched").await;
+    let destination = rewarded.reward_accounts[3].pubkey;
+
+    // Neither op reaches 5 on its own, together they cross it
+    let mut accounts = vec![
+        AccountMeta::new(rewarded.counter, false),
+        AccountMeta::new_readonly(find_config_address(&ID).0, false),
+        AccountMeta::new_readonly(payer.pubkey(), true),
+    ];
+    accounts.extend(rewarded.reward_accounts.iter().cloned());
+    let batch = Instruction::new_with_borsh(
+        ID,
+        &CounterInstruction::Batch(vec![CounterOp::Increment(3), CounterOp::Increment(3)]),
+        accounts,
+    );
+    send(&mut banks_client, &payer, vec![batch]).await.unwrap();
+    assert_eq!(balance(&mut banks_client, &destination).await, 500);
+
+    // Paid once, an increment from there on crosses nothing
+    let increment = rewarded_increment(&rewarded, &payer.pubkey(), 1);
+    send(&mut banks_client, &payer, vec![increment])
+        .await
+        .unwrap();
+    assert_eq!(balance(&mut banks_client, &destination).await, 500);
+}
+
+#[tokio::test]
+async fn a_merge_crossing_a_milestone_mints_the_reward() {
+    let (mut banks_client, payer, rewarded) =
+        start(|counter| find_rewards_address(&ID, counter).0, "merged").await;
+    let destination = rewarded.reward_accounts[3].pubkey;
+    let (source, _) = find_counter_address(&ID, &payer.pubkey(), "source");
+    send(
+        &mut banks_client,
+        &payer,
+        vec![
+            initialize_pda_ix(&ID, &payer.pubkey(), "source", Default::default()),
+            increment_ix(&ID, &source, 7),
+        ],
+    )
+    .await
+    .unwrap();
+
+    // The destination's reward accounts come last, minting to its authority
+    let mut accounts = vec![
+        AccountMeta::new(rewarded.counter, false),
+        AccountMeta::new_readonly(payer.pubkey(), true),
+        AccountMeta::new(source, false),
+        AccountMeta::new_readonly(payer.pubkey(), true),
+        AccountMeta::new_readonly(find_config_address(&ID).0, false),
+    ];
+    accounts.extend(rewarded.reward_accounts.iter().cloned());
+    let merge = Instruction::new_with_borsh(ID, &CounterInstruction::Merge, accounts);
+    send(&mut banks_client, &payer, vec![merge]).await.unwrap();
+    assert_eq!(balance(&mut banks_client, &destination).await, 500);
+}
+
+#[tokio::test]
+async fn clearing_rewards_closes_the_rewards_account() {
+    let (mut banks_client, payer, rewarded) =
+        start(|counter| find_rewards_address(&ID, counter).0, "cleared").await;
+    let rewards = rewarded.reward_accounts[0].pubkey;
+    let receiver = Pubkey::new_unique();
+    let rent = banks_client
+        .get_account(rewards)
+        .await
+        .unwrap()
+        .unwrap()
+        .lamports;
+
+    let clear = Instruction::new_with_borsh(
+        ID,
+        &CounterInstruction::ClearRewards,
+        vec![
+            AccountMeta::new(rewarded.counter, false),
+            AccountMeta::new_readonly(payer.pubkey(), true),
+            AccountMeta::new(rewards, false),
+            AccountMeta::new(receiver, false),
+        ],
+    );
+    send(&mut banks_client, &payer, vec![clear]).await.unwrap();
+    assert_eq!(banks_client.get_account(rewards).await.unwrap(), None);
+    let receiver = banks_client.get_account(receiver).await.unwrap().unwrap();
+    assert_eq!(receiver.lamports, rent);
+
+    // Past the first milestone with no reward accounts at all
+    let increment = increment_ix(&ID, &rewarded.counter, 5);
+    send(&mut banks_client, &payer, vec![increment])
+        .await
+        .unwrap();
+}